@@ -0,0 +1,17 @@
+-- Migration 014: Shared Ruleset Versioning
+-- Created: 2026-10-18
+-- Description: Link published rulesets to their source entity and track versions
+
+ALTER TABLE shared_rulesets
+    ADD COLUMN source_id UUID, -- playbook_setups.id, grading_rubrics.id or daily_plans.id
+    ADD COLUMN version INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN published_at TIMESTAMPTZ DEFAULT NOW();
+
+-- Republishing the same source bumps the version instead of creating a duplicate
+CREATE UNIQUE INDEX idx_shared_rulesets_creator_source
+    ON shared_rulesets(creator_user_id, ruleset_type, source_id)
+    WHERE source_id IS NOT NULL;
+
+CREATE INDEX idx_shared_rulesets_public_popular
+    ON shared_rulesets(is_public, import_count DESC)
+    WHERE is_public = TRUE;
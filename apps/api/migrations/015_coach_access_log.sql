@@ -0,0 +1,18 @@
+-- Migration 015: Coach Access Audit Log
+-- Created: 2026-10-18
+-- Description: Audit trail of every coach read of a trader's data
+
+CREATE TABLE coach_access_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    link_id UUID NOT NULL REFERENCES accountability_links(id) ON DELETE CASCADE,
+    coach_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    trader_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    
+    resource VARCHAR(50) NOT NULL, -- trades, plans, mood_logs, tilt_events, reviews
+    resource_id UUID, -- NULL for list views
+    
+    accessed_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_coach_access_log_trader ON coach_access_log(trader_user_id, accessed_at DESC);
+CREATE INDEX idx_coach_access_log_coach ON coach_access_log(coach_user_id, accessed_at DESC);
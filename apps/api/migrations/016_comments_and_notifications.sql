@@ -0,0 +1,67 @@
+-- Migration 016: Comments and Notifications
+-- Created: 2026-10-18
+-- Description: Threaded coach/trader comments, trade review flags, in-app notifications
+
+-- Comments attached to a trader's journal entries
+CREATE TABLE comments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    trader_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE, -- owner of the subject
+    author_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    subject_type VARCHAR(30) NOT NULL, -- trade, daily_plan, periodic_review
+    subject_id UUID NOT NULL,
+    parent_id UUID REFERENCES comments(id) ON DELETE CASCADE,
+
+    body TEXT NOT NULL,
+    mentions UUID[] DEFAULT '{}',
+
+    edited_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_comments_subject ON comments(subject_type, subject_id, created_at);
+CREATE INDEX idx_comments_trader ON comments(trader_user_id);
+CREATE INDEX idx_comments_parent ON comments(parent_id);
+
+-- Per-user read receipts
+CREATE TABLE comment_reads (
+    comment_id UUID NOT NULL REFERENCES comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    read_at TIMESTAMPTZ DEFAULT NOW(),
+
+    PRIMARY KEY (comment_id, user_id)
+);
+
+-- "Needs review" flag a trader raises on a trade and a coach clears
+CREATE TABLE trade_review_flags (
+    trade_id UUID PRIMARY KEY REFERENCES trades(id) ON DELETE CASCADE,
+    trader_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    note TEXT,
+
+    raised_at TIMESTAMPTZ DEFAULT NOW(),
+    cleared_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    cleared_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_trade_review_flags_open ON trade_review_flags(trader_user_id) WHERE cleared_at IS NULL;
+
+-- In-app notification channel
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    kind VARCHAR(50) NOT NULL, -- comment, comment_mention, review_requested, review_cleared
+    title VARCHAR(255) NOT NULL,
+    body TEXT,
+    data JSONB DEFAULT '{}',
+
+    read_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_notifications_user ON notifications(user_id, created_at DESC);
+CREATE INDEX idx_notifications_unread ON notifications(user_id) WHERE read_at IS NULL;
+
+CREATE TRIGGER update_comments_updated_at BEFORE UPDATE ON comments
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,21 @@
+-- Migration 017: Streak History
+-- Created: 2026-10-18
+-- Description: Per-activity history behind user_streaks
+
+CREATE TABLE streak_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    streak_type VARCHAR(50) NOT NULL, -- journal, planning, plan_adherence, review
+    source VARCHAR(50) NOT NULL, -- daily_plan, mood_log, trade_notes, periodic_review
+    source_id UUID,
+
+    activity_date DATE NOT NULL, -- trading day (or week start) the activity was credited to
+    quality DECIMAL(4,2) NOT NULL, -- 0.00 - 1.00
+    count_after INTEGER NOT NULL,
+    decay_value_after DECIMAL(10,2) NOT NULL,
+
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX idx_streak_events_user_type ON streak_events(user_id, streak_type, created_at DESC);
@@ -0,0 +1,31 @@
+-- Migration 018: Economic Calendar
+-- Created: 2026-10-18
+-- Description: Per-user imported/custom economic events and trade-event proximity links
+
+-- NULL user_id rows are a shared calendar visible to everyone
+ALTER TABLE economic_events
+    ADD COLUMN user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    ADD COLUMN timezone VARCHAR(100) NOT NULL DEFAULT 'America/New_York', -- zone of event_date/event_time
+    ADD COLUMN event_at TIMESTAMPTZ, -- resolved instant; NULL for all-day events
+    ADD COLUMN source VARCHAR(20) NOT NULL DEFAULT 'custom', -- custom, ics, csv
+    ADD COLUMN external_uid VARCHAR(255), -- ICS UID or derived CSV key, for idempotent re-imports
+    ADD COLUMN updated_at TIMESTAMPTZ DEFAULT NOW();
+
+CREATE INDEX idx_economic_events_user_id ON economic_events(user_id);
+CREATE INDEX idx_economic_events_event_at ON economic_events(event_at);
+CREATE UNIQUE INDEX idx_economic_events_user_uid ON economic_events(user_id, external_uid);
+
+CREATE TRIGGER update_economic_events_updated_at BEFORE UPDATE ON economic_events
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Trades entered near a high-impact event
+CREATE TABLE trade_economic_events (
+    trade_id UUID NOT NULL REFERENCES trades(id) ON DELETE CASCADE,
+    event_id UUID NOT NULL REFERENCES economic_events(id) ON DELETE CASCADE,
+    minutes_from_event INTEGER NOT NULL, -- entry minus event; negative = entered before the release
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+
+    PRIMARY KEY (trade_id, event_id)
+);
+
+CREATE INDEX idx_trade_economic_events_event ON trade_economic_events(event_id);
@@ -0,0 +1,25 @@
+-- Migration 019: Trade Media Storage
+-- Created: 2026-10-18
+-- Description: Object-storage keys, thumbnails and upload state for trade media
+
+-- Align trade_media with the object-key based upload pipeline
+ALTER TABLE trade_media
+    ADD COLUMN IF NOT EXISTS s3_key TEXT,
+    ADD COLUMN IF NOT EXISTS s3_url TEXT,
+    ADD COLUMN IF NOT EXISTS caption TEXT,
+    ADD COLUMN thumbnail_key TEXT, -- object key of the generated JPEG thumbnail
+    ADD COLUMN upload_status VARCHAR(20) NOT NULL DEFAULT 'uploaded'; -- pending (presigned, not yet confirmed), uploaded
+
+ALTER TABLE trade_media
+    ALTER COLUMN storage_url DROP NOT NULL,
+    ALTER COLUMN file_name DROP NOT NULL;
+
+-- Rows created before this migration only have a URL; an empty key means
+-- there is no object we manage
+UPDATE trade_media SET s3_url = storage_url WHERE s3_url IS NULL;
+UPDATE trade_media SET s3_url = '' WHERE s3_url IS NULL;
+UPDATE trade_media SET s3_key = '' WHERE s3_key IS NULL;
+
+ALTER TABLE trade_media
+    ALTER COLUMN s3_key SET NOT NULL,
+    ALTER COLUMN s3_url SET NOT NULL;
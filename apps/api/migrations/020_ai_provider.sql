@@ -0,0 +1,13 @@
+-- Migration 020: AI Provider Tracking
+-- Created: 2026-10-18
+-- Description: Record which LLM provider produced each review
+
+ALTER TABLE ai_reviews ADD COLUMN IF NOT EXISTS provider VARCHAR(50);
+
+-- The model now comes from configuration; a column default would mislabel rows
+ALTER TABLE ai_reviews ALTER COLUMN model_used DROP DEFAULT;
+
+UPDATE ai_reviews SET provider = 'anthropic'
+WHERE provider IS NULL AND model_used LIKE 'claude%';
+
+CREATE INDEX IF NOT EXISTS idx_ai_reviews_provider_model ON ai_reviews(provider, model_used);
@@ -0,0 +1,24 @@
+-- Migration 021: AI Usage Metering
+-- Created: 2026-10-18
+-- Description: Per-call token and cost ledger plus per-user AI budgets
+
+CREATE TABLE ai_usage_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    review_id UUID REFERENCES ai_reviews(id) ON DELETE SET NULL,
+    feature VARCHAR(50) NOT NULL, -- trade_review, general_review, review_chat, chart_analysis
+    provider VARCHAR(50) NOT NULL,
+    model VARCHAR(100) NOT NULL,
+    input_tokens INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL,
+    cost_usd DECIMAL(12,6) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_ai_usage_events_user_created ON ai_usage_events(user_id, created_at);
+CREATE INDEX idx_ai_usage_events_review ON ai_usage_events(review_id);
+
+-- Per-user overrides; NULL falls back to the server default
+ALTER TABLE user_profiles
+    ADD COLUMN ai_daily_budget_usd DECIMAL(10,2),
+    ADD COLUMN ai_monthly_budget_usd DECIMAL(10,2);
@@ -0,0 +1,10 @@
+-- Migration 022: AI Plan of Attack
+-- Created: 2026-10-18
+-- Description: Audit trail for the AI-generated pre-market plan of attack
+
+ALTER TABLE daily_plans
+    ADD COLUMN ai_plan_prompt TEXT,
+    ADD COLUMN ai_plan_prompt_version VARCHAR(50),
+    ADD COLUMN ai_plan_provider VARCHAR(50),
+    ADD COLUMN ai_plan_model VARCHAR(100),
+    ADD COLUMN ai_plan_generated_at TIMESTAMPTZ;
@@ -0,0 +1,8 @@
+-- Migration 023: AI Review Drafts
+-- Created: 2026-10-18
+-- Description: Link periodic reviews to the AI draft they were accepted from
+
+ALTER TABLE periodic_reviews
+    ADD COLUMN ai_review_id UUID REFERENCES ai_reviews(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_periodic_reviews_ai_review_id ON periodic_reviews(ai_review_id);
@@ -0,0 +1,174 @@
+-- Migration 024: Journal Search
+-- Created: 2026-10-18
+-- Description: Full-text and embedding search over trade notes, mood logs, plans and reviews
+
+CREATE TABLE journal_search_documents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    -- Source row; one document per searchable text field
+    entity_type VARCHAR(30) NOT NULL, -- trade, mood_log, daily_plan, periodic_review
+    entity_id UUID NOT NULL,
+    field VARCHAR(50) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    entity_date DATE,
+
+    content TEXT NOT NULL,
+    search_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('english', content)) STORED,
+
+    -- Filled in by the API's embedding indexer; cleared when content changes
+    embedding REAL[],
+    embedding_model VARCHAR(100),
+
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+
+    UNIQUE(entity_type, entity_id, field)
+);
+
+CREATE INDEX idx_journal_search_vector ON journal_search_documents USING GIN(search_vector);
+CREATE INDEX idx_journal_search_user_type ON journal_search_documents(user_id, entity_type);
+CREATE INDEX idx_journal_search_unembedded ON journal_search_documents(updated_at)
+    WHERE embedding IS NULL;
+
+-- Writes one field's document, or removes it when the field is blank
+CREATE OR REPLACE FUNCTION upsert_journal_search_document(
+    p_user_id UUID,
+    p_entity_type TEXT,
+    p_entity_id UUID,
+    p_field TEXT,
+    p_title TEXT,
+    p_entity_date DATE,
+    p_content TEXT
+) RETURNS VOID AS $$
+BEGIN
+    IF p_content IS NULL OR btrim(p_content) = '' THEN
+        DELETE FROM journal_search_documents
+        WHERE entity_type = p_entity_type AND entity_id = p_entity_id AND field = p_field;
+        RETURN;
+    END IF;
+
+    INSERT INTO journal_search_documents (
+        user_id, entity_type, entity_id, field, title, entity_date, content
+    )
+    VALUES (p_user_id, p_entity_type, p_entity_id, p_field, left(p_title, 255), p_entity_date, p_content)
+    ON CONFLICT (entity_type, entity_id, field) DO UPDATE SET
+        title = EXCLUDED.title,
+        entity_date = EXCLUDED.entity_date,
+        content = EXCLUDED.content,
+        embedding = CASE WHEN journal_search_documents.content = EXCLUDED.content
+            THEN journal_search_documents.embedding END,
+        embedding_model = CASE WHEN journal_search_documents.content = EXCLUDED.content
+            THEN journal_search_documents.embedding_model END,
+        updated_at = NOW();
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION sync_trade_search_documents() RETURNS TRIGGER AS $$
+DECLARE
+    v_title TEXT;
+    v_date DATE;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM journal_search_documents WHERE entity_type = 'trade' AND entity_id = OLD.id;
+        RETURN OLD;
+    END IF;
+
+    v_title := NEW.symbol || ' ' || NEW.direction::TEXT || COALESCE(' - ' || NEW.setup_name, '');
+    v_date := (NEW.entry_time AT TIME ZONE 'UTC')::DATE;
+    PERFORM upsert_journal_search_document(NEW.user_id, 'trade', NEW.id, 'thesis', v_title, v_date, NEW.pre_trade_thesis);
+    PERFORM upsert_journal_search_document(NEW.user_id, 'trade', NEW.id, 'notes', v_title, v_date, NEW.notes);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER sync_trade_search_documents
+    AFTER INSERT OR DELETE OR UPDATE OF symbol, direction, setup_name, entry_time, pre_trade_thesis, notes
+    ON trades
+    FOR EACH ROW EXECUTE FUNCTION sync_trade_search_documents();
+
+CREATE OR REPLACE FUNCTION sync_mood_log_search_documents() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM journal_search_documents WHERE entity_type = 'mood_log' AND entity_id = OLD.id;
+        RETURN OLD;
+    END IF;
+
+    PERFORM upsert_journal_search_document(NEW.user_id, 'mood_log', NEW.id, 'notes', 'Mood log', NEW.log_date, NEW.notes);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER sync_mood_log_search_documents
+    AFTER INSERT OR DELETE OR UPDATE OF log_date, notes
+    ON mood_logs
+    FOR EACH ROW EXECUTE FUNCTION sync_mood_log_search_documents();
+
+CREATE OR REPLACE FUNCTION sync_daily_plan_search_documents() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM journal_search_documents WHERE entity_type = 'daily_plan' AND entity_id = OLD.id;
+        RETURN OLD;
+    END IF;
+
+    PERFORM upsert_journal_search_document(NEW.user_id, 'daily_plan', NEW.id, 'notes', 'Daily plan', NEW.plan_date, NEW.notes);
+    PERFORM upsert_journal_search_document(NEW.user_id, 'daily_plan', NEW.id, 'bias_reasoning', 'Daily plan', NEW.plan_date, NEW.bias_reasoning);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER sync_daily_plan_search_documents
+    AFTER INSERT OR DELETE OR UPDATE OF plan_date, notes, bias_reasoning
+    ON daily_plans
+    FOR EACH ROW EXECUTE FUNCTION sync_daily_plan_search_documents();
+
+CREATE OR REPLACE FUNCTION sync_periodic_review_search_documents() RETURNS TRIGGER AS $$
+DECLARE
+    v_title TEXT;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        DELETE FROM journal_search_documents WHERE entity_type = 'periodic_review' AND entity_id = OLD.id;
+        RETURN OLD;
+    END IF;
+
+    v_title := initcap(NEW.review_type) || ' review';
+    PERFORM upsert_journal_search_document(NEW.user_id, 'periodic_review', NEW.id, 'what_went_well', v_title, NEW.period_start, NEW.what_went_well);
+    PERFORM upsert_journal_search_document(NEW.user_id, 'periodic_review', NEW.id, 'what_to_improve', v_title, NEW.period_start, NEW.what_to_improve);
+    PERFORM upsert_journal_search_document(NEW.user_id, 'periodic_review', NEW.id, 'key_lessons', v_title, NEW.period_start, array_to_string(NEW.key_lessons, E'\n'));
+    PERFORM upsert_journal_search_document(NEW.user_id, 'periodic_review', NEW.id, 'rules_broken', v_title, NEW.period_start, array_to_string(NEW.rules_broken, E'\n'));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER sync_periodic_review_search_documents
+    AFTER INSERT OR DELETE OR UPDATE OF review_type, period_start, what_went_well, what_to_improve, key_lessons, rules_broken
+    ON periodic_reviews
+    FOR EACH ROW EXECUTE FUNCTION sync_periodic_review_search_documents();
+
+-- Backfill documents for existing journal text
+SELECT upsert_journal_search_document(user_id, 'trade', id, f.field,
+        symbol || ' ' || direction::TEXT || COALESCE(' - ' || setup_name, ''),
+        (entry_time AT TIME ZONE 'UTC')::DATE, f.content)
+FROM trades,
+    LATERAL (VALUES ('thesis', pre_trade_thesis), ('notes', notes)) AS f(field, content)
+WHERE f.content IS NOT NULL;
+
+SELECT upsert_journal_search_document(user_id, 'mood_log', id, 'notes', 'Mood log', log_date, notes)
+FROM mood_logs
+WHERE notes IS NOT NULL;
+
+SELECT upsert_journal_search_document(user_id, 'daily_plan', id, f.field, 'Daily plan', plan_date, f.content)
+FROM daily_plans,
+    LATERAL (VALUES ('notes', notes), ('bias_reasoning', bias_reasoning)) AS f(field, content)
+WHERE f.content IS NOT NULL;
+
+SELECT upsert_journal_search_document(user_id, 'periodic_review', id, f.field,
+        initcap(review_type) || ' review', period_start, f.content)
+FROM periodic_reviews,
+    LATERAL (VALUES
+        ('what_went_well', what_went_well),
+        ('what_to_improve', what_to_improve),
+        ('key_lessons', array_to_string(key_lessons, E'\n')),
+        ('rules_broken', array_to_string(rules_broken, E'\n'))
+    ) AS f(field, content)
+WHERE f.content IS NOT NULL;
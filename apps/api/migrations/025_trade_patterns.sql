@@ -0,0 +1,42 @@
+-- Migration 025: Trade Pattern Mining
+-- Created: 2026-10-18
+-- Description: Ranked recurring losing patterns found by the pattern mining job
+
+CREATE TABLE trade_pattern_findings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    rank INTEGER NOT NULL,
+    pattern_type VARCHAR(50) NOT NULL, -- symbol, setup, setup_hour, hour, weekday, direction, conviction, tag, emotion, mood, after_losses, oversized_conviction
+    condition_key VARCHAR(255) NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL,
+
+    -- Trades matching the condition vs. all other closed trades
+    sample_size INTEGER NOT NULL,
+    loss_count INTEGER NOT NULL,
+    win_rate DECIMAL(6,2) NOT NULL,
+    baseline_win_rate DECIMAL(6,2) NOT NULL,
+    expectancy DECIMAL(15,2) NOT NULL,
+    baseline_expectancy DECIMAL(15,2) NOT NULL,
+    total_pnl DECIMAL(15,2) NOT NULL,
+
+    -- One-sided Fisher exact test: matching trades lose more often
+    p_value DOUBLE PRECISION NOT NULL,
+    score DOUBLE PRECISION NOT NULL,
+
+    supporting_trade_ids UUID[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE(user_id, condition_key)
+);
+
+CREATE INDEX idx_trade_pattern_findings_user_rank ON trade_pattern_findings(user_id, rank);
+
+-- One row per user; findings are replaced wholesale on every run
+CREATE TABLE trade_pattern_runs (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    trades_analyzed INTEGER NOT NULL,
+    findings_count INTEGER NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,16 @@
+-- Migration 026: Email Tokens
+-- Created: 2026-10-18
+-- Description: Single-use email verification and password reset tokens; also the send log for rate limits
+
+CREATE TABLE email_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(), -- the signed token's jti
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    purpose VARCHAR(20) NOT NULL CHECK (purpose IN ('verify_email', 'password_reset')),
+    email VARCHAR(255) NOT NULL, -- address the link was sent to
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ, -- set when consumed or superseded by a newer token
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_email_tokens_user_purpose ON email_tokens(user_id, purpose, created_at DESC);
+CREATE INDEX idx_email_tokens_expires_at ON email_tokens(expires_at);
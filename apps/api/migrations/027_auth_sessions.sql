@@ -0,0 +1,38 @@
+-- Migration 027: Auth Sessions
+-- Created: 2026-10-18
+-- Description: Group rotating refresh tokens into per-device session families with reuse detection
+
+-- One row per signed-in device; every refresh token belongs to exactly one
+CREATE TABLE auth_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+
+    user_agent TEXT,
+    ip_address VARCHAR(45), -- as reported by the client or proxy, informational only
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL, -- slides forward with each rotation
+
+    revoked_at TIMESTAMPTZ,
+    revoked_reason VARCHAR(30) CHECK (revoked_reason IN ('logout', 'user', 'reuse_detected', 'password_reset'))
+);
+
+CREATE INDEX idx_auth_sessions_user_active ON auth_sessions(user_id) WHERE revoked_at IS NULL;
+CREATE INDEX idx_auth_sessions_expires_at ON auth_sessions(expires_at);
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN session_id UUID REFERENCES auth_sessions(id) ON DELETE CASCADE,
+    ADD COLUMN rotated_at TIMESTAMPTZ; -- set when exchanged; presenting it again is reuse
+
+-- Each still-valid token becomes its own session; dead tokens are dropped
+INSERT INTO auth_sessions (id, user_id, created_at, last_used_at, expires_at)
+SELECT id, user_id, COALESCE(created_at, NOW()), COALESCE(created_at, NOW()), expires_at
+FROM refresh_tokens
+WHERE revoked = false AND expires_at > NOW();
+
+UPDATE refresh_tokens SET session_id = id WHERE id IN (SELECT id FROM auth_sessions);
+DELETE FROM refresh_tokens WHERE session_id IS NULL;
+
+ALTER TABLE refresh_tokens ALTER COLUMN session_id SET NOT NULL;
+CREATE INDEX idx_refresh_tokens_session_id ON refresh_tokens(session_id);
@@ -0,0 +1,29 @@
+-- Migration 028: Two-Factor Authentication
+-- Created: 2026-10-18
+-- Description: Optional TOTP second factor with hashed one-time recovery codes
+
+CREATE TABLE user_two_factor (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL, -- base32 TOTP secret; needed in the clear to compute codes
+    enabled_at TIMESTAMPTZ, -- NULL while enrolment awaits its first valid code
+
+    -- Replay protection: codes at or before this 30-second step are rejected
+    last_used_step BIGINT,
+
+    -- Brute-force protection for the six-digit codes
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMPTZ,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE two_factor_recovery_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(255) NOT NULL, -- argon2
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_two_factor_recovery_codes_user_id ON two_factor_recovery_codes(user_id) WHERE used_at IS NULL;
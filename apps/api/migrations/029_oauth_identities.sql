@@ -0,0 +1,33 @@
+-- Migration 029: OAuth Identities
+-- Created: 2026-10-18
+-- Description: External OAuth/OIDC identities linked to users, and pending authorization requests
+
+CREATE TABLE oauth_identities (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(50) NOT NULL, -- google, github, oidc
+    subject VARCHAR(255) NOT NULL, -- the provider's stable user id
+    email VARCHAR(255), -- as reported at link time, for display
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMPTZ,
+
+    UNIQUE(provider, subject),
+    UNIQUE(user_id, provider)
+);
+
+CREATE INDEX idx_oauth_identities_user_id ON oauth_identities(user_id);
+
+-- State and PKCE verifier between redirecting to the provider and its callback
+CREATE TABLE oauth_states (
+    state_hash VARCHAR(64) PRIMARY KEY,
+    provider VARCHAR(50) NOT NULL,
+    pkce_verifier VARCHAR(128) NOT NULL,
+    link_user_id UUID REFERENCES users(id) ON DELETE CASCADE, -- set when linking to a signed-in account
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_oauth_states_expires_at ON oauth_states(expires_at);
+
+-- Accounts created through a provider have no usable password until reset
+ALTER TABLE users ADD COLUMN password_set BOOLEAN NOT NULL DEFAULT TRUE;
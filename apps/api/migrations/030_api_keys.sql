@@ -0,0 +1,18 @@
+-- Migration 030: Personal API Keys
+-- Created: 2026-10-18
+-- Description: Long-lived, scoped credentials for scripts and integrations
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL, -- first characters of the key, shown to tell keys apart
+    key_hash VARCHAR(64) NOT NULL UNIQUE, -- SHA-256 of the full key; the key itself is never stored
+    scopes TEXT[] NOT NULL, -- trades:read, trades:write, analytics:read, ai:use
+    expires_at TIMESTAMPTZ, -- NULL never expires
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_api_keys_user_id ON api_keys(user_id) WHERE revoked_at IS NULL;
@@ -0,0 +1,6 @@
+-- Migration 031: Coach Trade Permission
+-- Created: 2026-10-18
+-- Description: Let traders hide their trades from a coach; existing links keep trade access
+
+ALTER TABLE accountability_links
+    ADD COLUMN can_view_trades BOOLEAN NOT NULL DEFAULT TRUE;
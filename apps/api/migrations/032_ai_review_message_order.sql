@@ -0,0 +1,23 @@
+-- Migration 032: AI Review Message Order
+-- Created: 2026-10-18
+-- Description: Insertion ordinal for review chat messages, so a reply always sorts after its prompt
+
+ALTER TABLE ai_review_messages ADD COLUMN seq BIGINT;
+
+-- Existing rows keep their created_at order, prompt before reply on ties
+WITH ordered AS (
+    SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, role = 'assistant', id) AS seq
+    FROM ai_review_messages
+)
+UPDATE ai_review_messages m SET seq = ordered.seq
+FROM ordered
+WHERE m.id = ordered.id;
+
+CREATE SEQUENCE ai_review_messages_seq_seq OWNED BY ai_review_messages.seq;
+SELECT setval('ai_review_messages_seq_seq', COALESCE(MAX(seq), 0) + 1, false) FROM ai_review_messages;
+
+ALTER TABLE ai_review_messages
+    ALTER COLUMN seq SET DEFAULT nextval('ai_review_messages_seq_seq'),
+    ALTER COLUMN seq SET NOT NULL;
+
+CREATE INDEX idx_ai_review_messages_review_seq ON ai_review_messages(review_id, seq);
@@ -0,0 +1,11 @@
+-- Migration 033: Trade Pattern Q-Values
+-- Created: 2026-10-18
+-- Description: Benjamini-Hochberg adjusted p-values for trade pattern findings
+
+-- Findings stored so far were thresholded on unadjusted p-values. Drop them
+-- along with their runs so the miner redoes every user on its next pass.
+DELETE FROM trade_pattern_findings;
+DELETE FROM trade_pattern_runs;
+
+-- False discovery rate adjusted across every condition tested in the run
+ALTER TABLE trade_pattern_findings ADD COLUMN q_value DOUBLE PRECISION NOT NULL;
@@ -0,0 +1,8 @@
+-- Migration 034: OAuth Browser Binding
+-- Created: 2026-10-18
+-- Description: Tie each pending OAuth state to the browser that started the flow
+
+-- Pending flows predate the nonce and can't be completed anymore
+DELETE FROM oauth_states;
+
+ALTER TABLE oauth_states ADD COLUMN browser_nonce_hash VARCHAR(64) NOT NULL;
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use rust_decimal::Decimal;
 use std::env;
 
 #[derive(Debug, Clone)]
@@ -10,6 +11,16 @@ pub struct Config {
     pub jwt_access_expiry_seconds: i64,
     pub jwt_refresh_expiry_seconds: i64,
     pub anthropic_api_key: Option<String>,
+    pub anthropic_base_url: String,
+    pub ai_provider: String,
+    pub ai_model: Option<String>,
+    pub openai_api_key: Option<String>,
+    pub openai_base_url: String,
+    pub ai_daily_budget_usd: Decimal,
+    pub ai_monthly_budget_usd: Decimal,
+    pub ai_price_table: Option<String>,
+    pub embedding_provider: String,
+    pub embedding_model: Option<String>,
     pub s3_endpoint: String,
     pub s3_region: String,
     pub s3_bucket: String,
@@ -21,6 +32,19 @@ pub struct Config {
     pub smtp_username: Option<String>,
     pub smtp_password: Option<String>,
     pub smtp_from_email: Option<String>,
+    pub smtp_tls: String,
+    pub app_base_url: String,
+    pub google_client_id: Option<String>,
+    pub google_client_secret: Option<String>,
+    pub github_client_id: Option<String>,
+    pub github_client_secret: Option<String>,
+    pub oidc_issuer_url: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    pub oidc_display_name: String,
+    pub oauth_redirect_url: String,
+    pub trading_sessions: Option<String>,
+    pub trading_session_timezone: String,
 }
 
 impl Config {
@@ -56,6 +80,38 @@ impl Config {
 
         let anthropic_api_key = env::var("ANTHROPIC_API_KEY").ok();
 
+        let anthropic_base_url = env::var("ANTHROPIC_BASE_URL")
+            .unwrap_or_else(|_| "https://api.anthropic.com".to_string());
+
+        let ai_provider = env::var("AI_PROVIDER")
+            .unwrap_or_else(|_| "anthropic".to_string())
+            .to_lowercase();
+
+        let ai_model = env::var("AI_MODEL").ok().filter(|m| !m.trim().is_empty());
+
+        let openai_api_key = env::var("OPENAI_API_KEY").ok();
+
+        let openai_base_url = env::var("OPENAI_BASE_URL")
+            .unwrap_or_else(|_| "https://api.openai.com/v1".to_string());
+
+        let ai_daily_budget_usd = env::var("AI_DAILY_BUDGET_USD")
+            .unwrap_or_else(|_| "2".to_string())
+            .parse()
+            .context("AI_DAILY_BUDGET_USD must be a valid amount")?;
+
+        let ai_monthly_budget_usd = env::var("AI_MONTHLY_BUDGET_USD")
+            .unwrap_or_else(|_| "25".to_string())
+            .parse()
+            .context("AI_MONTHLY_BUDGET_USD must be a valid amount")?;
+
+        let ai_price_table = env::var("AI_PRICE_TABLE").ok().filter(|p| !p.trim().is_empty());
+
+        let embedding_provider = env::var("EMBEDDING_PROVIDER")
+            .unwrap_or_else(|_| "local".to_string())
+            .to_lowercase();
+
+        let embedding_model = env::var("EMBEDDING_MODEL").ok().filter(|m| !m.trim().is_empty());
+
         let s3_endpoint = env::var("S3_ENDPOINT")
             .unwrap_or_else(|_| "http://localhost:9000".to_string());
 
@@ -81,6 +137,29 @@ impl Config {
         let smtp_username = env::var("SMTP_USERNAME").ok();
         let smtp_password = env::var("SMTP_PASSWORD").ok();
         let smtp_from_email = env::var("SMTP_FROM_EMAIL").ok();
+        let smtp_tls = env::var("SMTP_TLS")
+            .unwrap_or_else(|_| "starttls".to_string())
+            .to_lowercase();
+
+        let app_base_url = env::var("APP_BASE_URL")
+            .unwrap_or_else(|_| "http://localhost:5173".to_string());
+
+        // Each sign-in provider is enabled by setting its client id
+        let google_client_id = env::var("GOOGLE_CLIENT_ID").ok();
+        let google_client_secret = env::var("GOOGLE_CLIENT_SECRET").ok();
+        let github_client_id = env::var("GITHUB_CLIENT_ID").ok();
+        let github_client_secret = env::var("GITHUB_CLIENT_SECRET").ok();
+        let oidc_issuer_url = env::var("OIDC_ISSUER_URL").ok();
+        let oidc_client_id = env::var("OIDC_CLIENT_ID").ok();
+        let oidc_client_secret = env::var("OIDC_CLIENT_SECRET").ok();
+        let oidc_display_name = env::var("OIDC_DISPLAY_NAME")
+            .unwrap_or_else(|_| "Single sign-on".to_string());
+        let oauth_redirect_url = env::var("OAUTH_REDIRECT_URL")
+            .unwrap_or_else(|_| "http://localhost:5173/oauth/callback".to_string());
+
+        let trading_sessions = env::var("TRADING_SESSIONS").ok();
+        let trading_session_timezone = env::var("TRADING_SESSION_TIMEZONE")
+            .unwrap_or_else(|_| crate::services::DEFAULT_SESSION_TIMEZONE.to_string());
 
         Ok(Config {
             database_url,
@@ -90,6 +169,16 @@ impl Config {
             jwt_access_expiry_seconds,
             jwt_refresh_expiry_seconds,
             anthropic_api_key,
+            anthropic_base_url,
+            ai_provider,
+            ai_model,
+            openai_api_key,
+            openai_base_url,
+            ai_daily_budget_usd,
+            ai_monthly_budget_usd,
+            ai_price_table,
+            embedding_provider,
+            embedding_model,
             s3_endpoint,
             s3_region,
             s3_bucket,
@@ -101,6 +190,19 @@ impl Config {
             smtp_username,
             smtp_password,
             smtp_from_email,
+            smtp_tls,
+            app_base_url,
+            google_client_id,
+            google_client_secret,
+            github_client_id,
+            github_client_secret,
+            oidc_issuer_url,
+            oidc_client_id,
+            oidc_client_secret,
+            oidc_display_name,
+            oauth_redirect_url,
+            trading_sessions,
+            trading_session_timezone,
         })
     }
 
@@ -113,6 +215,58 @@ impl Config {
             anyhow::bail!("PORT must be greater than 0");
         }
 
+        if !crate::services::AI_PROVIDERS.contains(&self.ai_provider.as_str()) {
+            anyhow::bail!(
+                "AI_PROVIDER must be one of: {}",
+                crate::services::AI_PROVIDERS.join(", ")
+            );
+        }
+
+        if self.ai_daily_budget_usd.is_sign_negative() || self.ai_monthly_budget_usd.is_sign_negative() {
+            anyhow::bail!("AI budgets must not be negative");
+        }
+
+        if let Err(e) = crate::services::PriceTable::parse(self.ai_price_table.as_deref()) {
+            anyhow::bail!("AI_PRICE_TABLE is invalid: {}", e);
+        }
+
+        if !crate::services::EMBEDDING_PROVIDERS.contains(&self.embedding_provider.as_str()) {
+            anyhow::bail!(
+                "EMBEDDING_PROVIDER must be one of: {}",
+                crate::services::EMBEDDING_PROVIDERS.join(", ")
+            );
+        }
+
+        if !crate::services::SMTP_TLS_MODES.contains(&self.smtp_tls.as_str()) {
+            anyhow::bail!(
+                "SMTP_TLS must be one of: {}",
+                crate::services::SMTP_TLS_MODES.join(", ")
+            );
+        }
+
+        if let Err(e) = crate::services::EmailService::parse_from(self.smtp_from_email.as_deref()) {
+            anyhow::bail!("SMTP_FROM_EMAIL is invalid: {}", e);
+        }
+
+        if let Err(e) = crate::services::SessionTable::parse(
+            self.trading_sessions.as_deref(),
+            &self.trading_session_timezone,
+        ) {
+            anyhow::bail!("TRADING_SESSIONS is invalid: {}", e);
+        }
+
+        if self.oidc_issuer_url.is_some() != self.oidc_client_id.is_some() {
+            anyhow::bail!("OIDC_ISSUER_URL and OIDC_CLIENT_ID must be set together");
+        }
+
+        if self.google_client_id.is_some() && self.google_client_secret.is_none() {
+            anyhow::bail!("GOOGLE_CLIENT_SECRET is required when GOOGLE_CLIENT_ID is set");
+        }
+
+        if self.github_client_id.is_some() && self.github_client_secret.is_none() {
+            anyhow::bail!("GITHUB_CLIENT_SECRET is required when GITHUB_CLIENT_ID is set");
+        }
+
         if self.cors_origins.is_empty() {
             anyhow::bail!("CORS_ORIGINS must contain at least one origin");
         }
@@ -120,3 +274,64 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl Config {
+    /// Offline defaults for unit tests; override fields with struct update
+    /// syntax rather than copying the whole literal.
+    pub fn for_tests() -> Self {
+        Config {
+            database_url: "".to_string(),
+            port: 3000,
+            cors_origins: vec!["http://localhost:5173".to_string()],
+            jwt_secret: "test_secret_key_at_least_32_characters_long".to_string(),
+            jwt_access_expiry_seconds: 900,
+            jwt_refresh_expiry_seconds: 2592000,
+            anthropic_api_key: None,
+            anthropic_base_url: "".to_string(),
+            ai_provider: "fake".to_string(),
+            ai_model: None,
+            openai_api_key: None,
+            openai_base_url: "".to_string(),
+            ai_daily_budget_usd: rust_decimal::Decimal::ZERO,
+            ai_monthly_budget_usd: rust_decimal::Decimal::ZERO,
+            ai_price_table: None,
+            embedding_provider: "local".to_string(),
+            embedding_model: None,
+            s3_endpoint: "".to_string(),
+            s3_region: "".to_string(),
+            s3_bucket: "".to_string(),
+            s3_access_key: "".to_string(),
+            s3_secret_key: "".to_string(),
+            max_pool_connections: 10,
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_from_email: None,
+            smtp_tls: "starttls".to_string(),
+            app_base_url: "http://localhost:5173".to_string(),
+            google_client_id: None,
+            google_client_secret: None,
+            github_client_id: None,
+            github_client_secret: None,
+            oidc_issuer_url: None,
+            oidc_client_id: None,
+            oidc_client_secret: None,
+            oidc_display_name: "Single sign-on".to_string(),
+            oauth_redirect_url: "http://localhost:5173/oauth/callback".to_string(),
+            trading_sessions: None,
+            trading_session_timezone: crate::services::DEFAULT_SESSION_TIMEZONE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_for_tests_is_valid() {
+        Config::for_tests().validate().unwrap();
+    }
+}
@@ -12,6 +12,7 @@ pub enum AppError {
     BadRequest(String),
     Unauthorized(String),
     Forbidden(String),
+    EmailNotVerified(String),
     NotFound(String),
     Conflict(String),
     Internal(String),
@@ -27,6 +28,7 @@ impl fmt::Display for AppError {
             AppError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
             AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
             AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            AppError::EmailNotVerified(msg) => write!(f, "Email Not Verified: {}", msg),
             AppError::NotFound(msg) => write!(f, "Not Found: {}", msg),
             AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
             AppError::Internal(msg) => write!(f, "Internal Error: {}", msg),
@@ -55,11 +57,11 @@ struct ErrorDetail {
     trace_id: Option<String>,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let trace_id = Uuid::new_v4().to_string();
-
-        let (status, code, message, include_trace) = match &self {
+impl AppError {
+    /// Status, error code, client-safe message and whether to attach a trace
+    /// id. Shared by JSON responses and SSE error events.
+    pub fn client_parts(&self) -> (StatusCode, &'static str, String, bool) {
+        match self {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "BAD_REQUEST", msg.clone(), false),
             AppError::Unauthorized(_) => (
                 StatusCode::UNAUTHORIZED,
@@ -73,28 +75,41 @@ impl IntoResponse for AppError {
                 "You do not have permission to perform this action".to_string(),
                 false,
             ),
+            AppError::EmailNotVerified(msg) => (StatusCode::FORBIDDEN, "EMAIL_NOT_VERIFIED", msg.clone(), false),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "NOT_FOUND", msg.clone(), false),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, "CONFLICT", msg.clone(), false),
-            AppError::Internal(msg) => {
-                tracing::error!(trace_id = %trace_id, error = %msg, "Internal server error");
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "INTERNAL_ERROR",
-                    "An internal error occurred. If this persists, contact support.".to_string(),
-                    true,
-                )
-            }
+            AppError::Internal(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INTERNAL_ERROR",
+                "An internal error occurred. If this persists, contact support.".to_string(),
+                true,
+            ),
             AppError::Validation(msg) => (StatusCode::UNPROCESSABLE_ENTITY, "VALIDATION_ERROR", msg.clone(), false),
             AppError::RateLimited(msg) => (StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED", msg.clone(), false),
-            AppError::AiError(msg) => {
-                tracing::warn!(trace_id = %trace_id, error = %msg, "AI service error");
-                (StatusCode::SERVICE_UNAVAILABLE, "AI_ERROR", msg.clone(), true)
-            }
-            AppError::BrokerError(msg) => {
-                tracing::warn!(trace_id = %trace_id, error = %msg, "Broker error");
-                (StatusCode::BAD_GATEWAY, "BROKER_ERROR", msg.clone(), true)
+            AppError::AiError(msg) => (StatusCode::SERVICE_UNAVAILABLE, "AI_ERROR", msg.clone(), true),
+            AppError::BrokerError(msg) => (StatusCode::BAD_GATEWAY, "BROKER_ERROR", msg.clone(), true),
+        }
+    }
+
+    /// Logs server-side failures under the trace id handed to the client.
+    pub fn log(&self, trace_id: &str) {
+        match self {
+            AppError::Internal(msg) => {
+                tracing::error!(trace_id = %trace_id, error = %msg, "Internal server error")
             }
-        };
+            AppError::AiError(msg) => tracing::warn!(trace_id = %trace_id, error = %msg, "AI service error"),
+            AppError::BrokerError(msg) => tracing::warn!(trace_id = %trace_id, error = %msg, "Broker error"),
+            _ => {}
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let trace_id = Uuid::new_v4().to_string();
+
+        self.log(&trace_id);
+        let (status, code, message, include_trace) = self.client_parts();
 
         let body = Json(ErrorResponse {
             error: ErrorDetail {
@@ -5,16 +5,21 @@ mod models;
 mod routes;
 mod services;
 mod state;
+#[cfg(test)]
+mod test_db;
 
 use crate::config::Config;
-use crate::routes::{ai_review, analytics, auth, csv, health, planning, playbook, psychology, review, risk, tags, trades};
-use crate::services::{AiService, AuthService};
+use crate::middleware::require_scope;
+use crate::routes::{accountability, ai_query, ai_review, ai_usage, analytics, api_keys, auth, coach, comments, csv, economic_events, health, media, notifications, oauth, planning, playbook, profile, psychology, review, risk, rulesets, search, streaks, tags, trades, two_factor};
+use crate::services::{API_SCOPE_AI_USE, API_SCOPE_ANALYTICS_READ, API_SCOPE_ANALYTICS_WRITE, API_SCOPE_TRADES_READ, API_SCOPE_TRADES_WRITE, AiService, AiUsageService, AuthService, EmailService, OAuthService, PatternMiningService, SearchService, SessionAnalyticsService, SessionService, StorageService, MAX_MULTIPART_BYTES};
 use crate::state::AppState;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{delete, get, post, put},
     Router,
 };
 use sqlx::postgres::PgPoolOptions;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -55,8 +60,19 @@ async fn main() -> anyhow::Result<()> {
     // Create shared services
     let auth_service = Arc::new(AuthService::new(&config));
     let ai_service = Arc::new(AiService::new(&config));
+    let ai_usage_service = Arc::new(AiUsageService::new(&config));
+    let storage_service = Arc::new(StorageService::new(&config));
+    let search_service = Arc::new(SearchService::new(&config));
+    let email_service = Arc::new(EmailService::new(&config));
+    let oauth_service = Arc::new(OAuthService::new(&config));
+    let session_analytics_service = Arc::new(SessionAnalyticsService::new(&config));
     let pool = Arc::new(pool);
 
+    // Start background jobs
+    search_service.clone().spawn_indexer(pool.clone());
+    PatternMiningService::spawn_miner(pool.clone());
+    SessionService::spawn_purger(pool.clone());
+
     // Configure CORS
     let cors_origins: Vec<_> = config
         .cors_origins
@@ -88,32 +104,64 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/v1/auth/refresh", post(auth::refresh))
         .route("/api/v1/auth/logout", post(auth::logout))
         .route("/api/v1/auth/me", get(auth::me))
+        .route("/api/v1/auth/sessions", get(auth::list_sessions))
+        .route("/api/v1/auth/sessions", delete(auth::revoke_all_sessions))
+        .route("/api/v1/auth/sessions/:id", delete(auth::revoke_session))
+        .route("/api/v1/auth/2fa", get(two_factor::get_two_factor_status))
+        .route("/api/v1/auth/2fa/setup", post(two_factor::setup_two_factor))
+        .route("/api/v1/auth/2fa/confirm", post(two_factor::confirm_two_factor))
+        .route("/api/v1/auth/2fa/verify", post(two_factor::verify_two_factor_login))
+        .route("/api/v1/auth/2fa/recovery-codes", post(two_factor::regenerate_recovery_codes))
+        .route("/api/v1/auth/2fa/disable", post(two_factor::disable_two_factor))
+        .route("/api/v1/auth/oauth/providers", get(oauth::list_oauth_providers))
+        .route("/api/v1/auth/oauth/callback", post(oauth::complete_oauth_login))
+        .route("/api/v1/auth/oauth/link/callback", post(oauth::complete_oauth_link))
+        .route("/api/v1/auth/oauth/:provider/authorize", post(oauth::start_oauth_login))
+        .route("/api/v1/auth/oauth/:provider/link", post(oauth::start_oauth_link))
+        .route("/api/v1/auth/identities", get(oauth::list_identities))
+        .route("/api/v1/auth/identities/:id", delete(oauth::unlink_identity))
+        .route("/api/v1/auth/api-keys", get(api_keys::list_api_keys))
+        .route("/api/v1/auth/api-keys", post(api_keys::create_api_key))
+        .route("/api/v1/auth/api-keys/:id", delete(api_keys::revoke_api_key))
+        // Profile routes
+        .route("/api/v1/profile", get(profile::get_profile))
+        .route("/api/v1/profile", put(profile::update_profile))
+        .route("/api/v1/profile/onboarding", post(profile::complete_onboarding))
+        .route("/api/v1/profile/daily-limits", get(profile::get_daily_limits))
+        .route("/api/v1/auth/verify-email", post(auth::verify_email))
+        .route("/api/v1/auth/verify-email/send", post(auth::send_verification_email))
+        .route("/api/v1/auth/forgot-password", post(auth::forgot_password))
+        .route("/api/v1/auth/reset-password", post(auth::reset_password))
         // Trade routes
-        .route("/api/v1/trades", post(trades::create_trade))
-        .route("/api/v1/trades", get(trades::list_trades))
-        .route("/api/v1/trades/stats", get(trades::get_trade_stats))
-        .route("/api/v1/trades/:id", get(trades::get_trade))
-        .route("/api/v1/trades/:id", put(trades::update_trade))
-        .route("/api/v1/trades/:id", delete(trades::delete_trade))
-        .route("/api/v1/trades/:id/close", post(trades::close_trade))
-        .route("/api/v1/trades/:id/legs", post(trades::add_trade_leg))
+        .route("/api/v1/trades", post(trades::create_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades", get(trades::list_trades).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/trades/stats", get(trades::get_trade_stats).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/trades/:id", get(trades::get_trade).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/trades/:id", put(trades::update_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id", delete(trades::delete_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id/close", post(trades::close_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id/legs", post(trades::add_trade_leg).layer(require_scope(API_SCOPE_TRADES_WRITE)))
         // Tag routes
-        .route("/api/v1/tags", post(tags::create_tag))
-        .route("/api/v1/tags", get(tags::list_tags))
-        .route("/api/v1/tags/:id", get(tags::get_tag))
-        .route("/api/v1/tags/:id", put(tags::update_tag))
-        .route("/api/v1/tags/:id", delete(tags::delete_tag))
-        .route("/api/v1/trades/:trade_id/tags/:tag_id", post(tags::add_tag_to_trade))
-        .route("/api/v1/trades/:trade_id/tags/:tag_id", delete(tags::remove_tag_from_trade))
+        .route("/api/v1/tags", post(tags::create_tag).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/tags", get(tags::list_tags).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/tags/:id", get(tags::get_tag).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/tags/:id", put(tags::update_tag).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/tags/:id", delete(tags::delete_tag).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:trade_id/tags/:tag_id", post(tags::add_tag_to_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:trade_id/tags/:tag_id", delete(tags::remove_tag_from_trade).layer(require_scope(API_SCOPE_TRADES_WRITE)))
         // CSV import routes
-        .route("/api/v1/csv/import", post(csv::import_csv))
-        .route("/api/v1/csv/template", get(csv::get_csv_template))
+        .route("/api/v1/csv/import", post(csv::import_csv).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/csv/template", get(csv::get_csv_template).layer(require_scope(API_SCOPE_TRADES_READ)))
         // Analytics routes
-        .route("/api/v1/analytics/equity-curve", get(analytics::get_equity_curve))
-        .route("/api/v1/analytics/win-loss-distribution", get(analytics::get_win_loss_distribution))
-        .route("/api/v1/analytics/setup-performance", get(analytics::get_setup_performance))
-        .route("/api/v1/analytics/time-based", get(analytics::get_time_based_analytics))
-        .route("/api/v1/analytics/drawdown", get(analytics::get_drawdown_analysis))
+        .route("/api/v1/analytics/equity-curve", get(analytics::get_equity_curve).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/win-loss-distribution", get(analytics::get_win_loss_distribution).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/setup-performance", get(analytics::get_setup_performance).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/time-based", get(analytics::get_time_based_analytics).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/drawdown", get(analytics::get_drawdown_analysis).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/event-proximity", get(analytics::get_event_proximity_analytics).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/ai-review-scores", get(analytics::get_ai_review_score_analytics).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/patterns", get(analytics::get_trade_patterns).layer(require_scope(API_SCOPE_ANALYTICS_READ)))
+        .route("/api/v1/analytics/patterns/run", post(analytics::run_trade_patterns).layer(require_scope(API_SCOPE_ANALYTICS_WRITE)))
         // Planning routes
         .route("/api/v1/plans", post(planning::create_daily_plan))
         .route("/api/v1/plans", get(planning::list_daily_plans))
@@ -121,15 +169,21 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/v1/plans/:id", get(planning::get_daily_plan))
         .route("/api/v1/plans/:id", put(planning::update_daily_plan))
         .route("/api/v1/plans/:id", delete(planning::delete_daily_plan))
+        .route("/api/v1/plans/:id/plan-of-attack", post(planning::generate_plan_of_attack))
         .route("/api/v1/plans/:id/watchlist", post(planning::add_watchlist_item))
         .route("/api/v1/plans/:plan_id/watchlist/:item_id", put(planning::update_watchlist_item))
         .route("/api/v1/plans/:plan_id/watchlist/:item_id", delete(planning::delete_watchlist_item))
         // AI Review routes
-        .route("/api/v1/ai/reviews", post(ai_review::create_ai_review))
-        .route("/api/v1/ai/reviews", get(ai_review::list_ai_reviews))
-        .route("/api/v1/ai/reviews/:id", get(ai_review::get_ai_review))
-        .route("/api/v1/ai/reviews/:id", delete(ai_review::delete_ai_review))
-        .route("/api/v1/ai/reviews/:id/chat", post(ai_review::continue_chat))
+        .route("/api/v1/ai/reviews", post(ai_review::create_ai_review).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews", get(ai_review::list_ai_reviews).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews/stream", post(ai_review::create_ai_review_stream).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews/:id", get(ai_review::get_ai_review).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews/:id", delete(ai_review::delete_ai_review).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews/:id/chat", post(ai_review::continue_chat).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/reviews/:id/chat/stream", post(ai_review::continue_chat_stream).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/query", post(ai_query::query_trades).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/usage", get(ai_usage::get_ai_usage).layer(require_scope(API_SCOPE_AI_USE)))
+        .route("/api/v1/ai/usage/budget", put(ai_usage::update_ai_budget))
         // Risk Management routes
         .route("/api/v1/risk/position-size", post(risk::calculate_position_size))
         .route("/api/v1/risk/risk-reward", post(risk::calculate_risk_reward))
@@ -152,14 +206,97 @@ async fn main() -> anyhow::Result<()> {
         // Review routes
         .route("/api/v1/reviews", post(review::create_review))
         .route("/api/v1/reviews", get(review::list_reviews))
+        .route("/api/v1/reviews/draft", post(review::draft_review))
         .route("/api/v1/reviews/:id", get(review::get_review))
         .route("/api/v1/reviews/:id", put(review::update_review))
         .route("/api/v1/reviews/:id", delete(review::delete_review))
+        // Shared ruleset routes
+        .route("/api/v1/rulesets", post(rulesets::publish_ruleset))
+        .route("/api/v1/rulesets", get(rulesets::list_public_rulesets))
+        .route("/api/v1/rulesets/mine", get(rulesets::list_my_rulesets))
+        .route("/api/v1/rulesets/shared/:token", get(rulesets::get_ruleset_by_token))
+        .route("/api/v1/rulesets/shared/:token/import", post(rulesets::import_ruleset_by_token))
+        .route("/api/v1/rulesets/:id", get(rulesets::get_ruleset))
+        .route("/api/v1/rulesets/:id", put(rulesets::update_ruleset))
+        .route("/api/v1/rulesets/:id", delete(rulesets::delete_ruleset))
+        .route("/api/v1/rulesets/:id/import", post(rulesets::import_ruleset))
+        // Accountability routes
+        .route("/api/v1/accountability/invites", post(accountability::invite_coach))
+        .route("/api/v1/accountability/invites", get(accountability::list_coach_invites))
+        .route("/api/v1/accountability/coaches", get(accountability::list_my_coaches))
+        .route("/api/v1/accountability/links/:id/accept", post(accountability::accept_invite))
+        .route("/api/v1/accountability/links/:id/revoke", post(accountability::revoke_link))
+        .route("/api/v1/accountability/links/:id/permissions", put(accountability::update_link_permissions))
+        .route("/api/v1/accountability/access-log", get(accountability::list_coach_access_log))
+        // Coach routes
+        .route("/api/v1/coach/traders", get(coach::list_coached_traders))
+        .route("/api/v1/coach/traders/:trader_id/trades", get(coach::list_trader_trades))
+        .route("/api/v1/coach/traders/:trader_id/trades/:trade_id", get(coach::get_trader_trade))
+        .route("/api/v1/coach/traders/:trader_id/plans", get(coach::list_trader_plans))
+        .route("/api/v1/coach/traders/:trader_id/mood-logs", get(coach::list_trader_mood_logs))
+        .route("/api/v1/coach/traders/:trader_id/tilt-events", get(coach::list_trader_tilt_events))
+        .route("/api/v1/coach/traders/:trader_id/reviews", get(coach::list_trader_reviews))
+        .route("/api/v1/coach/traders/:trader_id/trades/:trade_id/clear-review", post(comments::clear_review_flag))
+        .route("/api/v1/coach/review-queue", get(comments::list_review_queue))
+        // Comment routes
+        .route("/api/v1/comments", get(comments::list_comments))
+        .route("/api/v1/comments", post(comments::create_comment))
+        .route("/api/v1/comments/read", post(comments::mark_thread_read))
+        .route("/api/v1/comments/:id", put(comments::update_comment))
+        .route("/api/v1/comments/:id", delete(comments::delete_comment))
+        .route("/api/v1/trades/:id/needs-review", put(comments::raise_review_flag))
+        .route("/api/v1/trades/:id/needs-review", delete(comments::withdraw_review_flag))
+        // Notification routes
+        .route("/api/v1/notifications", get(notifications::list_notifications))
+        .route("/api/v1/notifications/read-all", post(notifications::mark_all_notifications_read))
+        .route("/api/v1/notifications/:id/read", post(notifications::mark_notification_read))
+        // Streak routes
+        .route("/api/v1/streaks", get(streaks::get_streaks))
+        .route("/api/v1/streaks/:streak_type/history", get(streaks::get_streak_history))
+        // Economic calendar routes
+        .route("/api/v1/economic-events", get(economic_events::list_economic_events))
+        .route("/api/v1/economic-events", post(economic_events::create_economic_event))
+        .route("/api/v1/economic-events/import", post(economic_events::import_economic_events))
+        .route("/api/v1/economic-events/tag-trades", post(economic_events::tag_trades_with_events))
+        .route("/api/v1/economic-events/:id", get(economic_events::get_economic_event))
+        .route("/api/v1/economic-events/:id", put(economic_events::update_economic_event))
+        .route("/api/v1/economic-events/:id", delete(economic_events::delete_economic_event))
+        // Media routes
+        .route(
+            "/api/v1/trades/:id/media",
+            post(media::upload_media).layer((
+                DefaultBodyLimit::max(MAX_MULTIPART_BYTES),
+                require_scope(API_SCOPE_TRADES_WRITE),
+            )),
+        )
+        .route("/api/v1/trades/:id/media/presign", post(media::presign_media_upload).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id/media/:media_id/complete", post(media::complete_media_upload).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id/media/:media_id", delete(media::delete_media).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route("/api/v1/trades/:id/media/:media_id/annotations", get(media::list_annotations).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/trades/:id/media/:media_id/annotations", post(media::create_annotation).layer(require_scope(API_SCOPE_TRADES_WRITE)))
+        .route(
+            "/api/v1/trades/:id/media/:media_id/annotations/:annotation_id",
+            put(media::update_annotation).layer(require_scope(API_SCOPE_TRADES_WRITE)),
+        )
+        .route(
+            "/api/v1/trades/:id/media/:media_id/annotations/:annotation_id",
+            delete(media::delete_annotation).layer(require_scope(API_SCOPE_TRADES_WRITE)),
+        )
+        .route("/api/v1/trades/:id/media/:media_id/render", get(media::render_annotated_media).layer(require_scope(API_SCOPE_TRADES_READ)))
+        .route("/api/v1/trades/:id/media/:media_id/analyze", post(media::analyze_media).layer(require_scope(API_SCOPE_AI_USE)))
+        // Search routes
+        .route("/api/v1/search", get(search::search_journal).layer(require_scope(API_SCOPE_TRADES_READ)))
         // Add unified state
         .with_state(AppState {
             pool: pool.clone(),
             auth_service: auth_service.clone(),
             ai_service: ai_service.clone(),
+            ai_usage_service: ai_usage_service.clone(),
+            storage_service: storage_service.clone(),
+            search_service: search_service.clone(),
+            email_service: email_service.clone(),
+            oauth_service: oauth_service.clone(),
+            session_analytics_service: session_analytics_service.clone(),
         })
         // Add middleware
         .layer(cors)
@@ -171,7 +308,7 @@ async fn main() -> anyhow::Result<()> {
     
     tracing::info!("Server listening on {}", addr);
     
-    axum::serve(listener, app)
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await?;
 
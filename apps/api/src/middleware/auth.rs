@@ -1,19 +1,28 @@
 use crate::error::AppError;
-use crate::models::AuthUser;
-use crate::services::AuthService;
+use crate::models::{AuthUser, ClientInfo, RequiredScope};
+use crate::services::{is_api_key, ApiKeyService, AuthService, EmailTokenService};
 use axum::{
     extract::FromRequestParts,
-    extract::FromRef,
+    extract::{ConnectInfo, FromRef},
     http::request::Parts,
     response::{IntoResponse, Response},
+    Extension,
 };
+use sqlx::PgPool;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+const MAX_USER_AGENT_CHARS: usize = 512;
+const MAX_IP_ADDRESS_CHARS: usize = 45;
+
+/// Accepts a login JWT or a personal API key. API keys only reach routes
+/// that declare a `RequiredScope`, and only when the key carries it.
 #[axum::async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
     S: Send + Sync,
     Arc<AuthService>: FromRef<S>,
+    Arc<PgPool>: FromRef<S>,
 {
     type Rejection = Response;
 
@@ -38,12 +47,44 @@ where
         // Use FromRef to get AuthService without borrowing parts mutably
         let auth_service = Arc::<AuthService>::from_ref(state);
 
-        auth_service
-            .verify_access_token(token)
-            .map_err(|e| e.into_response())
+        if !is_api_key(token) {
+            return auth_service
+                .verify_access_token(token)
+                .map_err(|e| e.into_response());
+        }
+
+        let RequiredScope(scope) = parts
+            .extensions
+            .get::<RequiredScope>()
+            .copied()
+            .ok_or_else(|| {
+                AppError::Forbidden("API keys cannot be used for this endpoint".to_string())
+                    .into_response()
+            })?;
+
+        let pool = Arc::<PgPool>::from_ref(state);
+        let auth_user = ApiKeyService::authenticate(&pool, &auth_service, token)
+            .await
+            .map_err(|e| e.into_response())?;
+
+        if !auth_user.has_scope(scope) {
+            return Err(AppError::Forbidden(format!(
+                "This API key does not have the '{}' scope",
+                scope
+            ))
+            .into_response());
+        }
+
+        Ok(auth_user)
     }
 }
 
+/// Lets API keys with `scope` call a route, e.g.
+/// `get(trades::list_trades).layer(require_scope(API_SCOPE_TRADES_READ))`.
+pub fn require_scope(scope: &'static str) -> Extension<RequiredScope> {
+    Extension(RequiredScope(scope))
+}
+
 pub struct OptionalAuth(pub Option<AuthUser>);
 
 #[axum::async_trait]
@@ -71,3 +112,67 @@ where
         Ok(OptionalAuth(None))
     }
 }
+
+/// An authenticated user with a verified email address. Use in place of
+/// `AuthUser` on routes that share data with others or act in public.
+pub struct VerifiedUser(pub AuthUser);
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for VerifiedUser
+where
+    S: Send + Sync,
+    Arc<AuthService>: FromRef<S>,
+    Arc<PgPool>: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_user = AuthUser::from_request_parts(parts, state).await?;
+
+        let pool = Arc::<PgPool>::from_ref(state);
+        EmailTokenService::require_verified_email(&pool, auth_user.user_id)
+            .await
+            .map_err(|e| e.into_response())?;
+
+        Ok(VerifiedUser(auth_user))
+    }
+}
+
+/// Never rejects. Forwarding headers are trusted as-is: the values are only
+/// shown back to the user on their session list.
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+
+        let user_agent = header("User-Agent")
+            .map(|ua| ua.chars().take(MAX_USER_AGENT_CHARS).collect());
+
+        let ip_address = header("X-Forwarded-For")
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .or_else(|| header("X-Real-IP"))
+            .map(str::to_string)
+            .or_else(|| {
+                parts
+                    .extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip().to_string())
+            })
+            .map(|ip| ip.chars().take(MAX_IP_ADDRESS_CHARS).collect());
+
+        Ok(ClientInfo { user_agent, ip_address })
+    }
+}
@@ -0,0 +1,152 @@
+use crate::models::{AssetClass, ConvictionLevel, TradeDirection, TradeStatus};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `accountability_links` table from migrations 012 and 031.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AccountabilityLink {
+    pub id: Uuid,
+    pub trader_user_id: Uuid,
+    pub coach_user_id: Uuid,
+    pub can_view_trades: bool,
+    pub can_view_plan: bool,
+    pub can_view_grades: bool,
+    pub can_view_tilt: bool,
+    pub can_view_pnl: bool,
+    pub can_view_mood: bool,
+    pub can_view_reviews: bool,
+    pub status: String,
+    pub invited_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A link joined with the email of the other party, for list views.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AccountabilityLinkWithUser {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub link: AccountabilityLink,
+    pub counterpart_email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InviteCoachRequest {
+    pub coach_email: String,
+    pub can_view_trades: Option<bool>,
+    pub can_view_plan: Option<bool>,
+    pub can_view_grades: Option<bool>,
+    pub can_view_tilt: Option<bool>,
+    pub can_view_pnl: Option<bool>,
+    pub can_view_mood: Option<bool>,
+    pub can_view_reviews: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateLinkPermissionsRequest {
+    pub can_view_trades: Option<bool>,
+    pub can_view_plan: Option<bool>,
+    pub can_view_grades: Option<bool>,
+    pub can_view_tilt: Option<bool>,
+    pub can_view_pnl: Option<bool>,
+    pub can_view_mood: Option<bool>,
+    pub can_view_reviews: Option<bool>,
+}
+
+/// Coach dashboard row: one per actively linked trader.
+#[derive(Debug, Serialize, FromRow)]
+pub struct CoachedTrader {
+    pub link_id: Uuid,
+    pub trader_user_id: Uuid,
+    pub trader_email: String,
+    pub can_view_trades: bool,
+    pub can_view_plan: bool,
+    pub can_view_grades: bool,
+    pub can_view_tilt: bool,
+    pub can_view_pnl: bool,
+    pub can_view_mood: bool,
+    pub can_view_reviews: bool,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub last_trade_at: Option<DateTime<Utc>>,
+    pub open_trades: Option<i64>,
+    pub unacknowledged_tilt_events: Option<i64>,
+}
+
+/// A trader's trade as a coach sees it: `Trade` with every field that can
+/// be withheld made optional. P&L and position sizing are null unless the
+/// link grants `can_view_pnl`, grades unless it grants `can_view_grades`.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CoachTrade {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub symbol: String,
+    pub direction: TradeDirection,
+    pub asset_class: AssetClass,
+    pub status: TradeStatus,
+
+    pub entry_date: DateTime<Utc>,
+    pub entry_price: Decimal,
+    pub quantity: Option<Decimal>,
+    pub stop_loss: Option<Decimal>,
+    pub take_profit: Option<Decimal>,
+
+    pub exit_date: Option<DateTime<Utc>>,
+    pub exit_price: Option<Decimal>,
+    pub actual_exit_price: Option<Decimal>,
+
+    pub pnl: Option<Decimal>,
+    pub pnl_percent: Option<Decimal>,
+    pub commissions: Option<Decimal>,
+    pub net_pnl: Option<Decimal>,
+    pub r_multiple: Option<Decimal>,
+    pub mae: Option<Decimal>,
+    pub mfe: Option<Decimal>,
+    pub hold_time_minutes: Option<i32>,
+
+    pub risk_amount: Option<Decimal>,
+    pub risk_percent: Option<Decimal>,
+    pub position_size_pct: Option<Decimal>,
+    pub conviction: Option<ConvictionLevel>,
+    pub setup_name: Option<String>,
+    pub timeframe: Option<String>,
+
+    pub thesis: Option<String>,
+    pub mistakes: Option<String>,
+    pub lessons: Option<String>,
+    pub emotional_state: Option<String>,
+    pub market_condition: Option<String>,
+
+    pub execution_grade: Option<String>,
+    pub patience_grade: Option<String>,
+    pub discipline_grade: Option<String>,
+    pub overall_grade: Option<String>,
+
+    pub is_paper_trade: bool,
+    pub is_revenge_trade: bool,
+    pub broke_rules: bool,
+    pub followed_plan: bool,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Matches `coach_access_log` table from migration 015.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CoachAccessLog {
+    pub id: Uuid,
+    pub link_id: Uuid,
+    pub coach_user_id: Uuid,
+    pub trader_user_id: Uuid,
+    pub resource: String,
+    pub resource_id: Option<Uuid>,
+    pub accessed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CoachListQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-/// Matches `ai_reviews` table from migration 008.
+/// Matches `ai_reviews` table from migrations 008 and 020.
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct AiReview {
     pub id: Uuid,
@@ -28,6 +28,7 @@ pub struct AiReview {
     pub cost_usd: Option<Decimal>,
     pub prompt_version: Option<String>,
     pub model_used: Option<String>,
+    pub provider: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -60,12 +61,105 @@ pub struct AiReviewResponse {
     pub messages: Vec<AiReviewMessage>,
 }
 
+/// Structured trade review the model is asked to return. Maps onto the
+/// score and content columns of `ai_reviews`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeReviewOutput {
+    pub overall_score: Decimal, // 0-10
+    pub execution_quality_score: Decimal,
+    pub risk_management_score: Decimal,
+    pub plan_adherence_score: Decimal,
+    pub thesis_alignment_score: Option<Decimal>, // 1-5
+    pub strengths: Vec<String>,
+    pub weaknesses: Vec<String>,
+    pub key_lesson: String,
+    pub actionable_fixes: Vec<String>,
+    pub alternative_scenario: Option<String>,
+    pub emotional_state_detected: Option<String>,
+}
+
+/// Structured result of a chart screenshot analysis, stored in
+/// `trade_media.ai_analysis` (migration 006).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartAnalysis {
+    pub pattern: ChartPattern,
+    pub entry_quality: ChartAssessment,
+    pub stop_placement: StopPlacementAssessment,
+    #[serde(default)]
+    pub key_levels: Vec<String>,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartPattern {
+    pub name: String,
+    pub confidence: f64, // 0-1
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartAssessment {
+    pub rating: String, // excellent, good, fair, poor
+    pub assessment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StopPlacementAssessment {
+    pub rating: String, // excellent, good, fair, poor
+    pub assessment: String,
+    pub suggested_stop: Option<String>,
+}
+
+/// What gets written to `trade_media.ai_analysis`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredChartAnalysis {
+    #[serde(flatten)]
+    pub analysis: ChartAnalysis,
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub model_used: String,
+    pub tokens_used: i32,
+    pub analyzed_at: DateTime<Utc>,
+}
+
 // --- Claude API types (not DB-mapped) ---
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClaudeMessage {
     pub role: String,
-    pub content: String,
+    pub content: ClaudeMessageContent,
+}
+
+impl ClaudeMessage {
+    pub fn text(role: &str, text: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: ClaudeMessageContent::Text(text.into()),
+        }
+    }
+}
+
+/// Either a plain string or a list of typed blocks (text, images).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClaudeMessageContent {
+    Text(String),
+    Blocks(Vec<ClaudeContentBlock>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClaudeContentBlock {
+    Text { text: String },
+    Image { source: ClaudeImageSource },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaudeImageSource {
+    #[serde(rename = "type")]
+    pub source_type: String, // base64
+    pub media_type: String,
+    pub data: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,11 +167,15 @@ pub struct ClaudeRequest {
     pub model: String,
     pub max_tokens: i32,
     pub messages: Vec<ClaudeMessage>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ClaudeResponse {
     pub id: String,
+    #[serde(default)]
+    pub model: Option<String>,
     pub content: Vec<ClaudeContent>,
     pub usage: ClaudeUsage,
 }
@@ -86,6 +184,7 @@ pub struct ClaudeResponse {
 pub struct ClaudeContent {
     #[serde(rename = "type")]
     pub content_type: String,
+    #[serde(default)]
     pub text: String,
 }
 
@@ -0,0 +1,77 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `ai_usage_events` table from migration 021.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AiUsageEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub review_id: Option<Uuid>,
+    pub feature: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    pub cost_usd: Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `granularity` is `day` (default) or `month`. Dates are UTC, matching
+/// how budgets reset.
+#[derive(Debug, Deserialize)]
+pub struct AiUsageQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub granularity: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AiUsagePeriod {
+    pub period: NaiveDate,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: Decimal,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AiUsageBreakdown {
+    pub key: String,
+    pub calls: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub cost_usd: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AiBudgetStatus {
+    pub daily_budget_usd: Decimal,
+    pub monthly_budget_usd: Decimal,
+    pub spent_today_usd: Decimal,
+    pub spent_this_month_usd: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AiUsageResponse {
+    pub budget: AiBudgetStatus,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub granularity: String,
+    pub total_cost_usd: Decimal,
+    pub periods: Vec<AiUsagePeriod>,
+    pub by_feature: Vec<AiUsageBreakdown>,
+    pub by_model: Vec<AiUsageBreakdown>,
+    /// Most recent calls in the range, newest first.
+    pub recent: Vec<AiUsageEvent>,
+}
+
+/// Replaces both of the user's overrides. Overrides may only lower the
+/// server limits; `null` falls back to the server default.
+#[derive(Debug, Deserialize)]
+pub struct UpdateAiBudgetRequest {
+    pub daily_budget_usd: Option<Decimal>,
+    pub monthly_budget_usd: Option<Decimal>,
+}
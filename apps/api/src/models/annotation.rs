@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A single drawing on a chart screenshot. Stored as an element of the
+/// `trade_media.annotations` JSONB array (migration 006).
+///
+/// All coordinates and sizes are fractions of the image (0.0 to 1.0, origin
+/// top-left), so annotations survive thumbnails and resized exports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChartAnnotation {
+    pub id: Uuid,
+    /// Incremented on every update; clients send it back to detect lost edits.
+    pub version: i32,
+    #[serde(flatten)]
+    pub shape: AnnotationShape,
+    pub color: Option<String>, // #RRGGBB or #RRGGBBAA
+    pub stroke_width: Option<f64>, // fraction of image width
+    pub note: Option<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnnotationShape {
+    Line {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+    },
+    Rectangle {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: Option<String>,
+    },
+    Arrow {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+    },
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        font_size: Option<f64>, // fraction of image height
+    },
+    PriceLevel {
+        y: f64,
+        price: Option<Decimal>,
+        label: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAnnotationRequest {
+    #[serde(flatten)]
+    pub shape: AnnotationShape,
+    pub color: Option<String>,
+    pub stroke_width: Option<f64>,
+    pub note: Option<String>,
+}
+
+/// `version` must match the stored annotation or the update is rejected.
+/// `shape` replaces the geometry wholesale when present.
+#[derive(Debug, Deserialize)]
+pub struct UpdateAnnotationRequest {
+    pub version: i32,
+    pub shape: Option<AnnotationShape>,
+    pub color: Option<String>,
+    pub stroke_width: Option<f64>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteAnnotationQuery {
+    pub version: i32,
+}
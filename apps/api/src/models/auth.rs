@@ -35,14 +35,14 @@ pub struct RefreshTokenRequest {
     pub refresh_token: String,
 }
 
+/// State of a `refresh_tokens` row needed to rotate it.
 #[derive(Debug, Clone, FromRow)]
 pub struct RefreshToken {
     pub id: Uuid,
-    pub user_id: Uuid,
-    pub token_hash: String,
+    pub session_id: Uuid,
     pub expires_at: DateTime<Utc>,
     pub revoked: bool,
-    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,10 +51,73 @@ pub struct Claims {
     pub email: String,
     pub exp: i64,
     pub iat: i64,
+    /// Session the token was issued for; absent on tokens issued before sessions existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct AuthUser {
     pub user_id: Uuid,
     pub email: String,
+    pub session_id: Option<Uuid>,
+    /// Set when authenticated with an API key; a login session has every scope.
+    pub scopes: Option<Vec<String>>,
+}
+
+impl AuthUser {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|s| s == scope))
+    }
+}
+
+pub const EMAIL_TOKEN_VERIFY_EMAIL: &str = "verify_email";
+pub const EMAIL_TOKEN_PASSWORD_RESET: &str = "password_reset";
+
+/// Account and address a consumed `email_tokens` row (migration 026) was
+/// issued for.
+#[derive(Debug, Clone, FromRow)]
+pub struct EmailToken {
+    pub user_id: Uuid,
+    pub email: String,
+}
+
+/// Claims of a signed email link. Deliberately has no `email` field so it can
+/// never be decoded as an access token, and vice versa.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailTokenClaims {
+    pub sub: String, // user_id
+    pub jti: String, // email_tokens.id
+    pub purpose: String,
+    pub exp: i64,
+    pub iat: i64,
+}
+
+/// Claims of the short-lived token handed out between the password and
+/// second-factor steps of a 2FA login.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeClaims {
+    pub sub: String, // user_id
+    pub purpose: String,
+    pub exp: i64,
+    pub iat: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[serde(alias = "newPassword")]
+    pub new_password: String,
 }
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `comments` table from migration 016.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct Comment {
+    pub id: Uuid,
+    pub trader_user_id: Uuid,
+    pub author_user_id: Uuid,
+    pub subject_type: String,
+    pub subject_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+    pub mentions: Option<Vec<Uuid>>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A comment as seen by the current user, with author and read state.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct CommentWithState {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub comment: Comment,
+    pub author_email: String,
+    pub is_read: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentThreadQuery {
+    pub subject_type: String,
+    pub subject_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCommentRequest {
+    pub subject_type: String,
+    pub subject_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub body: String,
+    pub mentions: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCommentRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarkThreadReadRequest {
+    pub subject_type: String,
+    pub subject_id: Uuid,
+}
+
+/// Matches `trade_review_flags` table from migration 016.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TradeReviewFlag {
+    pub trade_id: Uuid,
+    pub trader_user_id: Uuid,
+    pub note: Option<String>,
+    pub raised_at: DateTime<Utc>,
+    pub cleared_by: Option<Uuid>,
+    pub cleared_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RaiseReviewFlagRequest {
+    pub note: Option<String>,
+}
+
+/// Coach review queue row: an open flag joined with its trade and trader.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReviewQueueItem {
+    pub trade_id: Uuid,
+    pub trader_user_id: Uuid,
+    pub trader_email: String,
+    pub symbol: String,
+    pub entry_date: DateTime<Utc>,
+    pub note: Option<String>,
+    pub raised_at: DateTime<Utc>,
+}
+
+pub fn validate_comment_body(body: &str) -> Result<(), String> {
+    let trimmed = body.trim();
+    if trimmed.is_empty() {
+        return Err("Comment cannot be empty".to_string());
+    }
+    if trimmed.len() > 10_000 {
+        return Err("Comment must be 10000 characters or fewer".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `economic_events` table from migrations 012 and 018.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct EconomicEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub event_date: NaiveDate,
+    pub event_time: Option<NaiveTime>,
+    pub timezone: String,
+    pub event_at: Option<DateTime<Utc>>,
+    pub title: String,
+    pub currency: Option<String>,
+    pub impact: Option<String>,
+    pub description: Option<String>,
+    pub source: String,
+    pub external_uid: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateEconomicEventRequest {
+    pub event_date: NaiveDate,
+    pub event_time: Option<NaiveTime>,
+    pub timezone: Option<String>,
+    pub title: String,
+    pub currency: Option<String>,
+    pub impact: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateEconomicEventRequest {
+    pub event_date: Option<NaiveDate>,
+    pub event_time: Option<NaiveTime>,
+    pub timezone: Option<String>,
+    pub title: Option<String>,
+    pub currency: Option<String>,
+    pub impact: Option<String>,
+    pub description: Option<String>,
+}
+
+/// `impact` and `currency` accept comma-separated lists.
+#[derive(Debug, Deserialize)]
+pub struct EconomicEventQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub impact: Option<String>,
+    pub currency: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportEconomicEventsRequest {
+    pub format: String, // ics, csv
+    pub content: String,
+    /// Zone for floating ICS times and CSV rows without a timezone column.
+    /// Defaults to the user's profile timezone.
+    pub timezone: Option<String>,
+    pub default_currency: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EconomicEventImportResponse {
+    pub inserted_count: usize,
+    pub updated_count: usize,
+    pub error_count: usize,
+    pub errors: Vec<EconomicEventImportError>,
+    pub trades_tagged: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EconomicEventImportError {
+    pub row: usize,
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagTradesRequest {
+    pub window_minutes: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagTradesResponse {
+    pub window_minutes: i32,
+    pub trades_tagged: u64,
+}
+
+pub fn validate_event_title(title: &str) -> Result<(), String> {
+    let trimmed = title.trim();
+    if trimmed.is_empty() || trimmed.len() > 255 {
+        return Err("Title must be between 1 and 255 characters".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_currency(currency: &str) -> Result<(), String> {
+    if currency.len() > 10 || !currency.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid currency '{}'", currency));
+    }
+    Ok(())
+}
@@ -4,9 +4,24 @@ pub mod trade;
 pub mod tag;
 pub mod planning;
 pub mod ai_review;
+pub mod ai_usage;
 pub mod psychology;
 pub mod playbook;
 pub mod review;
+pub mod ruleset;
+pub mod accountability;
+pub mod comment;
+pub mod notification;
+pub mod streak;
+pub mod economic_event;
+pub mod annotation;
+pub mod search;
+pub mod trade_query;
+pub mod pattern;
+pub mod session;
+pub mod two_factor;
+pub mod oauth;
+pub mod api_key;
 
 pub use user::*;
 pub use auth::*;
@@ -14,6 +29,21 @@ pub use trade::*;
 pub use tag::*;
 pub use planning::*;
 pub use ai_review::*;
+pub use ai_usage::*;
 pub use psychology::*;
 pub use playbook::*;
 pub use review::*;
+pub use ruleset::*;
+pub use accountability::*;
+pub use comment::*;
+pub use notification::*;
+pub use streak::*;
+pub use economic_event::*;
+pub use annotation::*;
+pub use search::*;
+pub use trade_query::*;
+pub use pattern::*;
+pub use session::*;
+pub use two_factor::*;
+pub use oauth::*;
+pub use api_key::*;
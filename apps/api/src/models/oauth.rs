@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `oauth_identities` table from migration 029.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct OAuthIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+/// Who the provider says signed in, after a successful code exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthProviderInfo {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthAuthorizeResponse {
+    /// Send the browser here; the provider redirects back to `OAUTH_REDIRECT_URL`
+    pub authorization_url: String,
+    /// Kept by the browser that started the flow (e.g. in sessionStorage) and
+    /// posted back with the callback, so a `code`/`state` pair from someone
+    /// else's flow can't be completed in this browser.
+    pub browser_nonce: String,
+}
+
+/// Query parameters the provider appended to the redirect, posted back by the web app.
+#[derive(Debug, Deserialize)]
+pub struct OAuthCallbackRequest {
+    pub code: String,
+    pub state: String,
+    /// `browser_nonce` from the authorize response that started the flow.
+    pub browser_nonce: String,
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `trade_pattern_findings` table from migrations 025 and 033.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TradePatternFinding {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub rank: i32,
+    pub pattern_type: String,
+    pub condition_key: String,
+    pub title: String,
+    pub description: String,
+    pub sample_size: i32,
+    pub loss_count: i32,
+    pub win_rate: Decimal,
+    pub baseline_win_rate: Decimal,
+    pub expectancy: Decimal,
+    pub baseline_expectancy: Decimal,
+    pub total_pnl: Decimal,
+    pub p_value: f64,
+    /// `p_value` adjusted for every condition tested in the run
+    /// (Benjamini-Hochberg).
+    pub q_value: f64,
+    pub score: f64,
+    pub supporting_trade_ids: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Matches `trade_pattern_runs` table from migration 025.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TradePatternRun {
+    pub user_id: Uuid,
+    pub trades_analyzed: i32,
+    pub findings_count: i32,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TradePatternsResponse {
+    /// `None` until the job has run for this user.
+    pub last_run: Option<TradePatternRun>,
+    pub findings: Vec<TradePatternFinding>,
+}
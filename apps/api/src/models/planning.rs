@@ -1,10 +1,11 @@
+use super::EconomicEvent;
 use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-/// Matches `daily_plans` table from migration 007.
+/// Matches `daily_plans` table from migrations 007 and 022.
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct DailyPlan {
     pub id: Uuid,
@@ -18,6 +19,13 @@ pub struct DailyPlan {
     pub checklist_items: Option<serde_json::Value>,
     pub notes: Option<String>,
     pub ai_plan_of_attack: Option<String>,
+    /// Exact prompt behind `ai_plan_of_attack`, kept for auditing.
+    #[serde(skip_serializing)]
+    pub ai_plan_prompt: Option<String>,
+    pub ai_plan_prompt_version: Option<String>,
+    pub ai_plan_provider: Option<String>,
+    pub ai_plan_model: Option<String>,
+    pub ai_plan_generated_at: Option<DateTime<Utc>>,
     pub adherence_score: Option<Decimal>,
     pub adherence_details: Option<serde_json::Value>,
     pub completed: bool,
@@ -95,4 +103,5 @@ pub struct DailyPlanWithWatchlist {
     #[serde(flatten)]
     pub plan: DailyPlan,
     pub watchlist: Vec<WatchlistItem>,
+    pub economic_events: Vec<EconomicEvent>,
 }
@@ -119,3 +119,18 @@ pub fn validate_playbook_setup(req: &CreatePlaybookSetupRequest) -> Result<(), S
 
     Ok(())
 }
+
+/// Matches `grading_rubrics` table from migration 010.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct GradingRubric {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub criteria: Option<serde_json::Value>,
+    pub threshold_a: Option<Decimal>,
+    pub threshold_b: Option<Decimal>,
+    pub threshold_c: Option<Decimal>,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -68,3 +68,19 @@ pub fn validate_mood_score(value: i32, field_name: &str) -> Result<(), String> {
     }
     Ok(())
 }
+
+/// Matches `tilt_events` table from migration 009.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TiltEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub severity: String,
+    pub trigger_type: String,
+    pub message: String,
+    pub suggested_action: Option<String>,
+    pub trade_ids: Option<Vec<Uuid>>,
+    pub historical_win_rate: Option<Decimal>,
+    pub acknowledged: bool,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,10 +1,11 @@
+use super::AiReview;
 use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-/// Matches `periodic_reviews` table from migration 013.
+/// Matches `periodic_reviews` table from migrations 013 and 023.
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct PeriodicReview {
     pub id: Uuid,
@@ -31,6 +32,8 @@ pub struct PeriodicReview {
     pub patience_rating: Option<i32>,
     pub execution_rating: Option<i32>,
     pub overall_rating: Option<i32>,
+    /// AI draft the narrative fields were accepted from, if any.
+    pub ai_review_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -53,6 +56,7 @@ pub struct CreateReviewRequest {
     pub patience_rating: Option<i32>,
     pub execution_rating: Option<i32>,
     pub overall_rating: Option<i32>,
+    pub ai_review_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,6 +74,38 @@ pub struct UpdateReviewRequest {
     pub patience_rating: Option<i32>,
     pub execution_rating: Option<i32>,
     pub overall_rating: Option<i32>,
+    pub ai_review_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DraftReviewRequest {
+    pub review_type: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+}
+
+/// Narrative fields the model drafts for a periodic review. Nothing is
+/// saved to the review until the user submits their edited version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewDraftOutput {
+    pub what_went_well: String,
+    pub what_to_improve: String,
+    pub key_lessons: Vec<String>,
+    #[serde(default)]
+    pub rules_broken: Vec<String>,
+    pub goals_next_period: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewDraftResponse {
+    pub review_type: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    /// Review already saved for this period, which accepting the draft
+    /// would update rather than create.
+    pub existing_review_id: Option<Uuid>,
+    pub draft: ReviewDraftOutput,
+    pub ai_review: AiReview,
 }
 
 #[derive(Debug, Serialize)]
@@ -110,6 +146,24 @@ pub fn validate_review_type(review_type: &str) -> Result<&'static str, String> {
     }
 }
 
+/// Only weekly and monthly reviews can be drafted, matching the
+/// `ai_reviews.review_type` values.
+pub fn validate_draft_request(req: &DraftReviewRequest) -> Result<(), String> {
+    if !matches!(req.review_type.as_str(), "weekly" | "monthly") {
+        return Err(format!(
+            "Invalid review type '{}'. Drafts are available for: weekly, monthly",
+            req.review_type
+        ));
+    }
+    if req.period_end <= req.period_start {
+        return Err("period_end must be after period_start".to_string());
+    }
+    if (req.period_end - req.period_start).num_days() > 31 {
+        return Err("Draft periods can span at most 31 days".to_string());
+    }
+    Ok(())
+}
+
 pub fn validate_rating(value: i32, field_name: &str) -> Result<(), String> {
     if !(1..=10).contains(&value) {
         return Err(format!("{} must be between 1 and 10", field_name));
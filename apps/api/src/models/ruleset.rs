@@ -0,0 +1,169 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `shared_rulesets` table from migrations 010 and 014.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SharedRuleset {
+    pub id: Uuid,
+    pub creator_user_id: Uuid,
+    pub ruleset_type: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub ruleset_data: serde_json::Value,
+    pub is_public: bool,
+    pub share_token: Option<String>,
+    pub import_count: i32,
+    pub source_id: Option<Uuid>,
+    pub version: i32,
+    pub published_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A ruleset as seen by anyone other than its creator. The share token and
+/// source entity are never exposed.
+#[derive(Debug, Serialize)]
+pub struct PublicRuleset {
+    pub id: Uuid,
+    pub ruleset_type: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub ruleset_data: serde_json::Value,
+    pub import_count: i32,
+    pub version: i32,
+    pub published_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<SharedRuleset> for PublicRuleset {
+    fn from(r: SharedRuleset) -> Self {
+        Self {
+            id: r.id,
+            ruleset_type: r.ruleset_type,
+            name: r.name,
+            description: r.description,
+            ruleset_data: r.ruleset_data,
+            import_count: r.import_count,
+            version: r.version,
+            published_at: r.published_at,
+            updated_at: r.updated_at,
+        }
+    }
+}
+
+/// `ruleset_data` payload for `playbook_setup` rulesets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaybookSetupRulesetData {
+    pub description: Option<String>,
+    pub criteria: Option<serde_json::Value>,
+    pub expected_r_min: Option<Decimal>,
+    pub expected_r_max: Option<Decimal>,
+    pub min_conviction: Option<i32>,
+    pub preferred_timeframe: Option<String>,
+    pub market_regimes: Option<Vec<String>>,
+    pub common_mistakes: Option<String>,
+}
+
+/// `ruleset_data` payload for `rubric` rulesets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RubricRulesetData {
+    pub criteria: Option<serde_json::Value>,
+    pub threshold_a: Option<Decimal>,
+    pub threshold_b: Option<Decimal>,
+    pub threshold_c: Option<Decimal>,
+}
+
+/// `ruleset_data` payload for `checklist` rulesets.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChecklistRulesetData {
+    pub items: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishRulesetRequest {
+    pub ruleset_type: String,
+    pub source_id: Uuid,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRulesetRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+    pub rotate_share_token: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportRulesetRequest {
+    /// Name for the imported entity; defaults to the ruleset name.
+    pub name: Option<String>,
+    /// rename (default), replace or fail
+    pub on_conflict: Option<String>,
+    /// Target plan date, required when importing a checklist.
+    pub plan_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportRulesetResponse {
+    pub ruleset_id: Uuid,
+    pub ruleset_type: String,
+    pub version: i32,
+    pub imported_id: Uuid,
+    pub name: String,
+    pub renamed: bool,
+    pub replaced: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RulesetListQuery {
+    pub ruleset_type: Option<String>,
+    pub search: Option<String>,
+    /// popular (default) or recent
+    pub sort: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+const MAX_NAME_LENGTH: usize = 255;
+const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+
+pub fn validate_ruleset_type(ruleset_type: &str) -> Result<&'static str, String> {
+    match ruleset_type {
+        "checklist" => Ok("checklist"),
+        "rubric" => Ok("rubric"),
+        "playbook_setup" => Ok("playbook_setup"),
+        _ => Err(format!(
+            "Invalid ruleset type '{}'. Allowed: checklist, rubric, playbook_setup",
+            ruleset_type
+        )),
+    }
+}
+
+pub fn validate_ruleset_text(name: Option<&str>, description: Option<&str>) -> Result<(), String> {
+    if let Some(name) = name {
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+            return Err(format!(
+                "Name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            ));
+        }
+    }
+
+    if let Some(desc) = description {
+        if desc.len() > MAX_DESCRIPTION_LENGTH {
+            return Err(format!(
+                "Description must be {} characters or fewer",
+                MAX_DESCRIPTION_LENGTH
+            ));
+        }
+    }
+
+    Ok(())
+}
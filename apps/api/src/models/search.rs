@@ -0,0 +1,37 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const SEARCH_ENTITY_TYPES: &[&str] = &["trade", "mood_log", "daily_plan", "periodic_review"];
+pub const SEARCH_MODES: &[&str] = &["text", "semantic", "hybrid"];
+
+/// `types` is a comma-separated subset of [`SEARCH_ENTITY_TYPES`]. `mode`
+/// defaults to `hybrid` when embeddings are enabled, otherwise `text`.
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub types: Option<String>,
+    pub mode: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub entity_type: String,
+    pub entity_id: Uuid,
+    pub field: String,
+    pub title: String,
+    pub entity_date: Option<NaiveDate>,
+    /// HTML-escaped excerpt with matched words wrapped in `<mark>`.
+    pub snippet: String,
+    pub score: f64,
+    /// `text`, `semantic` or both.
+    pub matched_by: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub mode: String,
+    pub results: Vec<SearchResult>,
+}
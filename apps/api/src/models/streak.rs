@@ -0,0 +1,60 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Matches `user_streaks` table from migration 012.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct UserStreak {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub streak_type: String,
+    pub current_count: Option<i32>,
+    pub best_count: Option<i32>,
+    pub decay_value: Option<Decimal>,
+    pub last_activity_date: Option<NaiveDate>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Matches `streak_events` table from migration 017.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct StreakEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub streak_type: String,
+    pub source: String,
+    pub source_id: Option<Uuid>,
+    pub activity_date: NaiveDate,
+    pub quality: Decimal,
+    pub count_after: i32,
+    pub decay_value_after: Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A streak as of today in the user's timezone. `current_count` is zero once
+/// the streak has lapsed, even before the next activity resets the stored row.
+#[derive(Debug, Serialize)]
+pub struct StreakSummary {
+    pub streak_type: String,
+    pub current_count: i32,
+    pub best_count: i32,
+    pub decay_value: Decimal,
+    pub last_activity_date: Option<NaiveDate>,
+    pub is_active: bool,
+    pub completed_today: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreaksResponse {
+    pub timezone: String,
+    pub today: NaiveDate,
+    pub is_trading_day: bool,
+    pub streaks: Vec<StreakSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreakHistoryQuery {
+    pub limit: Option<i64>,
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
@@ -210,13 +210,37 @@ pub struct TradeMedia {
     pub media_type: String,
     pub s3_key: String,
     pub s3_url: String,
+    pub thumbnail_key: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub file_name: Option<String>,
     pub file_size: Option<i64>,
     pub mime_type: Option<String>,
     pub caption: Option<String>,
     pub annotations: Option<serde_json::Value>,
+    pub ai_analysis: Option<serde_json::Value>,
+    pub upload_status: String, // pending, uploaded
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PresignMediaRequest {
+    pub media_type: String, // screenshot, recording, order_confirmation
+    pub file_name: String,
+    pub mime_type: String,
+    pub file_size: i64,
+    pub caption: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PresignMediaResponse {
+    pub media: TradeMedia,
+    pub upload_url: String,
+    pub method: String,
+    /// Headers the client must send with the PUT for the signature to match.
+    pub headers: std::collections::HashMap<String, String>,
+    pub expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, FromRow, Serialize)]
 pub struct TradeTag {
     pub id: Uuid,
@@ -225,7 +249,10 @@ pub struct TradeTag {
     pub category: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Trade filters shared by the trade list, stats and natural-language query
+/// endpoints. Entry times of day are in the user's profile timezone; the
+/// `_from` bound is inclusive and the `_to` bound exclusive.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TradeFilters {
     pub status: Option<TradeStatus>,
     pub direction: Option<TradeDirection>,
@@ -237,6 +264,7 @@ pub struct TradeFilters {
     pub is_revenge_trade: Option<bool>,
     pub broke_rules: Option<bool>,
     pub followed_plan: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_tag_ids")]
     pub tag_ids: Option<Vec<Uuid>>,
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
@@ -244,16 +272,44 @@ pub struct TradeFilters {
     pub max_pnl: Option<Decimal>,
     pub min_r_multiple: Option<Decimal>,
     pub max_r_multiple: Option<Decimal>,
+    pub entry_time_from: Option<NaiveTime>,
+    pub entry_time_to: Option<NaiveTime>,
 }
 
+/// Tag ids as a JSON array or, from a query string, comma-separated.
+fn deserialize_tag_ids<'de, D>(deserializer: D) -> Result<Option<Vec<Uuid>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TagIds {
+        List(Vec<Uuid>),
+        Csv(String),
+    }
+
+    match Option::<TagIds>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(TagIds::List(ids)) => Ok(Some(ids)),
+        Some(TagIds::Csv(ids)) => ids
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| Uuid::parse_str(id).map_err(serde::de::Error::custom))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+    }
+}
+
+/// Paging and sorting for the trade list. Its filters are read from the
+/// same query string as a separate `TradeFilters`: flattening them in here
+/// would hand every value to serde as a string and reject the booleans.
 #[derive(Debug, Deserialize)]
 pub struct TradeListQuery {
     pub page: Option<i64>,
     pub per_page: Option<i64>,
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
-    #[serde(flatten)]
-    pub filters: TradeFilters,
 }
 
 #[derive(Debug, Serialize)]
@@ -280,3 +336,11 @@ pub struct TradeStats {
     pub largest_loss: Decimal,
     pub avg_hold_time_minutes: Option<i32>,
 }
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TradeStatsGroup {
+    pub key: String,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub stats: TradeStats,
+}
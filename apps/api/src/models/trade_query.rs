@@ -0,0 +1,124 @@
+use super::{Trade, TradeFilters, TradeStats, TradeStatsGroup};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_QUESTION_CHARS: usize = 500;
+pub const MAX_QUERY_TRADES: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct TradeQueryRequest {
+    pub question: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeQueryKind {
+    /// Aggregate statistics over closed trades, optionally grouped
+    Stats,
+    /// The matching trades themselves
+    List,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeGroupBy {
+    Symbol,
+    Setup,
+    Direction,
+    AssetClass,
+    Weekday,
+    Hour,
+    Month,
+}
+
+/// What to compute over the filtered trades. Only a fixed set of
+/// aggregations exists; the model picks one, it never writes SQL.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TradeAggregation {
+    pub kind: TradeQueryKind,
+    #[serde(default)]
+    pub group_by: Option<TradeGroupBy>,
+    #[serde(default)]
+    pub sort_by: Option<String>,
+    #[serde(default)]
+    pub sort_order: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// The model's reading of a question, returned to the user as-is so they
+/// can see exactly which filters produced the answer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterpretedTradeQuery {
+    pub filters: TradeFilters,
+    pub aggregation: TradeAggregation,
+    pub explanation: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TradeQueryData {
+    Stats { stats: TradeStats },
+    Groups { groups: Vec<TradeStatsGroup> },
+    Trades { trades: Vec<Trade> },
+}
+
+#[derive(Debug, Serialize)]
+pub struct TradeQueryResponse {
+    pub question: String,
+    pub interpreted: InterpretedTradeQuery,
+    pub timezone: String,
+    pub data: TradeQueryData,
+}
+
+pub fn validate_trade_filters(filters: &TradeFilters) -> Result<(), String> {
+    if let (Some(from), Some(to)) = (filters.from_date, filters.to_date) {
+        if from > to {
+            return Err("from_date must not be after to_date".to_string());
+        }
+    }
+    if let (Some(min), Some(max)) = (filters.min_pnl, filters.max_pnl) {
+        if min > max {
+            return Err("min_pnl must not be greater than max_pnl".to_string());
+        }
+    }
+    if let (Some(min), Some(max)) = (filters.min_r_multiple, filters.max_r_multiple) {
+        if min > max {
+            return Err("min_r_multiple must not be greater than max_r_multiple".to_string());
+        }
+    }
+    if let (Some(from), Some(to)) = (filters.entry_time_from, filters.entry_time_to) {
+        // from > to is a window that wraps past midnight
+        if from == to {
+            return Err("entry_time_from and entry_time_to must differ".to_string());
+        }
+    }
+    for (name, value) in [("symbol", &filters.symbol), ("setup_name", &filters.setup_name)] {
+        if value.as_deref().is_some_and(|v| v.trim().is_empty() || v.len() > 100) {
+            return Err(format!("{} must be between 1 and 100 characters", name));
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_trade_aggregation(aggregation: &TradeAggregation) -> Result<(), String> {
+    match aggregation.kind {
+        TradeQueryKind::Stats => {
+            if aggregation.sort_by.is_some() || aggregation.limit.is_some() {
+                return Err("sort_by and limit only apply to kind \"list\"".to_string());
+            }
+        }
+        TradeQueryKind::List => {
+            if aggregation.group_by.is_some() {
+                return Err("group_by only applies to kind \"stats\"".to_string());
+            }
+            if aggregation
+                .limit
+                .is_some_and(|l| !(1..=MAX_QUERY_TRADES).contains(&l))
+            {
+                return Err(format!("limit must be between 1 and {}", MAX_QUERY_TRADES));
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
@@ -30,6 +30,8 @@ pub struct UserProfile {
     pub max_trades_per_day: Option<i32>,
     pub max_daily_loss: Option<rust_decimal::Decimal>,
     pub default_commissions: Option<rust_decimal::Decimal>,
+    pub ai_daily_budget_usd: Option<rust_decimal::Decimal>,
+    pub ai_monthly_budget_usd: Option<rust_decimal::Decimal>,
     pub onboarding_completed: bool,
     pub onboarding_completed_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
@@ -64,3 +66,31 @@ pub struct UserWithProfile {
     pub user: User,
     pub profile: Option<UserProfile>,
 }
+
+/// Profile settings applied where a request or plan leaves them out.
+#[derive(Debug, Clone, Default, FromRow)]
+pub struct TradingDefaults {
+    pub default_risk_pct: Option<rust_decimal::Decimal>,
+    pub default_commissions: Option<rust_decimal::Decimal>,
+    pub max_trades_per_day: Option<i32>,
+    pub max_daily_loss: Option<rust_decimal::Decimal>,
+}
+
+/// Where a day's limits came from.
+pub const DAILY_LIMITS_FROM_PLAN: &str = "plan";
+pub const DAILY_LIMITS_FROM_PROFILE: &str = "profile";
+
+#[derive(Debug, Serialize)]
+pub struct DailyLimits {
+    pub date: NaiveDate,
+    pub max_trades: Option<i32>,
+    pub max_daily_loss: Option<rust_decimal::Decimal>,
+    /// `plan` when that day's plan exists, otherwise `profile`
+    pub source: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DailyLimitsQuery {
+    /// Defaults to today in the profile timezone
+    pub date: Option<NaiveDate>,
+}
@@ -0,0 +1,241 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::VerifiedUser;
+use crate::models::{
+    AccountabilityLink, AccountabilityLinkWithUser, AuthUser, CoachAccessLog, CoachListQuery,
+    InviteCoachRequest, UpdateLinkPermissionsRequest,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Trader invites a coach by email. A previously revoked link can be
+/// re-invited; a pending or active one cannot. Requires a verified email
+/// address, since the coach gains access to the journal.
+pub async fn invite_coach(
+    State(pool): State<Arc<PgPool>>,
+    VerifiedUser(auth_user): VerifiedUser,
+    Json(req): Json<InviteCoachRequest>,
+) -> AppResult<Json<AccountabilityLink>> {
+    let coach_email = req.coach_email.trim();
+    if !coach_email.contains('@') {
+        return Err(AppError::Validation("Invalid email format".to_string()));
+    }
+
+    let coach_user_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM users WHERE LOWER(email) = LOWER($1)",
+    )
+    .bind(coach_email)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("No user with that email".to_string()))?;
+
+    if coach_user_id == auth_user.user_id {
+        return Err(AppError::Validation("You cannot invite yourself as a coach".to_string()));
+    }
+
+    let link = sqlx::query_as::<_, AccountabilityLink>(
+        r#"
+        INSERT INTO accountability_links (
+            trader_user_id, coach_user_id,
+            can_view_plan, can_view_grades, can_view_tilt,
+            can_view_pnl, can_view_mood, can_view_reviews, can_view_trades
+        )
+        VALUES (
+            $1, $2,
+            COALESCE($3, TRUE), COALESCE($4, TRUE), COALESCE($5, TRUE),
+            COALESCE($6, FALSE), COALESCE($7, TRUE), COALESCE($8, TRUE), COALESCE($9, TRUE)
+        )
+        ON CONFLICT (trader_user_id, coach_user_id) DO UPDATE SET
+            can_view_plan = EXCLUDED.can_view_plan,
+            can_view_grades = EXCLUDED.can_view_grades,
+            can_view_tilt = EXCLUDED.can_view_tilt,
+            can_view_pnl = EXCLUDED.can_view_pnl,
+            can_view_mood = EXCLUDED.can_view_mood,
+            can_view_reviews = EXCLUDED.can_view_reviews,
+            can_view_trades = EXCLUDED.can_view_trades,
+            status = 'pending',
+            invited_at = NOW(),
+            accepted_at = NULL,
+            revoked_at = NULL
+        WHERE accountability_links.status = 'revoked'
+        RETURNING *
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(coach_user_id)
+    .bind(req.can_view_plan)
+    .bind(req.can_view_grades)
+    .bind(req.can_view_tilt)
+    .bind(req.can_view_pnl)
+    .bind(req.can_view_mood)
+    .bind(req.can_view_reviews)
+    .bind(req.can_view_trades)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| {
+        AppError::Conflict("This coach has already been invited or is already linked".to_string())
+    })?;
+
+    tracing::info!(link_id = %link.id, "Coach invited");
+    Ok(Json(link))
+}
+
+/// Coaches the current trader has invited or linked with.
+pub async fn list_my_coaches(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<AccountabilityLinkWithUser>>> {
+    let links = sqlx::query_as::<_, AccountabilityLinkWithUser>(
+        r#"
+        SELECT al.*, u.email as counterpart_email
+        FROM accountability_links al
+        JOIN users u ON u.id = al.coach_user_id
+        WHERE al.trader_user_id = $1 AND al.status IN ('pending', 'active')
+        ORDER BY al.invited_at DESC
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(links))
+}
+
+/// Pending invitations addressed to the current user as coach.
+pub async fn list_coach_invites(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<AccountabilityLinkWithUser>>> {
+    let links = sqlx::query_as::<_, AccountabilityLinkWithUser>(
+        r#"
+        SELECT al.*, u.email as counterpart_email
+        FROM accountability_links al
+        JOIN users u ON u.id = al.trader_user_id
+        WHERE al.coach_user_id = $1 AND al.status = 'pending'
+        ORDER BY al.invited_at DESC
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(links))
+}
+
+pub async fn accept_invite(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+) -> AppResult<Json<AccountabilityLink>> {
+    let link = sqlx::query_as::<_, AccountabilityLink>(
+        r#"
+        UPDATE accountability_links SET
+            status = 'active',
+            accepted_at = NOW()
+        WHERE id = $1 AND coach_user_id = $2 AND status = 'pending'
+        RETURNING *
+        "#,
+    )
+    .bind(link_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Pending invitation not found".to_string()))?;
+
+    tracing::info!(link_id = %link.id, "Coach invitation accepted");
+    Ok(Json(link))
+}
+
+/// Either the trader or the coach can end the link at any time.
+pub async fn revoke_link(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+) -> AppResult<Json<AccountabilityLink>> {
+    let link = sqlx::query_as::<_, AccountabilityLink>(
+        r#"
+        UPDATE accountability_links SET
+            status = 'revoked',
+            revoked_at = NOW()
+        WHERE id = $1
+          AND (trader_user_id = $2 OR coach_user_id = $2)
+          AND status IN ('pending', 'active')
+        RETURNING *
+        "#,
+    )
+    .bind(link_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Accountability link not found".to_string()))?;
+
+    tracing::info!(link_id = %link.id, "Accountability link revoked");
+    Ok(Json(link))
+}
+
+/// Only the trader controls what the coach may see.
+pub async fn update_link_permissions(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(link_id): Path<Uuid>,
+    Json(req): Json<UpdateLinkPermissionsRequest>,
+) -> AppResult<Json<AccountabilityLink>> {
+    let link = sqlx::query_as::<_, AccountabilityLink>(
+        r#"
+        UPDATE accountability_links SET
+            can_view_plan = COALESCE($1, can_view_plan),
+            can_view_grades = COALESCE($2, can_view_grades),
+            can_view_tilt = COALESCE($3, can_view_tilt),
+            can_view_pnl = COALESCE($4, can_view_pnl),
+            can_view_mood = COALESCE($5, can_view_mood),
+            can_view_reviews = COALESCE($6, can_view_reviews),
+            can_view_trades = COALESCE($7, can_view_trades)
+        WHERE id = $8 AND trader_user_id = $9 AND status IN ('pending', 'active')
+        RETURNING *
+        "#,
+    )
+    .bind(req.can_view_plan)
+    .bind(req.can_view_grades)
+    .bind(req.can_view_tilt)
+    .bind(req.can_view_pnl)
+    .bind(req.can_view_mood)
+    .bind(req.can_view_reviews)
+    .bind(req.can_view_trades)
+    .bind(link_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Accountability link not found".to_string()))?;
+
+    Ok(Json(link))
+}
+
+/// The trader's view of every coach read of their data.
+pub async fn list_coach_access_log(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<CoachAccessLog>>> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let entries = sqlx::query_as::<_, CoachAccessLog>(
+        r#"
+        SELECT * FROM coach_access_log
+        WHERE trader_user_id = $1
+        ORDER BY accessed_at DESC
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(entries))
+}
@@ -0,0 +1,67 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{AuthUser, TradeQueryRequest, TradeQueryResponse, MAX_QUESTION_CHARS};
+use crate::services::{
+    AiService, AiUsageService, StreakService, TradeQueryService, TRADE_QUERY_PROMPT_VERSION,
+};
+use axum::{extract::State, Json};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Answers a plain-English question about the user's trades. The model only
+/// interprets the question into filters; the data comes from the same query
+/// builder as the trade list and stats endpoints.
+pub async fn query_trades(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Json(req): Json<TradeQueryRequest>,
+) -> AppResult<Json<TradeQueryResponse>> {
+    let question = req.question.trim();
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(AppError::Validation(format!(
+            "Question must be between 1 and {} characters",
+            MAX_QUESTION_CHARS
+        )));
+    }
+
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+
+    let tz = StreakService::user_timezone(&pool, auth_user.user_id).await?;
+    let mut usage = Vec::new();
+    let result = ai_service
+        .interpret_trade_query(question, Utc::now().with_timezone(&tz), &mut usage)
+        .await;
+    let query = usage_service
+        .record_on_error(&pool, auth_user.user_id, "trade_query", ai_service.provider_name(), &usage, result)
+        .await?;
+
+    // Record spend before running the query so a failing query still counts
+    usage_service
+        .record(
+            pool.as_ref(),
+            auth_user.user_id,
+            None,
+            "trade_query",
+            ai_service.provider_name(),
+            &usage,
+        )
+        .await?;
+
+    let data = TradeQueryService::execute(&pool, auth_user.user_id, &query, tz).await?;
+
+    tracing::info!(
+        user_id = %auth_user.user_id,
+        prompt_version = TRADE_QUERY_PROMPT_VERSION,
+        interpreted = ?query,
+        "AI trade query answered"
+    );
+
+    Ok(Json(TradeQueryResponse {
+        question: question.to_string(),
+        interpreted: query,
+        timezone: tz.name().to_string(),
+        data,
+    }))
+}
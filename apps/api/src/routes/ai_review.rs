@@ -1,72 +1,215 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{
     AiReview, AiReviewMessage, AiReviewResponse, AuthUser, ChatMessageRequest,
-    ClaudeMessage, CreateAiReviewRequest, Trade,
+    ClaudeMessage, CreateAiReviewRequest, StoredChartAnalysis, Trade,
+};
+use crate::services::{
+    AiContextService, AiService, AiUsageService, ChatStreamOutcome, LlmCompletion, GENERAL_PROMPT_VERSION,
+    TRADE_REVIEW_PROMPT_VERSION,
 };
-use crate::services::AiService;
 use axum::{
     extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
+use serde_json::json;
 use sqlx::PgPool;
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
 
-const MODEL_NAME: &str = "claude-sonnet-4-20250514";
+/// Chart analyses already run on the trade's screenshots, oldest first.
+async fn trade_chart_analyses(pool: &PgPool, trade_id: Uuid) -> AppResult<Vec<StoredChartAnalysis>> {
+    let rows = sqlx::query_scalar::<_, serde_json::Value>(
+        r#"
+        SELECT ai_analysis FROM trade_media
+        WHERE trade_id = $1 AND upload_status = 'uploaded' AND ai_analysis IS NOT NULL
+        ORDER BY created_at
+        "#,
+    )
+    .bind(trade_id)
+    .fetch_all(pool)
+    .await?;
 
-pub async fn create_ai_review(
-    State(pool): State<Arc<PgPool>>,
-    State(ai_service): State<Arc<AiService>>,
-    auth_user: AuthUser,
-    Json(req): Json<CreateAiReviewRequest>,
-) -> AppResult<Json<AiReviewResponse>> {
-    let prompt = req.prompt.trim();
+    Ok(rows
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect())
+}
+
+/// A review's conversation in insertion order. `seq` rather than
+/// `created_at`, which is shared by every row inserted in one transaction.
+const REVIEW_MESSAGES_SQL: &str =
+    "SELECT * FROM ai_review_messages WHERE review_id = $1 ORDER BY seq";
+
+type EventSender = mpsc::UnboundedSender<Result<Event, Infallible>>;
+type EventStream = Sse<UnboundedReceiverStream<Result<Event, Infallible>>>;
+
+/// A validated review request, ready to send to the model.
+struct PendingReview {
+    trade_id: Option<Uuid>,
+    review_type: String,
+    prompt: String,
+    messages: Vec<ClaudeMessage>,
+    chart_analysis: Option<String>,
+    is_trade_review: bool,
+}
+
+impl PendingReview {
+    /// Feature name the review's usage is metered under.
+    fn feature(&self) -> &'static str {
+        if self.is_trade_review {
+            "trade_review"
+        } else {
+            "general_review"
+        }
+    }
+}
+
+async fn prepare_review(
+    pool: &PgPool,
+    ai_service: &AiService,
+    user_id: Uuid,
+    req: CreateAiReviewRequest,
+) -> AppResult<PendingReview> {
+    let prompt = req.prompt.trim().to_string();
     if prompt.is_empty() || prompt.len() > 10_000 {
         return Err(AppError::Validation(
             "Prompt must be between 1 and 10,000 characters".to_string(),
         ));
     }
 
-    let (response_text, tokens) = if let Some(trade_id) = req.trade_id {
+    let mut pending = PendingReview {
+        trade_id: req.trade_id,
+        review_type: req.review_type.unwrap_or_else(|| "trade".to_string()),
+        messages: vec![ClaudeMessage::text("user", prompt.clone())],
+        prompt,
+        chart_analysis: None,
+        is_trade_review: false,
+    };
+
+    if let Some(trade_id) = req.trade_id {
         let trade = sqlx::query_as::<_, Trade>(
             "SELECT * FROM trades WHERE id = $1 AND user_id = $2",
         )
         .bind(trade_id)
-        .bind(auth_user.user_id)
-        .fetch_optional(pool.as_ref())
+        .bind(user_id)
+        .fetch_optional(pool)
         .await?
         .ok_or_else(|| AppError::NotFound("Trade not found".to_string()))?;
 
-        let text = ai_service.analyze_trade(&trade).await?;
-        (text, None)
-    } else {
-        let (response, tok) = ai_service
-            .chat(vec![ClaudeMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            }])
+        let charts = trade_chart_analyses(pool, trade.id)
+            .await?
+            .into_iter()
+            .map(|stored| stored.analysis)
+            .collect::<Vec<_>>();
+        if !charts.is_empty() {
+            pending.chart_analysis = Some(
+                charts
+                    .iter()
+                    .map(AiService::describe_chart_analysis)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+
+        let context = AiContextService::for_trade(pool, &trade).await?;
+        tracing::debug!(
+            trade_id = %trade.id,
+            personality = context.personality.as_str(),
+            sections = context.sections.len(),
+            "Built trade review context"
+        );
+        pending.messages = ai_service.trade_review_messages(
+            &trade,
+            &charts,
+            Some(&pending.prompt),
+            Some(&context),
+        );
+        pending.is_trade_review = true;
+    }
+
+    Ok(pending)
+}
+
+/// Validates the model's reply (repairing trade reviews if needed) and
+/// persists the review with its opening exchange.
+async fn finish_review(
+    pool: &PgPool,
+    ai_service: &AiService,
+    usage_service: &AiUsageService,
+    user_id: Uuid,
+    pending: PendingReview,
+    completion: LlmCompletion,
+) -> AppResult<AiReviewResponse> {
+    let model = completion.model.clone();
+    let feature = pending.feature();
+    let mut usage = Vec::new();
+    let (structured, raw_response, assistant_text, prompt_version) = if pending.is_trade_review {
+        let result = ai_service
+            .complete_trade_review(pending.messages, completion, &mut usage)
+            .await;
+        let result = usage_service
+            .record_on_error(pool, user_id, feature, ai_service.provider_name(), &usage, result)
             .await?;
-        (response, Some(tok))
+        if result.attempts > 1 {
+            tracing::info!(attempts = result.attempts, "AI trade review needed repair");
+        }
+        let text = AiService::format_trade_review(&result.review);
+        (
+            Some(result.review),
+            result.raw_response,
+            text,
+            TRADE_REVIEW_PROMPT_VERSION,
+        )
+    } else {
+        usage.push(completion.usage());
+        (None, completion.text.clone(), completion.text, GENERAL_PROMPT_VERSION)
     };
+    let tokens: i32 = usage.iter().map(|u| u.tokens()).sum();
+    let cost = usage_service.cost(&usage);
+
+    let mut tx = pool.begin().await?;
 
     // Insert into ai_reviews using actual DB columns from migration 008
     let review = sqlx::query_as::<_, AiReview>(
         r#"
         INSERT INTO ai_reviews (
             user_id, trade_id, review_type,
-            raw_response, tokens_used, model_used
+            overall_score, execution_quality_score, risk_management_score,
+            plan_adherence_score, thesis_alignment_score,
+            strengths, weaknesses, key_lesson, actionable_fixes,
+            alternative_scenario, emotional_state_detected,
+            raw_response, chart_analysis, tokens_used, cost_usd, prompt_version, provider, model_used
         )
-        VALUES ($1, $2, $3, $4, $5, $6)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21)
         RETURNING *
         "#,
     )
-    .bind(auth_user.user_id)
-    .bind(req.trade_id)
-    .bind(req.review_type.as_deref().unwrap_or("trade"))
-    .bind(&response_text)
+    .bind(user_id)
+    .bind(pending.trade_id)
+    .bind(&pending.review_type)
+    .bind(structured.as_ref().map(|r| r.overall_score))
+    .bind(structured.as_ref().map(|r| r.execution_quality_score))
+    .bind(structured.as_ref().map(|r| r.risk_management_score))
+    .bind(structured.as_ref().map(|r| r.plan_adherence_score))
+    .bind(structured.as_ref().and_then(|r| r.thesis_alignment_score))
+    .bind(structured.as_ref().map(|r| r.strengths.clone()))
+    .bind(structured.as_ref().map(|r| r.weaknesses.clone()))
+    .bind(structured.as_ref().map(|r| r.key_lesson.clone()))
+    .bind(structured.as_ref().map(|r| r.actionable_fixes.clone()))
+    .bind(structured.as_ref().and_then(|r| r.alternative_scenario.clone()))
+    .bind(structured.as_ref().and_then(|r| r.emotional_state_detected.clone()))
+    .bind(&raw_response)
+    .bind(&pending.chart_analysis)
     .bind(tokens)
-    .bind(MODEL_NAME)
-    .fetch_one(pool.as_ref())
+    .bind(cost)
+    .bind(prompt_version)
+    .bind(ai_service.provider_name())
+    .bind(&model)
+    .fetch_one(&mut *tx)
     .await?;
 
     // Store the conversation as messages
@@ -74,23 +217,163 @@ pub async fn create_ai_review(
         "INSERT INTO ai_review_messages (review_id, role, content) VALUES ($1, 'user', $2) RETURNING *",
     )
     .bind(review.id)
-    .bind(prompt)
-    .fetch_one(pool.as_ref())
+    .bind(&pending.prompt)
+    .fetch_one(&mut *tx)
     .await?;
 
     let assistant_msg = sqlx::query_as::<_, AiReviewMessage>(
-        "INSERT INTO ai_review_messages (review_id, role, content) VALUES ($1, 'assistant', $2) RETURNING *",
+        "INSERT INTO ai_review_messages (review_id, role, content, tokens_used) VALUES ($1, 'assistant', $2, $3) RETURNING *",
     )
     .bind(review.id)
-    .bind(&response_text)
-    .fetch_one(pool.as_ref())
+    .bind(&assistant_text)
+    .bind(tokens)
+    .fetch_one(&mut *tx)
     .await?;
 
+    usage_service
+        .record(&mut *tx, user_id, Some(review.id), feature, ai_service.provider_name(), &usage)
+        .await?;
+
+    tx.commit().await?;
+
     tracing::info!(review_id = %review.id, "AI review created");
 
-    Ok(Json(AiReviewResponse {
+    Ok(AiReviewResponse {
         review,
         messages: vec![user_msg, assistant_msg],
+    })
+}
+
+/// Sends a named SSE event. Returns false once the client has gone away.
+fn send_event<T: serde::Serialize>(tx: &EventSender, name: &str, data: &T) -> bool {
+    match Event::default().event(name).json_data(data) {
+        Ok(event) => tx.send(Ok(event)).is_ok(),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to encode SSE event");
+            false
+        }
+    }
+}
+
+/// Ends the stream with `done` carrying the saved review, or `error` with
+/// the same code and message the JSON endpoints would return.
+fn send_result(tx: &EventSender, result: AppResult<AiReviewResponse>) {
+    match result {
+        Ok(response) => {
+            send_event(tx, "done", &response);
+        }
+        Err(e) => {
+            let trace_id = Uuid::new_v4().to_string();
+            e.log(&trace_id);
+            let (status, code, message, include_trace) = e.client_parts();
+            send_event(
+                tx,
+                "error",
+                &json!({
+                    "status": status.as_u16(),
+                    "code": code,
+                    "message": message,
+                    "trace_id": include_trace.then_some(trace_id),
+                }),
+            );
+        }
+    }
+}
+
+/// Who a streamed call is metered against if the client abandons it.
+struct StreamOwner {
+    pool: Arc<PgPool>,
+    usage_service: Arc<AiUsageService>,
+    user_id: Uuid,
+    review_id: Option<Uuid>,
+    feature: &'static str,
+}
+
+/// Runs the model in the background, forwarding text as `delta` events.
+/// If the client disconnects the upstream request is cancelled and only the
+/// usage so far is recorded.
+fn stream_reply<F, Fut>(
+    ai_service: Arc<AiService>,
+    messages: Vec<ClaudeMessage>,
+    owner: StreamOwner,
+    finish: F,
+) -> EventStream
+where
+    F: FnOnce(LlmCompletion) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = AppResult<AiReviewResponse>> + Send,
+{
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let outcome = ai_service
+            .chat_stream(messages, |text| send_event(&tx, "delta", &json!({ "text": text })))
+            .await;
+
+        let result = match outcome {
+            Ok(ChatStreamOutcome::Completed(completion)) => finish(completion).await,
+            Ok(ChatStreamOutcome::ClientDisconnected(usage)) => {
+                tracing::info!("AI stream abandoned by client; recording usage only");
+                owner
+                    .usage_service
+                    .record_discarded(
+                        &owner.pool,
+                        owner.user_id,
+                        owner.review_id,
+                        owner.feature,
+                        ai_service.provider_name(),
+                        &[usage],
+                    )
+                    .await;
+                return;
+            }
+            Err(e) => Err(e),
+        };
+        send_result(&tx, result);
+    });
+
+    Sse::new(UnboundedReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+pub async fn create_ai_review(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateAiReviewRequest>,
+) -> AppResult<Json<AiReviewResponse>> {
+    let pending = prepare_review(&pool, &ai_service, auth_user.user_id, req).await?;
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+    let completion = ai_service.chat(pending.messages.clone()).await?;
+    let review =
+        finish_review(&pool, &ai_service, &usage_service, auth_user.user_id, pending, completion).await?;
+    Ok(Json(review))
+}
+
+/// Same as `create_ai_review`, streamed as server-sent events: `delta`
+/// events with text as it is generated, then `done` or `error`. Validation
+/// failures before the model is called are returned as normal JSON errors.
+pub async fn create_ai_review_stream(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateAiReviewRequest>,
+) -> AppResult<EventStream> {
+    let user_id = auth_user.user_id;
+    let pending = prepare_review(&pool, &ai_service, user_id, req).await?;
+    usage_service.ensure_within_budget(&pool, user_id).await?;
+    let messages = pending.messages.clone();
+    let service = ai_service.clone();
+    let owner = StreamOwner {
+        pool: pool.clone(),
+        usage_service: usage_service.clone(),
+        user_id,
+        review_id: None,
+        feature: pending.feature(),
+    };
+
+    Ok(stream_reply(ai_service, messages, owner, move |completion| async move {
+        finish_review(&pool, &service, &usage_service, user_id, pending, completion).await
     }))
 }
 
@@ -108,9 +391,7 @@ pub async fn get_ai_review(
     .await?
     .ok_or_else(|| AppError::NotFound("AI review not found".to_string()))?;
 
-    let messages = sqlx::query_as::<_, AiReviewMessage>(
-        "SELECT * FROM ai_review_messages WHERE review_id = $1 ORDER BY created_at",
-    )
+    let messages = sqlx::query_as::<_, AiReviewMessage>(REVIEW_MESSAGES_SQL)
     .bind(review_id)
     .fetch_all(pool.as_ref())
     .await?;
@@ -132,91 +413,159 @@ pub async fn list_ai_reviews(
     Ok(Json(reviews))
 }
 
-pub async fn continue_chat(
-    State(pool): State<Arc<PgPool>>,
-    State(ai_service): State<Arc<AiService>>,
-    auth_user: AuthUser,
-    Path(review_id): Path<Uuid>,
-    Json(req): Json<ChatMessageRequest>,
-) -> AppResult<Json<AiReviewResponse>> {
-    let message = req.message.trim();
+/// A validated follow-up message with the conversation so far.
+struct PendingChat {
+    user_id: Uuid,
+    review_id: Uuid,
+    message: String,
+    messages: Vec<ClaudeMessage>,
+}
+
+async fn prepare_chat(
+    pool: &PgPool,
+    user_id: Uuid,
+    review_id: Uuid,
+    req: ChatMessageRequest,
+) -> AppResult<PendingChat> {
+    let message = req.message.trim().to_string();
     if message.is_empty() || message.len() > 10_000 {
         return Err(AppError::Validation(
             "Message must be between 1 and 10,000 characters".to_string(),
         ));
     }
 
-    let review = sqlx::query_as::<_, AiReview>(
-        "SELECT * FROM ai_reviews WHERE id = $1 AND user_id = $2",
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM ai_reviews WHERE id = $1 AND user_id = $2)",
     )
     .bind(review_id)
-    .bind(auth_user.user_id)
-    .fetch_optional(pool.as_ref())
+    .bind(user_id)
+    .fetch_one(pool)
     .await?
+    .then_some(())
     .ok_or_else(|| AppError::NotFound("AI review not found".to_string()))?;
 
-    let existing_messages = sqlx::query_as::<_, AiReviewMessage>(
-        "SELECT * FROM ai_review_messages WHERE review_id = $1 ORDER BY created_at",
-    )
+    let existing_messages = sqlx::query_as::<_, AiReviewMessage>(REVIEW_MESSAGES_SQL)
     .bind(review_id)
-    .fetch_all(pool.as_ref())
+    .fetch_all(pool)
     .await?;
 
-    let mut claude_messages: Vec<ClaudeMessage> = existing_messages
+    let mut messages: Vec<ClaudeMessage> = existing_messages
         .iter()
-        .map(|m| ClaudeMessage {
-            role: m.role.clone(),
-            content: m.content.clone(),
-        })
+        .map(|m| ClaudeMessage::text(&m.role, m.content.clone()))
         .collect();
 
-    claude_messages.push(ClaudeMessage {
-        role: "user".to_string(),
-        content: message.to_string(),
-    });
+    messages.push(ClaudeMessage::text("user", message.clone()));
 
-    let (response_text, tokens) = ai_service.chat(claude_messages).await?;
+    Ok(PendingChat {
+        user_id,
+        review_id,
+        message,
+        messages,
+    })
+}
 
-    // Update token count on the review
-    sqlx::query("UPDATE ai_reviews SET tokens_used = COALESCE(tokens_used, 0) + $1 WHERE id = $2")
-        .bind(tokens)
-        .bind(review_id)
-        .execute(pool.as_ref())
+async fn finish_chat(
+    pool: &PgPool,
+    provider: &str,
+    usage_service: &AiUsageService,
+    pending: PendingChat,
+    completion: LlmCompletion,
+) -> AppResult<AiReviewResponse> {
+    let review_id = pending.review_id;
+    let tokens = completion.tokens();
+    let mut tx = pool.begin().await?;
+
+    let cost = usage_service
+        .record(&mut *tx, pending.user_id, Some(review_id), "review_chat", provider, &[completion.usage()])
         .await?;
 
+    // Update token count and spend on the review. The row lock this takes
+    // holds off other chats on the review until commit, so each exchange
+    // gets adjacent `seq` values.
+    sqlx::query(
+        "UPDATE ai_reviews SET tokens_used = COALESCE(tokens_used, 0) + $1, cost_usd = COALESCE(cost_usd, 0) + $2 WHERE id = $3",
+    )
+    .bind(tokens)
+    .bind(cost)
+    .bind(review_id)
+    .execute(&mut *tx)
+    .await?;
+
     // Insert both messages
     sqlx::query("INSERT INTO ai_review_messages (review_id, role, content, tokens_used) VALUES ($1, 'user', $2, NULL)")
         .bind(review_id)
-        .bind(message)
-        .execute(pool.as_ref())
+        .bind(&pending.message)
+        .execute(&mut *tx)
         .await?;
 
     sqlx::query("INSERT INTO ai_review_messages (review_id, role, content, tokens_used) VALUES ($1, 'assistant', $2, $3)")
         .bind(review_id)
-        .bind(&response_text)
+        .bind(&completion.text)
         .bind(tokens)
-        .execute(pool.as_ref())
+        .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
+
     // Re-fetch all messages for the response
-    let all_messages = sqlx::query_as::<_, AiReviewMessage>(
-        "SELECT * FROM ai_review_messages WHERE review_id = $1 ORDER BY created_at",
-    )
+    let all_messages = sqlx::query_as::<_, AiReviewMessage>(REVIEW_MESSAGES_SQL)
     .bind(review_id)
-    .fetch_all(pool.as_ref())
+    .fetch_all(pool)
     .await?;
 
-    // Re-fetch updated review (tokens_used changed)
+    // Re-fetch updated review (tokens_used and cost_usd changed)
     let updated_review = sqlx::query_as::<_, AiReview>(
         "SELECT * FROM ai_reviews WHERE id = $1",
     )
     .bind(review_id)
-    .fetch_one(pool.as_ref())
+    .fetch_one(pool)
     .await?;
 
-    Ok(Json(AiReviewResponse {
+    Ok(AiReviewResponse {
         review: updated_review,
         messages: all_messages,
+    })
+}
+
+pub async fn continue_chat(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Path(review_id): Path<Uuid>,
+    Json(req): Json<ChatMessageRequest>,
+) -> AppResult<Json<AiReviewResponse>> {
+    let pending = prepare_chat(&pool, auth_user.user_id, review_id, req).await?;
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+    let completion = ai_service.chat(pending.messages.clone()).await?;
+    let response =
+        finish_chat(&pool, ai_service.provider_name(), &usage_service, pending, completion).await?;
+    Ok(Json(response))
+}
+
+/// Streaming variant of `continue_chat`; see `create_ai_review_stream`.
+pub async fn continue_chat_stream(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Path(review_id): Path<Uuid>,
+    Json(req): Json<ChatMessageRequest>,
+) -> AppResult<EventStream> {
+    let pending = prepare_chat(&pool, auth_user.user_id, review_id, req).await?;
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+    let messages = pending.messages.clone();
+    let provider = ai_service.provider_name();
+    let owner = StreamOwner {
+        pool: pool.clone(),
+        usage_service: usage_service.clone(),
+        user_id: auth_user.user_id,
+        review_id: Some(review_id),
+        feature: "review_chat",
+    };
+
+    Ok(stream_reply(ai_service, messages, owner, move |completion| async move {
+        finish_chat(&pool, provider, &usage_service, pending, completion).await
     }))
 }
 
@@ -237,3 +586,105 @@ pub async fn delete_ai_review(
 
     Ok(Json(serde_json::json!({ "message": "Review deleted" })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::services::FakeProvider;
+    use crate::test_db::TestDatabase;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_chats_keep_replies_after_their_prompts() {
+        let Some(db) = TestDatabase::migrated().await else { return };
+        let user_id = db.insert_user("chat@example.com").await;
+        let review_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO ai_reviews (user_id, review_type) VALUES ($1, 'general') RETURNING id",
+        )
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        let usage_service = Arc::new(AiUsageService::new(&Config::for_tests()));
+
+        let mut chats = tokio::task::JoinSet::new();
+        for turn in 1..=6 {
+            let (pool, usage_service) = (db.pool.clone(), usage_service.clone());
+            chats.spawn(async move {
+                let pending = PendingChat {
+                    user_id,
+                    review_id,
+                    message: format!("question {}", turn),
+                    messages: Vec::new(),
+                };
+                let completion = LlmCompletion {
+                    text: format!("answer {}", turn),
+                    model: "fake-deterministic".to_string(),
+                    input_tokens: 10,
+                    output_tokens: 5,
+                };
+                finish_chat(&pool, "fake", &usage_service, pending, completion).await
+            });
+        }
+        while let Some(result) = chats.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let messages = sqlx::query_as::<_, AiReviewMessage>(REVIEW_MESSAGES_SQL)
+            .bind(review_id)
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        db.drop().await;
+
+        assert_eq!(messages.len(), 12);
+        for pair in messages.chunks(2) {
+            assert_eq!(pair[0].role, "user");
+            assert_eq!(pair[1].content, pair[0].content.replace("question", "answer"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_stream_is_still_metered() {
+        let Some(db) = TestDatabase::migrated().await else { return };
+        let user_id = db.insert_user("stream@example.com").await;
+        let pool = Arc::new(db.pool.clone());
+        let ai_service = Arc::new(AiService::with_provider(Arc::new(FakeProvider::with_replies(vec![
+            "A long answer that nobody reads".to_string(),
+        ]))));
+        let owner = StreamOwner {
+            pool: pool.clone(),
+            usage_service: Arc::new(AiUsageService::new(&Config::for_tests())),
+            user_id,
+            review_id: None,
+            feature: "general_review",
+        };
+
+        // Dropping the response is what a disconnected client looks like
+        drop(stream_reply(ai_service, vec![ClaudeMessage::text("user", "Review")], owner, |_| async {
+            Err(AppError::Internal("finished a stream that was abandoned".to_string()))
+        }));
+
+        let mut recorded = None;
+        for _ in 0..50 {
+            recorded = sqlx::query_as::<_, (String, i32, i32)>(
+                "SELECT feature, input_tokens, output_tokens FROM ai_usage_events WHERE user_id = $1",
+            )
+            .bind(user_id)
+            .fetch_optional(pool.as_ref())
+            .await
+            .unwrap();
+            if recorded.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        db.drop().await;
+
+        let (feature, input_tokens, output_tokens) = recorded.expect("no usage recorded");
+        assert_eq!(feature, "general_review");
+        assert_eq!(input_tokens, 2);
+        assert!(output_tokens > 0);
+    }
+}
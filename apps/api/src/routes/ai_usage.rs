@@ -0,0 +1,172 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AiBudgetStatus, AiUsageBreakdown, AiUsageEvent, AiUsagePeriod, AiUsageQuery, AiUsageResponse, AuthUser,
+    UpdateAiBudgetRequest,
+};
+use crate::services::AiUsageService;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, Duration, Months, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MAX_USAGE_RANGE_DAYS: i64 = 731;
+const RECENT_USAGE_LIMIT: i64 = 25;
+
+async fn usage_breakdown(
+    pool: &PgPool,
+    user_id: Uuid,
+    column: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> AppResult<Vec<AiUsageBreakdown>> {
+    // `column` is one of two fixed identifiers, never user input
+    let rows = sqlx::query_as::<_, AiUsageBreakdown>(&format!(
+        r#"
+        SELECT {column} AS key,
+               COUNT(*) AS calls,
+               COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens,
+               COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens,
+               COALESCE(SUM(cost_usd), 0) AS cost_usd
+        FROM ai_usage_events
+        WHERE user_id = $1
+          AND created_at >= $2::date AT TIME ZONE 'UTC'
+          AND created_at < ($3::date + 1) AT TIME ZONE 'UTC'
+        GROUP BY {column}
+        ORDER BY cost_usd DESC, calls DESC
+        "#
+    ))
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Spend over time plus current budget status. Defaults to the last 30
+/// days by day, or the last 12 months by month.
+pub async fn get_ai_usage(
+    State(pool): State<Arc<PgPool>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Query(query): Query<AiUsageQuery>,
+) -> AppResult<Json<AiUsageResponse>> {
+    let granularity = query.granularity.as_deref().unwrap_or("day").to_lowercase();
+    if granularity != "day" && granularity != "month" {
+        return Err(AppError::Validation(
+            "granularity must be 'day' or 'month'".to_string(),
+        ));
+    }
+
+    let to = query.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = query.from.unwrap_or_else(|| {
+        if granularity == "month" {
+            to.with_day(1)
+                .and_then(|d| d.checked_sub_months(Months::new(11)))
+                .unwrap_or(to)
+        } else {
+            to - Duration::days(29)
+        }
+    });
+    if from > to {
+        return Err(AppError::Validation("from must be on or before to".to_string()));
+    }
+    if (to - from).num_days() > MAX_USAGE_RANGE_DAYS {
+        return Err(AppError::Validation(format!(
+            "Date range cannot exceed {} days",
+            MAX_USAGE_RANGE_DAYS
+        )));
+    }
+
+    let periods = sqlx::query_as::<_, AiUsagePeriod>(
+        r#"
+        SELECT date_trunc($4, created_at AT TIME ZONE 'UTC')::date AS period,
+               COUNT(*) AS calls,
+               COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens,
+               COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens,
+               COALESCE(SUM(cost_usd), 0) AS cost_usd
+        FROM ai_usage_events
+        WHERE user_id = $1
+          AND created_at >= $2::date AT TIME ZONE 'UTC'
+          AND created_at < ($3::date + 1) AT TIME ZONE 'UTC'
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(from)
+    .bind(to)
+    .bind(&granularity)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let by_feature = usage_breakdown(&pool, auth_user.user_id, "feature", from, to).await?;
+    let by_model = usage_breakdown(&pool, auth_user.user_id, "model", from, to).await?;
+    let recent = sqlx::query_as::<_, AiUsageEvent>(
+        r#"
+        SELECT * FROM ai_usage_events
+        WHERE user_id = $1
+          AND created_at >= $2::date AT TIME ZONE 'UTC'
+          AND created_at < ($3::date + 1) AT TIME ZONE 'UTC'
+        ORDER BY created_at DESC
+        LIMIT $4
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(from)
+    .bind(to)
+    .bind(RECENT_USAGE_LIMIT)
+    .fetch_all(pool.as_ref())
+    .await?;
+    let budget = usage_service.status(&pool, auth_user.user_id).await?;
+    let total_cost_usd = periods.iter().map(|p| p.cost_usd).sum::<Decimal>();
+
+    Ok(Json(AiUsageResponse {
+        budget,
+        from,
+        to,
+        granularity,
+        total_cost_usd,
+        periods,
+        by_feature,
+        by_model,
+        recent,
+    }))
+}
+
+pub async fn update_ai_budget(
+    State(pool): State<Arc<PgPool>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Json(req): Json<UpdateAiBudgetRequest>,
+) -> AppResult<Json<AiBudgetStatus>> {
+    let (daily, monthly) = usage_service.server_budgets();
+    AiUsageService::validate_override("daily_budget_usd", daily, req.daily_budget_usd)
+        .map_err(AppError::Validation)?;
+    AiUsageService::validate_override("monthly_budget_usd", monthly, req.monthly_budget_usd)
+        .map_err(AppError::Validation)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_profiles (user_id, ai_daily_budget_usd, ai_monthly_budget_usd)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE SET
+            ai_daily_budget_usd = EXCLUDED.ai_daily_budget_usd,
+            ai_monthly_budget_usd = EXCLUDED.ai_monthly_budget_usd
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(req.daily_budget_usd.map(|d| d.round_dp(2)))
+    .bind(req.monthly_budget_usd.map(|d| d.round_dp(2)))
+    .execute(pool.as_ref())
+    .await?;
+
+    let status = usage_service.status(&pool, auth_user.user_id).await?;
+    Ok(Json(status))
+}
@@ -1,9 +1,18 @@
-use crate::error::AppResult;
-use crate::models::AuthUser;
-use axum::{extract::State, Json};
+use crate::error::{AppError, AppResult};
+use crate::models::{AuthUser, TradePatternsResponse};
+use crate::services::{
+    hold_time_buckets, minute_histogram, session_performance, EconomicCalendarService,
+    HoldTimePerformance, MinuteBucketPerformance, PatternMiningService, SessionAnalyticsService,
+    SessionPerformance, StreakService, DEFAULT_HISTOGRAM_BUCKET_MINUTES, DEFAULT_PROXIMITY_MINUTES,
+    HISTOGRAM_BUCKET_MINUTES, MAX_PATTERN_FINDINGS, MAX_PROXIMITY_MINUTES,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 use std::sync::Arc;
 
@@ -155,11 +164,25 @@ pub async fn get_setup_performance(
     Ok(Json(setups))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TimeBasedQuery {
+    /// Width of the time-of-day histogram buckets; one of 1, 5, 10, 15, 30, 60
+    pub bucket_minutes: Option<u32>,
+}
+
+/// Hours, weekdays, months and the histogram are in the profile timezone;
+/// sessions are in `session_timezone`.
 #[derive(Debug, Serialize)]
 pub struct TimeBasedAnalytics {
+    pub timezone: String,
+    pub session_timezone: String,
     pub hourly: Vec<HourlyPerformance>,
     pub daily: Vec<DailyPerformance>,
     pub monthly: Vec<MonthlyPerformance>,
+    pub sessions: Vec<SessionPerformance>,
+    pub bucket_minutes: u32,
+    pub minute_histogram: Vec<MinuteBucketPerformance>,
+    pub hold_time: Vec<HoldTimePerformance>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -189,13 +212,29 @@ pub struct MonthlyPerformance {
 
 pub async fn get_time_based_analytics(
     State(pool): State<Arc<PgPool>>,
+    State(session_analytics): State<Arc<SessionAnalyticsService>>,
     auth_user: AuthUser,
+    Query(query): Query<TimeBasedQuery>,
 ) -> AppResult<Json<TimeBasedAnalytics>> {
+    let bucket_minutes = query.bucket_minutes.unwrap_or(DEFAULT_HISTOGRAM_BUCKET_MINUTES);
+    if !HISTOGRAM_BUCKET_MINUTES.contains(&bucket_minutes) {
+        return Err(AppError::Validation(format!(
+            "bucket_minutes must be one of: {}",
+            HISTOGRAM_BUCKET_MINUTES
+                .iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    let tz = StreakService::user_timezone(&pool, auth_user.user_id).await?;
+
     // Hourly performance
     let hourly = sqlx::query_as::<_, HourlyPerformance>(
         r#"
         SELECT 
-            EXTRACT(HOUR FROM entry_date)::INTEGER as hour,
+            EXTRACT(HOUR FROM entry_date AT TIME ZONE $2)::INTEGER as hour,
             COUNT(*) as trade_count,
             COALESCE(
                 CAST(COUNT(*) FILTER (WHERE net_pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
@@ -209,6 +248,7 @@ pub async fn get_time_based_analytics(
         "#,
     )
     .bind(auth_user.user_id)
+    .bind(tz.name())
     .fetch_all(pool.as_ref())
     .await?;
 
@@ -216,8 +256,8 @@ pub async fn get_time_based_analytics(
     let daily = sqlx::query_as::<_, DailyPerformance>(
         r#"
         SELECT 
-            EXTRACT(DOW FROM entry_date)::INTEGER as day_of_week,
-            TO_CHAR(entry_date, 'Day') as day_name,
+            EXTRACT(DOW FROM entry_date AT TIME ZONE $2)::INTEGER as day_of_week,
+            TO_CHAR(entry_date AT TIME ZONE $2, 'FMDay') as day_name,
             COUNT(*) as trade_count,
             COALESCE(
                 CAST(COUNT(*) FILTER (WHERE net_pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
@@ -231,6 +271,7 @@ pub async fn get_time_based_analytics(
         "#,
     )
     .bind(auth_user.user_id)
+    .bind(tz.name())
     .fetch_all(pool.as_ref())
     .await?;
 
@@ -238,7 +279,7 @@ pub async fn get_time_based_analytics(
     let monthly = sqlx::query_as::<_, MonthlyPerformance>(
         r#"
         SELECT 
-            TO_CHAR(entry_date, 'YYYY-MM') as month,
+            TO_CHAR(entry_date AT TIME ZONE $2, 'YYYY-MM') as month,
             COUNT(*) as trade_count,
             COALESCE(SUM(net_pnl), 0) as total_pnl,
             COALESCE(
@@ -253,13 +294,24 @@ pub async fn get_time_based_analytics(
         "#,
     )
     .bind(auth_user.user_id)
+    .bind(tz.name())
     .fetch_all(pool.as_ref())
     .await?;
 
+    // Session, minute-of-day and hold-time breakdowns
+    let trades = SessionAnalyticsService::closed_trades(&pool, auth_user.user_id).await?;
+    let session_table = session_analytics.sessions();
+
     Ok(Json(TimeBasedAnalytics {
+        timezone: tz.name().to_string(),
+        session_timezone: session_table.timezone().name().to_string(),
         hourly,
         daily,
         monthly,
+        sessions: session_performance(&trades, session_table),
+        bucket_minutes,
+        minute_histogram: minute_histogram(&trades, tz, bucket_minutes),
+        hold_time: hold_time_buckets(&trades),
     }))
 }
 
@@ -337,3 +389,287 @@ pub async fn get_drawdown_analysis(
         drawdown_periods: vec![],
     }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct EventProximityQuery {
+    pub window_minutes: Option<i32>,
+    /// Comma-separated impact levels; defaults to `high`.
+    pub impact: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventProximityAnalytics {
+    pub window_minutes: i32,
+    pub impacts: Vec<String>,
+    pub by_proximity: Vec<EventProximityPerformance>,
+    pub by_event: Vec<EventPerformance>,
+}
+
+/// `proximity` is `before_event` (entered within the window ahead of a
+/// release), `after_event`, or `no_event`.
+#[derive(Debug, Serialize, FromRow)]
+pub struct EventProximityPerformance {
+    pub proximity: String,
+    pub trade_count: i64,
+    pub win_rate: Decimal,
+    pub total_pnl: Decimal,
+    pub avg_pnl: Decimal,
+    pub avg_r_multiple: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct EventPerformance {
+    pub event_title: String,
+    pub trade_count: i64,
+    pub win_rate: Decimal,
+    pub total_pnl: Decimal,
+    pub avg_pnl: Decimal,
+    pub avg_minutes_from_event: Option<Decimal>,
+}
+
+/// Closed-trade performance split by distance to the nearest economic event,
+/// e.g. to see whether trading around CPI or FOMC costs money.
+pub async fn get_event_proximity_analytics(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<EventProximityQuery>,
+) -> AppResult<Json<EventProximityAnalytics>> {
+    let window_minutes = query.window_minutes.unwrap_or(DEFAULT_PROXIMITY_MINUTES);
+    if !(1..=MAX_PROXIMITY_MINUTES).contains(&window_minutes) {
+        return Err(AppError::Validation(format!(
+            "window_minutes must be between 1 and {}",
+            MAX_PROXIMITY_MINUTES
+        )));
+    }
+
+    let impacts = match query.impact {
+        Some(ref list) => list
+            .split(',')
+            .map(|i| {
+                EconomicCalendarService::normalize_impact(i)
+                    .ok_or_else(|| AppError::Validation(format!("Invalid impact '{}'", i.trim())))
+            })
+            .collect::<AppResult<Vec<_>>>()?,
+        None => vec!["high".to_string()],
+    };
+
+    // Each closed trade paired with its nearest qualifying event, if any.
+    let nearest_cte = r#"
+        WITH nearest AS (
+            SELECT t.net_pnl, t.r_multiple, ev.title, ev.minutes_from_event
+            FROM trades t
+            LEFT JOIN LATERAL (
+                SELECT
+                    e.title,
+                    CAST(EXTRACT(EPOCH FROM (t.entry_date - e.event_at)) / 60 AS DECIMAL) as minutes_from_event
+                FROM economic_events e
+                WHERE (e.user_id = t.user_id OR e.user_id IS NULL)
+                  AND e.impact = ANY($3)
+                  AND e.event_at BETWEEN t.entry_date - make_interval(mins => $2)
+                                     AND t.entry_date + make_interval(mins => $2)
+                ORDER BY ABS(EXTRACT(EPOCH FROM (t.entry_date - e.event_at)))
+                LIMIT 1
+            ) ev ON TRUE
+            WHERE t.user_id = $1 AND t.status = 'closed'
+        )
+    "#;
+
+    let by_proximity = sqlx::query_as::<_, EventProximityPerformance>(&format!(
+        r#"
+        {}
+        SELECT
+            CASE
+                WHEN title IS NULL THEN 'no_event'
+                WHEN minutes_from_event < 0 THEN 'before_event'
+                ELSE 'after_event'
+            END as proximity,
+            COUNT(*) as trade_count,
+            COALESCE(
+                CAST(COUNT(*) FILTER (WHERE net_pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
+                0
+            ) as win_rate,
+            COALESCE(SUM(net_pnl), 0) as total_pnl,
+            COALESCE(AVG(net_pnl), 0) as avg_pnl,
+            AVG(r_multiple) as avg_r_multiple
+        FROM nearest
+        GROUP BY proximity
+        ORDER BY proximity
+        "#,
+        nearest_cte
+    ))
+    .bind(auth_user.user_id)
+    .bind(window_minutes)
+    .bind(&impacts)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let by_event = sqlx::query_as::<_, EventPerformance>(&format!(
+        r#"
+        {}
+        SELECT
+            title as event_title,
+            COUNT(*) as trade_count,
+            COALESCE(
+                CAST(COUNT(*) FILTER (WHERE net_pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
+                0
+            ) as win_rate,
+            COALESCE(SUM(net_pnl), 0) as total_pnl,
+            COALESCE(AVG(net_pnl), 0) as avg_pnl,
+            AVG(minutes_from_event) as avg_minutes_from_event
+        FROM nearest
+        WHERE title IS NOT NULL
+        GROUP BY title
+        ORDER BY total_pnl ASC
+        "#,
+        nearest_cte
+    ))
+    .bind(auth_user.user_id)
+    .bind(window_minutes)
+    .bind(&impacts)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(EventProximityAnalytics {
+        window_minutes,
+        impacts,
+        by_proximity,
+        by_event,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AiReviewScoreQuery {
+    pub prompt_version: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AiReviewScoreAnalytics {
+    pub by_prompt_version: Vec<AiReviewScoreGroup>,
+    pub by_setup: Vec<AiReviewScoreGroup>,
+    pub by_outcome: Vec<AiReviewScoreGroup>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AiReviewScoreGroup {
+    pub group_key: String,
+    pub review_count: i64,
+    pub avg_overall_score: Option<Decimal>,
+    pub avg_execution_quality_score: Option<Decimal>,
+    pub avg_risk_management_score: Option<Decimal>,
+    pub avg_plan_adherence_score: Option<Decimal>,
+    pub avg_net_pnl: Option<Decimal>,
+}
+
+/// Average AI review scores. Setup and outcome groups use only the latest
+/// scored review of each trade so re-reviews aren't double counted; the
+/// prompt version breakdown uses every scored review to compare prompt
+/// iterations.
+pub async fn get_ai_review_score_analytics(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<AiReviewScoreQuery>,
+) -> AppResult<Json<AiReviewScoreAnalytics>> {
+    let aggregates = r#"
+        COUNT(*) as review_count,
+        ROUND(AVG(r.overall_score), 2) as avg_overall_score,
+        ROUND(AVG(r.execution_quality_score), 2) as avg_execution_quality_score,
+        ROUND(AVG(r.risk_management_score), 2) as avg_risk_management_score,
+        ROUND(AVG(r.plan_adherence_score), 2) as avg_plan_adherence_score,
+        ROUND(AVG(t.net_pnl), 2) as avg_net_pnl
+    "#;
+
+    let by_prompt_version = sqlx::query_as::<_, AiReviewScoreGroup>(&format!(
+        r#"
+        SELECT COALESCE(r.prompt_version, 'unversioned') as group_key, {}
+        FROM ai_reviews r
+        LEFT JOIN trades t ON t.id = r.trade_id
+        WHERE r.user_id = $1 AND r.overall_score IS NOT NULL
+        GROUP BY group_key
+        ORDER BY group_key
+        "#,
+        aggregates
+    ))
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    // Latest scored review per trade, optionally restricted to one prompt version
+    let latest = r#"
+        WITH latest AS (
+            SELECT DISTINCT ON (trade_id) *
+            FROM ai_reviews
+            WHERE user_id = $1
+              AND trade_id IS NOT NULL
+              AND overall_score IS NOT NULL
+              AND ($2::text IS NULL OR prompt_version = $2)
+            ORDER BY trade_id, created_at DESC
+        )
+    "#;
+
+    let by_setup = sqlx::query_as::<_, AiReviewScoreGroup>(&format!(
+        r#"
+        {}
+        SELECT COALESCE(t.setup_name, 'Unspecified') as group_key, {}
+        FROM latest r
+        JOIN trades t ON t.id = r.trade_id
+        GROUP BY group_key
+        ORDER BY review_count DESC, group_key
+        "#,
+        latest, aggregates
+    ))
+    .bind(auth_user.user_id)
+    .bind(&query.prompt_version)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let by_outcome = sqlx::query_as::<_, AiReviewScoreGroup>(&format!(
+        r#"
+        {}
+        SELECT
+            CASE
+                WHEN t.status <> 'closed' OR t.net_pnl IS NULL THEN 'open'
+                WHEN t.net_pnl > 0 THEN 'win'
+                WHEN t.net_pnl < 0 THEN 'loss'
+                ELSE 'breakeven'
+            END as group_key,
+            {}
+        FROM latest r
+        JOIN trades t ON t.id = r.trade_id
+        GROUP BY group_key
+        ORDER BY group_key
+        "#,
+        latest, aggregates
+    ))
+    .bind(auth_user.user_id)
+    .bind(&query.prompt_version)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(AiReviewScoreAnalytics {
+        by_prompt_version,
+        by_setup,
+        by_outcome,
+    }))
+}
+
+/// Recurring losing patterns from the last mining run, best ranked first.
+pub async fn get_trade_patterns(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<TradePatternsResponse>> {
+    let last_run = PatternMiningService::last_run(&pool, auth_user.user_id).await?;
+    let findings = PatternMiningService::findings(&pool, auth_user.user_id, MAX_PATTERN_FINDINGS as i64).await?;
+
+    Ok(Json(TradePatternsResponse { last_run, findings }))
+}
+
+/// Mines patterns now instead of waiting for the background job.
+pub async fn run_trade_patterns(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<TradePatternsResponse>> {
+    let findings = PatternMiningService::run_for_user(&pool, auth_user.user_id).await?;
+    let last_run = PatternMiningService::last_run(&pool, auth_user.user_id).await?;
+
+    Ok(Json(TradePatternsResponse { last_run, findings }))
+}
@@ -0,0 +1,49 @@
+use crate::error::AppResult;
+use crate::models::{ApiKey, AuthUser, CreateApiKeyRequest, CreatedApiKey};
+use crate::services::{ApiKeyService, AuthService};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn list_api_keys(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<ApiKey>>> {
+    Ok(Json(ApiKeyService::list(&pool, auth_user.user_id).await?))
+}
+
+/// The full key is in this response only; store it now.
+pub async fn create_api_key(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> AppResult<Json<CreatedApiKey>> {
+    let created = ApiKeyService::create(&pool, &auth_service, auth_user.user_id, &req).await?;
+
+    tracing::info!(
+        user_id = %auth_user.user_id,
+        api_key_id = %created.api_key.id,
+        scopes = ?created.api_key.scopes,
+        "API key created"
+    );
+
+    Ok(Json(created))
+}
+
+pub async fn revoke_api_key(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(key_id): Path<Uuid>,
+) -> AppResult<Json<Value>> {
+    ApiKeyService::revoke(&pool, auth_user.user_id, key_id).await?;
+
+    tracing::info!(user_id = %auth_user.user_id, api_key_id = %key_id, "API key revoked");
+
+    Ok(Json(json!({ "message": "API key revoked" })))
+}
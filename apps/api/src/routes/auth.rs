@@ -1,17 +1,99 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    AuthResponse, AuthUser, LoginRequest, RefreshToken, RefreshTokenRequest, RegisterRequest,
-    User, UserInfo, UserProfile,
+    AuthResponse, AuthUser, ClientInfo, ForgotPasswordRequest, LoginRequest, LoginResponse,
+    RefreshTokenRequest, RegisterRequest, ResetPasswordRequest, RevokeSessionsQuery,
+    SessionInfo, TwoFactorChallengeResponse, User, UserInfo, UserProfile, VerifyEmailRequest,
+    EMAIL_TOKEN_PASSWORD_RESET, EMAIL_TOKEN_VERIFY_EMAIL,
+};
+use crate::services::{
+    ApiKeyService, AuthService, EmailService, EmailTemplate, EmailTokenService, SessionService,
+    TwoFactorService, SESSION_REVOKED_BY_USER, SESSION_REVOKED_LOGOUT,
+    SESSION_REVOKED_PASSWORD_RESET,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
 };
-use crate::services::AuthService;
-use axum::{extract::State, Json};
 use sqlx::PgPool;
+use std::future::Future;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Sends in the background so responses neither wait on SMTP nor reveal
+/// through their timing whether an email went out.
+fn spawn_email<F>(delivery: F)
+where
+    F: Future<Output = AppResult<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        if let Err(e) = delivery.await {
+            tracing::warn!(error = %e, "Background email was not sent");
+        }
+    });
+}
+
+async fn deliver_verification_email(
+    pool: Arc<PgPool>,
+    auth_service: Arc<AuthService>,
+    email_service: Arc<EmailService>,
+    user_id: Uuid,
+    email: String,
+) -> AppResult<()> {
+    let token = EmailTokenService::issue(
+        &pool,
+        &auth_service,
+        user_id,
+        &email,
+        EMAIL_TOKEN_VERIFY_EMAIL,
+    )
+    .await?;
+    let link = email_service.link("/verify-email", &token);
+    email_service
+        .send(&email, &EmailTemplate::VerifyEmail { link: &link })
+        .await
+}
+
+async fn deliver_password_reset(
+    pool: Arc<PgPool>,
+    auth_service: Arc<AuthService>,
+    email_service: Arc<EmailService>,
+    email: String,
+) -> AppResult<()> {
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, email, password_hash, email_verified, created_at, updated_at
+        FROM users
+        WHERE email = $1
+        "#,
+    )
+    .bind(&email)
+    .fetch_optional(pool.as_ref())
+    .await?;
+
+    let Some(user) = user else {
+        tracing::debug!("Password reset requested for unknown email");
+        return Ok(());
+    };
+
+    let token = EmailTokenService::issue(
+        &pool,
+        &auth_service,
+        user.id,
+        &user.email,
+        EMAIL_TOKEN_PASSWORD_RESET,
+    )
+    .await?;
+    let link = email_service.link("/reset-password", &token);
+    email_service
+        .send(&user.email, &EmailTemplate::PasswordReset { link: &link })
+        .await
+}
+
 pub async fn register(
     State(pool): State<Arc<PgPool>>,
     State(auth_service): State<Arc<AuthService>>,
+    State(email_service): State<Arc<EmailService>>,
+    client: ClientInfo,
     Json(req): Json<RegisterRequest>,
 ) -> AppResult<Json<AuthResponse>> {
     // Validate email format
@@ -59,41 +141,94 @@ pub async fn register(
     .execute(pool.as_ref())
     .await?;
 
-    // Generate tokens
-    let access_token = auth_service.generate_access_token(user.id, &user.email)?;
-    let (refresh_token, refresh_token_hash) = auth_service.generate_refresh_token();
-    let refresh_expiry = auth_service.get_refresh_token_expiry();
+    // Start a session and issue tokens
+    let session = SessionService::create(&pool, &auth_service, user.id, &client).await?;
+    let access_token =
+        auth_service.generate_access_token(user.id, &user.email, session.session_id)?;
 
-    // Store refresh token
-    sqlx::query(
+    spawn_email(deliver_verification_email(
+        pool.clone(),
+        auth_service.clone(),
+        email_service,
+        user.id,
+        user.email.clone(),
+    ));
+
+    Ok(Json(AuthResponse {
+        access_token,
+        refresh_token: session.refresh_token,
+        user: UserInfo {
+            id: user.id,
+            email: user.email,
+            email_verified: user.email_verified,
+            onboarding_completed: false,
+        },
+    }))
+}
+
+/// Finishes a first-factor login: a 2FA challenge if the user enrolled,
+/// otherwise a new session.
+pub(crate) async fn complete_login(
+    pool: &PgPool,
+    auth_service: &AuthService,
+    user: User,
+    client: &ClientInfo,
+) -> AppResult<LoginResponse> {
+    if TwoFactorService::is_enabled(pool, user.id).await? {
+        let (challenge_token, expires_at) = auth_service.generate_two_factor_challenge(user.id)?;
+        return Ok(LoginResponse::TwoFactorRequired(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge_token,
+            expires_at,
+        }));
+    }
+
+    let response = sign_in(pool, auth_service, user, client).await?;
+    Ok(LoginResponse::Authenticated(response))
+}
+
+/// Starts a session for an authenticated user and builds the token response.
+pub(crate) async fn sign_in(
+    pool: &PgPool,
+    auth_service: &AuthService,
+    user: User,
+    client: &ClientInfo,
+) -> AppResult<AuthResponse> {
+    // Get profile to check onboarding status
+    let profile = sqlx::query_as::<_, UserProfile>(
         r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, $3)
+        SELECT * FROM user_profiles WHERE user_id = $1
         "#,
     )
     .bind(user.id)
-    .bind(&refresh_token_hash)
-    .bind(refresh_expiry)
-    .execute(pool.as_ref())
+    .fetch_optional(pool)
     .await?;
 
-    Ok(Json(AuthResponse {
+    // Start a session and issue tokens
+    let session = SessionService::create(pool, auth_service, user.id, client).await?;
+    let access_token =
+        auth_service.generate_access_token(user.id, &user.email, session.session_id)?;
+
+    Ok(AuthResponse {
         access_token,
-        refresh_token,
+        refresh_token: session.refresh_token,
         user: UserInfo {
             id: user.id,
             email: user.email,
             email_verified: user.email_verified,
-            onboarding_completed: false,
+            onboarding_completed: profile.map(|p| p.onboarding_completed).unwrap_or(false),
         },
-    }))
+    })
 }
 
+/// With 2FA enabled, a correct password yields a challenge token to redeem
+/// at `/auth/2fa/verify` instead of tokens.
 pub async fn login(
     State(pool): State<Arc<PgPool>>,
     State(auth_service): State<Arc<AuthService>>,
+    client: ClientInfo,
     Json(req): Json<LoginRequest>,
-) -> AppResult<Json<AuthResponse>> {
+) -> AppResult<Json<LoginResponse>> {
     // Find user
     let user = sqlx::query_as::<_, User>(
         r#"
@@ -114,64 +249,17 @@ pub async fn login(
         ));
     }
 
-    // Get profile to check onboarding status
-    let profile = sqlx::query_as::<_, UserProfile>(
-        r#"
-        SELECT * FROM user_profiles WHERE user_id = $1
-        "#,
-    )
-    .bind(user.id)
-    .fetch_optional(pool.as_ref())
-    .await?;
-
-    // Generate tokens
-    let access_token = auth_service.generate_access_token(user.id, &user.email)?;
-    let (refresh_token, refresh_token_hash) = auth_service.generate_refresh_token();
-    let refresh_expiry = auth_service.get_refresh_token_expiry();
-
-    // Store refresh token
-    sqlx::query(
-        r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, $3)
-        "#,
-    )
-    .bind(user.id)
-    .bind(&refresh_token_hash)
-    .bind(refresh_expiry)
-    .execute(pool.as_ref())
-    .await?;
-
-    Ok(Json(AuthResponse {
-        access_token,
-        refresh_token,
-        user: UserInfo {
-            id: user.id,
-            email: user.email,
-            email_verified: user.email_verified,
-            onboarding_completed: profile.map(|p| p.onboarding_completed).unwrap_or(false),
-        },
-    }))
+    Ok(Json(complete_login(&pool, &auth_service, user, &client).await?))
 }
 
 pub async fn refresh(
     State(pool): State<Arc<PgPool>>,
     State(auth_service): State<Arc<AuthService>>,
+    client: ClientInfo,
     Json(req): Json<RefreshTokenRequest>,
 ) -> AppResult<Json<AuthResponse>> {
-    let token_hash = auth_service.hash_token(&req.refresh_token);
-
-    // Find and validate refresh token
-    let refresh_token = sqlx::query_as::<_, RefreshToken>(
-        r#"
-        SELECT * FROM refresh_tokens
-        WHERE token_hash = $1 AND revoked = false AND expires_at > NOW()
-        "#,
-    )
-    .bind(&token_hash)
-    .fetch_optional(pool.as_ref())
-    .await?
-    .ok_or_else(|| AppError::Unauthorized("Invalid or expired refresh token".to_string()))?;
+    // Rotate within the session; reusing an old token revokes the session
+    let session = SessionService::rotate(&pool, &auth_service, &req.refresh_token, &client).await?;
 
     // Get user
     let user = sqlx::query_as::<_, User>(
@@ -181,7 +269,7 @@ pub async fn refresh(
         WHERE id = $1
         "#,
     )
-    .bind(refresh_token.user_id)
+    .bind(session.user_id)
     .fetch_one(pool.as_ref())
     .await?;
 
@@ -195,37 +283,12 @@ pub async fn refresh(
     .fetch_optional(pool.as_ref())
     .await?;
 
-    // Revoke old refresh token
-    sqlx::query(
-        r#"
-        UPDATE refresh_tokens SET revoked = true WHERE id = $1
-        "#,
-    )
-    .bind(refresh_token.id)
-    .execute(pool.as_ref())
-    .await?;
-
-    // Generate new tokens
-    let access_token = auth_service.generate_access_token(user.id, &user.email)?;
-    let (new_refresh_token, new_refresh_token_hash) = auth_service.generate_refresh_token();
-    let refresh_expiry = auth_service.get_refresh_token_expiry();
-
-    // Store new refresh token
-    sqlx::query(
-        r#"
-        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
-        VALUES ($1, $2, $3)
-        "#,
-    )
-    .bind(user.id)
-    .bind(&new_refresh_token_hash)
-    .bind(refresh_expiry)
-    .execute(pool.as_ref())
-    .await?;
+    let access_token =
+        auth_service.generate_access_token(user.id, &user.email, session.session_id)?;
 
     Ok(Json(AuthResponse {
         access_token,
-        refresh_token: new_refresh_token,
+        refresh_token: session.refresh_token,
         user: UserInfo {
             id: user.id,
             email: user.email,
@@ -235,23 +298,25 @@ pub async fn refresh(
     }))
 }
 
+/// Ends the calling session. Tokens issued before sessions existed carry no
+/// session id, so those sign out everywhere.
 pub async fn logout(
     State(pool): State<Arc<PgPool>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Revoke all refresh tokens for this user
-    sqlx::query(
-        r#"
-        UPDATE refresh_tokens SET revoked = true WHERE user_id = $1
-        "#,
-    )
-    .bind(auth_user.user_id)
-    .execute(pool.as_ref())
-    .await?;
+    match auth_user.session_id {
+        Some(session_id) => {
+            SessionService::revoke(&pool, auth_user.user_id, session_id, SESSION_REVOKED_LOGOUT)
+                .await?;
+        }
+        None => {
+            SessionService::revoke_all(pool.as_ref(), auth_user.user_id, None, SESSION_REVOKED_LOGOUT)
+                .await?;
+        }
+    }
 
     Ok(Json(serde_json::json!({ "message": "Logged out successfully" })))
 }
-
 pub async fn me(
     State(pool): State<Arc<PgPool>>,
     auth_user: AuthUser,
@@ -283,3 +348,178 @@ pub async fn me(
         onboarding_completed: profile.map(|p| p.onboarding_completed).unwrap_or(false),
     }))
 }
+
+/// Re-sends the verification link to the signed-in user's address.
+pub async fn send_verification_email(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    State(email_service): State<Arc<EmailService>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, email, password_hash, email_verified, created_at, updated_at
+        FROM users
+        WHERE id = $1
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    if user.email_verified {
+        return Err(AppError::Conflict("Email already verified".to_string()));
+    }
+
+    deliver_verification_email(pool, auth_service, email_service, user.id, user.email).await?;
+
+    Ok(Json(serde_json::json!({ "message": "Verification email sent" })))
+}
+
+pub async fn verify_email(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let token =
+        EmailTokenService::consume(&pool, &auth_service, &req.token, EMAIL_TOKEN_VERIFY_EMAIL)
+            .await?;
+
+    // The link only vouches for the address it was sent to
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET email_verified = true, updated_at = NOW()
+        WHERE id = $1 AND email = $2
+        "#,
+    )
+    .bind(token.user_id)
+    .bind(&token.email)
+    .execute(pool.as_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("This link is invalid or has expired".to_string()));
+    }
+
+    tracing::info!(user_id = %token.user_id, "Email verified");
+
+    Ok(Json(serde_json::json!({ "message": "Email verified" })))
+}
+
+/// Always answers the same way so the endpoint can't be used to probe which
+/// addresses have accounts.
+pub async fn forgot_password(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    State(email_service): State<Arc<EmailService>>,
+    Json(req): Json<ForgotPasswordRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let email = req.email.trim().to_string();
+    if !email.contains('@') {
+        return Err(AppError::Validation("Invalid email format".to_string()));
+    }
+
+    spawn_email(deliver_password_reset(pool, auth_service, email_service, email));
+
+    Ok(Json(serde_json::json!({
+        "message": "If an account exists for that email, a reset link has been sent"
+    })))
+}
+
+/// Sets a new password from a reset link, signs out every session and revokes
+/// every API key, so nothing issued under the old password keeps working.
+pub async fn reset_password(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    State(email_service): State<Arc<EmailService>>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    // Validate before consuming so a weak password doesn't burn the link
+    if req.new_password.len() < 8 {
+        return Err(AppError::Validation(
+            "Password must be at least 8 characters".to_string(),
+        ));
+    }
+
+    let token =
+        EmailTokenService::consume(&pool, &auth_service, &req.token, EMAIL_TOKEN_PASSWORD_RESET)
+            .await?;
+    let password_hash = auth_service.hash_password(&req.new_password)?;
+
+    let mut tx = pool.begin().await?;
+
+    // Following the link also proves the address, if it hasn't changed since
+    let email = sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE users
+        SET password_hash = $2, password_set = true, email_verified = (email_verified OR email = $3),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING email
+        "#,
+    )
+    .bind(token.user_id)
+    .bind(&password_hash)
+    .bind(&token.email)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    SessionService::revoke_all(&mut *tx, token.user_id, None, SESSION_REVOKED_PASSWORD_RESET)
+        .await?;
+    ApiKeyService::revoke_all(&mut *tx, token.user_id).await?;
+
+    tx.commit().await?;
+
+    tracing::info!(user_id = %token.user_id, "Password reset");
+
+    spawn_email(async move {
+        email_service.send(&email, &EmailTemplate::PasswordChanged).await
+    });
+
+    Ok(Json(serde_json::json!({ "message": "Password has been reset" })))
+}
+
+pub async fn list_sessions(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<SessionInfo>>> {
+    let sessions = SessionService::list(&pool, auth_user.user_id, auth_user.session_id).await?;
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    if !SessionService::revoke(&pool, auth_user.user_id, id, SESSION_REVOKED_BY_USER).await? {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+
+    tracing::info!(user_id = %auth_user.user_id, session_id = %id, "Session revoked");
+
+    Ok(Json(serde_json::json!({ "message": "Session revoked" })))
+}
+
+/// Signs out every session, or every other one with `except_current=true`.
+pub async fn revoke_all_sessions(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<RevokeSessionsQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let except = if query.except_current {
+        Some(auth_user.session_id.ok_or_else(|| {
+            AppError::BadRequest("Sign in again to keep this session".to_string())
+        })?)
+    } else {
+        None
+    };
+
+    let revoked =
+        SessionService::revoke_all(pool.as_ref(), auth_user.user_id, except, SESSION_REVOKED_BY_USER)
+            .await?;
+
+    tracing::info!(user_id = %auth_user.user_id, revoked, "Sessions revoked");
+
+    Ok(Json(serde_json::json!({ "message": "Sessions revoked", "revoked": revoked })))
+}
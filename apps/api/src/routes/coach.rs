@@ -0,0 +1,333 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AuthUser, CoachListQuery, CoachTrade, CoachedTrader, DailyPlan, MoodLog, PeriodicReview,
+    TiltEvent,
+};
+use crate::services::{AccountabilityService, CoachResource};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Redaction happens in SQL so a coach never receives hidden values, even in
+// memory. In every query below $1 is the trader id, $2 is `can_view_pnl` and
+// $3 (trades only) is `can_view_grades`. Position size goes with P&L: with
+// prices and direction visible, quantity would give the P&L straight back,
+// and size relative to risk or account reveals the account's value.
+
+const COACH_TRADE_COLUMNS: &str = r#"
+    id, user_id, symbol, direction, asset_class, status,
+    entry_date, entry_price,
+    CASE WHEN $2 THEN quantity END AS quantity,
+    stop_loss, take_profit,
+    exit_date, exit_price, actual_exit_price,
+    CASE WHEN $2 THEN pnl END AS pnl,
+    CASE WHEN $2 THEN pnl_percent END AS pnl_percent,
+    CASE WHEN $2 THEN commissions END AS commissions,
+    CASE WHEN $2 THEN net_pnl END AS net_pnl,
+    r_multiple, mae, mfe, hold_time_minutes,
+    CASE WHEN $2 THEN risk_amount END AS risk_amount,
+    CASE WHEN $2 THEN risk_percent END AS risk_percent,
+    CASE WHEN $2 THEN position_size_pct END AS position_size_pct,
+    conviction, setup_name, timeframe,
+    thesis, mistakes, lessons, emotional_state, market_condition,
+    CASE WHEN $3 THEN execution_grade END AS execution_grade,
+    CASE WHEN $3 THEN patience_grade END AS patience_grade,
+    CASE WHEN $3 THEN discipline_grade END AS discipline_grade,
+    CASE WHEN $3 THEN overall_grade END AS overall_grade,
+    is_paper_trade, is_revenge_trade, broke_rules, followed_plan,
+    created_at, updated_at
+"#;
+
+const COACH_PLAN_COLUMNS: &str = r#"
+    id, user_id, plan_date, market_bias, bias_reasoning, session_goals, max_trades,
+    CASE WHEN $2 THEN max_daily_loss END AS max_daily_loss,
+    checklist_items, notes,
+    CASE WHEN $2 THEN ai_plan_of_attack END AS ai_plan_of_attack,
+    CASE WHEN $2 THEN ai_plan_prompt END AS ai_plan_prompt,
+    ai_plan_prompt_version, ai_plan_provider, ai_plan_model, ai_plan_generated_at,
+    adherence_score, adherence_details, completed, completed_at, created_at, updated_at
+"#;
+
+const COACH_REVIEW_COLUMNS: &str = r#"
+    id, user_id, review_type, period_start, period_end,
+    total_trades, winning_trades, losing_trades, win_rate,
+    CASE WHEN $2 THEN total_pnl END AS total_pnl,
+    avg_r_multiple, what_went_well, what_to_improve, key_lessons, rules_broken,
+    best_trade_id, worst_trade_id, goals_met, goals_missed, goals_next_period,
+    discipline_rating, patience_rating, execution_rating, overall_rating,
+    ai_review_id, created_at, updated_at
+"#;
+
+fn page(query: &CoachListQuery) -> (i64, i64) {
+    (
+        query.limit.unwrap_or(50).clamp(1, 100),
+        query.offset.unwrap_or(0).max(0),
+    )
+}
+
+/// Coach dashboard: every trader with an active link to the current user.
+pub async fn list_coached_traders(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<CoachedTrader>>> {
+    let traders = sqlx::query_as::<_, CoachedTrader>(
+        r#"
+        SELECT
+            al.id as link_id,
+            al.trader_user_id,
+            u.email as trader_email,
+            al.can_view_trades, al.can_view_plan, al.can_view_grades, al.can_view_tilt,
+            al.can_view_pnl, al.can_view_mood, al.can_view_reviews,
+            al.accepted_at,
+            CASE WHEN al.can_view_trades THEN
+                (SELECT MAX(t.entry_date) FROM trades t WHERE t.user_id = al.trader_user_id)
+            END as last_trade_at,
+            CASE WHEN al.can_view_trades THEN
+                (SELECT COUNT(*) FROM trades t
+                 WHERE t.user_id = al.trader_user_id AND t.status = 'open')
+            END as open_trades,
+            CASE WHEN al.can_view_tilt THEN
+                (SELECT COUNT(*) FROM tilt_events te
+                 WHERE te.user_id = al.trader_user_id AND te.acknowledged = FALSE)
+            END as unacknowledged_tilt_events
+        FROM accountability_links al
+        JOIN users u ON u.id = al.trader_user_id
+        WHERE al.coach_user_id = $1 AND al.status = 'active'
+        ORDER BY u.email
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let trader_ids: Vec<Uuid> = traders.iter().map(|t| t.trader_user_id).collect();
+    AccountabilityService::log_list_access(
+        &pool,
+        auth_user.user_id,
+        CoachResource::Dashboard,
+        &trader_ids,
+    )
+    .await?;
+
+    Ok(Json(traders))
+}
+
+pub async fn list_trader_trades(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trader_id): Path<Uuid>,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<CoachTrade>>> {
+    let link = AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::Trades,
+        None,
+    )
+    .await?;
+
+    let (limit, offset) = page(&query);
+    let sql = format!(
+        "SELECT {} FROM trades WHERE user_id = $1 ORDER BY entry_date DESC LIMIT $4 OFFSET $5",
+        COACH_TRADE_COLUMNS
+    );
+
+    let trades = sqlx::query_as::<_, CoachTrade>(&sql)
+        .bind(trader_id)
+        .bind(link.can_view_pnl)
+        .bind(link.can_view_grades)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    Ok(Json(trades))
+}
+
+pub async fn get_trader_trade(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trader_id, trade_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<CoachTrade>> {
+    let link = AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::Trades,
+        Some(trade_id),
+    )
+    .await?;
+
+    let sql = format!(
+        "SELECT {} FROM trades WHERE user_id = $1 AND id = $4",
+        COACH_TRADE_COLUMNS
+    );
+
+    let trade = sqlx::query_as::<_, CoachTrade>(&sql)
+        .bind(trader_id)
+        .bind(link.can_view_pnl)
+        .bind(link.can_view_grades)
+        .bind(trade_id)
+        .fetch_optional(pool.as_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trade not found".to_string()))?;
+
+    Ok(Json(trade))
+}
+
+pub async fn list_trader_plans(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trader_id): Path<Uuid>,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<DailyPlan>>> {
+    let link = AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::Plans,
+        None,
+    )
+    .await?;
+
+    let (limit, offset) = page(&query);
+    let sql = format!(
+        "SELECT {} FROM daily_plans WHERE user_id = $1 ORDER BY plan_date DESC LIMIT $3 OFFSET $4",
+        COACH_PLAN_COLUMNS
+    );
+
+    let plans = sqlx::query_as::<_, DailyPlan>(&sql)
+        .bind(trader_id)
+        .bind(link.can_view_pnl)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    Ok(Json(plans))
+}
+
+pub async fn list_trader_mood_logs(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trader_id): Path<Uuid>,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<MoodLog>>> {
+    AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::MoodLogs,
+        None,
+    )
+    .await?;
+
+    let (limit, offset) = page(&query);
+    let logs = sqlx::query_as::<_, MoodLog>(
+        "SELECT * FROM mood_logs WHERE user_id = $1 ORDER BY log_date DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(trader_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(logs))
+}
+
+pub async fn list_trader_tilt_events(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trader_id): Path<Uuid>,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<TiltEvent>>> {
+    AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::TiltEvents,
+        None,
+    )
+    .await?;
+
+    let (limit, offset) = page(&query);
+    let events = sqlx::query_as::<_, TiltEvent>(
+        "SELECT * FROM tilt_events WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(trader_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(events))
+}
+
+pub async fn list_trader_reviews(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trader_id): Path<Uuid>,
+    Query(query): Query<CoachListQuery>,
+) -> AppResult<Json<Vec<PeriodicReview>>> {
+    let link = AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::Reviews,
+        None,
+    )
+    .await?;
+
+    let (limit, offset) = page(&query);
+    let sql = format!(
+        "SELECT {} FROM periodic_reviews WHERE user_id = $1 ORDER BY period_end DESC LIMIT $3 OFFSET $4",
+        COACH_REVIEW_COLUMNS
+    );
+
+    let reviews = sqlx::query_as::<_, PeriodicReview>(&sql)
+        .bind(trader_id)
+        .bind(link.can_view_pnl)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    Ok(Json(reviews))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trade_pnl_and_size_need_pnl_permission() {
+        for column in [
+            "quantity",
+            "pnl",
+            "pnl_percent",
+            "commissions",
+            "net_pnl",
+            "risk_amount",
+            "risk_percent",
+            "position_size_pct",
+        ] {
+            let gated = format!("CASE WHEN $2 THEN {} END AS {},", column, column);
+            assert!(COACH_TRADE_COLUMNS.contains(&gated), "{} is not redacted", column);
+        }
+    }
+
+    #[test]
+    fn test_plan_loss_limit_and_ai_plan_need_pnl_permission() {
+        // The stored prompt embeds max_daily_loss and per-setup P&L, and the
+        // generated plan can repeat them.
+        for column in ["max_daily_loss", "ai_plan_of_attack", "ai_plan_prompt"] {
+            let gated = format!("CASE WHEN $2 THEN {} END AS {},", column, column);
+            assert!(COACH_PLAN_COLUMNS.contains(&gated), "{} is not redacted", column);
+        }
+    }
+}
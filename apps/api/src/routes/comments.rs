@@ -0,0 +1,384 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    validate_comment_body, AuthUser, Comment, CommentThreadQuery, CommentWithState,
+    CreateCommentRequest, MarkThreadReadRequest, RaiseReviewFlagRequest, ReviewQueueItem,
+    TradeReviewFlag, UpdateCommentRequest,
+};
+use crate::services::{
+    AccountabilityService, CoachResource, CommentService, CommentSubject, NotificationKind,
+    NotificationService,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+fn parse_subject(value: &str) -> AppResult<CommentSubject> {
+    CommentSubject::parse(value).map_err(AppError::Validation)
+}
+
+async fn user_email(pool: &PgPool, user_id: Uuid) -> AppResult<String> {
+    let email = sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(email)
+}
+
+/// Full thread for a trade, daily plan or periodic review, oldest first.
+/// Replies reference their parent via `parent_id`.
+pub async fn list_comments(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<CommentThreadQuery>,
+) -> AppResult<Json<Vec<CommentWithState>>> {
+    let subject = parse_subject(&query.subject_type)?;
+    CommentService::authorize(&pool, auth_user.user_id, subject, query.subject_id).await?;
+
+    let comments = sqlx::query_as::<_, CommentWithState>(
+        r#"
+        SELECT
+            c.*,
+            u.email as author_email,
+            (c.author_user_id = $3 OR EXISTS(
+                SELECT 1 FROM comment_reads r WHERE r.comment_id = c.id AND r.user_id = $3
+            )) as is_read
+        FROM comments c
+        JOIN users u ON u.id = c.author_user_id
+        WHERE c.subject_type = $1 AND c.subject_id = $2
+        ORDER BY c.created_at ASC
+        "#,
+    )
+    .bind(subject.as_str())
+    .bind(query.subject_id)
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(comments))
+}
+
+pub async fn create_comment(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateCommentRequest>,
+) -> AppResult<Json<Comment>> {
+    let subject = parse_subject(&req.subject_type)?;
+    validate_comment_body(&req.body).map_err(AppError::Validation)?;
+
+    let trader_user_id =
+        CommentService::authorize(&pool, auth_user.user_id, subject, req.subject_id).await?;
+
+    if let Some(parent_id) = req.parent_id {
+        let in_thread = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM comments WHERE id = $1 AND subject_type = $2 AND subject_id = $3)",
+        )
+        .bind(parent_id)
+        .bind(subject.as_str())
+        .bind(req.subject_id)
+        .fetch_one(pool.as_ref())
+        .await?;
+
+        in_thread
+            .then_some(())
+            .ok_or_else(|| AppError::NotFound("Parent comment not found".to_string()))?;
+    }
+
+    let participants = CommentService::participants(&pool, trader_user_id, subject).await?;
+    let mut mentions = req.mentions.unwrap_or_default();
+    mentions.sort();
+    mentions.dedup();
+    CommentService::validate_mentions(&participants, &mentions).map_err(AppError::Validation)?;
+
+    let comment = sqlx::query_as::<_, Comment>(
+        r#"
+        INSERT INTO comments (
+            trader_user_id, author_user_id, subject_type, subject_id, parent_id, body, mentions
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#,
+    )
+    .bind(trader_user_id)
+    .bind(auth_user.user_id)
+    .bind(subject.as_str())
+    .bind(req.subject_id)
+    .bind(req.parent_id)
+    .bind(req.body.trim())
+    .bind(&mentions)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    let recipients =
+        CommentService::notification_recipients(auth_user.user_id, &participants, &mentions);
+    if !recipients.is_empty() {
+        let author_email = user_email(&pool, auth_user.user_id).await?;
+        NotificationService::notify(
+            &pool,
+            &recipients,
+            &format!("{} commented on a {}", author_email, subject.as_str().replace('_', " ")),
+            Some(comment.body.as_str()),
+            serde_json::json!({
+                "comment_id": comment.id,
+                "subject_type": comment.subject_type,
+                "subject_id": comment.subject_id,
+                "trader_user_id": comment.trader_user_id,
+            }),
+        )
+        .await;
+    }
+
+    tracing::info!(comment_id = %comment.id, "Comment created");
+    Ok(Json(comment))
+}
+
+/// Authors can edit their own comments.
+pub async fn update_comment(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(comment_id): Path<Uuid>,
+    Json(req): Json<UpdateCommentRequest>,
+) -> AppResult<Json<Comment>> {
+    validate_comment_body(&req.body).map_err(AppError::Validation)?;
+
+    let comment = sqlx::query_as::<_, Comment>(
+        r#"
+        UPDATE comments SET
+            body = $1,
+            edited_at = NOW()
+        WHERE id = $2 AND author_user_id = $3
+        RETURNING *
+        "#,
+    )
+    .bind(req.body.trim())
+    .bind(comment_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))?;
+
+    Ok(Json(comment))
+}
+
+/// Authors can delete their own comments; traders can delete any comment on
+/// their own journal. Replies are removed with their parent.
+pub async fn delete_comment(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(comment_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        "DELETE FROM comments WHERE id = $1 AND (author_user_id = $2 OR trader_user_id = $2)",
+    )
+    .bind(comment_id)
+    .bind(auth_user.user_id)
+    .execute(pool.as_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({"message": "Comment deleted successfully"})))
+}
+
+/// Marks every comment in a thread as read for the current user.
+pub async fn mark_thread_read(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<MarkThreadReadRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let subject = parse_subject(&req.subject_type)?;
+    CommentService::authorize(&pool, auth_user.user_id, subject, req.subject_id).await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO comment_reads (comment_id, user_id)
+        SELECT id, $3 FROM comments
+        WHERE subject_type = $1 AND subject_id = $2 AND author_user_id <> $3
+        ON CONFLICT (comment_id, user_id) DO NOTHING
+        "#,
+    )
+    .bind(subject.as_str())
+    .bind(req.subject_id)
+    .bind(auth_user.user_id)
+    .execute(pool.as_ref())
+    .await?;
+
+    Ok(Json(serde_json::json!({"marked_read": result.rows_affected()})))
+}
+
+/// Trader flags one of their trades for coach review. Raising an already
+/// cleared flag reopens it.
+pub async fn raise_review_flag(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trade_id): Path<Uuid>,
+    Json(req): Json<RaiseReviewFlagRequest>,
+) -> AppResult<Json<TradeReviewFlag>> {
+    let symbol = sqlx::query_scalar::<_, String>(
+        "SELECT symbol FROM trades WHERE id = $1 AND user_id = $2",
+    )
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Trade not found".to_string()))?;
+
+    let flag = sqlx::query_as::<_, TradeReviewFlag>(
+        r#"
+        INSERT INTO trade_review_flags (trade_id, trader_user_id, note)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (trade_id) DO UPDATE SET
+            note = EXCLUDED.note,
+            raised_at = NOW(),
+            cleared_by = NULL,
+            cleared_at = NULL
+        RETURNING *
+        "#,
+    )
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .bind(req.note.as_deref().map(str::trim))
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    let coaches = CommentService::participants(&pool, auth_user.user_id, CommentSubject::Trade)
+        .await?
+        .into_iter()
+        .filter(|id| *id != auth_user.user_id)
+        .map(|id| (id, NotificationKind::ReviewRequested))
+        .collect::<Vec<_>>();
+    NotificationService::notify(
+        &pool,
+        &coaches,
+        &format!("Review requested on {} trade", symbol),
+        flag.note.as_deref(),
+        serde_json::json!({"trade_id": trade_id, "trader_user_id": auth_user.user_id}),
+    )
+    .await;
+
+    tracing::info!(trade_id = %trade_id, "Trade flagged for review");
+    Ok(Json(flag))
+}
+
+/// Trader withdraws their own review request.
+pub async fn withdraw_review_flag(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(trade_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        "DELETE FROM trade_review_flags WHERE trade_id = $1 AND trader_user_id = $2 AND cleared_at IS NULL",
+    )
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .execute(pool.as_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Open review flag not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({"message": "Review request withdrawn"})))
+}
+
+/// Open review flags on trades of traders who share their trades with the
+/// coach (`$1`).
+const REVIEW_QUEUE_SQL: &str = r#"
+    SELECT
+        f.trade_id, f.trader_user_id, u.email as trader_email,
+        t.symbol, t.entry_date, f.note, f.raised_at
+    FROM trade_review_flags f
+    JOIN accountability_links al
+        ON al.trader_user_id = f.trader_user_id
+       AND al.coach_user_id = $1
+       AND al.status = 'active'
+       AND al.can_view_trades
+    JOIN trades t ON t.id = f.trade_id
+    JOIN users u ON u.id = f.trader_user_id
+    WHERE f.cleared_at IS NULL
+    ORDER BY f.raised_at ASC
+"#;
+
+/// Open review requests across every trader the coach is linked to. Each
+/// trader shown gets a `trades` entry in the coach access log.
+pub async fn list_review_queue(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<ReviewQueueItem>>> {
+    let items = sqlx::query_as::<_, ReviewQueueItem>(REVIEW_QUEUE_SQL)
+        .bind(auth_user.user_id)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    let mut trader_ids: Vec<Uuid> = items.iter().map(|i| i.trader_user_id).collect();
+    trader_ids.sort();
+    trader_ids.dedup();
+    AccountabilityService::log_list_access(
+        &pool,
+        auth_user.user_id,
+        CoachResource::Trades,
+        &trader_ids,
+    )
+    .await?;
+
+    Ok(Json(items))
+}
+
+/// Coach marks a flagged trade as reviewed.
+pub async fn clear_review_flag(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trader_id, trade_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<TradeReviewFlag>> {
+    AccountabilityService::authorize_coach_access(
+        &pool,
+        auth_user.user_id,
+        trader_id,
+        CoachResource::Trades,
+        Some(trade_id),
+    )
+    .await?;
+
+    let flag = sqlx::query_as::<_, TradeReviewFlag>(
+        r#"
+        UPDATE trade_review_flags SET
+            cleared_by = $1,
+            cleared_at = NOW()
+        WHERE trade_id = $2 AND trader_user_id = $3 AND cleared_at IS NULL
+        RETURNING *
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(trade_id)
+    .bind(trader_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Open review flag not found".to_string()))?;
+
+    let coach_email = user_email(&pool, auth_user.user_id).await?;
+    NotificationService::notify(
+        &pool,
+        &[(trader_id, NotificationKind::ReviewCleared)],
+        &format!("{} reviewed your trade", coach_email),
+        None,
+        serde_json::json!({"trade_id": trade_id, "coach_user_id": auth_user.user_id}),
+    )
+    .await;
+
+    Ok(Json(flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review_queue_needs_trade_permission() {
+        assert!(REVIEW_QUEUE_SQL.contains("AND al.can_view_trades"));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{AssetClass, AuthUser, ConvictionLevel, Trade, TradeDirection};
-use crate::services::TradeCalculationService;
+use crate::services::{
+    EconomicCalendarService, ProfileService, TradeCalculationService, DEFAULT_PROXIMITY_MINUTES,
+};
 use axum::{extract::State, Json};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
@@ -53,9 +55,11 @@ pub async fn import_csv(
 ) -> AppResult<Json<CsvImportResponse>> {
     let mut success_count = 0;
     let mut errors = Vec::new();
+    // Rows without commissions get the profile's, as in `create_trade`
+    let default_commissions = ProfileService::default_commissions(&pool, auth_user.user_id).await?;
 
     for (index, row) in req.trades.iter().enumerate() {
-        match import_single_trade(&pool, &auth_user, row).await {
+        match import_single_trade(&pool, &auth_user, row, default_commissions).await {
             Ok(_) => success_count += 1,
             Err(e) => {
                 errors.push(CsvImportError {
@@ -67,6 +71,19 @@ pub async fn import_csv(
         }
     }
 
+    if success_count > 0 {
+        if let Err(e) = EconomicCalendarService::tag_trades(
+            &pool,
+            auth_user.user_id,
+            None,
+            DEFAULT_PROXIMITY_MINUTES,
+        )
+        .await
+        {
+            tracing::warn!(user_id = %auth_user.user_id, error = %e, "Failed to tag imported trades");
+        }
+    }
+
     Ok(Json(CsvImportResponse {
         success_count,
         error_count: errors.len(),
@@ -78,6 +95,7 @@ async fn import_single_trade(
     pool: &PgPool,
     auth_user: &AuthUser,
     row: &CsvTradeRow,
+    default_commissions: Option<Decimal>,
 ) -> AppResult<Trade> {
     // Parse direction
     let direction = match row.direction.to_lowercase().as_str() {
@@ -147,7 +165,7 @@ async fn import_single_trade(
         Some(comm_str.parse::<Decimal>()
             .map_err(|_| AppError::Validation(format!("Invalid commissions: {}", comm_str)))?)
     } else {
-        None
+        default_commissions
     };
 
     // Parse conviction
@@ -0,0 +1,307 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    validate_currency, validate_event_title, AuthUser, CreateEconomicEventRequest, EconomicEvent,
+    EconomicEventImportResponse, EconomicEventQuery, ImportEconomicEventsRequest, TagTradesRequest,
+    TagTradesResponse, UpdateEconomicEventRequest,
+};
+use crate::services::{
+    EconomicCalendarService, StreakService, DEFAULT_PROXIMITY_MINUTES, MAX_PROXIMITY_MINUTES,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MAX_IMPORT_BYTES: usize = 2 * 1024 * 1024;
+
+fn parse_list(value: &Option<String>) -> Option<Vec<String>> {
+    value.as_ref().map(|v| {
+        v.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
+}
+
+fn normalize_impact(impact: &Option<String>) -> AppResult<Option<String>> {
+    match impact {
+        Some(i) => EconomicCalendarService::normalize_impact(i)
+            .map(Some)
+            .ok_or_else(|| AppError::Validation(format!("Invalid impact '{}'", i))),
+        None => Ok(None),
+    }
+}
+
+fn normalize_currency(currency: &Option<String>) -> AppResult<Option<String>> {
+    match currency {
+        Some(c) => {
+            validate_currency(c).map_err(AppError::Validation)?;
+            Ok(Some(c.to_uppercase()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Events visible to the user (their own plus the shared calendar), filtered
+/// by date range, impact and currency.
+pub async fn list_economic_events(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<EconomicEventQuery>,
+) -> AppResult<Json<Vec<EconomicEvent>>> {
+    let impacts = match parse_list(&query.impact) {
+        Some(list) => Some(
+            list.iter()
+                .map(|i| {
+                    EconomicCalendarService::normalize_impact(i)
+                        .ok_or_else(|| AppError::Validation(format!("Invalid impact '{}'", i)))
+                })
+                .collect::<AppResult<Vec<_>>>()?,
+        ),
+        None => None,
+    };
+    let currencies =
+        parse_list(&query.currency).map(|list| list.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>());
+    let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+
+    let events = sqlx::query_as::<_, EconomicEvent>(
+        r#"
+        SELECT * FROM economic_events
+        WHERE (user_id = $1 OR user_id IS NULL)
+          AND ($2::date IS NULL OR event_date >= $2)
+          AND ($3::date IS NULL OR event_date <= $3)
+          AND ($4::text[] IS NULL OR impact = ANY($4))
+          AND ($5::text[] IS NULL OR currency = ANY($5))
+        ORDER BY event_date, event_time NULLS FIRST, title
+        LIMIT $6
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(impacts)
+    .bind(currencies)
+    .bind(limit)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(events))
+}
+
+pub async fn get_economic_event(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(event_id): Path<Uuid>,
+) -> AppResult<Json<EconomicEvent>> {
+    let event = sqlx::query_as::<_, EconomicEvent>(
+        "SELECT * FROM economic_events WHERE id = $1 AND (user_id = $2 OR user_id IS NULL)",
+    )
+    .bind(event_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Economic event not found".to_string()))?;
+
+    Ok(Json(event))
+}
+
+pub async fn create_economic_event(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<CreateEconomicEventRequest>,
+) -> AppResult<Json<EconomicEvent>> {
+    validate_event_title(&req.title).map_err(AppError::Validation)?;
+    let impact = normalize_impact(&req.impact)?;
+    let currency = normalize_currency(&req.currency)?;
+    let tz = match req.timezone {
+        Some(ref name) => EconomicCalendarService::parse_timezone(name)?,
+        None => StreakService::user_timezone(&pool, auth_user.user_id).await?,
+    };
+    let event_at = EconomicCalendarService::resolve_event_at(req.event_date, req.event_time, tz);
+
+    let event = sqlx::query_as::<_, EconomicEvent>(
+        r#"
+        INSERT INTO economic_events (
+            user_id, event_date, event_time, timezone, event_at,
+            title, currency, impact, description, source
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'custom')
+        RETURNING *
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(req.event_date)
+    .bind(req.event_time)
+    .bind(tz.name())
+    .bind(event_at)
+    .bind(req.title.trim())
+    .bind(currency)
+    .bind(impact)
+    .bind(&req.description)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    tracing::info!(event_id = %event.id, "Economic event created");
+    Ok(Json(event))
+}
+
+/// Only the user's own events can be edited; the shared calendar is read-only.
+pub async fn update_economic_event(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(event_id): Path<Uuid>,
+    Json(req): Json<UpdateEconomicEventRequest>,
+) -> AppResult<Json<EconomicEvent>> {
+    if let Some(ref title) = req.title {
+        validate_event_title(title).map_err(AppError::Validation)?;
+    }
+    let impact = normalize_impact(&req.impact)?;
+    let currency = normalize_currency(&req.currency)?;
+
+    let existing = sqlx::query_as::<_, EconomicEvent>(
+        "SELECT * FROM economic_events WHERE id = $1 AND user_id = $2",
+    )
+    .bind(event_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Economic event not found".to_string()))?;
+
+    // The resolved instant depends on date, time and zone together.
+    let event_date = req.event_date.unwrap_or(existing.event_date);
+    let event_time = req.event_time.or(existing.event_time);
+    let tz = EconomicCalendarService::parse_timezone(
+        req.timezone.as_deref().unwrap_or(&existing.timezone),
+    )?;
+    let event_at = EconomicCalendarService::resolve_event_at(event_date, event_time, tz);
+
+    let event = sqlx::query_as::<_, EconomicEvent>(
+        r#"
+        UPDATE economic_events SET
+            event_date = $1,
+            event_time = $2,
+            timezone = $3,
+            event_at = $4,
+            title = COALESCE($5, title),
+            currency = COALESCE($6, currency),
+            impact = COALESCE($7, impact),
+            description = COALESCE($8, description)
+        WHERE id = $9
+        RETURNING *
+        "#,
+    )
+    .bind(event_date)
+    .bind(event_time)
+    .bind(tz.name())
+    .bind(event_at)
+    .bind(req.title.as_deref().map(str::trim))
+    .bind(currency)
+    .bind(impact)
+    .bind(&req.description)
+    .bind(event_id)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    Ok(Json(event))
+}
+
+pub async fn delete_economic_event(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(event_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM economic_events WHERE id = $1 AND user_id = $2")
+        .bind(event_id)
+        .bind(auth_user.user_id)
+        .execute(pool.as_ref())
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Economic event not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({"message": "Economic event deleted successfully"})))
+}
+
+/// Bulk import from an ICS or CSV file. Re-importing the same file updates
+/// events in place. Trades are re-tagged afterwards so proximity reflects
+/// the new calendar.
+pub async fn import_economic_events(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<ImportEconomicEventsRequest>,
+) -> AppResult<Json<EconomicEventImportResponse>> {
+    if req.content.len() > MAX_IMPORT_BYTES {
+        return Err(AppError::Validation("Import file must be 2 MB or smaller".to_string()));
+    }
+
+    let tz = match req.timezone {
+        Some(ref name) => EconomicCalendarService::parse_timezone(name)?,
+        None => StreakService::user_timezone(&pool, auth_user.user_id).await?,
+    };
+    let default_currency = req.default_currency.as_deref();
+
+    let (events, errors) = match req.format.to_lowercase().as_str() {
+        "ics" | "ical" => EconomicCalendarService::parse_ics(&req.content, tz, default_currency),
+        "csv" => EconomicCalendarService::parse_csv(&req.content, tz, default_currency),
+        other => {
+            return Err(AppError::Validation(format!(
+                "Invalid format '{}'. Must be 'ics' or 'csv'",
+                other
+            )))
+        }
+    };
+
+    let source = if req.format.eq_ignore_ascii_case("csv") { "csv" } else { "ics" };
+    let (inserted_count, updated_count) =
+        EconomicCalendarService::store_events(&pool, auth_user.user_id, source, &events).await?;
+
+    let trades_tagged = if events.is_empty() {
+        0
+    } else {
+        EconomicCalendarService::tag_trades(&pool, auth_user.user_id, None, DEFAULT_PROXIMITY_MINUTES)
+            .await?
+    };
+
+    tracing::info!(
+        user_id = %auth_user.user_id,
+        inserted = inserted_count,
+        updated = updated_count,
+        errors = errors.len(),
+        "Economic events imported"
+    );
+
+    Ok(Json(EconomicEventImportResponse {
+        inserted_count,
+        updated_count,
+        error_count: errors.len(),
+        errors,
+        trades_tagged,
+    }))
+}
+
+/// Rebuilds event-proximity tags for all of the user's trades.
+pub async fn tag_trades_with_events(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<TagTradesRequest>,
+) -> AppResult<Json<TagTradesResponse>> {
+    let window_minutes = req.window_minutes.unwrap_or(DEFAULT_PROXIMITY_MINUTES);
+    if !(1..=MAX_PROXIMITY_MINUTES).contains(&window_minutes) {
+        return Err(AppError::Validation(format!(
+            "window_minutes must be between 1 and {}",
+            MAX_PROXIMITY_MINUTES
+        )));
+    }
+
+    let trades_tagged =
+        EconomicCalendarService::tag_trades(&pool, auth_user.user_id, None, window_minutes).await?;
+
+    Ok(Json(TagTradesResponse {
+        window_minutes,
+        trades_tagged,
+    }))
+}
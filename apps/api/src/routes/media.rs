@@ -0,0 +1,604 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AuthUser, ChartAnnotation, CreateAnnotationRequest, DeleteAnnotationQuery, PresignMediaRequest,
+    PresignMediaResponse, StoredChartAnalysis, Trade, TradeMedia, UpdateAnnotationRequest,
+};
+use crate::services::{
+    AiService, AiUsageService, AnnotationService, MediaKind, StorageService, ValidatedUpload,
+    PRESIGN_EXPIRY,
+};
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::header,
+    Json,
+};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Enough leading bytes to identify any of the allowed formats.
+const SNIFF_BYTES: u64 = 64;
+/// Presigned rows that never completed are swept after this long.
+const STALE_PENDING_MINUTES: i32 = 60;
+
+fn validate_caption(caption: &Option<String>) -> AppResult<()> {
+    match caption {
+        Some(c) if c.len() > 1000 => Err(AppError::Validation(
+            "Caption must be 1000 characters or fewer".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_file_name(file_name: &str) -> AppResult<()> {
+    let trimmed = file_name.trim();
+    if trimmed.is_empty() || trimmed.len() > 255 {
+        return Err(AppError::Validation(
+            "File name must be between 1 and 255 characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn ensure_trade_owner(pool: &PgPool, trade_id: Uuid, user_id: Uuid) -> AppResult<()> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM trades WHERE id = $1 AND user_id = $2)
+        "#,
+    )
+    .bind(trade_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?
+    .then_some(())
+    .ok_or_else(|| AppError::NotFound("Trade not found".to_string()))
+}
+
+/// Builds and stores a JPEG thumbnail. Best-effort: the original upload is
+/// still usable without one.
+async fn store_thumbnail(
+    storage: &StorageService,
+    user_id: Uuid,
+    trade_id: Uuid,
+    media_id: Uuid,
+    bytes: Vec<u8>,
+) -> Option<String> {
+    let thumbnail = match tokio::task::spawn_blocking(move || StorageService::generate_thumbnail(&bytes)).await {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            tracing::warn!(media_id = %media_id, "Thumbnail generation failed: {}", e);
+            return None;
+        }
+        Err(e) => {
+            tracing::warn!(media_id = %media_id, "Thumbnail task panicked: {}", e);
+            return None;
+        }
+    };
+
+    let key = StorageService::thumbnail_key(user_id, trade_id, media_id);
+    match storage.put_object(&key, thumbnail, "image/jpeg").await {
+        Ok(()) => Some(key),
+        Err(e) => {
+            tracing::warn!(media_id = %media_id, "Failed to store thumbnail: {}", e);
+            None
+        }
+    }
+}
+
+/// Removes presigned rows for the trade whose uploads were never confirmed.
+async fn sweep_stale_pending(pool: &PgPool, storage: &StorageService, trade_id: Uuid) -> AppResult<()> {
+    let keys = sqlx::query_scalar::<_, String>(
+        r#"
+        DELETE FROM trade_media
+        WHERE trade_id = $1
+          AND upload_status = 'pending'
+          AND created_at < NOW() - make_interval(mins => $2)
+        RETURNING s3_key
+        "#,
+    )
+    .bind(trade_id)
+    .bind(STALE_PENDING_MINUTES)
+    .fetch_all(pool)
+    .await?;
+
+    if !keys.is_empty() {
+        storage.delete_objects(keys).await;
+    }
+    Ok(())
+}
+
+/// Direct upload through the API. Fields: `file` (required), `media_type`
+/// (defaults to screenshot) and `caption`.
+pub async fn upload_media(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    auth_user: AuthUser,
+    Path(trade_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> AppResult<Json<TradeMedia>> {
+    ensure_trade_owner(&pool, trade_id, auth_user.user_id).await?;
+
+    let mut file: Option<(Option<String>, String, Vec<u8>)> = None;
+    let mut media_type = "screenshot".to_string();
+    let mut caption: Option<String> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "file" => {
+                let file_name = field.file_name().map(str::to_string);
+                let content_type = field.content_type().unwrap_or_default().to_string();
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Failed to read file: {}", e)))?;
+                file = Some((file_name, content_type, bytes.to_vec()));
+            }
+            "media_type" | "caption" => {
+                let value = field
+                    .text()
+                    .await
+                    .map_err(|e| AppError::BadRequest(format!("Invalid '{}' field: {}", name, e)))?;
+                if name == "media_type" {
+                    media_type = value;
+                } else if !value.trim().is_empty() {
+                    caption = Some(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (file_name, content_type, bytes) =
+        file.ok_or_else(|| AppError::Validation("Missing 'file' field".to_string()))?;
+    if let Some(ref name) = file_name {
+        validate_file_name(name)?;
+    }
+    validate_caption(&caption)?;
+
+    let upload = StorageService::validate_upload(&media_type, &content_type, bytes.len() as i64)
+        .map_err(AppError::Validation)?;
+    StorageService::verify_content(&upload, &bytes).map_err(AppError::Validation)?;
+
+    let media_id = Uuid::new_v4();
+    let key = StorageService::object_key(auth_user.user_id, trade_id, media_id, upload.extension);
+    let file_size = bytes.len() as i64;
+    let thumbnail_source = (upload.kind == MediaKind::Image).then(|| bytes.clone());
+
+    storage.put_object(&key, bytes, &upload.mime_type).await?;
+
+    let thumbnail_key = match thumbnail_source {
+        Some(source) => store_thumbnail(&storage, auth_user.user_id, trade_id, media_id, source).await,
+        None => None,
+    };
+
+    let inserted = sqlx::query_as::<_, TradeMedia>(
+        r#"
+        INSERT INTO trade_media (
+            id, trade_id, media_type, s3_key, s3_url, thumbnail_key, thumbnail_url,
+            file_name, file_size, mime_type, caption, upload_status
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, 'uploaded')
+        RETURNING *
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(&media_type)
+    .bind(&key)
+    .bind(storage.public_url(&key))
+    .bind(&thumbnail_key)
+    .bind(thumbnail_key.as_deref().map(|k| storage.public_url(k)))
+    .bind(file_name.as_deref().map(str::trim))
+    .bind(file_size)
+    .bind(&upload.mime_type)
+    .bind(&caption)
+    .fetch_one(pool.as_ref())
+    .await;
+
+    let media = match inserted {
+        Ok(media) => media,
+        Err(e) => {
+            // Don't leave objects behind for a row that was never written
+            storage.delete_objects(vec![key].into_iter().chain(thumbnail_key).collect()).await;
+            return Err(e.into());
+        }
+    };
+
+    tracing::info!(media_id = %media.id, trade_id = %trade_id, "Trade media uploaded");
+    Ok(Json(media))
+}
+
+/// Issues a presigned PUT for uploading straight to the bucket. The row stays
+/// `pending` until the client calls the complete endpoint.
+pub async fn presign_media_upload(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    auth_user: AuthUser,
+    Path(trade_id): Path<Uuid>,
+    Json(req): Json<PresignMediaRequest>,
+) -> AppResult<Json<PresignMediaResponse>> {
+    ensure_trade_owner(&pool, trade_id, auth_user.user_id).await?;
+    validate_file_name(&req.file_name)?;
+    validate_caption(&req.caption)?;
+
+    let upload = StorageService::validate_upload(&req.media_type, &req.mime_type, req.file_size)
+        .map_err(AppError::Validation)?;
+
+    sweep_stale_pending(&pool, &storage, trade_id).await?;
+
+    let media_id = Uuid::new_v4();
+    let key = StorageService::object_key(auth_user.user_id, trade_id, media_id, upload.extension);
+    let upload_url = storage.presign_put(&key, &upload.mime_type).await?;
+
+    let media = sqlx::query_as::<_, TradeMedia>(
+        r#"
+        INSERT INTO trade_media (
+            id, trade_id, media_type, s3_key, s3_url, file_name, file_size,
+            mime_type, caption, upload_status
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'pending')
+        RETURNING *
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(&req.media_type)
+    .bind(&key)
+    .bind(storage.public_url(&key))
+    .bind(req.file_name.trim())
+    .bind(req.file_size)
+    .bind(&upload.mime_type)
+    .bind(&req.caption)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    let expires_at = Utc::now()
+        + chrono::Duration::from_std(PRESIGN_EXPIRY).unwrap_or_else(|_| chrono::Duration::minutes(15));
+
+    Ok(Json(PresignMediaResponse {
+        media,
+        upload_url,
+        method: "PUT".to_string(),
+        headers: HashMap::from([("Content-Type".to_string(), upload.mime_type)]),
+        expires_at,
+    }))
+}
+
+/// Confirms a presigned upload: checks the stored object's size and content,
+/// generates a thumbnail for images and marks the row uploaded. A file that
+/// fails validation is removed along with its row.
+pub async fn complete_media_upload(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<TradeMedia>> {
+    let media = sqlx::query_as::<_, TradeMedia>(
+        r#"
+        SELECT m.* FROM trade_media m
+        JOIN trades t ON t.id = m.trade_id
+        WHERE m.id = $1 AND m.trade_id = $2 AND t.user_id = $3
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+
+    if media.upload_status == "uploaded" {
+        return Ok(Json(media));
+    }
+
+    let (size, _) = storage
+        .head_object(&media.s3_key)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("File has not been uploaded yet".to_string()))?;
+
+    let declared_mime = media.mime_type.clone().unwrap_or_default();
+    let checked: Result<(ValidatedUpload, Option<Vec<u8>>), String> =
+        match StorageService::validate_upload(&media.media_type, &declared_mime, size) {
+            Ok(upload) => {
+                // Images are small enough to pull whole for the thumbnail;
+                // everything else only needs its magic bytes
+                let limit = (upload.kind != MediaKind::Image).then_some(SNIFF_BYTES);
+                let bytes = storage.get_object(&media.s3_key, limit).await?;
+                StorageService::verify_content(&upload, &bytes).map(|()| (upload, Some(bytes)))
+            }
+            Err(e) => Err(e),
+        };
+
+    let (upload, bytes) = match checked {
+        Ok(ok) => ok,
+        Err(reason) => {
+            sqlx::query("DELETE FROM trade_media WHERE id = $1")
+                .bind(media_id)
+                .execute(pool.as_ref())
+                .await?;
+            storage.delete_objects(vec![media.s3_key]).await;
+            return Err(AppError::Validation(reason));
+        }
+    };
+
+    let thumbnail_key = match (upload.kind, bytes) {
+        (MediaKind::Image, Some(bytes)) => {
+            store_thumbnail(&storage, auth_user.user_id, trade_id, media_id, bytes).await
+        }
+        _ => None,
+    };
+
+    let media = sqlx::query_as::<_, TradeMedia>(
+        r#"
+        UPDATE trade_media SET
+            upload_status = 'uploaded',
+            file_size = $1,
+            thumbnail_key = $2,
+            thumbnail_url = $3
+        WHERE id = $4
+        RETURNING *
+        "#,
+    )
+    .bind(size)
+    .bind(&thumbnail_key)
+    .bind(thumbnail_key.as_deref().map(|k| storage.public_url(k)))
+    .bind(media_id)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    tracing::info!(media_id = %media.id, trade_id = %trade_id, "Trade media upload completed");
+    Ok(Json(media))
+}
+
+pub async fn delete_media(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<serde_json::Value>> {
+    let (s3_key, thumbnail_key) = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        DELETE FROM trade_media m
+        USING trades t
+        WHERE m.id = $1 AND m.trade_id = $2 AND t.id = m.trade_id AND t.user_id = $3
+        RETURNING m.s3_key, m.thumbnail_key
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+
+    storage
+        .delete_objects(std::iter::once(s3_key).chain(thumbnail_key).collect())
+        .await;
+
+    Ok(Json(serde_json::json!({"message": "Media deleted successfully"})))
+}
+
+async fn find_media(pool: &PgPool, trade_id: Uuid, media_id: Uuid, user_id: Uuid) -> AppResult<TradeMedia> {
+    sqlx::query_as::<_, TradeMedia>(
+        r#"
+        SELECT m.* FROM trade_media m
+        JOIN trades t ON t.id = m.trade_id
+        WHERE m.id = $1 AND m.trade_id = $2 AND t.user_id = $3 AND m.upload_status = 'uploaded'
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))
+}
+
+/// Locks the media row and reads its annotation list. Edits are
+/// read-modify-write on one JSONB column, so the row lock serializes them
+/// while per-annotation versions catch stale clients.
+async fn lock_annotations(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    trade_id: Uuid,
+    media_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<Vec<ChartAnnotation>> {
+    let stored = sqlx::query_scalar::<_, Option<serde_json::Value>>(
+        r#"
+        SELECT m.annotations FROM trade_media m
+        JOIN trades t ON t.id = m.trade_id
+        WHERE m.id = $1 AND m.trade_id = $2 AND t.user_id = $3 AND m.upload_status = 'uploaded'
+        FOR UPDATE OF m
+        "#,
+    )
+    .bind(media_id)
+    .bind(trade_id)
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+
+    AnnotationService::parse_stored(stored)
+}
+
+async fn save_annotations(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    media_id: Uuid,
+    annotations: &[ChartAnnotation],
+) -> AppResult<()> {
+    let value = serde_json::to_value(annotations)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize annotations: {}", e)))?;
+
+    sqlx::query("UPDATE trade_media SET annotations = $1 WHERE id = $2")
+        .bind(value)
+        .bind(media_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_annotations(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<Vec<ChartAnnotation>>> {
+    let media = find_media(&pool, trade_id, media_id, auth_user.user_id).await?;
+    Ok(Json(AnnotationService::parse_stored(media.annotations)?))
+}
+
+pub async fn create_annotation(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<CreateAnnotationRequest>,
+) -> AppResult<Json<ChartAnnotation>> {
+    let mut tx = pool.begin().await?;
+    let mut annotations = lock_annotations(&mut tx, trade_id, media_id, auth_user.user_id).await?;
+    let annotation = AnnotationService::create(&mut annotations, req, auth_user.user_id)?;
+    save_annotations(&mut tx, media_id, &annotations).await?;
+    tx.commit().await?;
+
+    tracing::info!(annotation_id = %annotation.id, media_id = %media_id, "Chart annotation created");
+    Ok(Json(annotation))
+}
+
+pub async fn update_annotation(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id, annotation_id)): Path<(Uuid, Uuid, Uuid)>,
+    Json(req): Json<UpdateAnnotationRequest>,
+) -> AppResult<Json<ChartAnnotation>> {
+    let mut tx = pool.begin().await?;
+    let mut annotations = lock_annotations(&mut tx, trade_id, media_id, auth_user.user_id).await?;
+    let annotation = AnnotationService::update(&mut annotations, annotation_id, req)?;
+    save_annotations(&mut tx, media_id, &annotations).await?;
+    tx.commit().await?;
+
+    Ok(Json(annotation))
+}
+
+pub async fn delete_annotation(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id, annotation_id)): Path<(Uuid, Uuid, Uuid)>,
+    Query(query): Query<DeleteAnnotationQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut tx = pool.begin().await?;
+    let mut annotations = lock_annotations(&mut tx, trade_id, media_id, auth_user.user_id).await?;
+    AnnotationService::remove(&mut annotations, annotation_id, query.version)?;
+    save_annotations(&mut tx, media_id, &annotations).await?;
+    tx.commit().await?;
+
+    Ok(Json(serde_json::json!({"message": "Annotation deleted successfully"})))
+}
+
+fn ensure_image(media: &TradeMedia, action: &str) -> AppResult<()> {
+    let is_image = media
+        .mime_type
+        .as_deref()
+        .and_then(MediaKind::from_mime)
+        .is_some_and(|(kind, _)| kind == MediaKind::Image);
+    if !is_image {
+        return Err(AppError::Validation(format!("Only image media can be {}", action)));
+    }
+    Ok(())
+}
+
+/// The screenshot with its annotations drawn in, as a PNG.
+pub async fn render_annotated_media(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<([(header::HeaderName, &'static str); 1], Vec<u8>)> {
+    let media = find_media(&pool, trade_id, media_id, auth_user.user_id).await?;
+    ensure_image(&media, "rendered")?;
+
+    let annotations = AnnotationService::parse_stored(media.annotations)?;
+    let bytes = storage.get_object(&media.s3_key, None).await?;
+    let png = tokio::task::spawn_blocking(move || AnnotationService::render(&bytes, &annotations))
+        .await
+        .map_err(|e| AppError::Internal(format!("Render task failed: {}", e)))?
+        .map_err(AppError::Internal)?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+/// Runs vision analysis on a chart screenshot and stores the result on the
+/// media row. The trader's annotations are drawn in first so the model sees
+/// the levels they marked. Re-running replaces the previous analysis.
+pub async fn analyze_media(
+    State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Path((trade_id, media_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<TradeMedia>> {
+    let media = find_media(&pool, trade_id, media_id, auth_user.user_id).await?;
+    ensure_image(&media, "analyzed")?;
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+
+    let trade = sqlx::query_as::<_, Trade>("SELECT * FROM trades WHERE id = $1")
+        .bind(trade_id)
+        .fetch_one(pool.as_ref())
+        .await?;
+
+    let annotations = AnnotationService::parse_stored(media.annotations.clone())?;
+    let original = storage.get_object(&media.s3_key, None).await?;
+    let (image, mime_type) = if annotations.is_empty() {
+        (original, media.mime_type.clone().unwrap_or_default())
+    } else {
+        let png = tokio::task::spawn_blocking(move || AnnotationService::render(&original, &annotations))
+            .await
+            .map_err(|e| AppError::Internal(format!("Render task failed: {}", e)))?
+            .map_err(AppError::Internal)?;
+        (png, "image/png".to_string())
+    };
+
+    let mut usage = Vec::new();
+    let result = ai_service
+        .analyze_chart(&image, &mime_type, Some(&trade), &mut usage)
+        .await;
+    let (analysis, completion) = usage_service
+        .record_on_error(&pool, auth_user.user_id, "chart_analysis", ai_service.provider_name(), &usage, result)
+        .await?;
+    let tokens_used = completion.tokens();
+    usage_service
+        .record(
+            pool.as_ref(),
+            auth_user.user_id,
+            None,
+            "chart_analysis",
+            ai_service.provider_name(),
+            &usage,
+        )
+        .await?;
+    let stored = StoredChartAnalysis {
+        analysis,
+        provider: Some(ai_service.provider_name().to_string()),
+        model_used: completion.model,
+        tokens_used,
+        analyzed_at: Utc::now(),
+    };
+    let value = serde_json::to_value(&stored)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize analysis: {}", e)))?;
+
+    let media = sqlx::query_as::<_, TradeMedia>(
+        "UPDATE trade_media SET ai_analysis = $1 WHERE id = $2 RETURNING *",
+    )
+    .bind(value)
+    .bind(media_id)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    tracing::info!(media_id = %media_id, tokens_used, "Chart screenshot analyzed");
+    Ok(Json(media))
+}
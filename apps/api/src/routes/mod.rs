@@ -6,10 +6,25 @@ pub mod csv;
 pub mod analytics;
 pub mod planning;
 pub mod ai_review;
+pub mod ai_usage;
+pub mod ai_query;
 pub mod risk;
 pub mod psychology;
 pub mod playbook;
 pub mod review;
+pub mod rulesets;
+pub mod accountability;
+pub mod coach;
+pub mod comments;
+pub mod notifications;
+pub mod streaks;
+pub mod economic_events;
+pub mod media;
+pub mod search;
+pub mod two_factor;
+pub mod oauth;
+pub mod api_keys;
+pub mod profile;
 
 pub use auth::*;
 pub use health::*;
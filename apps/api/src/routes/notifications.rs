@@ -0,0 +1,80 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{AuthUser, Notification, NotificationList, NotificationQuery};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn list_notifications(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<NotificationQuery>,
+) -> AppResult<Json<NotificationList>> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let notifications = sqlx::query_as::<_, Notification>(
+        r#"
+        SELECT * FROM notifications
+        WHERE user_id = $1 AND ($2 = FALSE OR read_at IS NULL)
+        ORDER BY created_at DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(query.unread_only.unwrap_or(false))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    let unread_count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read_at IS NULL",
+    )
+    .bind(auth_user.user_id)
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    Ok(Json(NotificationList {
+        notifications,
+        unread_count,
+    }))
+}
+
+pub async fn mark_notification_read(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> AppResult<Json<Notification>> {
+    let notification = sqlx::query_as::<_, Notification>(
+        r#"
+        UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+        WHERE id = $1 AND user_id = $2
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Notification not found".to_string()))?;
+
+    Ok(Json(notification))
+}
+
+pub async fn mark_all_notifications_read(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        "UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL",
+    )
+    .bind(auth_user.user_id)
+    .execute(pool.as_ref())
+    .await?;
+
+    Ok(Json(serde_json::json!({"marked_read": result.rows_affected()})))
+}
@@ -0,0 +1,98 @@
+use crate::error::AppResult;
+use crate::models::{
+    AuthUser, ClientInfo, LoginResponse, OAuthAuthorizeResponse, OAuthCallbackRequest,
+    OAuthIdentity, OAuthProviderInfo,
+};
+use crate::routes::auth::complete_login;
+use crate::services::{AuthService, OAuthService};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub async fn list_oauth_providers(
+    State(oauth_service): State<Arc<OAuthService>>,
+) -> Json<Vec<OAuthProviderInfo>> {
+    Json(oauth_service.providers())
+}
+
+pub async fn start_oauth_login(
+    State(pool): State<Arc<PgPool>>,
+    State(oauth_service): State<Arc<OAuthService>>,
+    Path(provider): Path<String>,
+) -> AppResult<Json<OAuthAuthorizeResponse>> {
+    Ok(Json(oauth_service.begin(&pool, &provider, None).await?))
+}
+
+/// The web app posts the `code` and `state` the provider redirected back with,
+/// plus the `browser_nonce` it kept from `start_oauth_login`.
+pub async fn complete_oauth_login(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    State(oauth_service): State<Arc<OAuthService>>,
+    client: ClientInfo,
+    Json(req): Json<OAuthCallbackRequest>,
+) -> AppResult<Json<LoginResponse>> {
+    let identity = oauth_service
+        .complete(&pool, &req.state, &req.code, &req.browser_nonce, None)
+        .await?;
+    let user = oauth_service
+        .resolve_login(&pool, &auth_service, &identity)
+        .await?;
+
+    tracing::info!(user_id = %user.id, provider = %identity.provider, "User signed in through OAuth");
+
+    Ok(Json(complete_login(&pool, &auth_service, user, &client).await?))
+}
+
+pub async fn start_oauth_link(
+    State(pool): State<Arc<PgPool>>,
+    State(oauth_service): State<Arc<OAuthService>>,
+    auth_user: AuthUser,
+    Path(provider): Path<String>,
+) -> AppResult<Json<OAuthAuthorizeResponse>> {
+    Ok(Json(
+        oauth_service
+            .begin(&pool, &provider, Some(auth_user.user_id))
+            .await?,
+    ))
+}
+
+pub async fn complete_oauth_link(
+    State(pool): State<Arc<PgPool>>,
+    State(oauth_service): State<Arc<OAuthService>>,
+    auth_user: AuthUser,
+    Json(req): Json<OAuthCallbackRequest>,
+) -> AppResult<Json<Vec<OAuthIdentity>>> {
+    let identity = oauth_service
+        .complete(&pool, &req.state, &req.code, &req.browser_nonce, Some(auth_user.user_id))
+        .await?;
+    oauth_service
+        .link(&pool, auth_user.user_id, &identity)
+        .await?;
+
+    Ok(Json(OAuthService::identities(&pool, auth_user.user_id).await?))
+}
+
+pub async fn list_identities(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<OAuthIdentity>>> {
+    Ok(Json(OAuthService::identities(&pool, auth_user.user_id).await?))
+}
+
+pub async fn unlink_identity(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(identity_id): Path<Uuid>,
+) -> AppResult<Json<Value>> {
+    OAuthService::unlink(&pool, auth_user.user_id, identity_id).await?;
+
+    tracing::info!(user_id = %auth_user.user_id, identity_id = %identity_id, "OAuth identity unlinked");
+
+    Ok(Json(json!({ "message": "Linked account removed" })))
+}
@@ -1,8 +1,12 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    AuthUser, CreateDailyPlanRequest, CreateWatchlistItemRequest, DailyPlan, DailyPlanWithWatchlist,
+    AuthUser, ClaudeMessage, CreateDailyPlanRequest, CreateWatchlistItemRequest, DailyPlan, DailyPlanWithWatchlist,
     UpdateDailyPlanRequest, UpdateWatchlistItemRequest, WatchlistItem,
 };
+use crate::services::{
+    AiContextService, AiService, AiUsageService, EconomicCalendarService, StreakService, StreakType,
+    PLAN_OF_ATTACK_PROMPT_VERSION,
+};
 use axum::{
     extract::{Path, Query, State},
     Json,
@@ -66,6 +70,17 @@ pub async fn create_daily_plan(
 
     tracing::info!(plan_id = %plan.id, date = %plan.plan_date, "Daily plan created");
 
+    StreakService::track(
+        &pool,
+        auth_user.user_id,
+        StreakType::Planning,
+        "daily_plan",
+        Some(plan.id),
+        plan.plan_date,
+        StreakService::plan_quality(&plan),
+    )
+    .await;
+
     Ok(Json(plan))
 }
 
@@ -90,7 +105,14 @@ pub async fn get_daily_plan(
     .fetch_all(pool.as_ref())
     .await?;
 
-    Ok(Json(DailyPlanWithWatchlist { plan, watchlist }))
+    let economic_events =
+        EconomicCalendarService::events_for_day(&pool, auth_user.user_id, plan.plan_date).await?;
+
+    Ok(Json(DailyPlanWithWatchlist {
+        plan,
+        watchlist,
+        economic_events,
+    }))
 }
 
 pub async fn get_daily_plan_by_date(
@@ -121,7 +143,15 @@ pub async fn get_daily_plan_by_date(
         .fetch_all(pool.as_ref())
         .await?;
 
-        Ok(Json(Some(DailyPlanWithWatchlist { plan, watchlist })))
+        let economic_events =
+            EconomicCalendarService::events_for_day(&pool, auth_user.user_id, plan.plan_date)
+                .await?;
+
+        Ok(Json(Some(DailyPlanWithWatchlist {
+            plan,
+            watchlist,
+            economic_events,
+        })))
     } else {
         Ok(Json(None))
     }
@@ -199,6 +229,80 @@ pub async fn update_daily_plan(
     .fetch_one(pool.as_ref())
     .await?;
 
+    if req.completed == Some(true) {
+        StreakService::track_plan_adherence(&pool, &plan).await;
+    }
+
+    Ok(Json(plan))
+}
+
+/// Generates (or regenerates) the plan's AI plan of attack from its
+/// watchlist, the day's events, recent setup results and active goals.
+pub async fn generate_plan_of_attack(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Path(plan_id): Path<Uuid>,
+) -> AppResult<Json<DailyPlan>> {
+    let plan = sqlx::query_as::<_, DailyPlan>(
+        "SELECT * FROM daily_plans WHERE id = $1 AND user_id = $2",
+    )
+    .bind(plan_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Daily plan not found".to_string()))?;
+
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+
+    let context = AiContextService::for_plan(&pool, &plan).await?;
+    let prompt = AiService::build_plan_of_attack_prompt(&plan, &context);
+    let completion = ai_service
+        .chat(vec![ClaudeMessage::text("user", prompt.clone())])
+        .await?;
+
+    let mut tx = pool.begin().await?;
+
+    let plan = sqlx::query_as::<_, DailyPlan>(
+        r#"
+        UPDATE daily_plans SET
+            ai_plan_of_attack = $1,
+            ai_plan_prompt = $2,
+            ai_plan_prompt_version = $3,
+            ai_plan_provider = $4,
+            ai_plan_model = $5,
+            ai_plan_generated_at = NOW()
+        WHERE id = $6 AND user_id = $7
+        RETURNING *
+        "#,
+    )
+    .bind(completion.text.trim())
+    .bind(&prompt)
+    .bind(PLAN_OF_ATTACK_PROMPT_VERSION)
+    .bind(ai_service.provider_name())
+    .bind(&completion.model)
+    .bind(plan_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Daily plan not found".to_string()))?;
+
+    usage_service
+        .record(
+            &mut *tx,
+            auth_user.user_id,
+            None,
+            "plan_of_attack",
+            ai_service.provider_name(),
+            &[completion.usage()],
+        )
+        .await?;
+
+    tx.commit().await?;
+
+    tracing::info!(plan_id = %plan.id, model = %completion.model, "AI plan of attack generated");
+
     Ok(Json(plan))
 }
 
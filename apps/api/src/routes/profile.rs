@@ -0,0 +1,54 @@
+use crate::error::AppResult;
+use crate::models::{AuthUser, DailyLimits, DailyLimitsQuery, UpdateProfileRequest, UserProfile};
+use crate::services::{ProfileService, StreakService};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub async fn get_profile(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<UserProfile>> {
+    Ok(Json(ProfileService::get(&pool, auth_user.user_id).await?))
+}
+
+pub async fn update_profile(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<UpdateProfileRequest>,
+) -> AppResult<Json<UserProfile>> {
+    let profile = ProfileService::update(&pool, auth_user.user_id, &req, false).await?;
+
+    tracing::info!(user_id = %auth_user.user_id, "Profile updated");
+
+    Ok(Json(profile))
+}
+
+/// Saves the onboarding answers and marks onboarding complete.
+pub async fn complete_onboarding(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Json(req): Json<UpdateProfileRequest>,
+) -> AppResult<Json<UserProfile>> {
+    let profile = ProfileService::update(&pool, auth_user.user_id, &req, true).await?;
+
+    tracing::info!(user_id = %auth_user.user_id, "Onboarding completed");
+
+    Ok(Json(profile))
+}
+
+pub async fn get_daily_limits(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Query(query): Query<DailyLimitsQuery>,
+) -> AppResult<Json<DailyLimits>> {
+    let date = match query.date {
+        Some(date) => date,
+        None => StreakService::today_in(StreakService::user_timezone(&pool, auth_user.user_id).await?),
+    };
+
+    Ok(Json(ProfileService::daily_limits_for(&pool, auth_user.user_id, date).await?))
+}
@@ -3,6 +3,7 @@ use crate::models::{
     AuthUser, CreateMoodLogRequest, MoodLog, PsychologyInsights, UpdateMoodLogRequest,
     validate_mood_score,
 };
+use crate::services::{StreakService, StreakType};
 use axum::{
     extract::{Path, Query, State},
     Json,
@@ -89,6 +90,17 @@ pub async fn create_mood_log(
     })?;
 
     tracing::info!(log_id = %log.id, date = %log.log_date, "Mood log created");
+
+    StreakService::track(
+        &pool,
+        auth_user.user_id,
+        StreakType::Journal,
+        "mood_log",
+        Some(log.id),
+        log.log_date,
+        StreakService::mood_log_quality(&log),
+    )
+    .await;
     Ok(Json(log))
 }
 
@@ -1,7 +1,12 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    AuthUser, CreateReviewRequest, DailyPnl, PeriodicReview, ReviewWithStats,
-    SetupSummary, UpdateReviewRequest, validate_review_request, validate_rating,
+    AiReview, AuthUser, ClaudeMessage, CreateReviewRequest, DailyPnl, DraftReviewRequest,
+    PeriodicReview, ReviewDraftResponse, ReviewWithStats, SetupSummary, UpdateReviewRequest,
+    validate_draft_request, validate_review_request, validate_rating,
+};
+use crate::services::{
+    AiContextService, AiService, AiUsageService, LlmUsage, StreakService, StreakType,
+    REVIEW_DRAFT_PROMPT_VERSION,
 };
 use axum::{
     extract::{Path, Query, State},
@@ -23,12 +28,168 @@ struct TradeStats {
     avg_r_multiple: Option<Decimal>,
 }
 
+/// Only weekly reviews feed the review streak. A week is credited once, so
+/// later edits to the same review don't extend it.
+async fn track_review_streak(pool: &PgPool, review: &PeriodicReview) {
+    if review.review_type == "weekly" {
+        StreakService::track(
+            pool,
+            review.user_id,
+            StreakType::Review,
+            "periodic_review",
+            Some(review.id),
+            review.period_start,
+            StreakService::review_quality(review),
+        )
+        .await;
+    }
+}
+
+/// Checks that an accepted draft belongs to the user and matches the
+/// review's type.
+async fn ensure_ai_draft(
+    pool: &PgPool,
+    user_id: Uuid,
+    ai_review_id: Uuid,
+    review_type: &str,
+) -> AppResult<()> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM ai_reviews WHERE id = $1 AND user_id = $2 AND review_type = $3)",
+    )
+    .bind(ai_review_id)
+    .bind(user_id)
+    .bind(review_type)
+    .fetch_one(pool)
+    .await?
+    .then_some(())
+    .ok_or_else(|| AppError::NotFound("AI review draft not found".to_string()))
+}
+
+/// Drafts the narrative fields of a weekly or monthly review with AI. The
+/// draft is recorded in `ai_reviews` but not applied: the user edits it and
+/// saves it through `create_review` or `update_review` with `ai_review_id`.
+pub async fn draft_review(
+    State(pool): State<Arc<PgPool>>,
+    State(ai_service): State<Arc<AiService>>,
+    State(usage_service): State<Arc<AiUsageService>>,
+    auth_user: AuthUser,
+    Json(req): Json<DraftReviewRequest>,
+) -> AppResult<Json<ReviewDraftResponse>> {
+    validate_draft_request(&req).map_err(AppError::Validation)?;
+    usage_service.ensure_within_budget(&pool, auth_user.user_id).await?;
+
+    let context = AiContextService::for_period(
+        &pool,
+        auth_user.user_id,
+        &req.review_type,
+        req.period_start,
+        req.period_end,
+    )
+    .await?;
+    let prompt =
+        AiService::build_review_draft_prompt(&req.review_type, req.period_start, req.period_end, &context);
+    let messages = vec![ClaudeMessage::text("user", prompt)];
+    let completion = ai_service.chat(messages.clone()).await?;
+    let model = completion.model.clone();
+    let mut usage = Vec::new();
+    let result = ai_service.complete_review_draft(messages, completion, &mut usage).await;
+    let result = usage_service
+        .record_on_error(&pool, auth_user.user_id, "review_draft", ai_service.provider_name(), &usage, result)
+        .await?;
+    if result.attempts > 1 {
+        tracing::info!(attempts = result.attempts, "AI review draft needed repair");
+    }
+    let tokens: i32 = usage.iter().map(LlmUsage::tokens).sum();
+    let cost = usage_service.cost(&usage);
+    let draft = result.draft;
+
+    let mut tx = pool.begin().await?;
+
+    let ai_review = sqlx::query_as::<_, AiReview>(
+        r#"
+        INSERT INTO ai_reviews (
+            user_id, review_type, strengths, weaknesses, key_lesson, actionable_fixes,
+            raw_response, tokens_used, cost_usd, prompt_version, provider, model_used
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING *
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(&req.review_type)
+    .bind(vec![draft.what_went_well.clone()])
+    .bind(vec![draft.what_to_improve.clone()])
+    .bind(draft.key_lessons.first())
+    .bind(&draft.goals_next_period)
+    .bind(&result.raw_response)
+    .bind(tokens)
+    .bind(cost)
+    .bind(REVIEW_DRAFT_PROMPT_VERSION)
+    .bind(ai_service.provider_name())
+    .bind(&model)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Keep the exchange so the draft can be discussed through review chat
+    sqlx::query(
+        r#"
+        INSERT INTO ai_review_messages (review_id, role, content, tokens_used)
+        VALUES ($1, 'user', $2, NULL), ($1, 'assistant', $3, $4)
+        "#,
+    )
+    .bind(ai_review.id)
+    .bind(format!(
+        "Draft my {} review for {} to {}.",
+        req.review_type, req.period_start, req.period_end
+    ))
+    .bind(AiService::format_review_draft(&draft))
+    .bind(tokens)
+    .execute(&mut *tx)
+    .await?;
+
+    usage_service
+        .record(
+            &mut *tx,
+            auth_user.user_id,
+            Some(ai_review.id),
+            "review_draft",
+            ai_service.provider_name(),
+            &usage,
+        )
+        .await?;
+
+    tx.commit().await?;
+
+    let existing_review_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM periodic_reviews WHERE user_id = $1 AND review_type = $2 AND period_start = $3",
+    )
+    .bind(auth_user.user_id)
+    .bind(&req.review_type)
+    .bind(req.period_start)
+    .fetch_optional(pool.as_ref())
+    .await?;
+
+    tracing::info!(ai_review_id = %ai_review.id, review_type = %req.review_type, "AI review draft created");
+
+    Ok(Json(ReviewDraftResponse {
+        review_type: req.review_type,
+        period_start: req.period_start,
+        period_end: req.period_end,
+        existing_review_id,
+        draft,
+        ai_review,
+    }))
+}
+
 pub async fn create_review(
     State(pool): State<Arc<PgPool>>,
     auth_user: AuthUser,
     Json(req): Json<CreateReviewRequest>,
 ) -> AppResult<Json<PeriodicReview>> {
     validate_review_request(&req).map_err(|e| AppError::Validation(e))?;
+    if let Some(ai_review_id) = req.ai_review_id {
+        ensure_ai_draft(&pool, auth_user.user_id, ai_review_id, &req.review_type).await?;
+    }
 
     // Auto-compute trade statistics for the period
     let stats = sqlx::query_as::<_, TradeStats>(
@@ -64,7 +225,8 @@ pub async fn create_review(
             what_went_well, what_to_improve, key_lessons, rules_broken,
             best_trade_id, worst_trade_id,
             goals_met, goals_missed, goals_next_period,
-            discipline_rating, patience_rating, execution_rating, overall_rating
+            discipline_rating, patience_rating, execution_rating, overall_rating,
+            ai_review_id
         )
         VALUES (
             $1, $2, $3, $4,
@@ -72,7 +234,8 @@ pub async fn create_review(
             $11, $12, $13, $14,
             $15, $16,
             $17, $18, $19,
-            $20, $21, $22, $23
+            $20, $21, $22, $23,
+            $24
         )
         RETURNING *
         "#,
@@ -100,6 +263,7 @@ pub async fn create_review(
     .bind(req.patience_rating)
     .bind(req.execution_rating)
     .bind(req.overall_rating)
+    .bind(req.ai_review_id)
     .fetch_one(pool.as_ref())
     .await
     .map_err(|e| match e {
@@ -116,6 +280,8 @@ pub async fn create_review(
         "Periodic review created"
     );
 
+    track_review_streak(&pool, &review).await;
+
     Ok(Json(review))
 }
 
@@ -242,16 +408,19 @@ pub async fn update_review(
         validate_rating(r, "overall_rating").map_err(|e| AppError::Validation(e))?;
     }
 
-    sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM periodic_reviews WHERE id = $1 AND user_id = $2)",
+    let review_type = sqlx::query_scalar::<_, String>(
+        "SELECT review_type FROM periodic_reviews WHERE id = $1 AND user_id = $2",
     )
     .bind(review_id)
     .bind(auth_user.user_id)
-    .fetch_one(pool.as_ref())
+    .fetch_optional(pool.as_ref())
     .await?
-    .then_some(())
     .ok_or_else(|| AppError::NotFound("Review not found".to_string()))?;
 
+    if let Some(ai_review_id) = req.ai_review_id {
+        ensure_ai_draft(&pool, auth_user.user_id, ai_review_id, &review_type).await?;
+    }
+
     let review = sqlx::query_as::<_, PeriodicReview>(
         r#"
         UPDATE periodic_reviews SET
@@ -268,8 +437,9 @@ pub async fn update_review(
             patience_rating = COALESCE($11, patience_rating),
             execution_rating = COALESCE($12, execution_rating),
             overall_rating = COALESCE($13, overall_rating),
+            ai_review_id = COALESCE($14, ai_review_id),
             updated_at = NOW()
-        WHERE id = $14
+        WHERE id = $15
         RETURNING *
         "#,
     )
@@ -286,10 +456,13 @@ pub async fn update_review(
     .bind(req.patience_rating)
     .bind(req.execution_rating)
     .bind(req.overall_rating)
+    .bind(req.ai_review_id)
     .bind(review_id)
     .fetch_one(pool.as_ref())
     .await?;
 
+    track_review_streak(&pool, &review).await;
+
     Ok(Json(review))
 }
 
@@ -1,38 +1,64 @@
-use crate::error::AppResult;
-use crate::services::RiskCalculator;
-use axum::Json;
+use crate::error::{AppError, AppResult};
+use crate::middleware::OptionalAuth;
+use crate::services::{ProfileService, RiskCalculator};
+use axum::{extract::State, Json};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
 pub struct PositionSizeRequest {
     pub account_size: Decimal,
-    pub risk_percent: Decimal,
+    /// Defaults to the profile's `default_risk_pct`
+    pub risk_percent: Option<Decimal>,
     pub entry_price: Decimal,
     pub stop_loss: Decimal,
 }
 
 #[derive(Debug, Serialize)]
 pub struct PositionSizeResponse {
+    pub risk_percent: Decimal,
     pub position_size: Decimal,
     pub risk_amount: Decimal,
     pub position_value: Decimal,
 }
 
+/// Public calculator. Signed-in users may omit `risk_percent` to use their
+/// profile's default; that is the only case that reads the database.
 pub async fn calculate_position_size(
+    State(pool): State<Arc<PgPool>>,
+    OptionalAuth(auth_user): OptionalAuth,
     Json(req): Json<PositionSizeRequest>,
 ) -> AppResult<Json<PositionSizeResponse>> {
+    let risk_percent = match (req.risk_percent, auth_user) {
+        (Some(risk_percent), _) => risk_percent,
+        (None, Some(auth_user)) => ProfileService::trading_defaults(&pool, auth_user.user_id)
+            .await?
+            .default_risk_pct
+            .ok_or_else(|| {
+                AppError::Validation(
+                    "risk_percent is required when no default risk is set in your profile"
+                        .to_string(),
+                )
+            })?,
+        (None, None) => {
+            return Err(AppError::Validation("risk_percent is required".to_string()));
+        }
+    };
+
     let position_size = RiskCalculator::calculate_position_size(
         req.account_size,
-        req.risk_percent,
+        risk_percent,
         req.entry_price,
         req.stop_loss,
     );
 
-    let risk_amount = req.account_size * (req.risk_percent / Decimal::from(100));
+    let risk_amount = req.account_size * (risk_percent / Decimal::from(100));
     let position_value = position_size * req.entry_price;
 
     Ok(Json(PositionSizeResponse {
+        risk_percent,
         position_size,
         risk_amount,
         position_value,
@@ -0,0 +1,658 @@
+use crate::error::{AppError, AppResult};
+use crate::middleware::VerifiedUser;
+use crate::models::{
+    validate_ruleset_text, validate_ruleset_type, AuthUser, ChecklistRulesetData, DailyPlan,
+    GradingRubric, ImportRulesetRequest, ImportRulesetResponse, PlaybookSetup,
+    PlaybookSetupRulesetData, PublicRuleset, PublishRulesetRequest, RubricRulesetData,
+    RulesetListQuery, SharedRuleset, UpdateRulesetRequest,
+};
+use crate::services::{ConflictStrategy, NameResolution, RulesetService};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Snapshot of a source entity, ready to be stored as `ruleset_data`.
+struct RulesetSnapshot {
+    name: String,
+    description: Option<String>,
+    data: serde_json::Value,
+}
+
+fn encode_ruleset_data<T: Serialize>(data: &T) -> AppResult<serde_json::Value> {
+    serde_json::to_value(data)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize ruleset: {}", e)))
+}
+
+fn decode_ruleset_data<T: DeserializeOwned>(ruleset: &SharedRuleset) -> AppResult<T> {
+    serde_json::from_value(ruleset.ruleset_data.clone()).map_err(|e| {
+        tracing::warn!(ruleset_id = %ruleset.id, error = %e, "Malformed ruleset data");
+        AppError::Validation("Ruleset data is malformed and cannot be imported".to_string())
+    })
+}
+
+async fn snapshot_source(
+    pool: &PgPool,
+    user_id: Uuid,
+    ruleset_type: &str,
+    source_id: Uuid,
+) -> AppResult<RulesetSnapshot> {
+    match ruleset_type {
+        "playbook_setup" => {
+            let setup = sqlx::query_as::<_, PlaybookSetup>(
+                "SELECT * FROM playbook_setups WHERE id = $1 AND user_id = $2",
+            )
+            .bind(source_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Playbook setup not found".to_string()))?;
+
+            let data = encode_ruleset_data(&PlaybookSetupRulesetData {
+                description: setup.description.clone(),
+                criteria: setup.criteria,
+                expected_r_min: setup.expected_r_min,
+                expected_r_max: setup.expected_r_max,
+                min_conviction: setup.min_conviction,
+                preferred_timeframe: setup.preferred_timeframe,
+                market_regimes: setup.market_regimes,
+                common_mistakes: setup.common_mistakes,
+            })?;
+
+            Ok(RulesetSnapshot {
+                name: setup.name,
+                description: setup.description,
+                data,
+            })
+        }
+        "rubric" => {
+            let rubric = sqlx::query_as::<_, GradingRubric>(
+                "SELECT * FROM grading_rubrics WHERE id = $1 AND user_id = $2",
+            )
+            .bind(source_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Grading rubric not found".to_string()))?;
+
+            let data = encode_ruleset_data(&RubricRulesetData {
+                criteria: rubric.criteria,
+                threshold_a: rubric.threshold_a,
+                threshold_b: rubric.threshold_b,
+                threshold_c: rubric.threshold_c,
+            })?;
+
+            Ok(RulesetSnapshot {
+                name: rubric.name,
+                description: None,
+                data,
+            })
+        }
+        _ => {
+            let plan = sqlx::query_as::<_, DailyPlan>(
+                "SELECT * FROM daily_plans WHERE id = $1 AND user_id = $2",
+            )
+            .bind(source_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Daily plan not found".to_string()))?;
+
+            let items = plan
+                .checklist_items
+                .filter(|v| v.as_array().is_some_and(|a| !a.is_empty()))
+                .ok_or_else(|| {
+                    AppError::Validation("Daily plan has no checklist items to publish".to_string())
+                })?;
+
+            Ok(RulesetSnapshot {
+                name: format!("Checklist {}", plan.plan_date),
+                description: None,
+                data: encode_ruleset_data(&ChecklistRulesetData { items })?,
+            })
+        }
+    }
+}
+
+/// Publishes a playbook setup, grading rubric or plan checklist. Publishing the
+/// same source again creates a new version of the existing ruleset and keeps
+/// its share token stable. Requires a verified email address.
+pub async fn publish_ruleset(
+    State(pool): State<Arc<PgPool>>,
+    VerifiedUser(auth_user): VerifiedUser,
+    Json(req): Json<PublishRulesetRequest>,
+) -> AppResult<Json<SharedRuleset>> {
+    let ruleset_type = validate_ruleset_type(&req.ruleset_type).map_err(AppError::Validation)?;
+    validate_ruleset_text(req.name.as_deref(), req.description.as_deref())
+        .map_err(AppError::Validation)?;
+
+    let snapshot = snapshot_source(&pool, auth_user.user_id, ruleset_type, req.source_id).await?;
+
+    let name = req
+        .name
+        .as_deref()
+        .map(|n| n.trim().to_string())
+        .unwrap_or(snapshot.name);
+    let description = req.description.or(snapshot.description);
+
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        r#"
+        INSERT INTO shared_rulesets (
+            creator_user_id, ruleset_type, source_id, name, description,
+            ruleset_data, is_public, share_token
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, COALESCE($7, FALSE), $8)
+        ON CONFLICT (creator_user_id, ruleset_type, source_id) WHERE source_id IS NOT NULL
+        DO UPDATE SET
+            name = EXCLUDED.name,
+            description = EXCLUDED.description,
+            ruleset_data = EXCLUDED.ruleset_data,
+            is_public = COALESCE($7, shared_rulesets.is_public),
+            version = shared_rulesets.version + 1,
+            published_at = NOW(),
+            updated_at = NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(ruleset_type)
+    .bind(req.source_id)
+    .bind(&name)
+    .bind(&description)
+    .bind(&snapshot.data)
+    .bind(req.is_public)
+    .bind(RulesetService::generate_share_token())
+    .fetch_one(pool.as_ref())
+    .await?;
+
+    tracing::info!(
+        ruleset_id = %ruleset.id,
+        ruleset_type = %ruleset.ruleset_type,
+        version = ruleset.version,
+        "Ruleset published"
+    );
+
+    Ok(Json(ruleset))
+}
+
+/// Browse public rulesets from all users.
+pub async fn list_public_rulesets(
+    State(pool): State<Arc<PgPool>>,
+    _auth_user: AuthUser,
+    Query(query): Query<RulesetListQuery>,
+) -> AppResult<Json<Vec<PublicRuleset>>> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(20).clamp(1, 100);
+    let offset = (page - 1) * per_page;
+
+    let ruleset_type = query
+        .ruleset_type
+        .as_deref()
+        .map(validate_ruleset_type)
+        .transpose()
+        .map_err(AppError::Validation)?;
+
+    // Whitelisted ORDER BY — never interpolate user input
+    let order_clause = match query.sort.as_deref().unwrap_or("popular") {
+        "popular" => "import_count DESC, published_at DESC",
+        "recent" => "published_at DESC",
+        other => {
+            return Err(AppError::Validation(format!(
+                "Invalid sort '{}'. Allowed: popular, recent",
+                other
+            )))
+        }
+    };
+
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| format!("%{}%", s));
+
+    let sql = format!(
+        r#"
+        SELECT * FROM shared_rulesets
+        WHERE is_public = TRUE
+          AND ($1::VARCHAR IS NULL OR ruleset_type = $1)
+          AND ($2::VARCHAR IS NULL OR name ILIKE $2 OR description ILIKE $2)
+        ORDER BY {}
+        LIMIT $3 OFFSET $4
+        "#,
+        order_clause
+    );
+
+    let rulesets = sqlx::query_as::<_, SharedRuleset>(&sql)
+        .bind(ruleset_type)
+        .bind(search)
+        .bind(per_page)
+        .bind(offset)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    Ok(Json(rulesets.into_iter().map(PublicRuleset::from).collect()))
+}
+
+/// Rulesets published by the current user, including their share tokens.
+pub async fn list_my_rulesets(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<SharedRuleset>>> {
+    let rulesets = sqlx::query_as::<_, SharedRuleset>(
+        "SELECT * FROM shared_rulesets WHERE creator_user_id = $1 ORDER BY updated_at DESC",
+    )
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(rulesets))
+}
+
+pub async fn get_ruleset(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(ruleset_id): Path<Uuid>,
+) -> AppResult<Json<PublicRuleset>> {
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        "SELECT * FROM shared_rulesets WHERE id = $1 AND (is_public = TRUE OR creator_user_id = $2)",
+    )
+    .bind(ruleset_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Ruleset not found".to_string()))?;
+
+    Ok(Json(PublicRuleset::from(ruleset)))
+}
+
+/// Fetch a ruleset by share token. No account is required.
+pub async fn get_ruleset_by_token(
+    State(pool): State<Arc<PgPool>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<PublicRuleset>> {
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        "SELECT * FROM shared_rulesets WHERE share_token = $1",
+    )
+    .bind(&token)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Ruleset not found".to_string()))?;
+
+    Ok(Json(PublicRuleset::from(ruleset)))
+}
+
+pub async fn update_ruleset(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(ruleset_id): Path<Uuid>,
+    Json(req): Json<UpdateRulesetRequest>,
+) -> AppResult<Json<SharedRuleset>> {
+    validate_ruleset_text(req.name.as_deref(), req.description.as_deref())
+        .map_err(AppError::Validation)?;
+
+    let new_token = req
+        .rotate_share_token
+        .unwrap_or(false)
+        .then(RulesetService::generate_share_token);
+
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        r#"
+        UPDATE shared_rulesets SET
+            name = COALESCE($1, name),
+            description = COALESCE($2, description),
+            is_public = COALESCE($3, is_public),
+            share_token = COALESCE($4, share_token),
+            updated_at = NOW()
+        WHERE id = $5 AND creator_user_id = $6
+        RETURNING *
+        "#,
+    )
+    .bind(req.name.as_deref().map(str::trim))
+    .bind(&req.description)
+    .bind(req.is_public)
+    .bind(new_token)
+    .bind(ruleset_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Ruleset not found".to_string()))?;
+
+    Ok(Json(ruleset))
+}
+
+pub async fn delete_ruleset(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(ruleset_id): Path<Uuid>,
+) -> AppResult<Json<serde_json::Value>> {
+    let result = sqlx::query(
+        "DELETE FROM shared_rulesets WHERE id = $1 AND creator_user_id = $2",
+    )
+    .bind(ruleset_id)
+    .bind(auth_user.user_id)
+    .execute(pool.as_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Ruleset not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({ "message": "Ruleset deleted" })))
+}
+
+pub async fn import_ruleset(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(ruleset_id): Path<Uuid>,
+    Json(req): Json<ImportRulesetRequest>,
+) -> AppResult<Json<ImportRulesetResponse>> {
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        "SELECT * FROM shared_rulesets WHERE id = $1 AND (is_public = TRUE OR creator_user_id = $2)",
+    )
+    .bind(ruleset_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Ruleset not found".to_string()))?;
+
+    import_into_account(&pool, auth_user.user_id, ruleset, req).await
+}
+
+pub async fn import_ruleset_by_token(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(token): Path<String>,
+    Json(req): Json<ImportRulesetRequest>,
+) -> AppResult<Json<ImportRulesetResponse>> {
+    let ruleset = sqlx::query_as::<_, SharedRuleset>(
+        "SELECT * FROM shared_rulesets WHERE share_token = $1",
+    )
+    .bind(&token)
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Ruleset not found".to_string()))?;
+
+    import_into_account(&pool, auth_user.user_id, ruleset, req).await
+}
+
+/// Copies a ruleset into the importer's account and bumps `import_count`,
+/// all in one transaction. Creators re-importing their own ruleset don't
+/// count, so the number only reflects other users.
+async fn import_into_account(
+    pool: &PgPool,
+    user_id: Uuid,
+    ruleset: SharedRuleset,
+    req: ImportRulesetRequest,
+) -> AppResult<Json<ImportRulesetResponse>> {
+    let strategy = ConflictStrategy::parse(req.on_conflict.as_deref())?;
+    validate_ruleset_text(req.name.as_deref(), None).map_err(AppError::Validation)?;
+    let desired_name = req.name.as_deref().unwrap_or(&ruleset.name);
+
+    let mut tx = pool.begin().await?;
+
+    let (imported_id, resolution) = match ruleset.ruleset_type.as_str() {
+        "playbook_setup" => {
+            let data: PlaybookSetupRulesetData = decode_ruleset_data(&ruleset)?;
+            import_playbook_setup(&mut tx, user_id, desired_name, strategy, data).await?
+        }
+        "rubric" => {
+            let data: RubricRulesetData = decode_ruleset_data(&ruleset)?;
+            import_rubric(&mut tx, user_id, desired_name, strategy, data).await?
+        }
+        "checklist" => {
+            let data: ChecklistRulesetData = decode_ruleset_data(&ruleset)?;
+            let plan_date = req.plan_date.ok_or_else(|| {
+                AppError::Validation("plan_date is required to import a checklist".to_string())
+            })?;
+            import_checklist(&mut tx, user_id, plan_date, strategy, data).await?
+        }
+        other => {
+            return Err(AppError::Internal(format!("Unknown ruleset type '{}'", other)));
+        }
+    };
+
+    if user_id != ruleset.creator_user_id {
+        sqlx::query("UPDATE shared_rulesets SET import_count = import_count + 1 WHERE id = $1")
+            .bind(ruleset.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    tracing::info!(
+        ruleset_id = %ruleset.id,
+        imported_id = %imported_id,
+        renamed = resolution.renamed,
+        replaced = resolution.replaced,
+        "Ruleset imported"
+    );
+
+    Ok(Json(ImportRulesetResponse {
+        ruleset_id: ruleset.id,
+        ruleset_type: ruleset.ruleset_type,
+        version: ruleset.version,
+        imported_id,
+        name: resolution.name,
+        renamed: resolution.renamed,
+        replaced: resolution.replaced,
+    }))
+}
+
+async fn import_playbook_setup(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    desired_name: &str,
+    strategy: ConflictStrategy,
+    data: PlaybookSetupRulesetData,
+) -> AppResult<(Uuid, NameResolution)> {
+    let existing = sqlx::query_scalar::<_, String>(
+        "SELECT name FROM playbook_setups WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let resolution =
+        RulesetService::resolve_import_name(desired_name, &existing, strategy, "playbook setup")?;
+
+    let sql = if resolution.replaced {
+        r#"
+        UPDATE playbook_setups SET
+            description = $3, criteria = $4,
+            expected_r_min = $5, expected_r_max = $6, min_conviction = $7,
+            preferred_timeframe = $8, market_regimes = $9, common_mistakes = $10,
+            updated_at = NOW()
+        WHERE user_id = $1 AND name = $2
+        RETURNING id
+        "#
+    } else {
+        r#"
+        INSERT INTO playbook_setups (
+            user_id, name, description, criteria,
+            expected_r_min, expected_r_max, min_conviction,
+            preferred_timeframe, market_regimes, common_mistakes
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id
+        "#
+    };
+
+    let id = sqlx::query_scalar::<_, Uuid>(sql)
+        .bind(user_id)
+        .bind(&resolution.name)
+        .bind(&data.description)
+        .bind(&data.criteria)
+        .bind(data.expected_r_min)
+        .bind(data.expected_r_max)
+        .bind(data.min_conviction)
+        .bind(&data.preferred_timeframe)
+        .bind(&data.market_regimes)
+        .bind(&data.common_mistakes)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok((id, resolution))
+}
+
+async fn import_rubric(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    desired_name: &str,
+    strategy: ConflictStrategy,
+    data: RubricRulesetData,
+) -> AppResult<(Uuid, NameResolution)> {
+    let existing = sqlx::query_scalar::<_, String>(
+        "SELECT name FROM grading_rubrics WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let resolution =
+        RulesetService::resolve_import_name(desired_name, &existing, strategy, "grading rubric")?;
+
+    let sql = if resolution.replaced {
+        r#"
+        UPDATE grading_rubrics SET
+            criteria = $3,
+            threshold_a = COALESCE($4, threshold_a),
+            threshold_b = COALESCE($5, threshold_b),
+            threshold_c = COALESCE($6, threshold_c),
+            updated_at = NOW()
+        WHERE user_id = $1 AND name = $2
+        RETURNING id
+        "#
+    } else {
+        r#"
+        INSERT INTO grading_rubrics (user_id, name, criteria, threshold_a, threshold_b, threshold_c)
+        VALUES ($1, $2, $3, COALESCE($4, 85), COALESCE($5, 70), COALESCE($6, 55))
+        RETURNING id
+        "#
+    };
+
+    let id = sqlx::query_scalar::<_, Uuid>(sql)
+        .bind(user_id)
+        .bind(&resolution.name)
+        .bind(&data.criteria)
+        .bind(data.threshold_a)
+        .bind(data.threshold_b)
+        .bind(data.threshold_c)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    Ok((id, resolution))
+}
+
+/// Checklists have no standalone table; they are applied to the daily plan for
+/// `plan_date`, creating the plan if needed. A plan that already has items is
+/// the "conflict": rename merges, replace overwrites, fail rejects.
+async fn import_checklist(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    plan_date: chrono::NaiveDate,
+    strategy: ConflictStrategy,
+    data: ChecklistRulesetData,
+) -> AppResult<(Uuid, NameResolution)> {
+    let plan = sqlx::query_as::<_, DailyPlan>(
+        "SELECT * FROM daily_plans WHERE user_id = $1 AND plan_date = $2 FOR UPDATE",
+    )
+    .bind(user_id)
+    .bind(plan_date)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let name = format!("Checklist {}", plan_date);
+
+    let Some(plan) = plan else {
+        let id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO daily_plans (user_id, plan_date, checklist_items) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(user_id)
+        .bind(plan_date)
+        .bind(&data.items)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        return Ok((id, NameResolution { name, renamed: false, replaced: false }));
+    };
+
+    let has_items = plan
+        .checklist_items
+        .as_ref()
+        .and_then(|v| v.as_array())
+        .is_some_and(|a| !a.is_empty());
+
+    let (items, resolution) = match (has_items, strategy) {
+        (false, _) => (data.items, NameResolution { name, renamed: false, replaced: false }),
+        (true, ConflictStrategy::Fail) => {
+            return Err(AppError::Conflict(format!(
+                "The plan for {} already has a checklist",
+                plan_date
+            )));
+        }
+        (true, ConflictStrategy::Replace) => {
+            (data.items, NameResolution { name, renamed: false, replaced: true })
+        }
+        (true, ConflictStrategy::Rename) => (
+            RulesetService::merge_checklist_items(plan.checklist_items.as_ref(), &data.items),
+            NameResolution { name, renamed: true, replaced: false },
+        ),
+    };
+
+    sqlx::query("UPDATE daily_plans SET checklist_items = $1, updated_at = NOW() WHERE id = $2")
+        .bind(&items)
+        .bind(plan.id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok((plan.id, resolution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn import_count(pool: &PgPool, id: Uuid) -> i32 {
+        sqlx::query_scalar("SELECT import_count FROM shared_rulesets WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_own_imports_do_not_bump_import_count() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let creator = db.insert_user("creator@example.com").await;
+        let other = db.insert_user("other@example.com").await;
+
+        let ruleset: SharedRuleset = sqlx::query_as(
+            r#"
+            INSERT INTO shared_rulesets (creator_user_id, ruleset_type, name, ruleset_data, is_public)
+            VALUES ($1, 'rubric', 'A+ setups', '{"criteria": []}', TRUE)
+            RETURNING *
+            "#,
+        )
+        .bind(creator)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+
+        let req = || ImportRulesetRequest { name: None, on_conflict: None, plan_date: None };
+        let Json(own) = import_into_account(&db.pool, creator, ruleset.clone(), req()).await.unwrap();
+        assert_eq!(own.ruleset_id, ruleset.id);
+        assert_eq!(import_count(&db.pool, ruleset.id).await, 0);
+
+        let Json(theirs) = import_into_account(&db.pool, other, ruleset.clone(), req()).await.unwrap();
+        assert_eq!(theirs.ruleset_id, ruleset.id);
+        assert_eq!(import_count(&db.pool, ruleset.id).await, 1);
+
+        db.drop().await;
+    }
+}
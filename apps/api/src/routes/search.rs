@@ -0,0 +1,19 @@
+use crate::error::AppResult;
+use crate::models::{AuthUser, SearchQuery, SearchResponse};
+use crate::services::SearchService;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub async fn search_journal(
+    State(pool): State<Arc<PgPool>>,
+    State(search_service): State<Arc<SearchService>>,
+    auth_user: AuthUser,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<SearchResponse>> {
+    let response = search_service.search(&pool, auth_user.user_id, &query).await?;
+    Ok(Json(response))
+}
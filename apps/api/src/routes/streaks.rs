@@ -0,0 +1,74 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{AuthUser, StreakEvent, StreakHistoryQuery, StreaksResponse, UserStreak};
+use crate::services::{MarketCalendar, StreakService, StreakState, StreakType};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Current and best count for every streak type, evaluated against today in
+/// the user's profile timezone.
+pub async fn get_streaks(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<StreaksResponse>> {
+    let tz = StreakService::user_timezone(&pool, auth_user.user_id).await?;
+    let today = StreakService::today_in(tz);
+
+    let rows = sqlx::query_as::<_, UserStreak>("SELECT * FROM user_streaks WHERE user_id = $1")
+        .bind(auth_user.user_id)
+        .fetch_all(pool.as_ref())
+        .await?;
+
+    let streaks = StreakType::ALL
+        .iter()
+        .map(|streak_type| {
+            let state = rows
+                .iter()
+                .find(|r| r.streak_type == streak_type.as_str())
+                .map(StreakState::from)
+                .unwrap_or(StreakState {
+                    current_count: 0,
+                    best_count: 0,
+                    decay_value: rust_decimal::Decimal::ZERO,
+                    last_activity_date: None,
+                });
+            StreakService::summarize(&state, *streak_type, today)
+        })
+        .collect();
+
+    Ok(Json(StreaksResponse {
+        timezone: tz.name().to_string(),
+        today,
+        is_trading_day: MarketCalendar::is_trading_day(today),
+        streaks,
+    }))
+}
+
+pub async fn get_streak_history(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+    Path(streak_type): Path<String>,
+    Query(query): Query<StreakHistoryQuery>,
+) -> AppResult<Json<Vec<StreakEvent>>> {
+    let streak_type = StreakType::parse(&streak_type).map_err(AppError::Validation)?;
+    let limit = query.limit.unwrap_or(90).clamp(1, 365);
+
+    let events = sqlx::query_as::<_, StreakEvent>(
+        r#"
+        SELECT * FROM streak_events
+        WHERE user_id = $1 AND streak_type = $2
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(auth_user.user_id)
+    .bind(streak_type.as_str())
+    .bind(limit)
+    .fetch_all(pool.as_ref())
+    .await?;
+
+    Ok(Json(events))
+}
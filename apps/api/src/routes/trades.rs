@@ -1,10 +1,13 @@
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    AuthUser, CloseTradeRequest, CreateTradeLegRequest, CreateTradeRequest, Trade, TradeLeg,
-    TradeListQuery, TradeListResponse, TradeMedia, TradeStats, TradeStatus, TradeTag,
+    AuthUser, CloseTradeRequest, CreateTradeLegRequest, CreateTradeRequest, Trade, TradeFilters,
+    TradeLeg, TradeListQuery, TradeListResponse, TradeMedia, TradeStats, TradeStatus, TradeTag,
     TradeWithDetails, UpdateTradeRequest,
 };
-use crate::services::TradeCalculationService;
+use crate::services::{
+    EconomicCalendarService, ProfileService, StorageService, StreakService,
+    TradeCalculationService, TradeQueryService,
+};
 use axum::{
     extract::{Path, Query, State},
     Json,
@@ -92,6 +95,12 @@ pub async fn create_trade(
         _ => None,
     };
 
+    // Fall back to the profile's per-trade commissions
+    let commissions = match req.commissions {
+        Some(commissions) => Some(commissions),
+        None => ProfileService::default_commissions(&pool, auth_user.user_id).await?,
+    };
+
     let trade = sqlx::query_as::<_, Trade>(
         r#"
         INSERT INTO trades (
@@ -124,7 +133,7 @@ pub async fn create_trade(
     .bind(&req.emotional_state)
     .bind(&req.market_condition)
     .bind(req.is_paper_trade.unwrap_or(false))
-    .bind(req.commissions)
+    .bind(commissions)
     .fetch_one(pool.as_ref())
     .await?;
 
@@ -135,6 +144,8 @@ pub async fn create_trade(
         "Trade created"
     );
 
+    EconomicCalendarService::tag_trade(&pool, auth_user.user_id, trade.id).await;
+
     Ok(Json(trade))
 }
 
@@ -183,7 +194,7 @@ pub async fn get_trade(
         sqlx::query_as::<_, TradeMedia>(
             r#"
             SELECT * FROM trade_media
-            WHERE trade_id = $1
+            WHERE trade_id = $1 AND upload_status = 'uploaded'
             ORDER BY created_at
             "#,
         )
@@ -199,148 +210,37 @@ pub async fn get_trade(
     }))
 }
 
-/// Validates that a sort column name is one of the allowed trade table columns.
-/// Returns the validated column name or an error. This prevents SQL injection
-/// by whitelisting rather than sanitizing.
-fn validate_sort_column(input: &str) -> AppResult<&'static str> {
-    match input {
-        "entry_date" => Ok("entry_date"),
-        "exit_date" => Ok("exit_date"),
-        "symbol" => Ok("symbol"),
-        "direction" => Ok("direction"),
-        "asset_class" => Ok("asset_class"),
-        "status" => Ok("status"),
-        "entry_price" => Ok("entry_price"),
-        "exit_price" => Ok("exit_price"),
-        "quantity" => Ok("quantity"),
-        "pnl" => Ok("pnl"),
-        "net_pnl" => Ok("net_pnl"),
-        "pnl_percent" => Ok("pnl_percent"),
-        "r_multiple" => Ok("r_multiple"),
-        "hold_time_minutes" => Ok("hold_time_minutes"),
-        "created_at" => Ok("created_at"),
-        "updated_at" => Ok("updated_at"),
-        _ => Err(AppError::Validation(format!(
-            "Invalid sort column '{}'. Allowed: entry_date, exit_date, symbol, direction, \
-             asset_class, status, entry_price, exit_price, quantity, pnl, net_pnl, \
-             pnl_percent, r_multiple, hold_time_minutes, created_at, updated_at",
-            input
-        ))),
-    }
-}
-
-/// Validates sort direction. Only "asc" and "desc" are allowed.
-fn validate_sort_order(input: &str) -> AppResult<&'static str> {
-    match input.to_lowercase().as_str() {
-        "asc" => Ok("ASC"),
-        "desc" => Ok("DESC"),
-        _ => Err(AppError::Validation(format!(
-            "Invalid sort order '{}'. Allowed: asc, desc",
-            input
-        ))),
-    }
-}
-
 pub async fn list_trades(
     State(pool): State<Arc<PgPool>>,
     auth_user: AuthUser,
     Query(query): Query<TradeListQuery>,
+    Query(filters): Query<TradeFilters>,
 ) -> AppResult<Json<TradeListResponse>> {
     let page = query.page.unwrap_or(1).max(1);
     let per_page = query.per_page.unwrap_or(50).clamp(1, 100);
     let offset = (page - 1) * per_page;
 
     // Validate sort parameters against whitelist — prevents SQL injection
-    let sort_column = validate_sort_column(
+    let sort_column = TradeQueryService::validate_sort_column(
         query.sort_by.as_deref().unwrap_or("entry_date"),
     )?;
-    let sort_direction = validate_sort_order(
+    let sort_direction = TradeQueryService::validate_sort_order(
         query.sort_order.as_deref().unwrap_or("desc"),
     )?;
 
-    // Build WHERE clause using parameterized conditions only
-    let mut conditions = vec!["user_id = $1".to_string()];
-    let mut param_count: i32 = 1;
-
-    if query.filters.status.is_some() {
-        param_count += 1;
-        conditions.push(format!("status = ${}", param_count));
-    }
-    if query.filters.direction.is_some() {
-        param_count += 1;
-        conditions.push(format!("direction = ${}", param_count));
-    }
-    if query.filters.asset_class.is_some() {
-        param_count += 1;
-        conditions.push(format!("asset_class = ${}", param_count));
-    }
-    if query.filters.symbol.is_some() {
-        param_count += 1;
-        conditions.push(format!("symbol ILIKE ${}", param_count));
-    }
-    if query.filters.setup_name.is_some() {
-        param_count += 1;
-        conditions.push(format!("setup_name ILIKE ${}", param_count));
-    }
-    if query.filters.conviction.is_some() {
-        param_count += 1;
-        conditions.push(format!("conviction = ${}", param_count));
-    }
-    if query.filters.is_paper_trade.is_some() {
-        param_count += 1;
-        conditions.push(format!("is_paper_trade = ${}", param_count));
-    }
-    if query.filters.from_date.is_some() {
-        param_count += 1;
-        conditions.push(format!("entry_date >= ${}", param_count));
-    }
-    if query.filters.to_date.is_some() {
-        param_count += 1;
-        conditions.push(format!("entry_date <= ${}", param_count));
-    }
-
-    let where_clause = conditions.join(" AND ");
-
-    // ORDER BY uses only validated static strings — safe from injection
-    let order_clause = format!("{} {}", sort_column, sort_direction);
-
-    // Helper closure: binds all active filter params to a query builder.
-    // We use a macro-style approach to avoid duplicating the bind chain.
-    macro_rules! bind_filters {
-        ($q:expr) => {{
-            let mut q = $q.bind(auth_user.user_id);
-            if let Some(ref v) = query.filters.status { q = q.bind(v); }
-            if let Some(ref v) = query.filters.direction { q = q.bind(v); }
-            if let Some(ref v) = query.filters.asset_class { q = q.bind(v); }
-            if let Some(ref v) = query.filters.symbol { q = q.bind(format!("%{}%", v)); }
-            if let Some(ref v) = query.filters.setup_name { q = q.bind(format!("%{}%", v)); }
-            if let Some(ref v) = query.filters.conviction { q = q.bind(v); }
-            if let Some(v) = query.filters.is_paper_trade { q = q.bind(v); }
-            if let Some(v) = query.filters.from_date { q = q.bind(v); }
-            if let Some(v) = query.filters.to_date { q = q.bind(v); }
-            q
-        }};
-    }
-
-    // Count total matching rows
-    let count_sql = format!("SELECT COUNT(*) FROM trades WHERE {}", where_clause);
-    let total = bind_filters!(sqlx::query_scalar::<_, i64>(&count_sql))
-        .fetch_one(pool.as_ref())
-        .await?;
-
-    // Fetch paginated trades
-    let trades_sql = format!(
-        "SELECT * FROM trades WHERE {} ORDER BY {} LIMIT ${} OFFSET ${}",
-        where_clause,
-        order_clause,
-        param_count + 1,
-        param_count + 2
-    );
-    let trades = bind_filters!(sqlx::query_as::<_, Trade>(&trades_sql))
-        .bind(per_page)
-        .bind(offset)
-        .fetch_all(pool.as_ref())
-        .await?;
+    let tz = StreakService::user_timezone(&pool, auth_user.user_id).await?;
+    let total = TradeQueryService::count(&pool, auth_user.user_id, &filters, tz).await?;
+    let trades = TradeQueryService::list(
+        &pool,
+        auth_user.user_id,
+        &filters,
+        tz,
+        sort_column,
+        sort_direction,
+        per_page,
+        offset,
+    )
+    .await?;
 
     let total_pages = if per_page > 0 {
         (total + per_page - 1) / per_page  // ceiling division without floating point
@@ -466,6 +366,10 @@ pub async fn update_trade(
 
     let trade = query.fetch_one(pool.as_ref()).await?;
 
+    if req.thesis.is_some() {
+        StreakService::track_trade_notes(&pool, &trade).await;
+    }
+
     Ok(Json(trade))
 }
 
@@ -537,14 +441,32 @@ pub async fn close_trade(
     .fetch_one(pool.as_ref())
     .await?;
 
+    if req.mistakes.is_some() || req.lessons.is_some() {
+        StreakService::track_trade_notes(&pool, &updated_trade).await;
+    }
+
     Ok(Json(updated_trade))
 }
 
 pub async fn delete_trade(
     State(pool): State<Arc<PgPool>>,
+    State(storage): State<Arc<StorageService>>,
     auth_user: AuthUser,
     Path(trade_id): Path<Uuid>,
 ) -> AppResult<Json<serde_json::Value>> {
+    // Media rows cascade with the trade, so collect their object keys first
+    let media_keys = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        SELECT m.s3_key, m.thumbnail_key FROM trade_media m
+        JOIN trades t ON t.id = m.trade_id
+        WHERE m.trade_id = $1 AND t.user_id = $2
+        "#,
+    )
+    .bind(trade_id)
+    .bind(auth_user.user_id)
+    .fetch_all(pool.as_ref())
+    .await?;
+
     let result = sqlx::query(
         r#"
         DELETE FROM trades WHERE id = $1 AND user_id = $2
@@ -559,6 +481,14 @@ pub async fn delete_trade(
         return Err(AppError::NotFound("Trade not found".to_string()));
     }
 
+    let keys = media_keys
+        .into_iter()
+        .flat_map(|(key, thumbnail)| std::iter::once(key).chain(thumbnail))
+        .collect::<Vec<_>>();
+    if !keys.is_empty() {
+        storage.delete_objects(keys).await;
+    }
+
     Ok(Json(serde_json::json!({ "message": "Trade deleted successfully" })))
 }
 
@@ -617,36 +547,58 @@ pub async fn add_trade_leg(
 pub async fn get_trade_stats(
     State(pool): State<Arc<PgPool>>,
     auth_user: AuthUser,
+    Query(filters): Query<TradeFilters>,
 ) -> AppResult<Json<TradeStats>> {
-    let stats = sqlx::query_as::<_, TradeStats>(
-        r#"
-        SELECT
-            COUNT(*) as total_trades,
-            COUNT(*) FILTER (WHERE pnl > 0) as winning_trades,
-            COUNT(*) FILTER (WHERE pnl <= 0) as losing_trades,
-            COALESCE(
-                CAST(COUNT(*) FILTER (WHERE pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
-                0
-            ) as win_rate,
-            COALESCE(SUM(net_pnl), 0) as total_pnl,
-            COALESCE(AVG(net_pnl) FILTER (WHERE pnl > 0), 0) as avg_win,
-            COALESCE(AVG(net_pnl) FILTER (WHERE pnl <= 0), 0) as avg_loss,
-            CASE
-                WHEN ABS(SUM(net_pnl) FILTER (WHERE pnl <= 0)) > 0
-                THEN ABS(SUM(net_pnl) FILTER (WHERE pnl > 0) / SUM(net_pnl) FILTER (WHERE pnl <= 0))
-                ELSE NULL
-            END as profit_factor,
-            AVG(r_multiple) as avg_r_multiple,
-            COALESCE(MAX(net_pnl), 0) as largest_win,
-            COALESCE(MIN(net_pnl), 0) as largest_loss,
-            AVG(hold_time_minutes)::INTEGER as avg_hold_time_minutes
-        FROM trades
-        WHERE user_id = $1 AND status = 'closed'
-        "#,
-    )
-    .bind(auth_user.user_id)
-    .fetch_one(pool.as_ref())
-    .await?;
+    let tz = StreakService::user_timezone(&pool, auth_user.user_id).await?;
+    let stats = TradeQueryService::stats(&pool, auth_user.user_id, &filters, tz).await?;
 
     Ok(Json(stats))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Uri;
+    use sqlx::{Postgres, QueryBuilder};
+
+    fn list_query(uri: &str) -> (TradeListQuery, TradeFilters) {
+        let uri: Uri = uri.parse().unwrap();
+        let Query(query) = Query::<TradeListQuery>::try_from_uri(&uri).unwrap();
+        let Query(filters) = Query::<TradeFilters>::try_from_uri(&uri).unwrap();
+        (query, filters)
+    }
+
+    #[test]
+    fn test_list_trades_applies_tag_pnl_and_r_filters() {
+        let (tag_a, tag_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (query, filters) = list_query(&format!(
+            "/api/v1/trades?page=2&tag_ids={},{}&min_pnl=-50&max_pnl=200.5\
+             &min_r_multiple=0.5&max_r_multiple=3&broke_rules=true",
+            tag_a, tag_b
+        ));
+        assert_eq!(query.page, Some(2));
+        assert_eq!(filters.tag_ids, Some(vec![tag_a, tag_b]));
+        assert_eq!(filters.broke_rules, Some(true));
+
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM trades WHERE ");
+        TradeQueryService::push_filters(&mut builder, Uuid::new_v4(), &filters, chrono_tz::UTC);
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM trades WHERE user_id = $1 AND broke_rules = $2 \
+             AND id IN (SELECT trade_id FROM trade_tags WHERE tag_id = ANY($3)) \
+             AND net_pnl >= $4 AND net_pnl <= $5 AND r_multiple >= $6 AND r_multiple <= $7"
+        );
+    }
+
+    #[test]
+    fn test_tag_ids_parse_from_json_and_reject_bad_ids() {
+        let tag = Uuid::new_v4();
+        let filters: TradeFilters =
+            serde_json::from_value(serde_json::json!({ "tag_ids": [tag] })).unwrap();
+        assert_eq!(filters.tag_ids, Some(vec![tag]));
+
+        let uri: Uri = "/api/v1/trades?tag_ids=not-a-uuid".parse().unwrap();
+        assert!(Query::<TradeFilters>::try_from_uri(&uri).is_err());
+    }
+}
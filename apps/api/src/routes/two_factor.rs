@@ -0,0 +1,137 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AuthResponse, AuthUser, ClientInfo, ConfirmTwoFactorRequest, RecoveryCodesResponse,
+    TwoFactorLoginRequest, TwoFactorReauthRequest, TwoFactorSetupResponse, TwoFactorStatus, User,
+};
+use crate::routes::auth::sign_in;
+use crate::services::{AuthService, TwoFactorService};
+use axum::{extract::State, Json};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn fetch_user(pool: &PgPool, user_id: Uuid) -> AppResult<User> {
+    Ok(sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, email, password_hash, email_verified, created_at, updated_at
+        FROM users
+        WHERE id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Password plus a second factor, required before weakening or resetting 2FA.
+async fn reauthenticate(
+    pool: &PgPool,
+    auth_service: &AuthService,
+    user_id: Uuid,
+    req: &TwoFactorReauthRequest,
+) -> AppResult<()> {
+    let user = fetch_user(pool, user_id).await?;
+    if !auth_service.verify_password(&req.password, &user.password_hash)? {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+
+    TwoFactorService::verify(
+        pool,
+        auth_service,
+        user_id,
+        req.code.as_deref(),
+        req.recovery_code.as_deref(),
+    )
+    .await
+}
+
+pub async fn get_two_factor_status(
+    State(pool): State<Arc<PgPool>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<TwoFactorStatus>> {
+    let status = TwoFactorService::status(&pool, auth_user.user_id).await?;
+    Ok(Json(status))
+}
+
+/// Returns a fresh secret and provisioning URI for the authenticator app.
+pub async fn setup_two_factor(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<TwoFactorSetupResponse>> {
+    let setup =
+        TwoFactorService::begin_enrolment(&pool, &auth_service, auth_user.user_id, &auth_user.email)
+            .await?;
+    Ok(Json(setup))
+}
+
+pub async fn confirm_two_factor(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    auth_user: AuthUser,
+    Json(req): Json<ConfirmTwoFactorRequest>,
+) -> AppResult<Json<RecoveryCodesResponse>> {
+    let recovery_codes =
+        TwoFactorService::confirm_enrolment(&pool, &auth_service, auth_user.user_id, &req.code)
+            .await?;
+
+    tracing::info!(user_id = %auth_user.user_id, "Two-factor authentication enabled");
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Second login step: trades the challenge from `login` and a code for tokens.
+pub async fn verify_two_factor_login(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    client: ClientInfo,
+    Json(req): Json<TwoFactorLoginRequest>,
+) -> AppResult<Json<AuthResponse>> {
+    let user_id = auth_service.verify_two_factor_challenge(&req.challenge_token)?;
+
+    TwoFactorService::verify(
+        &pool,
+        &auth_service,
+        user_id,
+        req.code.as_deref(),
+        req.recovery_code.as_deref(),
+    )
+    .await?;
+
+    let user = fetch_user(&pool, user_id).await?;
+    let response = sign_in(&pool, &auth_service, user, &client).await?;
+    Ok(Json(response))
+}
+
+/// Replaces all recovery codes; the old ones stop working.
+pub async fn regenerate_recovery_codes(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    auth_user: AuthUser,
+    Json(req): Json<TwoFactorReauthRequest>,
+) -> AppResult<Json<RecoveryCodesResponse>> {
+    reauthenticate(&pool, &auth_service, auth_user.user_id, &req).await?;
+
+    let recovery_codes =
+        TwoFactorService::regenerate_recovery_codes(&pool, &auth_service, auth_user.user_id)
+            .await?;
+
+    tracing::info!(user_id = %auth_user.user_id, "Recovery codes regenerated");
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+pub async fn disable_two_factor(
+    State(pool): State<Arc<PgPool>>,
+    State(auth_service): State<Arc<AuthService>>,
+    auth_user: AuthUser,
+    Json(req): Json<TwoFactorReauthRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    reauthenticate(&pool, &auth_service, auth_user.user_id, &req).await?;
+
+    TwoFactorService::disable(&pool, auth_user.user_id).await?;
+
+    tracing::info!(user_id = %auth_user.user_id, "Two-factor authentication disabled");
+
+    Ok(Json(serde_json::json!({ "message": "Two-factor authentication disabled" })))
+}
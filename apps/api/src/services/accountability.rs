@@ -0,0 +1,207 @@
+use crate::error::{AppError, AppResult};
+use crate::models::AccountabilityLink;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Trader data a coach can read through an accountability link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoachResource {
+    Trades,
+    Plans,
+    MoodLogs,
+    TiltEvents,
+    Reviews,
+    /// The coach dashboard's per-trader summary.
+    Dashboard,
+}
+
+impl CoachResource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoachResource::Trades => "trades",
+            CoachResource::Plans => "plans",
+            CoachResource::MoodLogs => "mood_logs",
+            CoachResource::TiltEvents => "tilt_events",
+            CoachResource::Reviews => "reviews",
+            CoachResource::Dashboard => "dashboard",
+        }
+    }
+
+    /// Whether the link's `can_view_*` flags allow reading this resource.
+    /// P&L and grade columns of visible trades are redacted separately.
+    pub fn is_permitted(&self, link: &AccountabilityLink) -> bool {
+        match self {
+            CoachResource::Trades => link.can_view_trades,
+            CoachResource::Plans => link.can_view_plan,
+            CoachResource::MoodLogs => link.can_view_mood,
+            CoachResource::TiltEvents => link.can_view_tilt,
+            CoachResource::Reviews => link.can_view_reviews,
+            CoachResource::Dashboard => true,
+        }
+    }
+}
+
+pub struct AccountabilityService;
+
+impl AccountabilityService {
+    /// Loads the active link between `coach_user_id` and `trader_user_id`,
+    /// checks it grants `resource`, and writes an audit log entry. Returns the
+    /// link so callers can apply field-level redaction.
+    pub async fn authorize_coach_access(
+        pool: &PgPool,
+        coach_user_id: Uuid,
+        trader_user_id: Uuid,
+        resource: CoachResource,
+        resource_id: Option<Uuid>,
+    ) -> AppResult<AccountabilityLink> {
+        let link = sqlx::query_as::<_, AccountabilityLink>(
+            r#"
+            SELECT * FROM accountability_links
+            WHERE coach_user_id = $1 AND trader_user_id = $2 AND status = 'active'
+            "#,
+        )
+        .bind(coach_user_id)
+        .bind(trader_user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Trader not found".to_string()))?;
+
+        if !resource.is_permitted(&link) {
+            return Err(AppError::Forbidden(format!(
+                "Coach {} lacks permission for {}",
+                coach_user_id,
+                resource.as_str()
+            )));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO coach_access_log (link_id, coach_user_id, trader_user_id, resource, resource_id)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(link.id)
+        .bind(coach_user_id)
+        .bind(trader_user_id)
+        .bind(resource.as_str())
+        .bind(resource_id)
+        .execute(pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Views that read several linked traders at once (the dashboard, the
+    /// review queue) skip `authorize_coach_access` and log one entry per
+    /// trader shown against their active link.
+    pub async fn log_list_access(
+        pool: &PgPool,
+        coach_user_id: Uuid,
+        resource: CoachResource,
+        trader_user_ids: &[Uuid],
+    ) -> AppResult<()> {
+        if trader_user_ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO coach_access_log (link_id, coach_user_id, trader_user_id, resource)
+            SELECT id, coach_user_id, trader_user_id, $2
+            FROM accountability_links
+            WHERE coach_user_id = $1 AND status = 'active' AND trader_user_id = ANY($3)
+            "#,
+        )
+        .bind(coach_user_id)
+        .bind(resource.as_str())
+        .bind(trader_user_ids)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn link(can_view_trades: bool, can_view_plan: bool, can_view_mood: bool) -> AccountabilityLink {
+        AccountabilityLink {
+            id: Uuid::new_v4(),
+            trader_user_id: Uuid::new_v4(),
+            coach_user_id: Uuid::new_v4(),
+            can_view_trades,
+            can_view_plan,
+            can_view_grades: true,
+            can_view_tilt: false,
+            can_view_pnl: false,
+            can_view_mood,
+            can_view_reviews: true,
+            status: "active".to_string(),
+            invited_at: Utc::now(),
+            accepted_at: Some(Utc::now()),
+            revoked_at: None,
+        }
+    }
+
+    #[test]
+    fn test_resource_permissions_follow_flags() {
+        let l = link(true, false, true);
+        assert!(CoachResource::Trades.is_permitted(&l));
+        assert!(!CoachResource::Plans.is_permitted(&l));
+        assert!(CoachResource::MoodLogs.is_permitted(&l));
+        assert!(!CoachResource::TiltEvents.is_permitted(&l));
+        assert!(CoachResource::Reviews.is_permitted(&l));
+
+        assert!(!CoachResource::Trades.is_permitted(&link(false, true, true)));
+    }
+
+    #[tokio::test]
+    async fn test_list_reads_are_logged_per_active_link() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let coach = db.insert_user("coach@example.com").await;
+        let mut traders = Vec::new();
+        let links = [
+            ("a@example.com", "active"),
+            ("b@example.com", "active"),
+            ("c@example.com", "revoked"),
+        ];
+        for (email, status) in links {
+            let trader_user_id = db.insert_user(email).await;
+            sqlx::query(
+                r#"
+                INSERT INTO accountability_links (trader_user_id, coach_user_id, status)
+                VALUES ($1, $2, $3)
+                "#,
+            )
+            .bind(trader_user_id)
+            .bind(coach)
+            .bind(status)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+            traders.push(trader_user_id);
+        }
+
+        AccountabilityService::log_list_access(&db.pool, coach, CoachResource::Trades, &traders)
+            .await
+            .unwrap();
+
+        let mut logged: Vec<(Uuid, String)> = sqlx::query_as(
+            "SELECT trader_user_id, resource FROM coach_access_log WHERE coach_user_id = $1",
+        )
+        .bind(coach)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        db.drop().await;
+
+        logged.sort();
+        let mut expected: Vec<(Uuid, String)> =
+            traders[..2].iter().map(|id| (*id, "trades".to_string())).collect();
+        expected.sort();
+        assert_eq!(logged, expected);
+    }
+}
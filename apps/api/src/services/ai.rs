@@ -1,40 +1,92 @@
 use crate::config::Config;
 use crate::error::{AppError, AppResult};
-use crate::models::{ClaudeMessage, ClaudeRequest, ClaudeResponse, Trade};
-use reqwest::Client;
+use crate::models::{
+    ChartAnalysis, ClaudeContentBlock, ClaudeImageSource, ClaudeMessage, ClaudeMessageContent,
+    DailyPlan, InterpretedTradeQuery, ReviewDraftOutput, Trade, TradeFilters, TradeReviewOutput,
+    MAX_QUERY_TRADES,
+};
+use crate::services::ai_context::{
+    AiContext, AiPersonality, PLAN_CONTEXT_TOKEN_BUDGET, REVIEW_CONTEXT_TOKEN_BUDGET,
+    TRADE_CONTEXT_TOKEN_BUDGET,
+};
+use crate::services::trade_query::TradeQueryService;
+use crate::services::llm::{
+    provider_from_config, ChatStreamOutcome, LlmCompletion, LlmProvider, LlmRequest, LlmUsage,
+};
+use rust_decimal::Decimal;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use std::io::Cursor;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
 const MAX_RETRIES: u32 = 2;
 const INITIAL_BACKOFF_MS: u64 = 500;
 const CIRCUIT_BREAKER_THRESHOLD: u64 = 5;
 const CIRCUIT_BREAKER_RESET_SECS: u64 = 60;
+/// Images above this size or edge length are downscaled before sending; the
+/// API rejects images over 5 MB and gains nothing past ~1568px.
+const MAX_VISION_IMAGE_BYTES: usize = 3_500_000;
+const MAX_VISION_IMAGE_EDGE: u32 = 1568;
+const CHART_RATINGS: &[&str] = &["excellent", "good", "fair", "poor"];
+/// Bumped whenever the trade review prompt or schema changes, so reviews
+/// from different prompt iterations can be compared.
+pub const TRADE_REVIEW_PROMPT_VERSION: &str = "trade-review-v3";
+pub const GENERAL_PROMPT_VERSION: &str = "general-v1";
+pub const PLAN_OF_ATTACK_PROMPT_VERSION: &str = "plan-of-attack-v1";
+pub const REVIEW_DRAFT_PROMPT_VERSION: &str = "review-draft-v1";
+pub const TRADE_QUERY_PROMPT_VERSION: &str = "trade-query-v2";
+/// Follow-up requests sent when the model's review fails validation.
+const MAX_REVIEW_REPAIR_ATTEMPTS: u32 = 2;
+
+/// Result of a structured trade review, including the raw text of the reply
+/// that passed validation.
+#[derive(Debug)]
+pub struct TradeReviewResult {
+    pub review: TradeReviewOutput,
+    pub raw_response: String,
+    pub attempts: u32,
+}
+
+/// A validated periodic review draft, with the same bookkeeping as
+/// `TradeReviewResult`.
+#[derive(Debug)]
+pub struct ReviewDraftResult {
+    pub draft: ReviewDraftOutput,
+    pub raw_response: String,
+    pub attempts: u32,
+}
 
 pub struct AiService {
-    api_key: Option<String>,
-    client: Client,
+    provider: Arc<dyn LlmProvider>,
     consecutive_failures: AtomicU64,
     last_failure_epoch: AtomicU64,
 }
 
 impl AiService {
     pub fn new(config: &Config) -> Self {
-        let client = Client::builder()
-            .timeout(REQUEST_TIMEOUT)
-            .connect_timeout(Duration::from_secs(10))
-            .pool_max_idle_per_host(4)
-            .build()
-            .unwrap_or_else(|_| Client::new());
+        Self::with_provider(provider_from_config(config))
+    }
 
+    pub fn with_provider(provider: Arc<dyn LlmProvider>) -> Self {
         Self {
-            api_key: config.anthropic_api_key.clone(),
-            client,
+            provider,
             consecutive_failures: AtomicU64::new(0),
             last_failure_epoch: AtomicU64::new(0),
         }
     }
 
+    /// Provider name recorded alongside reviews (`anthropic`, `openai`, `fake`).
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    /// Configured model; completions report the model actually served.
+    pub fn model(&self) -> &str {
+        self.provider.model()
+    }
+
     /// Checks whether the circuit breaker is open (too many recent failures).
     fn is_circuit_open(&self) -> bool {
         let failures = self.consecutive_failures.load(Ordering::Relaxed);
@@ -69,50 +121,572 @@ impl AiService {
         self.last_failure_epoch.store(now, Ordering::Relaxed);
     }
 
-    pub async fn analyze_trade(&self, trade: &Trade) -> AppResult<String> {
-        let prompt = self.build_trade_analysis_prompt(trade);
+    pub fn trade_review_messages(
+        &self,
+        trade: &Trade,
+        charts: &[ChartAnalysis],
+        focus: Option<&str>,
+        context: Option<&AiContext>,
+    ) -> Vec<ClaudeMessage> {
+        vec![ClaudeMessage::text(
+            "user",
+            self.build_trade_analysis_prompt(trade, charts, focus, context),
+        )]
+    }
+
+    /// Asks for a JSON review matching `trade_review_schema` and validates it.
+    pub async fn analyze_trade(
+        &self,
+        trade: &Trade,
+        charts: &[ChartAnalysis],
+        focus: Option<&str>,
+        context: Option<&AiContext>,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<TradeReviewResult> {
+        let messages = self.trade_review_messages(trade, charts, focus, context);
+        let completion = self.chat(messages.clone()).await?;
+        self.complete_trade_review(messages, completion, usage).await
+    }
+
+    /// Validates a review reply. One that fails is sent back with the errors
+    /// for correction, up to `MAX_REVIEW_REPAIR_ATTEMPTS` times. The usage of
+    /// every call, `first` included, is pushed onto `usage` as it happens, so
+    /// callers can meter the calls even when this returns an error.
+    pub async fn complete_trade_review(
+        &self,
+        messages: Vec<ClaudeMessage>,
+        first: LlmCompletion,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<TradeReviewResult> {
+        let (review, raw_response, attempts) = self
+            .complete_structured(messages, first, Self::parse_trade_review, "review", usage)
+            .await?;
+        Ok(TradeReviewResult { review, raw_response, attempts })
+    }
+
+    /// Same repair loop as `complete_trade_review`, for periodic review drafts.
+    pub async fn complete_review_draft(
+        &self,
+        messages: Vec<ClaudeMessage>,
+        first: LlmCompletion,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<ReviewDraftResult> {
+        let (draft, raw_response, attempts) = self
+            .complete_structured(messages, first, Self::parse_review_draft, "review draft", usage)
+            .await?;
+        Ok(ReviewDraftResult { draft, raw_response, attempts })
+    }
+
+    /// Turns a question into `TradeFilters` plus an aggregation, with the
+    /// same repair loop and usage reporting as reviews. `now` fixes "today"
+    /// in the user's timezone.
+    pub async fn interpret_trade_query(
+        &self,
+        question: &str,
+        now: chrono::DateTime<chrono_tz::Tz>,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<InterpretedTradeQuery> {
+        let messages = vec![ClaudeMessage::text(
+            "user",
+            Self::build_trade_query_prompt(question, now),
+        )];
+        let first = self.chat(messages.clone()).await?;
+        let (query, _, _) = self
+            .complete_structured(messages, first, Self::parse_trade_query, "query", usage)
+            .await?;
+        Ok(query)
+    }
+
+    async fn complete_structured<T>(
+        &self,
+        mut messages: Vec<ClaudeMessage>,
+        first: LlmCompletion,
+        parse: fn(&str) -> Result<T, String>,
+        what: &str,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<(T, String, u32)> {
+        usage.push(first.usage());
+        let mut response = first.text;
+        for attempt in 0..=MAX_REVIEW_REPAIR_ATTEMPTS {
+            let error = match parse(&response) {
+                Ok(value) => return Ok((value, response, attempt + 1)),
+                Err(e) => e,
+            };
+
+            tracing::warn!(attempt, kind = what, error = %error, "AI structured reply failed validation");
+            if attempt == MAX_REVIEW_REPAIR_ATTEMPTS {
+                break;
+            }
+
+            messages.push(ClaudeMessage::text("assistant", response));
+            messages.push(ClaudeMessage::text(
+                "user",
+                format!(
+                    "That response was invalid: {}. Reply with ONLY the corrected JSON object matching the schema.",
+                    error
+                ),
+            ));
+            let next = self.chat(messages.clone()).await?;
+            usage.push(next.usage());
+            response = next.text;
+        }
+
+        Err(AppError::AiError(format!(
+            "AI returned an invalid {}. Please try again.",
+            what
+        )))
+    }
+
+    /// JSON schema for `TradeReviewOutput`, embedded in the review prompt.
+    pub fn trade_review_schema() -> serde_json::Value {
+        let score = serde_json::json!({"type": "number", "minimum": 0, "maximum": 10});
+        let list = serde_json::json!({"type": "array", "items": {"type": "string"}, "minItems": 1, "maxItems": 5});
+        serde_json::json!({
+            "type": "object",
+            "additionalProperties": false,
+            "required": [
+                "overall_score", "execution_quality_score", "risk_management_score",
+                "plan_adherence_score", "strengths", "weaknesses", "key_lesson", "actionable_fixes"
+            ],
+            "properties": {
+                "overall_score": score,
+                "execution_quality_score": score,
+                "risk_management_score": score,
+                "plan_adherence_score": score,
+                "thesis_alignment_score": {"type": ["number", "null"], "minimum": 1, "maximum": 5},
+                "strengths": list,
+                "weaknesses": list,
+                "key_lesson": {"type": "string"},
+                "actionable_fixes": list,
+                "alternative_scenario": {"type": ["string", "null"]},
+                "emotional_state_detected": {"type": ["string", "null"], "maxLength": 50}
+            }
+        })
+    }
+
+    /// Removes trailing commas before `}` or `]`, the most common way model
+    /// JSON is malformed. String contents are left alone.
+    pub fn repair_json(json: &str) -> String {
+        let mut out = String::with_capacity(json.len());
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for c in json.chars() {
+            if in_string {
+                out.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    in_string = false;
+                }
+                continue;
+            }
+
+            match c {
+                '"' => {
+                    in_string = true;
+                    out.push(c);
+                }
+                '}' | ']' => {
+                    let trimmed_len = out.trim_end().len();
+                    if out[..trimmed_len].ends_with(',') {
+                        out.truncate(trimmed_len - 1);
+                    }
+                    out.push(c);
+                }
+                _ => out.push(c),
+            }
+        }
+        out
+    }
+
+    /// Parses and validates a review reply. Errors are phrased for the
+    /// model, since they are sent back verbatim on retry.
+    pub fn parse_trade_review(text: &str) -> Result<TradeReviewOutput, String> {
+        let json = Self::extract_json(text).ok_or("no JSON object found")?;
+        let mut review: TradeReviewOutput = serde_json::from_str(json)
+            .or_else(|_| serde_json::from_str(&Self::repair_json(json)))
+            .map_err(|e| format!("JSON does not match the schema ({})", e))?;
+
+        let ten = Decimal::from(10);
+        for (name, score) in [
+            ("overall_score", &mut review.overall_score),
+            ("execution_quality_score", &mut review.execution_quality_score),
+            ("risk_management_score", &mut review.risk_management_score),
+            ("plan_adherence_score", &mut review.plan_adherence_score),
+        ] {
+            if *score < Decimal::ZERO || *score > ten {
+                return Err(format!("{} must be between 0 and 10", name));
+            }
+            *score = score.round_dp(2);
+        }
+        if let Some(ref mut score) = review.thesis_alignment_score {
+            if *score < Decimal::ONE || *score > Decimal::from(5) {
+                return Err("thesis_alignment_score must be between 1 and 5 or null".to_string());
+            }
+            *score = score.round_dp(2);
+        }
+
+        for (name, list) in [
+            ("strengths", &mut review.strengths),
+            ("weaknesses", &mut review.weaknesses),
+            ("actionable_fixes", &mut review.actionable_fixes),
+        ] {
+            list.retain(|item| !item.trim().is_empty());
+            if list.is_empty() {
+                return Err(format!("{} must contain at least one item", name));
+            }
+            list.truncate(5);
+        }
+        if review.key_lesson.trim().is_empty() {
+            return Err("key_lesson must not be empty".to_string());
+        }
+        if let Some(ref mut state) = review.emotional_state_detected {
+            *state = state.trim().to_lowercase();
+            if state.chars().count() > 50 {
+                return Err("emotional_state_detected must be 50 characters or fewer".to_string());
+            }
+        }
+        Ok(review)
+    }
+
+    /// Readable version of a structured review, used as the assistant
+    /// message so follow-up chat has natural context.
+    pub fn format_trade_review(review: &TradeReviewOutput) -> String {
+        let bullets = |items: &[String]| {
+            items
+                .iter()
+                .map(|i| format!("- {}", i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let mut text = format!(
+            "Overall: {}/10 (execution {}, risk management {}, plan adherence {})\n\nStrengths:\n{}\n\nWeaknesses:\n{}\n\nKey lesson: {}\n\nActionable fixes:\n{}",
+            review.overall_score,
+            review.execution_quality_score,
+            review.risk_management_score,
+            review.plan_adherence_score,
+            bullets(&review.strengths),
+            bullets(&review.weaknesses),
+            review.key_lesson,
+            bullets(&review.actionable_fixes),
+        );
+        if let Some(ref scenario) = review.alternative_scenario {
+            text.push_str(&format!("\n\nAlternative scenario: {}", scenario));
+        }
+        text
+    }
+
+    /// JSON schema for `ReviewDraftOutput`, embedded in the draft prompt.
+    pub fn review_draft_schema() -> serde_json::Value {
+        let list = |min: u32| serde_json::json!({"type": "array", "items": {"type": "string"}, "minItems": min, "maxItems": 5});
+        serde_json::json!({
+            "type": "object",
+            "additionalProperties": false,
+            "required": ["what_went_well", "what_to_improve", "key_lessons", "rules_broken", "goals_next_period"],
+            "properties": {
+                "what_went_well": {"type": "string"},
+                "what_to_improve": {"type": "string"},
+                "key_lessons": list(1),
+                "rules_broken": list(0),
+                "goals_next_period": list(1)
+            }
+        })
+    }
+
+    pub fn parse_review_draft(text: &str) -> Result<ReviewDraftOutput, String> {
+        let json = Self::extract_json(text).ok_or("no JSON object found")?;
+        let mut draft: ReviewDraftOutput = serde_json::from_str(json)
+            .or_else(|_| serde_json::from_str(&Self::repair_json(json)))
+            .map_err(|e| format!("JSON does not match the schema ({})", e))?;
+
+        for (name, text) in [
+            ("what_went_well", &mut draft.what_went_well),
+            ("what_to_improve", &mut draft.what_to_improve),
+        ] {
+            *text = text.trim().to_string();
+            if text.is_empty() {
+                return Err(format!("{} must not be empty", name));
+            }
+        }
+        for (name, list, required) in [
+            ("key_lessons", &mut draft.key_lessons, true),
+            ("rules_broken", &mut draft.rules_broken, false),
+            ("goals_next_period", &mut draft.goals_next_period, true),
+        ] {
+            list.retain(|item| !item.trim().is_empty());
+            if required && list.is_empty() {
+                return Err(format!("{} must contain at least one item", name));
+            }
+            list.truncate(5);
+        }
+        Ok(draft)
+    }
+
+    /// JSON schema for `InterpretedTradeQuery`, embedded in the query prompt.
+    pub fn trade_query_schema() -> serde_json::Value {
+        let nullable = |kind: &str| serde_json::json!({"type": [kind, "null"]});
+        let one_of = |values: &[&str]| serde_json::json!({"enum": values.iter().map(|v| serde_json::Value::from(*v)).chain([serde_json::Value::Null]).collect::<Vec<_>>()});
+        let time = serde_json::json!({"type": ["string", "null"], "pattern": "^\\d{2}:\\d{2}:\\d{2}$"});
+        let datetime = serde_json::json!({"type": ["string", "null"], "format": "date-time"});
+        serde_json::json!({
+            "type": "object",
+            "additionalProperties": false,
+            "required": ["filters", "aggregation", "explanation"],
+            "properties": {
+                "filters": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "properties": {
+                        "status": one_of(&["Open", "Closed", "Cancelled"]),
+                        "direction": one_of(&["Long", "Short"]),
+                        "asset_class": one_of(&["Stocks", "Options", "Futures", "Forex", "Crypto"]),
+                        "symbol": nullable("string"),
+                        "setup_name": nullable("string"),
+                        "conviction": one_of(&["Low", "Medium", "High"]),
+                        "is_paper_trade": nullable("boolean"),
+                        "is_revenge_trade": nullable("boolean"),
+                        "broke_rules": nullable("boolean"),
+                        "followed_plan": nullable("boolean"),
+                        "from_date": datetime.clone(),
+                        "to_date": datetime,
+                        "min_pnl": nullable("number"),
+                        "max_pnl": nullable("number"),
+                        "min_r_multiple": nullable("number"),
+                        "max_r_multiple": nullable("number"),
+                        "entry_time_from": time.clone(),
+                        "entry_time_to": time
+                    }
+                },
+                "aggregation": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["kind"],
+                    "properties": {
+                        "kind": {"enum": ["stats", "list"]},
+                        "group_by": one_of(&["symbol", "setup", "direction", "asset_class", "weekday", "hour", "month"]),
+                        "sort_by": one_of(&["entry_date", "exit_date", "symbol", "net_pnl", "pnl_percent", "r_multiple", "hold_time_minutes"]),
+                        "sort_order": one_of(&["asc", "desc"]),
+                        "limit": {"type": ["integer", "null"], "minimum": 1, "maximum": MAX_QUERY_TRADES}
+                    }
+                },
+                "explanation": {"type": "string"}
+            }
+        })
+    }
+
+    pub fn parse_trade_query(text: &str) -> Result<InterpretedTradeQuery, String> {
+        let json = Self::extract_json(text).ok_or("no JSON object found")?;
+        let value: serde_json::Value = serde_json::from_str(json)
+            .or_else(|_| serde_json::from_str(&Self::repair_json(json)))
+            .map_err(|e| format!("invalid JSON ({})", e))?;
+
+        // Serde would silently drop an invented filter, widening the query
+        if let Some(filters) = value.get("filters").and_then(|f| f.as_object()) {
+            let known = serde_json::to_value(TradeFilters::default()).unwrap_or_default();
+            if let Some(unknown) = filters.keys().find(|k| known.get(k.as_str()).is_none()) {
+                return Err(format!("unknown filter \"{}\"", unknown));
+            }
+        }
+
+        let mut query: InterpretedTradeQuery = serde_json::from_value(value)
+            .map_err(|e| format!("JSON does not match the schema ({})", e))?;
+
+        query.explanation = query.explanation.trim().to_string();
+        if query.explanation.is_empty() {
+            return Err("explanation must not be empty".to_string());
+        }
+        crate::models::validate_trade_filters(&query.filters)?;
+        crate::models::validate_trade_aggregation(&query.aggregation)?;
+        if let Some(column) = &query.aggregation.sort_by {
+            TradeQueryService::validate_sort_column(column).map_err(|e| e.to_string())?;
+        }
+        if let Some(order) = &query.aggregation.sort_order {
+            TradeQueryService::validate_sort_order(order).map_err(|e| e.to_string())?;
+        }
+        Ok(query)
+    }
+
+    /// Readable version of a draft, stored as the assistant message so the
+    /// trader can discuss it in follow-up chat.
+    pub fn format_review_draft(draft: &ReviewDraftOutput) -> String {
+        let bullets = |items: &[String]| {
+            if items.is_empty() {
+                return "- None".to_string();
+            }
+            items
+                .iter()
+                .map(|i| format!("- {}", i))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        format!(
+            "What went well: {}\n\nWhat to improve: {}\n\nKey lessons:\n{}\n\nRules broken:\n{}\n\nGoals for next period:\n{}",
+            draft.what_went_well,
+            draft.what_to_improve,
+            bullets(&draft.key_lessons),
+            bullets(&draft.rules_broken),
+            bullets(&draft.goals_next_period),
+        )
+    }
+
+    /// Sends a chart screenshot to the model and parses the structured
+    /// pattern / entry / stop assessment it returns. The call's usage is
+    /// pushed onto `usage` before parsing, as in `complete_trade_review`.
+    pub async fn analyze_chart(
+        &self,
+        image: &[u8],
+        mime_type: &str,
+        trade: Option<&Trade>,
+        usage: &mut Vec<LlmUsage>,
+    ) -> AppResult<(ChartAnalysis, LlmCompletion)> {
+        let (image, mime_type) =
+            Self::prepare_image(image, mime_type).map_err(AppError::Validation)?;
+
         let messages = vec![ClaudeMessage {
             role: "user".to_string(),
-            content: prompt,
+            content: ClaudeMessageContent::Blocks(vec![
+                ClaudeContentBlock::Image {
+                    source: ClaudeImageSource {
+                        source_type: "base64".to_string(),
+                        media_type: mime_type,
+                        data: base64::engine::general_purpose::STANDARD.encode(&image),
+                    },
+                },
+                ClaudeContentBlock::Text {
+                    text: Self::build_chart_analysis_prompt(trade),
+                },
+            ]),
         }];
-        let (response, _) = self.chat(messages).await?;
-        Ok(response)
-    }
 
-    /// Sends messages to the Claude API with retry logic and circuit breaker.
-    pub async fn chat(&self, messages: Vec<ClaudeMessage>) -> AppResult<(String, i32)> {
-        let api_key = self.api_key.as_ref().ok_or_else(|| {
-            AppError::AiError("AI service is not configured. Set ANTHROPIC_API_KEY.".to_string())
+        let completion = self.chat(messages).await?;
+        usage.push(completion.usage());
+        let analysis = Self::parse_chart_analysis(&completion.text).map_err(|e| {
+            tracing::warn!(error = %e, "Unparseable chart analysis from AI");
+            AppError::AiError("AI returned an unreadable chart analysis.".to_string())
         })?;
+        Ok((analysis, completion))
+    }
+
+    /// Re-encodes oversized screenshots as a downscaled JPEG so they fit the
+    /// API's image limits.
+    pub fn prepare_image(bytes: &[u8], mime_type: &str) -> Result<(Vec<u8>, String), String> {
+        let reader = image::io::Reader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(|e| format!("Could not read image: {}", e))?;
+        let (width, height) = reader
+            .into_dimensions()
+            .map_err(|e| format!("Could not read image: {}", e))?;
+
+        if bytes.len() <= MAX_VISION_IMAGE_BYTES && width.max(height) <= MAX_VISION_IMAGE_EDGE {
+            return Ok((bytes.to_vec(), mime_type.to_string()));
+        }
+
+        let img = image::load_from_memory(bytes).map_err(|e| format!("Could not decode image: {}", e))?;
+        let rgb = img
+            .resize(MAX_VISION_IMAGE_EDGE, MAX_VISION_IMAGE_EDGE, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, 85)
+            .encode(&rgb, rgb.width(), rgb.height(), image::ColorType::Rgb8)
+            .map_err(|e| format!("Could not encode image: {}", e))?;
+        Ok((out, "image/jpeg".to_string()))
+    }
+
+    /// Pulls the JSON object out of a model reply, tolerating code fences
+    /// and surrounding prose.
+    pub fn extract_json(text: &str) -> Option<&str> {
+        let start = text.find('{')?;
+        let end = text.rfind('}')?;
+        (end > start).then(|| &text[start..=end])
+    }
+
+    pub fn parse_chart_analysis(text: &str) -> Result<ChartAnalysis, String> {
+        let json = Self::extract_json(text).ok_or("no JSON object in response")?;
+        let mut analysis: ChartAnalysis =
+            serde_json::from_str(json).map_err(|e| format!("invalid chart analysis JSON: {}", e))?;
+
+        for rating in [
+            &mut analysis.entry_quality.rating,
+            &mut analysis.stop_placement.rating,
+        ] {
+            *rating = rating.trim().to_lowercase();
+            if !CHART_RATINGS.contains(&rating.as_str()) {
+                return Err(format!("invalid rating '{}'", rating));
+            }
+        }
+        if !analysis.pattern.confidence.is_finite() {
+            return Err("invalid pattern confidence".to_string());
+        }
+        analysis.pattern.confidence = analysis.pattern.confidence.clamp(0.0, 1.0);
+        Ok(analysis)
+    }
+
+    /// One-paragraph rendering of a chart analysis for review prompts and
+    /// the `ai_reviews.chart_analysis` column.
+    pub fn describe_chart_analysis(analysis: &ChartAnalysis) -> String {
+        let mut text = format!(
+            "Pattern: {} ({:.0}% confidence) - {}. Entry quality: {} - {}. Stop placement: {} - {}.",
+            analysis.pattern.name,
+            analysis.pattern.confidence * 100.0,
+            analysis.pattern.description,
+            analysis.entry_quality.rating,
+            analysis.entry_quality.assessment,
+            analysis.stop_placement.rating,
+            analysis.stop_placement.assessment,
+        );
+        if let Some(ref stop) = analysis.stop_placement.suggested_stop {
+            text.push_str(&format!(" Suggested stop: {}.", stop));
+        }
+        if !analysis.key_levels.is_empty() {
+            text.push_str(&format!(" Key levels: {}.", analysis.key_levels.join(", ")));
+        }
+        text
+    }
+
+    fn preflight(&self) -> AppResult<()> {
+        self.provider.check_configured()?;
 
         if self.is_circuit_open() {
             return Err(AppError::AiError(
                 "AI service is temporarily unavailable. Please try again later.".to_string(),
             ));
         }
+        Ok(())
+    }
 
-        let request_body = ClaudeRequest {
-            model: "claude-sonnet-4-20250514".to_string(),
-            max_tokens: 4096,
+    fn backoff(attempt: u32) -> Duration {
+        Duration::from_millis(INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1))
+    }
+
+    /// Sends messages to the configured provider with retry logic and circuit breaker.
+    pub async fn chat(&self, messages: Vec<ClaudeMessage>) -> AppResult<LlmCompletion> {
+        self.preflight()?;
+
+        let request = LlmRequest {
             messages,
+            max_tokens: 4096,
         };
 
         let mut last_error: Option<AppError> = None;
 
         for attempt in 0..=MAX_RETRIES {
             if attempt > 0 {
-                let backoff = Duration::from_millis(INITIAL_BACKOFF_MS * 2u64.pow(attempt - 1));
-                tokio::time::sleep(backoff).await;
-                tracing::info!(attempt, "Retrying Claude API request");
+                tokio::time::sleep(Self::backoff(attempt)).await;
+                tracing::info!(attempt, "Retrying AI request");
             }
 
-            match self.send_request(api_key, &request_body).await {
+            match self.provider.complete(&request).await {
                 Ok(result) => {
                     self.record_success();
                     return Ok(result);
                 }
                 Err(e) => {
-                    tracing::warn!(attempt, error = %e, "Claude API request failed");
+                    tracing::warn!(attempt, provider = self.provider.name(), error = %e, "AI request failed");
 
                     // Don't retry on client errors (4xx) except 429 (rate limit)
                     if matches!(&e, AppError::Validation(_)) {
@@ -130,80 +704,80 @@ impl AiService {
         }))
     }
 
-    /// Performs a single HTTP request to the Claude API.
-    async fn send_request(
+    /// Streams a reply, calling `on_delta` with each text fragment as it
+    /// arrives. Failures are retried like `chat` until text has been
+    /// forwarded, after which they can't be. If `on_delta` returns false the
+    /// consumer has gone away, so the upstream request is dropped.
+    pub async fn chat_stream<F>(
         &self,
-        api_key: &str,
-        request_body: &ClaudeRequest,
-    ) -> AppResult<(String, i32)> {
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(request_body)
-            .send()
-            .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    AppError::AiError("AI request timed out. Please try again.".to_string())
-                } else if e.is_connect() {
-                    AppError::AiError("Could not reach AI service.".to_string())
-                } else {
-                    AppError::AiError(format!("AI request failed: {}", e))
-                }
-            })?;
-
-        let status = response.status();
-
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-
-            // Log the full error server-side, return a safe message to the client
-            tracing::error!(status = %status, body = %error_text, "Claude API error");
-
-            return match status.as_u16() {
-                401 => Err(AppError::AiError(
-                    "AI service authentication failed. Check API key.".to_string(),
-                )),
-                429 => Err(AppError::AiError(
-                    "AI rate limit reached. Please wait and try again.".to_string(),
-                )),
-                400 => Err(AppError::Validation(
-                    "Invalid request to AI service.".to_string(),
-                )),
-                _ => Err(AppError::AiError(
-                    "AI service returned an error. Please try again.".to_string(),
-                )),
-            };
-        }
+        messages: Vec<ClaudeMessage>,
+        mut on_delta: F,
+    ) -> AppResult<ChatStreamOutcome>
+    where
+        F: FnMut(&str) -> bool + Send,
+    {
+        self.preflight()?;
 
-        let claude_response: ClaudeResponse = response.json().await.map_err(|e| {
-            tracing::error!(error = %e, "Failed to parse Claude response");
-            AppError::AiError("Unexpected response from AI service.".to_string())
-        })?;
+        let request = LlmRequest {
+            messages,
+            max_tokens: 4096,
+        };
 
-        let text = claude_response
-            .content
-            .first()
-            .map(|c| c.text.clone())
-            .unwrap_or_default();
+        let mut forwarded = false;
+        let mut last_error: Option<AppError> = None;
+        for attempt in 0..=MAX_RETRIES {
+            if attempt > 0 {
+                tokio::time::sleep(Self::backoff(attempt)).await;
+                tracing::info!(attempt, "Retrying AI streaming request");
+            }
 
-        if text.is_empty() {
-            return Err(AppError::AiError(
-                "AI returned an empty response.".to_string(),
-            ));
-        }
+            let result = self
+                .provider
+                .stream(&request, &mut |text| {
+                    forwarded = true;
+                    on_delta(text)
+                })
+                .await;
 
-        let tokens = claude_response.usage.input_tokens + claude_response.usage.output_tokens;
+            match result {
+                Ok(ChatStreamOutcome::ClientDisconnected(usage)) => {
+                    tracing::info!("Client disconnected; cancelling AI stream");
+                    return Ok(ChatStreamOutcome::ClientDisconnected(usage));
+                }
+                Ok(outcome) => {
+                    self.record_success();
+                    return Ok(outcome);
+                }
+                Err(e) => {
+                    tracing::warn!(attempt, provider = self.provider.name(), error = %e, "AI streaming request failed");
+                    if matches!(&e, AppError::Validation(_)) {
+                        return Err(e);
+                    }
+                    if forwarded {
+                        self.record_failure();
+                        return Err(e);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
 
-        Ok((text, tokens))
+        self.record_failure();
+        Err(last_error.unwrap_or_else(|| {
+            AppError::AiError("AI request failed after retries".to_string())
+        }))
     }
 
-    fn build_trade_analysis_prompt(&self, trade: &Trade) -> String {
-        format!(
-            r#"You are an expert trading coach analyzing a trade. Provide constructive feedback.
+    fn build_trade_analysis_prompt(
+        &self,
+        trade: &Trade,
+        charts: &[ChartAnalysis],
+        focus: Option<&str>,
+        context: Option<&AiContext>,
+    ) -> String {
+        let personality = context.map(|c| c.personality).unwrap_or(AiPersonality::Balanced);
+        let mut prompt = format!(
+            r#"You are an expert trading coach analyzing a trade. {}
 
 Trade Details:
 - Symbol: {}
@@ -216,15 +790,10 @@ Trade Details:
 - Thesis: {}
 - Mistakes: {}
 - Lessons: {}
-
-Please analyze this trade and provide:
-1. What went well
-2. What could be improved
-3. Key lessons
-4. Suggestions for future trades
-5. Pattern recognition (if applicable)
-
-Be specific, actionable, and encouraging."#,
+- Stop Loss: {}
+- Emotional State: {}
+- Followed Plan: {}, Broke Rules: {}"#,
+            personality.instruction(),
             trade.symbol,
             format!("{:?}", trade.direction).to_uppercase(),
             trade.entry_price,
@@ -235,7 +804,190 @@ Be specific, actionable, and encouraging."#,
             trade.setup_name.as_deref().unwrap_or("Not specified"),
             trade.thesis.as_deref().unwrap_or("Not specified"),
             trade.mistakes.as_deref().unwrap_or("None noted"),
-            trade.lessons.as_deref().unwrap_or("None noted")
+            trade.lessons.as_deref().unwrap_or("None noted"),
+            trade.stop_loss.map(|s| format!("${}", s)).unwrap_or_else(|| "None".to_string()),
+            trade.emotional_state.as_deref().unwrap_or("Not recorded"),
+            trade.followed_plan,
+            trade.broke_rules
+        );
+
+        if !charts.is_empty() {
+            prompt.push_str("\n\nChart screenshot analysis:");
+            for chart in charts {
+                prompt.push_str("\n- ");
+                prompt.push_str(&Self::describe_chart_analysis(chart));
+            }
+            prompt.push_str("\n\nUse the chart analysis to judge entry timing and stop placement.");
+        }
+
+        if let Some(history) = context
+            .map(|c| c.render(TRADE_CONTEXT_TOKEN_BUDGET))
+            .filter(|h| !h.is_empty())
+        {
+            prompt.push_str("\n\nThe trader's own history:\n");
+            prompt.push_str(&history);
+            prompt.push_str("\n\nGround your feedback in this history. Compare the trade against their record on the setup, their plan and playbook for the day, and any repeated mistakes, rather than giving generic advice.");
+        }
+
+        if let Some(focus) = focus.filter(|f| !f.trim().is_empty()) {
+            prompt.push_str(&format!("\n\nThe trader asked you to focus on: {}", focus.trim()));
+        }
+
+        prompt.push_str(&format!(
+            r#"
+
+Score the trade from 0 to 10 on overall quality, execution, risk management and plan adherence. Score thesis alignment from 1 to 5, or null if there was no thesis.
+
+Respond with ONLY a JSON object that validates against this JSON schema:
+{}"#,
+            Self::trade_review_schema()
+        ));
+
+        prompt
+    }
+
+    fn build_chart_analysis_prompt(trade: Option<&Trade>) -> String {
+        let context = trade
+            .map(|t| {
+                format!(
+                    "\nThe trader took this {} trade in {} at {} with a stop at {}.\n",
+                    format!("{:?}", t.direction).to_lowercase(),
+                    t.symbol,
+                    t.entry_price,
+                    t.stop_loss.map(|s| s.to_string()).unwrap_or_else(|| "no recorded level".to_string()),
+                )
+            })
+            .unwrap_or_default();
+
+        format!(
+            r#"You are an expert technical analyst reviewing a trader's chart screenshot.
+{}
+Identify the chart pattern, judge the entry relative to market structure, and assess stop placement.
+
+Respond with ONLY a JSON object in exactly this shape:
+{{
+  "pattern": {{"name": string, "confidence": number between 0 and 1, "description": string}},
+  "entry_quality": {{"rating": "excellent" | "good" | "fair" | "poor", "assessment": string}},
+  "stop_placement": {{"rating": "excellent" | "good" | "fair" | "poor", "assessment": string, "suggested_stop": string or null}},
+  "key_levels": [string],
+  "summary": string
+}}"#,
+            context
+        )
+    }
+
+    /// Prompt for a pre-market plan of attack, returned so it can be stored
+    /// with the generated plan.
+    pub fn build_plan_of_attack_prompt(plan: &DailyPlan, context: &AiContext) -> String {
+        let mut prompt = format!(
+            r#"You are an expert trading coach preparing a trader for the session on {}. {}
+
+Trader's plan:
+- Market bias: {}
+- Bias reasoning: {}
+- Session goals: {}
+- Max trades: {}, Max daily loss: {}
+- Notes: {}"#,
+            plan.plan_date,
+            context.personality.instruction(),
+            plan.market_bias.as_deref().unwrap_or("Not set"),
+            plan.bias_reasoning.as_deref().unwrap_or("Not specified"),
+            plan.session_goals
+                .as_ref()
+                .filter(|g| !g.is_empty())
+                .map(|g| g.join("; "))
+                .unwrap_or_else(|| "None".to_string()),
+            plan.max_trades.map(|n| n.to_string()).unwrap_or_else(|| "Not set".to_string()),
+            plan.max_daily_loss.map(|l| format!("${}", l)).unwrap_or_else(|| "Not set".to_string()),
+            plan.notes.as_deref().unwrap_or("None"),
+        );
+
+        let history = context.render(PLAN_CONTEXT_TOKEN_BUDGET);
+        if !history.is_empty() {
+            prompt.push_str("\n\n");
+            prompt.push_str(&history);
+        }
+
+        prompt.push_str(
+            r#"
+
+Write a concise plan of attack for the session in Markdown:
+1. Market read: how the bias and today's economic events should shape the session, including times to stand aside.
+2. Focus list: the best watchlist names in priority order, each with the trigger to enter, the invalidation level and the target, using the key levels given.
+3. Setups to favor or avoid, based on the trader's recent results by setup.
+4. Risk rules for the day: trade count, loss limit and when to stop.
+5. One mindset reminder tied to their active goals.
+
+Only reference symbols, levels and events listed above. Do not invent prices."#,
+        );
+
+        prompt
+    }
+
+    /// Prompt for drafting the narrative fields of a weekly or monthly review.
+    pub fn build_review_draft_prompt(
+        review_type: &str,
+        period_start: chrono::NaiveDate,
+        period_end: chrono::NaiveDate,
+        context: &AiContext,
+    ) -> String {
+        let mut prompt = format!(
+            r#"You are an expert trading coach drafting a trader's {} review for {} to {}. {}
+
+Write it in the first person, as the trader would, so they can edit and accept it."#,
+            review_type,
+            period_start,
+            period_end,
+            context.personality.instruction(),
+        );
+
+        let history = context.render(REVIEW_CONTEXT_TOKEN_BUDGET);
+        if history.is_empty() {
+            prompt.push_str("\n\nNo trades, mood logs or tilt alerts were recorded for this period.");
+        } else {
+            prompt.push_str("\n\n");
+            prompt.push_str(&history);
+        }
+
+        prompt.push_str(&format!(
+            r#"
+
+Base every point on the data above: cite the setups, days and recurring mistakes that drove the results. List only rules the trader actually broke. Make next period's goals specific and measurable, and say whether last period's goals were met.
+
+Respond with ONLY a JSON object that validates against this JSON schema:
+{}"#,
+            Self::review_draft_schema()
+        ));
+
+        prompt
+    }
+
+    /// Prompt for translating a trader's question into trade filters. The
+    /// model only fills in the schema; the query itself is built server-side.
+    pub fn build_trade_query_prompt(question: &str, now: chrono::DateTime<chrono_tz::Tz>) -> String {
+        format!(
+            r#"You translate a trader's question about their own trade journal into search filters.
+
+Current time in the trader's timezone ({}): {} (UTC offset {}).
+
+Rules:
+- Use only the filters the question asks for; leave everything else null.
+- from_date and to_date are RFC 3339 timestamps with the offset above. "Last month" means the previous calendar month; "this week" starts on Monday.
+- entry_time_from (inclusive) and entry_time_to (exclusive) are HH:MM:SS in the trader's timezone, e.g. "before 10am" is entry_time_to "10:00:00". A window past midnight has entry_time_from after entry_time_to, e.g. "22:00:00" to "02:00:00".
+- symbol and setup_name match by case-insensitive substring, so "NQ" also matches "NQZ6".
+- min_pnl and max_pnl are net P&L in dollars.
+- Use kind "stats" for win rate, P&L, expectancy and other aggregates (computed over closed trades), with group_by to break results down. Use kind "list" to show individual trades.
+- explanation is one sentence restating the question as the filters read it.
+
+Question: {}
+
+Respond with ONLY a JSON object that validates against this JSON schema:
+{}"#,
+            now.timezone().name(),
+            now.format("%A %Y-%m-%d %H:%M"),
+            now.format("%:z"),
+            question,
+            Self::trade_query_schema()
         )
     }
 
@@ -257,34 +1009,30 @@ Provide helpful, specific, and actionable advice."#,
 mod tests {
     use super::*;
     use crate::models::TradeDirection;
+    use axum::{
+        extract::State,
+        http::{header, HeaderMap},
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+    use image::{ImageBuffer, Rgb};
     use rust_decimal::Decimal;
+    use crate::services::ai_context::ContextSection;
+    use crate::services::llm::{FakeProvider, DEFAULT_ANTHROPIC_MODEL, FAKE_MODEL};
+    use std::sync::Mutex;
 
-    #[test]
-    fn test_build_trade_analysis_prompt() {
-        let config = Config {
-            database_url: "".to_string(),
-            port: 3000,
-            cors_origins: vec![],
-            jwt_secret: "test".to_string(),
-            jwt_access_expiry_seconds: 900,
-            jwt_refresh_expiry_seconds: 2592000,
+    fn test_config(base_url: &str) -> Config {
+        Config {
             anthropic_api_key: Some("test".to_string()),
-            s3_endpoint: "".to_string(),
-            s3_region: "".to_string(),
-            s3_bucket: "".to_string(),
-            s3_access_key: "".to_string(),
-            s3_secret_key: "".to_string(),
-            max_pool_connections: 10,
-            smtp_host: None,
-            smtp_port: None,
-            smtp_username: None,
-            smtp_password: None,
-            smtp_from_email: None,
-        };
+            anthropic_base_url: base_url.to_string(),
+            ai_provider: "anthropic".to_string(),
+            ..Config::for_tests()
+        }
+    }
 
-        let service = AiService::new(&config);
-        
-        let trade = Trade {
+    fn sample_trade() -> Trade {
+        Trade {
             id: uuid::Uuid::new_v4(),
             user_id: uuid::Uuid::new_v4(),
             symbol: "AAPL".to_string(),
@@ -328,12 +1076,540 @@ mod tests {
             followed_plan: true,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
-        };
+        }
+    }
 
-        let prompt = service.build_trade_analysis_prompt(&trade);
+    fn sample_analysis() -> ChartAnalysis {
+        ChartAnalysis {
+            pattern: crate::models::ChartPattern {
+                name: "Bull flag".to_string(),
+                confidence: 0.8,
+                description: "Tight consolidation after an impulse leg".to_string(),
+            },
+            entry_quality: crate::models::ChartAssessment {
+                rating: "good".to_string(),
+                assessment: "Entered on the breakout candle".to_string(),
+            },
+            stop_placement: crate::models::StopPlacementAssessment {
+                rating: "poor".to_string(),
+                assessment: "Stop sits inside the flag".to_string(),
+                suggested_stop: Some("147.80 below the flag low".to_string()),
+            },
+            key_levels: vec!["148.00".to_string(), "155.00".to_string()],
+            summary: "Valid setup, stop too tight".to_string(),
+        }
+    }
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = ImageBuffer::from_pixel(width, height, Rgb([10u8, 10, 10]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn test_build_trade_analysis_prompt() {
+        let service = AiService::new(&test_config("https://api.anthropic.com"));
+        let trade = sample_trade();
+
+        let prompt = service.build_trade_analysis_prompt(&trade, &[], None, None);
         
         assert!(prompt.contains("AAPL"));
         assert!(prompt.contains("Bull Flag"));
         assert!(prompt.contains("$150"));
+        assert!(!prompt.contains("Chart screenshot analysis"));
+
+        let prompt = service.build_trade_analysis_prompt(&trade, &[sample_analysis()], None, None);
+        assert!(prompt.contains("Chart screenshot analysis"));
+        assert!(prompt.contains("Stop placement: poor"));
+        assert!(prompt.contains("147.80 below the flag low"));
+    }
+
+    #[test]
+    fn test_build_trade_analysis_prompt_with_history() {
+        let service = AiService::new(&test_config("https://api.anthropic.com"));
+        let context = AiContext {
+            personality: AiPersonality::StrictCoach,
+            sections: vec![ContextSection {
+                title: "Track record on this setup",
+                priority: 0,
+                lines: vec!["'Bull Flag': 14 prior closed trades, 35.7% win rate".to_string()],
+            }],
+        };
+
+        let prompt = service.build_trade_analysis_prompt(&sample_trade(), &[], None, Some(&context));
+        assert!(prompt.contains(AiPersonality::StrictCoach.instruction()));
+        assert!(prompt.contains("The trader's own history"));
+        assert!(prompt.contains("- 'Bull Flag': 14 prior closed trades"));
+
+        let prompt = service.build_trade_analysis_prompt(&sample_trade(), &[], None, None);
+        assert!(prompt.contains(AiPersonality::Balanced.instruction()));
+        assert!(!prompt.contains("The trader's own history"));
+    }
+
+    #[test]
+    fn test_build_plan_of_attack_prompt() {
+        let plan = DailyPlan {
+            id: uuid::Uuid::new_v4(),
+            user_id: uuid::Uuid::new_v4(),
+            plan_date: chrono::NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+            market_bias: Some("bullish".to_string()),
+            bias_reasoning: Some("Held the 20-day EMA on Friday".to_string()),
+            session_goals: Some(vec!["Max two trades".to_string()]),
+            max_trades: Some(2),
+            max_daily_loss: Some(Decimal::from(500)),
+            checklist_items: None,
+            notes: None,
+            ai_plan_of_attack: None,
+            ai_plan_prompt: None,
+            ai_plan_prompt_version: None,
+            ai_plan_provider: None,
+            ai_plan_model: None,
+            ai_plan_generated_at: None,
+            adherence_score: None,
+            adherence_details: None,
+            completed: false,
+            completed_at: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        let context = AiContext {
+            personality: AiPersonality::EncouragingMentor,
+            sections: vec![ContextSection {
+                title: "Watchlist",
+                priority: 0,
+                lines: vec!["NVDA: bull flag; key levels [\"450\", \"462\"]".to_string()],
+            }],
+        };
+
+        let prompt = AiService::build_plan_of_attack_prompt(&plan, &context);
+        assert!(prompt.contains("2026-10-19"));
+        assert!(prompt.contains("Market bias: bullish"));
+        assert!(prompt.contains("Max trades: 2, Max daily loss: $500"));
+        assert!(prompt.contains("Watchlist:\n- NVDA: bull flag"));
+        assert!(prompt.contains(AiPersonality::EncouragingMentor.instruction()));
+    }
+
+    #[test]
+    fn test_parse_chart_analysis() {
+        let reply = format!(
+            "Here is the analysis:\n```json\n{}\n```",
+            serde_json::to_string(&sample_analysis()).unwrap().replace("\"good\"", "\"Good \"")
+        );
+        let parsed = AiService::parse_chart_analysis(&reply).unwrap();
+        assert_eq!(parsed, sample_analysis());
+
+        let mut bad = serde_json::to_value(sample_analysis()).unwrap();
+        bad["stop_placement"]["rating"] = serde_json::json!("amazing");
+        assert!(AiService::parse_chart_analysis(&bad.to_string()).is_err());
+        assert!(AiService::parse_chart_analysis("I can't see a chart here.").is_err());
+    }
+
+    #[test]
+    fn test_prepare_image_downscales_large_screenshots() {
+        let small = png_bytes(800, 400);
+        let (bytes, mime) = AiService::prepare_image(&small, "image/png").unwrap();
+        assert_eq!((bytes, mime.as_str()), (small, "image/png"));
+
+        let (bytes, mime) = AiService::prepare_image(&png_bytes(3136, 1000), "image/png").unwrap();
+        assert_eq!(mime, "image/jpeg");
+        let resized = image::load_from_memory(&bytes).unwrap();
+        assert_eq!((resized.width(), resized.height()), (1568, 500));
+    }
+
+    #[derive(Default)]
+    struct MockApi {
+        requests: Vec<(HeaderMap, serde_json::Value)>,
+        replies: std::collections::VecDeque<String>,
+    }
+
+    type SharedMock = Arc<Mutex<MockApi>>;
+
+    /// Minimal stand-in for the messages API: records each request and
+    /// answers with the next queued reply text.
+    async fn mock_messages(
+        State(mock): State<SharedMock>,
+        headers: HeaderMap,
+        Json(body): Json<serde_json::Value>,
+    ) -> Response {
+        let mut mock = mock.lock().unwrap();
+        let stream = body["stream"] == true;
+        mock.requests.push((headers, body));
+        let text = mock.replies.pop_front().unwrap_or_default();
+
+        if stream {
+            return ([(header::CONTENT_TYPE, "text/event-stream")], sse_body(&text)).into_response();
+        }
+        Json(serde_json::json!({
+            "id": "msg_mock",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": text}],
+            "usage": {"input_tokens": 1200, "output_tokens": 300}
+        }))
+        .into_response()
+    }
+
+    /// The streaming event sequence for `text`, delivered in 4-character deltas.
+    fn sse_body(text: &str) -> String {
+        let mut events = vec![
+            serde_json::json!({"type": "message_start", "message": {"usage": {"input_tokens": 1200, "output_tokens": 1}}}),
+            serde_json::json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        ];
+        let chars = text.chars().collect::<Vec<_>>();
+        for piece in chars.chunks(4) {
+            events.push(serde_json::json!({
+                "type": "content_block_delta",
+                "index": 0,
+                "delta": {"type": "text_delta", "text": piece.iter().collect::<String>()}
+            }));
+        }
+        events.push(serde_json::json!({"type": "content_block_stop", "index": 0}));
+        events.push(serde_json::json!({"type": "message_delta", "usage": {"output_tokens": 300}}));
+        events.push(serde_json::json!({"type": "message_stop"}));
+
+        events
+            .iter()
+            .map(|e| format!("event: {}\r\ndata: {}\r\n\r\n", e["type"].as_str().unwrap(), e))
+            .collect()
+    }
+
+    /// Starts the mock on an ephemeral port and returns a service pointed at it.
+    async fn mock_service(replies: Vec<String>) -> (AiService, SharedMock) {
+        let mock: SharedMock = Arc::new(Mutex::new(MockApi {
+            requests: Vec::new(),
+            replies: replies.into(),
+        }));
+        let app = Router::new()
+            .route("/v1/messages", post(mock_messages))
+            .with_state(mock.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (AiService::new(&test_config(&format!("http://{}/", addr))), mock)
+    }
+
+    #[tokio::test]
+    async fn test_analyze_chart_against_mock_api() {
+        let reply = format!("```json\n{}\n```", serde_json::to_string(&sample_analysis()).unwrap());
+        let (service, mock) = mock_service(vec![reply]).await;
+        let trade = sample_trade();
+        let mut usage = Vec::new();
+        let (analysis, completion) = service
+            .analyze_chart(&png_bytes(64, 32), "image/png", Some(&trade), &mut usage)
+            .await
+            .unwrap();
+        assert_eq!(usage, vec![completion.usage()]);
+
+        assert_eq!(analysis, sample_analysis());
+        assert_eq!(completion.tokens(), 1500);
+        assert_eq!(completion.model, DEFAULT_ANTHROPIC_MODEL);
+
+        let (headers, body) = mock.lock().unwrap().requests.remove(0);
+        assert_eq!(headers["x-api-key"], "test");
+        assert_eq!(body["model"], DEFAULT_ANTHROPIC_MODEL);
+        let content = &body["messages"][0]["content"];
+        assert_eq!(content[0]["type"], "image");
+        assert_eq!(content[0]["source"]["type"], "base64");
+        assert_eq!(content[0]["source"]["media_type"], "image/png");
+        assert_eq!(content[1]["type"], "text");
+        assert!(content[1]["text"].as_str().unwrap().contains("AAPL"));
+    }
+
+    fn sample_review() -> serde_json::Value {
+        serde_json::json!({
+            "overall_score": 7.456,
+            "execution_quality_score": 8,
+            "risk_management_score": "6.5",
+            "plan_adherence_score": 9,
+            "thesis_alignment_score": 4,
+            "strengths": ["Waited for confirmation", "  "],
+            "weaknesses": ["Stop inside the flag"],
+            "key_lesson": "Give the setup room to breathe",
+            "actionable_fixes": ["Place stops below structure"],
+            "alternative_scenario": null,
+            "emotional_state_detected": "Calm"
+        })
+    }
+
+    #[test]
+    fn test_parse_trade_review_normalizes_output() {
+        let review = AiService::parse_trade_review(&sample_review().to_string()).unwrap();
+        assert_eq!(review.overall_score, Decimal::new(746, 2));
+        assert_eq!(review.risk_management_score, Decimal::new(65, 1));
+        assert_eq!(review.strengths, vec!["Waited for confirmation".to_string()]);
+        assert_eq!(review.emotional_state_detected.as_deref(), Some("calm"));
+    }
+
+    #[test]
+    fn test_parse_trade_review_rejects_invalid_output() {
+        let mut out_of_range = sample_review();
+        out_of_range["overall_score"] = serde_json::json!(11);
+        let err = AiService::parse_trade_review(&out_of_range.to_string()).unwrap_err();
+        assert!(err.contains("overall_score"));
+
+        let mut missing = sample_review();
+        missing.as_object_mut().unwrap().remove("key_lesson");
+        assert!(AiService::parse_trade_review(&missing.to_string()).is_err());
+
+        let mut empty_list = sample_review();
+        empty_list["actionable_fixes"] = serde_json::json!([]);
+        assert!(AiService::parse_trade_review(&empty_list.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_parse_review_draft() {
+        let reply = r#"Here is your draft:
+{
+  "what_went_well": "  Sized down after the Tuesday loss. ",
+  "what_to_improve": "Stop chasing opening-range breakouts.",
+  "key_lessons": ["ORB entries after 10:00 lost money", ""],
+  "rules_broken": [],
+  "goals_next_period": ["No ORB trades after 10:00"],
+}"#;
+        let draft = AiService::parse_review_draft(reply).unwrap();
+        assert_eq!(draft.what_went_well, "Sized down after the Tuesday loss.");
+        assert_eq!(draft.key_lessons.len(), 1);
+        assert!(draft.rules_broken.is_empty());
+
+        let text = AiService::format_review_draft(&draft);
+        assert!(text.contains("Rules broken:\n- None"));
+
+        let missing_goals = r#"{"what_went_well": "a", "what_to_improve": "b", "key_lessons": ["c"], "rules_broken": [], "goals_next_period": []}"#;
+        let err = AiService::parse_review_draft(missing_goals).unwrap_err();
+        assert!(err.contains("goals_next_period"));
+    }
+
+    #[test]
+    fn test_build_review_draft_prompt() {
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+        let end = chrono::NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let context = AiContext {
+            personality: AiPersonality::Balanced,
+            sections: vec![ContextSection {
+                title: "Previous review",
+                priority: 1,
+                lines: vec!["Goals set for this period: Max 3 trades a day".to_string()],
+            }],
+        };
+
+        let prompt = AiService::build_review_draft_prompt("weekly", start, end, &context);
+        assert!(prompt.contains("weekly review for 2026-10-12 to 2026-10-16"));
+        assert!(prompt.contains("- Goals set for this period: Max 3 trades a day"));
+        assert!(prompt.contains("\"goals_next_period\""));
+
+        let empty = AiContext { personality: AiPersonality::Balanced, sections: Vec::new() };
+        let prompt = AiService::build_review_draft_prompt("monthly", start, end, &empty);
+        assert!(prompt.contains("No trades, mood logs or tilt alerts"));
+    }
+
+    #[test]
+    fn test_parse_trade_query() {
+        let reply = r#"Here you go: {
+            "filters": {"direction": "Short", "symbol": "NQ", "entry_time_to": "10:00:00",
+                        "from_date": "2026-09-01T00:00:00-04:00", "to_date": "2026-09-30T23:59:59-04:00",
+                        "min_pnl": null},
+            "aggregation": {"kind": "stats"},
+            "explanation": "Closed short NQ trades entered before 10:00 in September 2026."
+        }"#;
+        let query = AiService::parse_trade_query(reply).unwrap();
+        assert!(matches!(query.filters.direction, Some(TradeDirection::Short)));
+        assert_eq!(query.filters.entry_time_to, chrono::NaiveTime::from_hms_opt(10, 0, 0));
+        assert_eq!(
+            query.filters.from_date.unwrap().to_rfc3339(),
+            "2026-09-01T04:00:00+00:00"
+        );
+
+        let grouped = r#"{"filters": {"min_r_multiple": 1.5}, "aggregation": {"kind": "stats", "group_by": "weekday"}, "explanation": "Winners of 1.5R or more by weekday."}"#;
+        let query = AiService::parse_trade_query(grouped).unwrap();
+        assert_eq!(query.filters.min_r_multiple, Some(Decimal::new(15, 1)));
+        assert_eq!(query.aggregation.group_by, Some(crate::models::TradeGroupBy::Weekday));
+
+        let overnight = r#"{"filters": {"entry_time_from": "22:00:00", "entry_time_to": "02:00:00"}, "aggregation": {"kind": "stats"}, "explanation": "Trades entered overnight."}"#;
+        let query = AiService::parse_trade_query(overnight).unwrap();
+        assert_eq!(query.filters.entry_time_from, chrono::NaiveTime::from_hms_opt(22, 0, 0));
+    }
+
+    #[test]
+    fn test_parse_trade_query_rejects_what_it_cannot_run() {
+        let cases = [
+            (r#"{"filters": {"sql": "1=1"}, "aggregation": {"kind": "stats"}, "explanation": "x"}"#, "unknown filter \"sql\""),
+            (r#"{"filters": {}, "aggregation": {"kind": "list", "sort_by": "id; DROP TABLE trades"}, "explanation": "x"}"#, "Invalid sort column"),
+            (r#"{"filters": {}, "aggregation": {"kind": "list", "group_by": "symbol"}, "explanation": "x"}"#, "group_by only applies"),
+            (r#"{"filters": {}, "aggregation": {"kind": "pivot"}, "explanation": "x"}"#, "does not match the schema"),
+            (r#"{"filters": {"entry_time_from": "10:00:00", "entry_time_to": "10:00:00"}, "aggregation": {"kind": "stats"}, "explanation": "x"}"#, "must differ"),
+            (r#"{"filters": {}, "aggregation": {"kind": "stats"}, "explanation": " "}"#, "explanation must not be empty"),
+        ];
+        for (reply, expected) in cases {
+            let error = AiService::parse_trade_query(reply).unwrap_err();
+            assert!(error.contains(expected), "{} -> {}", reply, error);
+        }
+    }
+
+    #[test]
+    fn test_build_trade_query_prompt_anchors_dates() {
+        let now = chrono::TimeZone::with_ymd_and_hms(&chrono_tz::America::New_York, 2026, 10, 18, 9, 15, 0).unwrap();
+        let prompt = AiService::build_trade_query_prompt("win rate on short NQ trades before 10am last month", now);
+        assert!(prompt.contains("timezone (America/New_York): Sunday 2026-10-18 09:15 (UTC offset -04:00)"));
+        assert!(prompt.contains("Question: win rate on short NQ trades before 10am last month"));
+        assert!(prompt.contains("\"aggregation\""));
+    }
+
+    #[tokio::test]
+    async fn test_analyze_chart_reports_usage_of_unreadable_reply() {
+        let (service, _mock) = mock_service(vec!["I can't see a chart here.".to_string()]).await;
+        let mut usage = Vec::new();
+        let result = service
+            .analyze_chart(&png_bytes(64, 32), "image/png", None, &mut usage)
+            .await;
+        assert!(matches!(result, Err(AppError::AiError(_))));
+        assert_eq!(usage.iter().map(LlmUsage::tokens).sum::<i32>(), 1500);
+    }
+
+    #[tokio::test]
+    async fn test_interpret_trade_query_repairs_invalid_reply() {
+        let provider = Arc::new(FakeProvider::with_replies(vec![
+            r#"{"filters": {"ticker": "NQ"}, "aggregation": {"kind": "stats"}, "explanation": "x"}"#.to_string(),
+            r#"{"filters": {"symbol": "NQ"}, "aggregation": {"kind": "stats"}, "explanation": "NQ trades."}"#.to_string(),
+        ]));
+        let service = AiService::with_provider(provider.clone());
+        let now = chrono::Utc::now().with_timezone(&chrono_tz::UTC);
+
+        let mut usage = Vec::new();
+        let query = service
+            .interpret_trade_query("how am I doing on NQ", now, &mut usage)
+            .await
+            .unwrap();
+        assert_eq!(query.filters.symbol.as_deref(), Some("NQ"));
+        assert_eq!(usage.len(), 2);
+        let requests = provider.requests();
+        let correction = &requests[1].messages.last().unwrap().content;
+        assert!(matches!(correction, ClaudeMessageContent::Text(t) if t.contains("unknown filter \"ticker\"")));
+    }
+
+    #[test]
+    fn test_repair_json_strips_trailing_commas() {
+        let broken = r#"{"a": [1, 2, ], "b": "keep, } this",}"#;
+        let repaired = AiService::repair_json(broken);
+        assert_eq!(repaired, r#"{"a": [1, 2], "b": "keep, } this"}"#);
+
+        let mut review = sample_review().to_string();
+        review.insert(review.len() - 1, ',');
+        assert!(AiService::parse_trade_review(&review).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_analyze_trade_retries_malformed_output() {
+        let (service, mock) = mock_service(vec![
+            "Sure! Here's my review: the trade was great.".to_string(),
+            sample_review().to_string(),
+        ])
+        .await;
+
+        let mut usage = Vec::new();
+        let result = service
+            .analyze_trade(&sample_trade(), &[], Some("Was my stop too tight?"), None, &mut usage)
+            .await
+            .unwrap();
+        assert_eq!(result.attempts, 2);
+        assert_eq!(usage.iter().map(LlmUsage::tokens).sum::<i32>(), 3000);
+        assert_eq!(usage.len(), 2);
+        assert_eq!(result.review.plan_adherence_score, Decimal::from(9));
+
+        let mock = mock.lock().unwrap();
+        let first = mock.requests[0].1["messages"][0]["content"].as_str().unwrap();
+        assert!(first.contains("Was my stop too tight?"));
+        assert!(first.contains("\"overall_score\""));
+
+        // The retry carries the bad reply and the validation error
+        let retry = mock.requests[1].1["messages"].as_array().unwrap();
+        assert_eq!(retry.len(), 3);
+        assert_eq!(retry[1]["role"], "assistant");
+        assert!(retry[2]["content"].as_str().unwrap().contains("no JSON object found"));
+    }
+
+    #[tokio::test]
+    async fn test_analyze_trade_gives_up_after_repair_attempts() {
+        let (service, mock) = mock_service(vec!["{}".to_string(); 3]).await;
+
+        let mut usage = Vec::new();
+        let result = service
+            .analyze_trade(&sample_trade(), &[], None, None, &mut usage)
+            .await;
+        assert!(matches!(result, Err(AppError::AiError(_))));
+        assert_eq!(mock.lock().unwrap().requests.len(), 3);
+        // Every rejected reply was still paid for
+        assert_eq!(usage.len(), 3);
+        assert_eq!(usage.iter().map(LlmUsage::tokens).sum::<i32>(), 4500);
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_forwards_deltas() {
+        let (service, mock) = mock_service(vec!["Cut losers faster.".to_string()]).await;
+        let mut deltas = Vec::new();
+        let outcome = service
+            .chat_stream(vec![ClaudeMessage::text("user", "Review")], |text| {
+                deltas.push(text.to_string());
+                true
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outcome,
+            ChatStreamOutcome::Completed(LlmCompletion {
+                text: "Cut losers faster.".to_string(),
+                model: DEFAULT_ANTHROPIC_MODEL.to_string(),
+                input_tokens: 1200,
+                output_tokens: 300,
+            })
+        );
+        assert_eq!(deltas.len(), 5);
+        assert_eq!(deltas.concat(), "Cut losers faster.");
+        assert_eq!(mock.lock().unwrap().requests[0].1["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_stops_when_client_disconnects() {
+        let (service, _mock) = mock_service(vec!["A long answer that nobody reads".to_string()]).await;
+        let mut received = 0;
+        let outcome = service
+            .chat_stream(vec![ClaudeMessage::text("user", "Review")], |_| {
+                received += 1;
+                received < 2
+            })
+            .await
+            .unwrap();
+
+        // Input tokens come from message_start; output is estimated from
+        // the two deltas forwarded before the client left
+        assert_eq!(
+            outcome,
+            ChatStreamOutcome::ClientDisconnected(LlmUsage {
+                model: DEFAULT_ANTHROPIC_MODEL.to_string(),
+                input_tokens: 1200,
+                output_tokens: 2,
+            })
+        );
+        assert_eq!(received, 2);
+        assert!(!service.is_circuit_open());
+    }
+
+    #[tokio::test]
+    async fn test_trade_review_runs_offline_with_fake_provider() {
+        let fake = Arc::new(FakeProvider::new(FAKE_MODEL.to_string()));
+        let service = AiService::with_provider(fake.clone());
+        assert_eq!((service.provider_name(), service.model()), ("fake", FAKE_MODEL));
+
+        let result = service
+            .analyze_trade(&sample_trade(), &[], None, None, &mut Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(result.attempts, 1);
+        assert_eq!(result.review.overall_score, Decimal::new(65, 1));
+        assert!(fake.requests()[0].messages.len() == 1);
     }
 }
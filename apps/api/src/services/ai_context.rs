@@ -0,0 +1,1012 @@
+use crate::error::AppResult;
+use crate::models::{
+    DailyLimits, DailyPlan, EconomicEvent, MoodLog, PlaybookSetup, TiltEvent, Trade,
+    TradePatternFinding, WatchlistItem,
+};
+use crate::services::{
+    daily_limits, EconomicCalendarService, PatternMiningService, ProfileService, StreakService,
+};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Rough share of the prompt the trader's history may take up. Lower
+/// priority sections are trimmed first once it is spent.
+pub const TRADE_CONTEXT_TOKEN_BUDGET: usize = 1_200;
+pub const PLAN_CONTEXT_TOKEN_BUDGET: usize = 1_500;
+pub const REVIEW_CONTEXT_TOKEN_BUDGET: usize = 2_500;
+
+const SIMILAR_TRADE_LIMIT: i64 = 6;
+const TILT_EVENT_LIMIT: i64 = 5;
+const MAX_NOTE_CHARS: usize = 160;
+/// Setup performance in a plan of attack looks back this far.
+const PLAN_LOOKBACK_DAYS: i64 = 30;
+const PLAN_SETUP_LIMIT: i64 = 8;
+const REVIEW_TRADE_LIMIT: i64 = 60;
+const REVIEW_TILT_LIMIT: i64 = 20;
+/// Top-ranked mined patterns shown to the coach.
+const PATTERN_LIMIT: i64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiPersonality {
+    StrictCoach,
+    EncouragingMentor,
+    Balanced,
+}
+
+impl AiPersonality {
+    /// Unknown or missing values fall back to the profile default.
+    pub fn parse(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("strict_coach") => AiPersonality::StrictCoach,
+            Some("encouraging_mentor") => AiPersonality::EncouragingMentor,
+            _ => AiPersonality::Balanced,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiPersonality::StrictCoach => "strict_coach",
+            AiPersonality::EncouragingMentor => "encouraging_mentor",
+            AiPersonality::Balanced => "balanced",
+        }
+    }
+
+    pub fn instruction(&self) -> &'static str {
+        match self {
+            AiPersonality::StrictCoach => {
+                "Coach like a strict, no-nonsense trading mentor. Call out every rule break and repeated mistake directly and hold the trader to their own plan. Do not soften criticism."
+            }
+            AiPersonality::EncouragingMentor => {
+                "Coach like a supportive mentor. Lead with what the trader did well, frame mistakes as the next thing to work on, and keep the tone encouraging while staying honest."
+            }
+            AiPersonality::Balanced => {
+                "Coach in a balanced, direct tone. Acknowledge what went well, be candid about what did not, and keep the feedback specific and actionable."
+            }
+        }
+    }
+}
+
+/// One block of history, rendered as a titled bullet list.
+#[derive(Debug, Clone)]
+pub struct ContextSection {
+    pub title: &'static str,
+    /// Lower values are kept first when the budget runs short.
+    pub priority: u8,
+    pub lines: Vec<String>,
+}
+
+impl ContextSection {
+    fn new(title: &'static str, priority: u8) -> Self {
+        Self { title, priority, lines: Vec::new() }
+    }
+
+    fn push(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+}
+
+/// The trader's own history, for grounding a review or plan.
+#[derive(Debug, Clone)]
+pub struct AiContext {
+    pub personality: AiPersonality,
+    pub sections: Vec<ContextSection>,
+}
+
+impl AiContext {
+    /// Renders sections in priority order within `budget_tokens`. A section
+    /// that does not fit whole keeps as many leading lines as fit; one that
+    /// cannot fit a single line is dropped.
+    pub fn render(&self, budget_tokens: usize) -> String {
+        let mut sections = self
+            .sections
+            .iter()
+            .filter(|s| !s.lines.is_empty())
+            .collect::<Vec<_>>();
+        sections.sort_by_key(|s| s.priority);
+
+        let mut remaining = budget_tokens;
+        let mut blocks = Vec::new();
+        for section in sections {
+            let heading = format!("{}:", section.title);
+            // Counts the blank line that separates sections, too.
+            let mut cost = estimate_tokens(&heading) + 1;
+            let mut block = heading;
+            let mut kept = 0;
+            for line in &section.lines {
+                let line = format!("\n- {}", line);
+                let line_cost = estimate_tokens(&line);
+                if cost + line_cost > remaining {
+                    break;
+                }
+                cost += line_cost;
+                block.push_str(&line);
+                kept += 1;
+            }
+            if kept == 0 {
+                continue;
+            }
+            remaining -= cost;
+            blocks.push(block);
+        }
+
+        blocks.join("\n\n")
+    }
+}
+
+/// Same chars-per-token heuristic the offline provider meters with.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SetupStats {
+    trade_count: i64,
+    win_count: i64,
+    total_pnl: Decimal,
+    avg_r_multiple: Option<Decimal>,
+    broke_rules_count: i64,
+    broke_rules_pnl: Option<Decimal>,
+    avg_hold_minutes: Option<Decimal>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct SetupSummary {
+    setup_name: String,
+    trade_count: i64,
+    win_count: i64,
+    total_pnl: Decimal,
+    avg_r_multiple: Option<Decimal>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PeriodStats {
+    trade_count: i64,
+    win_count: i64,
+    total_pnl: Decimal,
+    avg_r_multiple: Option<Decimal>,
+    broke_rules_count: i64,
+    revenge_count: i64,
+    followed_plan_count: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PreviousReview {
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    what_to_improve: Option<String>,
+    goals_next_period: Option<Vec<String>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ActiveGoal {
+    title: String,
+    description: Option<String>,
+    target_value: Option<Decimal>,
+    current_value: Option<Decimal>,
+    target_date: Option<NaiveDate>,
+}
+
+pub struct AiContextService;
+
+impl AiContextService {
+    /// Gathers the history a coach would want before reviewing `trade`,
+    /// keyed to the trading day in the trader's timezone.
+    pub async fn for_trade(pool: &PgPool, trade: &Trade) -> AppResult<AiContext> {
+        let user_id = trade.user_id;
+        let personality = Self::personality(pool, user_id).await?;
+        let tz = StreakService::user_timezone(pool, user_id).await?;
+        let trade_date = trade.entry_date.with_timezone(&tz).date_naive();
+        let (day_start, day_end) = day_bounds(tz, trade_date);
+
+        let mut sections = Vec::new();
+
+        if let Some(setup) = trade.setup_name.as_deref().filter(|s| !s.trim().is_empty()) {
+            let stats = sqlx::query_as::<_, SetupStats>(
+                r#"
+                SELECT
+                    COUNT(*) as trade_count,
+                    COUNT(*) FILTER (WHERE net_pnl > 0) as win_count,
+                    COALESCE(SUM(net_pnl), 0) as total_pnl,
+                    AVG(r_multiple) as avg_r_multiple,
+                    COUNT(*) FILTER (WHERE broke_rules) as broke_rules_count,
+                    AVG(net_pnl) FILTER (WHERE broke_rules) as broke_rules_pnl,
+                    AVG(hold_time_minutes)::DECIMAL as avg_hold_minutes
+                FROM trades
+                WHERE user_id = $1 AND status = 'closed' AND id <> $2
+                    AND LOWER(setup_name) = LOWER($3)
+                "#,
+            )
+            .bind(user_id)
+            .bind(trade.id)
+            .bind(setup.trim())
+            .fetch_one(pool)
+            .await?;
+            sections.push(setup_section(setup.trim(), &stats));
+
+            let playbook = sqlx::query_as::<_, PlaybookSetup>(
+                "SELECT * FROM playbook_setups WHERE user_id = $1 AND LOWER(name) = LOWER($2) ORDER BY is_active DESC LIMIT 1",
+            )
+            .bind(user_id)
+            .bind(setup.trim())
+            .fetch_optional(pool)
+            .await?;
+            if let Some(playbook) = playbook {
+                sections.push(playbook_section(&playbook));
+            }
+        }
+
+        let plan = sqlx::query_as::<_, DailyPlan>(
+            "SELECT * FROM daily_plans WHERE user_id = $1 AND plan_date = $2",
+        )
+        .bind(user_id)
+        .bind(trade_date)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(plan) = plan {
+            let watchlist = sqlx::query_as::<_, WatchlistItem>(
+                "SELECT * FROM watchlist_items WHERE plan_id = $1 ORDER BY sort_order, created_at",
+            )
+            .bind(plan.id)
+            .fetch_all(pool)
+            .await?;
+            sections.push(plan_section(&plan, &watchlist, &trade.symbol));
+        } else {
+            let defaults = ProfileService::trading_defaults(pool, user_id).await?;
+            let limits = daily_limits(trade_date, None, &defaults);
+            if limits.max_trades.is_some() || limits.max_daily_loss.is_some() {
+                sections.push(standing_limits_section(&limits));
+            }
+        }
+
+        let mood = sqlx::query_as::<_, MoodLog>(
+            "SELECT * FROM mood_logs WHERE user_id = $1 AND log_date = $2",
+        )
+        .bind(user_id)
+        .bind(trade_date)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(mood) = mood {
+            sections.push(mood_section(&mood));
+        }
+
+        let tilt_events = sqlx::query_as::<_, TiltEvent>(
+            r#"
+            SELECT * FROM tilt_events
+            WHERE user_id = $1
+                AND ($2 = ANY(trade_ids) OR (created_at >= $3 AND created_at < $4))
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade.id)
+        .bind(day_start)
+        .bind(day_end)
+        .bind(TILT_EVENT_LIMIT)
+        .fetch_all(pool)
+        .await?;
+        if !tilt_events.is_empty() {
+            sections.push(tilt_section("Tilt alerts around this trade", 4, &tilt_events, tz));
+        }
+
+        let similar = sqlx::query_as::<_, Trade>(
+            r#"
+            SELECT * FROM trades
+            WHERE user_id = $1 AND status = 'closed' AND id <> $2 AND entry_date < $3
+                AND (symbol = $4 OR ($5::TEXT IS NOT NULL AND LOWER(setup_name) = LOWER($5)))
+            ORDER BY entry_date DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade.id)
+        .bind(trade.entry_date)
+        .bind(&trade.symbol)
+        .bind(trade.setup_name.as_deref().map(str::trim))
+        .bind(SIMILAR_TRADE_LIMIT)
+        .fetch_all(pool)
+        .await?;
+        if !similar.is_empty() {
+            sections.push(trades_section("Recent similar trades, newest first", 5, &similar, tz));
+        }
+
+        let patterns = PatternMiningService::findings(pool, user_id, PATTERN_LIMIT).await?;
+        if !patterns.is_empty() {
+            sections.push(patterns_section(6, &patterns));
+        }
+
+        Ok(AiContext { personality, sections })
+    }
+
+    /// Gathers what a pre-market plan of attack should be built from: the
+    /// watchlist, the day's economic events, recent results per setup, the
+    /// trader's active goals and their mined losing patterns.
+    pub async fn for_plan(pool: &PgPool, plan: &DailyPlan) -> AppResult<AiContext> {
+        let user_id = plan.user_id;
+        let personality = Self::personality(pool, user_id).await?;
+        let tz = StreakService::user_timezone(pool, user_id).await?;
+        let mut sections = Vec::new();
+
+        let watchlist = sqlx::query_as::<_, WatchlistItem>(
+            "SELECT * FROM watchlist_items WHERE plan_id = $1 ORDER BY sort_order, created_at",
+        )
+        .bind(plan.id)
+        .fetch_all(pool)
+        .await?;
+        if !watchlist.is_empty() {
+            sections.push(watchlist_section(&watchlist));
+        }
+
+        let events = EconomicCalendarService::events_for_day(pool, user_id, plan.plan_date).await?;
+        if !events.is_empty() {
+            sections.push(events_section(&events, tz));
+        }
+
+        let (lookback_start, _) = day_bounds(tz, plan.plan_date - Duration::days(PLAN_LOOKBACK_DAYS));
+        let (plan_start, _) = day_bounds(tz, plan.plan_date);
+        let setups = sqlx::query_as::<_, SetupSummary>(
+            r#"
+            SELECT
+                COALESCE(setup_name, 'No Setup') as setup_name,
+                COUNT(*) as trade_count,
+                COUNT(*) FILTER (WHERE net_pnl > 0) as win_count,
+                COALESCE(SUM(net_pnl), 0) as total_pnl,
+                AVG(r_multiple) as avg_r_multiple
+            FROM trades
+            WHERE user_id = $1 AND status = 'closed'
+                AND entry_date >= $2 AND entry_date < $3
+            GROUP BY COALESCE(setup_name, 'No Setup')
+            ORDER BY COUNT(*) DESC, SUM(net_pnl) DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(lookback_start)
+        .bind(plan_start)
+        .bind(PLAN_SETUP_LIMIT)
+        .fetch_all(pool)
+        .await?;
+        if !setups.is_empty() {
+            sections.push(setup_summary_section("Results by setup over the last 30 days", 2, &setups));
+        }
+
+        let goals = sqlx::query_as::<_, ActiveGoal>(
+            r#"
+            SELECT title, description, target_value, current_value, target_date
+            FROM trading_goals
+            WHERE user_id = $1 AND status = 'active'
+                AND (target_date IS NULL OR target_date >= $2)
+            ORDER BY target_date NULLS LAST, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(plan.plan_date)
+        .fetch_all(pool)
+        .await?;
+        if !goals.is_empty() {
+            sections.push(goals_section(&goals));
+        }
+
+        let patterns = PatternMiningService::findings(pool, user_id, PATTERN_LIMIT).await?;
+        if !patterns.is_empty() {
+            sections.push(patterns_section(4, &patterns));
+        }
+
+        Ok(AiContext { personality, sections })
+    }
+
+    /// Gathers a weekly or monthly review period: its results, journal
+    /// notes, tilt alerts and mood logs, plus the goals the previous review
+    /// of the same type set. Trades count by exit date, as in review stats.
+    pub async fn for_period(
+        pool: &PgPool,
+        user_id: Uuid,
+        review_type: &str,
+        period_start: NaiveDate,
+        period_end: NaiveDate,
+    ) -> AppResult<AiContext> {
+        let personality = Self::personality(pool, user_id).await?;
+        let tz = StreakService::user_timezone(pool, user_id).await?;
+        let mut sections = Vec::new();
+
+        let stats = sqlx::query_as::<_, PeriodStats>(
+            r#"
+            SELECT
+                COUNT(*) as trade_count,
+                COUNT(*) FILTER (WHERE net_pnl > 0) as win_count,
+                COALESCE(SUM(net_pnl), 0) as total_pnl,
+                AVG(r_multiple) as avg_r_multiple,
+                COUNT(*) FILTER (WHERE broke_rules) as broke_rules_count,
+                COUNT(*) FILTER (WHERE is_revenge_trade) as revenge_count,
+                COUNT(*) FILTER (WHERE followed_plan) as followed_plan_count
+            FROM trades
+            WHERE user_id = $1 AND status = 'closed'
+              AND DATE(exit_date) >= $2 AND DATE(exit_date) <= $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_one(pool)
+        .await?;
+        if stats.trade_count > 0 {
+            sections.push(period_stats_section(&stats));
+        }
+
+        let previous = sqlx::query_as::<_, PreviousReview>(
+            r#"
+            SELECT period_start, period_end, what_to_improve, goals_next_period
+            FROM periodic_reviews
+            WHERE user_id = $1 AND review_type = $2 AND period_end < $3
+            ORDER BY period_end DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(review_type)
+        .bind(period_start)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(previous) = previous {
+            sections.push(previous_review_section(&previous));
+        }
+
+        let setups = sqlx::query_as::<_, SetupSummary>(
+            r#"
+            SELECT
+                COALESCE(setup_name, 'No Setup') as setup_name,
+                COUNT(*) as trade_count,
+                COUNT(*) FILTER (WHERE net_pnl > 0) as win_count,
+                COALESCE(SUM(net_pnl), 0) as total_pnl,
+                AVG(r_multiple) as avg_r_multiple
+            FROM trades
+            WHERE user_id = $1 AND status = 'closed'
+              AND DATE(exit_date) >= $2 AND DATE(exit_date) <= $3
+            GROUP BY COALESCE(setup_name, 'No Setup')
+            ORDER BY COUNT(*) DESC, SUM(net_pnl) DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_all(pool)
+        .await?;
+        if !setups.is_empty() {
+            sections.push(setup_summary_section("Results by setup", 2, &setups));
+        }
+
+        let (range_start, _) = day_bounds(tz, period_start);
+        let (_, range_end) = day_bounds(tz, period_end);
+        let tilt_events = sqlx::query_as::<_, TiltEvent>(
+            r#"
+            SELECT * FROM tilt_events
+            WHERE user_id = $1 AND created_at >= $2 AND created_at < $3
+            ORDER BY created_at
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(range_start)
+        .bind(range_end)
+        .bind(REVIEW_TILT_LIMIT)
+        .fetch_all(pool)
+        .await?;
+        if !tilt_events.is_empty() {
+            sections.push(tilt_section("Tilt alerts", 3, &tilt_events, tz));
+        }
+
+        let moods = sqlx::query_as::<_, MoodLog>(
+            "SELECT * FROM mood_logs WHERE user_id = $1 AND log_date >= $2 AND log_date <= $3 ORDER BY log_date",
+        )
+        .bind(user_id)
+        .bind(period_start)
+        .bind(period_end)
+        .fetch_all(pool)
+        .await?;
+        if !moods.is_empty() {
+            sections.push(mood_logs_section(&moods));
+        }
+
+        // Trades with notes first: their mistakes and lessons are what the
+        // draft should draw on when the budget is tight.
+        let trades = sqlx::query_as::<_, Trade>(
+            r#"
+            SELECT * FROM trades
+            WHERE user_id = $1 AND status = 'closed'
+              AND DATE(exit_date) >= $2 AND DATE(exit_date) <= $3
+            ORDER BY (COALESCE(mistakes, '') = '' AND COALESCE(lessons, '') = ''), exit_date
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(period_start)
+        .bind(period_end)
+        .bind(REVIEW_TRADE_LIMIT)
+        .fetch_all(pool)
+        .await?;
+        if !trades.is_empty() {
+            sections.push(trades_section("Trades, journaled ones first", 5, &trades, tz));
+        }
+
+        let patterns = PatternMiningService::findings(pool, user_id, PATTERN_LIMIT).await?;
+        if !patterns.is_empty() {
+            sections.push(patterns_section(6, &patterns));
+        }
+
+        Ok(AiContext { personality, sections })
+    }
+
+    async fn personality(pool: &PgPool, user_id: Uuid) -> AppResult<AiPersonality> {
+        let value = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT ai_personality FROM user_profiles WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+
+        Ok(AiPersonality::parse(value.as_deref()))
+    }
+}
+
+/// UTC bounds of a calendar day in the trader's timezone.
+fn day_bounds(tz: Tz, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let at_midnight = |d: NaiveDate| {
+        tz.from_local_datetime(&d.and_time(chrono::NaiveTime::MIN))
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&d.and_time(chrono::NaiveTime::MIN)))
+    };
+    (at_midnight(date), at_midnight(date + Duration::days(1)))
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_NOTE_CHARS {
+        return text;
+    }
+    let cut = text.chars().take(MAX_NOTE_CHARS).collect::<String>();
+    format!("{}...", cut.trim_end())
+}
+
+fn setup_section(setup: &str, stats: &SetupStats) -> ContextSection {
+    let mut section = ContextSection::new("Track record on this setup", 0);
+    if stats.trade_count == 0 {
+        section.push(format!("This is the trader's first closed '{}' trade.", setup));
+        return section;
+    }
+
+    let win_rate = Decimal::from(stats.win_count * 100) / Decimal::from(stats.trade_count);
+    section.push(format!(
+        "'{}': {} prior closed trades, {}% win rate, total P&L ${}, average {}",
+        setup,
+        stats.trade_count,
+        win_rate.round_dp(1),
+        stats.total_pnl.round_dp(2),
+        stats
+            .avg_r_multiple
+            .map(|r| format!("{}R", r.round_dp(2)))
+            .unwrap_or_else(|| "R not recorded".to_string()),
+    ));
+    if stats.broke_rules_count > 0 {
+        section.push(format!(
+            "Broke rules on {} of them, averaging ${} per rule-breaking trade",
+            stats.broke_rules_count,
+            stats.broke_rules_pnl.unwrap_or_default().round_dp(2),
+        ));
+    }
+    if let Some(hold) = stats.avg_hold_minutes {
+        section.push(format!("Average hold time: {} minutes", hold.round_dp(0)));
+    }
+    section
+}
+
+fn playbook_section(playbook: &PlaybookSetup) -> ContextSection {
+    let mut section = ContextSection::new("Playbook definition for this setup", 1);
+    if let Some(desc) = playbook.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        section.push(format!("Description: {}", truncate(desc)));
+    }
+    if let Some(criteria) = playbook.criteria.as_ref().filter(|c| !c.is_null()) {
+        section.push(format!("Entry criteria: {}", truncate(&criteria_text(criteria))));
+    }
+    if playbook.expected_r_min.is_some() || playbook.expected_r_max.is_some() {
+        let bound = |r: Option<Decimal>| r.map(|r| r.to_string()).unwrap_or_else(|| "?".to_string());
+        section.push(format!(
+            "Expected R: {} to {}",
+            bound(playbook.expected_r_min),
+            bound(playbook.expected_r_max)
+        ));
+    }
+    if let Some(conviction) = playbook.min_conviction {
+        section.push(format!("Minimum conviction: {}", conviction));
+    }
+    if let Some(tf) = playbook.preferred_timeframe.as_deref() {
+        section.push(format!("Preferred timeframe: {}", tf));
+    }
+    if let Some(mistakes) = playbook.common_mistakes.as_deref().filter(|m| !m.trim().is_empty()) {
+        section.push(format!("Known mistakes with this setup: {}", truncate(mistakes)));
+    }
+    section
+}
+
+/// Criteria are free-form JSON: usually a list of strings or of objects
+/// with a label.
+fn criteria_text(criteria: &serde_json::Value) -> String {
+    match criteria {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Object(map) => ["label", "name", "description", "text"]
+                    .iter()
+                    .find_map(|key| map.get(*key).and_then(|v| v.as_str()))
+                    .map(str::to_string)
+                    .unwrap_or_else(|| item.to_string()),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("; "),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn plan_section(plan: &DailyPlan, watchlist: &[WatchlistItem], symbol: &str) -> ContextSection {
+    let mut section = ContextSection::new("Pre-market plan for the day", 2);
+    if let Some(bias) = plan.market_bias.as_deref() {
+        let reasoning = plan
+            .bias_reasoning
+            .as_deref()
+            .map(|r| format!(" ({})", truncate(r)))
+            .unwrap_or_default();
+        section.push(format!("Market bias: {}{}", bias, reasoning));
+    }
+    if let Some(goals) = plan.session_goals.as_ref().filter(|g| !g.is_empty()) {
+        section.push(format!("Session goals: {}", goals.join("; ")));
+    }
+    if plan.max_trades.is_some() || plan.max_daily_loss.is_some() {
+        section.push(format!(
+            "Limits: max {} trades, max daily loss {}",
+            plan.max_trades.map(|n| n.to_string()).unwrap_or_else(|| "unset".to_string()),
+            plan.max_daily_loss.map(|l| format!("${}", l)).unwrap_or_else(|| "unset".to_string()),
+        ));
+    }
+
+    match watchlist.iter().find(|w| w.symbol.eq_ignore_ascii_case(symbol)) {
+        Some(item) => {
+            let mut line = format!("{} was on the watchlist", item.symbol);
+            if let Some(setup) = item.setup_description.as_deref() {
+                line.push_str(&format!(" for: {}", truncate(setup)));
+            }
+            if let Some(levels) = item.key_levels.as_ref().filter(|l| !l.is_null()) {
+                line.push_str(&format!("; key levels {}", levels));
+            }
+            section.push(line);
+        }
+        None if !watchlist.is_empty() => section.push(format!(
+            "{} was NOT on the watchlist ({})",
+            symbol,
+            watchlist.iter().map(|w| w.symbol.as_str()).collect::<Vec<_>>().join(", ")
+        )),
+        None => {}
+    }
+    if let Some(notes) = plan.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        section.push(format!("Notes: {}", truncate(notes)));
+    }
+    section
+}
+
+/// No plan that day: the profile's standing limits still apply.
+fn standing_limits_section(limits: &DailyLimits) -> ContextSection {
+    let mut section = ContextSection::new("Standing daily limits (no plan that day)", 2);
+    section.push(format!(
+        "Limits: max {} trades, max daily loss {}",
+        limits.max_trades.map(|n| n.to_string()).unwrap_or_else(|| "unset".to_string()),
+        limits.max_daily_loss.map(|l| format!("${}", l)).unwrap_or_else(|| "unset".to_string()),
+    ));
+    section
+}
+
+fn mood_section(mood: &MoodLog) -> ContextSection {
+    let mut section = ContextSection::new("Mood log for the day (1-10 scales)", 3);
+    let scores = [
+        ("pre-market mood", mood.pre_market_mood),
+        ("post-market mood", mood.post_market_mood),
+        ("stress", mood.stress_level),
+        ("confidence", mood.confidence_level),
+        ("sleep quality", mood.sleep_quality),
+    ]
+    .iter()
+    .filter_map(|(name, score)| score.map(|s| format!("{} {}", name, s)))
+    .collect::<Vec<_>>();
+    if !scores.is_empty() {
+        section.push(scores.join(", "));
+    }
+    if let Some(emotions) = mood.emotions.as_ref().filter(|e| !e.is_empty()) {
+        section.push(format!("Emotions: {}", emotions.join(", ")));
+    }
+    if let Some(notes) = mood.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        section.push(format!("Notes: {}", truncate(notes)));
+    }
+    section
+}
+
+fn tilt_section(title: &'static str, priority: u8, events: &[TiltEvent], tz: Tz) -> ContextSection {
+    let mut section = ContextSection::new(title, priority);
+    for event in events {
+        section.push(format!(
+            "{} {} ({}): {}",
+            event.created_at.with_timezone(&tz).format("%Y-%m-%d %H:%M"),
+            event.trigger_type,
+            event.severity,
+            truncate(&event.message),
+        ));
+    }
+    section
+}
+
+fn trades_section(title: &'static str, priority: u8, trades: &[Trade], tz: Tz) -> ContextSection {
+    let mut section = ContextSection::new(title, priority);
+    for trade in trades {
+        let mut line = format!(
+            "{} {} {} {}: P&L {}, {}",
+            trade.entry_date.with_timezone(&tz).format("%Y-%m-%d"),
+            trade.symbol,
+            format!("{:?}", trade.direction).to_lowercase(),
+            trade.setup_name.as_deref().unwrap_or("no setup"),
+            trade.net_pnl.map(|p| format!("${}", p.round_dp(2))).unwrap_or_else(|| "N/A".to_string()),
+            trade.r_multiple.map(|r| format!("{}R", r.round_dp(2))).unwrap_or_else(|| "R N/A".to_string()),
+        );
+        if trade.broke_rules {
+            line.push_str(", broke rules");
+        }
+        if trade.is_revenge_trade {
+            line.push_str(", revenge trade");
+        }
+        if let Some(mistakes) = trade.mistakes.as_deref().filter(|m| !m.trim().is_empty()) {
+            line.push_str(&format!("; mistakes: {}", truncate(mistakes)));
+        }
+        if let Some(lessons) = trade.lessons.as_deref().filter(|l| !l.trim().is_empty()) {
+            line.push_str(&format!("; lessons: {}", truncate(lessons)));
+        }
+        section.push(line);
+    }
+    section
+}
+
+fn period_stats_section(stats: &PeriodStats) -> ContextSection {
+    let mut section = ContextSection::new("Period results", 0);
+    let win_rate = Decimal::from(stats.win_count * 100) / Decimal::from(stats.trade_count.max(1));
+    section.push(format!(
+        "{} closed trades, {}% win rate, net P&L ${}, average {}",
+        stats.trade_count,
+        win_rate.round_dp(1),
+        stats.total_pnl.round_dp(2),
+        stats
+            .avg_r_multiple
+            .map(|r| format!("{}R", r.round_dp(2)))
+            .unwrap_or_else(|| "R not recorded".to_string()),
+    ));
+    section.push(format!(
+        "Followed plan on {}, broke rules on {}, revenge trades: {}",
+        stats.followed_plan_count, stats.broke_rules_count, stats.revenge_count,
+    ));
+    section
+}
+
+fn previous_review_section(previous: &PreviousReview) -> ContextSection {
+    let mut section = ContextSection::new("Previous review", 1);
+    section.push(format!(
+        "Covered {} to {}",
+        previous.period_start, previous.period_end
+    ));
+    match previous.goals_next_period.as_ref().filter(|g| !g.is_empty()) {
+        Some(goals) => section.push(format!("Goals set for this period: {}", goals.join("; "))),
+        None => section.push("No goals were set for this period"),
+    }
+    if let Some(improve) = previous.what_to_improve.as_deref().filter(|t| !t.trim().is_empty()) {
+        section.push(format!("Planned to improve: {}", truncate(improve)));
+    }
+    section
+}
+
+fn mood_logs_section(moods: &[MoodLog]) -> ContextSection {
+    let mut section = ContextSection::new("Mood logs (1-10 scales)", 4);
+    for mood in moods {
+        let mut parts = [
+            ("pre", mood.pre_market_mood),
+            ("post", mood.post_market_mood),
+            ("stress", mood.stress_level),
+            ("confidence", mood.confidence_level),
+            ("sleep", mood.sleep_quality),
+        ]
+        .iter()
+        .filter_map(|(name, score)| score.map(|s| format!("{} {}", name, s)))
+        .collect::<Vec<_>>();
+        if let Some(emotions) = mood.emotions.as_ref().filter(|e| !e.is_empty()) {
+            parts.push(format!("felt {}", emotions.join(", ")));
+        }
+        if let Some(notes) = mood.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+            parts.push(truncate(notes));
+        }
+        if !parts.is_empty() {
+            section.push(format!("{}: {}", mood.log_date, parts.join("; ")));
+        }
+    }
+    section
+}
+
+fn watchlist_section(items: &[WatchlistItem]) -> ContextSection {
+    let mut section = ContextSection::new("Watchlist", 0);
+    for item in items {
+        let mut line = item.symbol.clone();
+        if let Some(setup) = item.setup_description.as_deref().filter(|s| !s.trim().is_empty()) {
+            line.push_str(&format!(": {}", truncate(setup)));
+        }
+        if let Some(levels) = item.key_levels.as_ref().filter(|l| !l.is_null()) {
+            line.push_str(&format!("; key levels {}", levels));
+        }
+        if let Some(catalysts) = item.catalysts.as_deref().filter(|c| !c.trim().is_empty()) {
+            line.push_str(&format!("; catalysts: {}", truncate(catalysts)));
+        }
+        if let Some(rr) = item.risk_reward_ratio {
+            line.push_str(&format!("; planned R:R {}", rr));
+        }
+        section.push(line);
+    }
+    section
+}
+
+fn events_section(events: &[EconomicEvent], tz: Tz) -> ContextSection {
+    let mut section = ContextSection::new("Economic events today", 1);
+    for event in events {
+        let time = event
+            .event_at
+            .map(|at| at.with_timezone(&tz).format("%H:%M").to_string())
+            .unwrap_or_else(|| "all day".to_string());
+        let mut line = format!("{} {}", time, event.title);
+        let tags = [event.currency.as_deref(), event.impact.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if !tags.is_empty() {
+            line.push_str(&format!(" ({})", tags.join(", ")));
+        }
+        section.push(line);
+    }
+    section
+}
+
+fn setup_summary_section(title: &'static str, priority: u8, setups: &[SetupSummary]) -> ContextSection {
+    let mut section = ContextSection::new(title, priority);
+    for setup in setups {
+        let win_rate = Decimal::from(setup.win_count * 100) / Decimal::from(setup.trade_count.max(1));
+        section.push(format!(
+            "{}: {} trades, {}% win rate, P&L ${}, average {}",
+            setup.setup_name,
+            setup.trade_count,
+            win_rate.round_dp(1),
+            setup.total_pnl.round_dp(2),
+            setup
+                .avg_r_multiple
+                .map(|r| format!("{}R", r.round_dp(2)))
+                .unwrap_or_else(|| "R not recorded".to_string()),
+        ));
+    }
+    section
+}
+
+fn patterns_section(priority: u8, patterns: &[TradePatternFinding]) -> ContextSection {
+    let mut section = ContextSection::new("Recurring losing patterns in their history", priority);
+    for pattern in patterns {
+        section.push(format!("{}: {}", pattern.title, pattern.description));
+    }
+    section
+}
+
+fn goals_section(goals: &[ActiveGoal]) -> ContextSection {
+    let mut section = ContextSection::new("Active goals", 3);
+    for goal in goals {
+        let mut line = goal.title.clone();
+        if let Some(target) = goal.target_value {
+            line.push_str(&format!(
+                " (at {} of {})",
+                goal.current_value.unwrap_or_default(),
+                target
+            ));
+        }
+        if let Some(date) = goal.target_date {
+            line.push_str(&format!(", due {}", date));
+        }
+        if let Some(desc) = goal.description.as_deref().filter(|d| !d.trim().is_empty()) {
+            line.push_str(&format!(": {}", truncate(desc)));
+        }
+        section.push(line);
+    }
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(title: &'static str, priority: u8, lines: &[&str]) -> ContextSection {
+        ContextSection {
+            title,
+            priority,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_personality_parse_defaults_to_balanced() {
+        assert_eq!(AiPersonality::parse(Some("strict_coach")), AiPersonality::StrictCoach);
+        assert_eq!(
+            AiPersonality::parse(Some("encouraging_mentor")),
+            AiPersonality::EncouragingMentor
+        );
+        assert_eq!(AiPersonality::parse(Some("sarcastic")), AiPersonality::Balanced);
+        assert_eq!(AiPersonality::parse(None), AiPersonality::Balanced);
+        assert_ne!(
+            AiPersonality::StrictCoach.instruction(),
+            AiPersonality::EncouragingMentor.instruction()
+        );
+    }
+
+    #[test]
+    fn test_render_orders_by_priority_and_skips_empty() {
+        let context = AiContext {
+            personality: AiPersonality::Balanced,
+            sections: vec![
+                section("Similar", 5, &["AAPL lost 1R"]),
+                section("Empty", 0, &[]),
+                section("Setup", 0, &["Bull Flag: 12 trades"]),
+            ],
+        };
+
+        let rendered = context.render(TRADE_CONTEXT_TOKEN_BUDGET);
+        assert!(!rendered.contains("Empty"));
+        let setup = rendered.find("Setup:").unwrap();
+        let similar = rendered.find("Similar:").unwrap();
+        assert!(setup < similar);
+        assert!(rendered.contains("- AAPL lost 1R"));
+    }
+
+    #[test]
+    fn test_render_trims_low_priority_lines_to_budget() {
+        let long = "x".repeat(200);
+        let context = AiContext {
+            personality: AiPersonality::Balanced,
+            sections: vec![
+                section("Setup", 0, &["Bull Flag: 12 trades, 58% win rate"]),
+                section("Similar", 5, &[&long, &long, &long]),
+            ],
+        };
+
+        let rendered = context.render(80);
+        assert!(estimate_tokens(&rendered) <= 80);
+        assert!(rendered.contains("Bull Flag"));
+        assert_eq!(rendered.matches(&long).count(), 1);
+
+        // Too small for even the top section's first line.
+        assert!(context.render(5).is_empty());
+    }
+
+    #[test]
+    fn test_criteria_text_handles_common_shapes() {
+        let criteria = serde_json::json!(["Above VWAP", {"label": "Volume > 2x"}, 3]);
+        assert_eq!(criteria_text(&criteria), "Above VWAP; Volume > 2x; 3");
+        assert_eq!(criteria_text(&serde_json::json!("Tight flag")), "Tight flag");
+    }
+
+    #[test]
+    fn test_day_bounds_follow_trader_timezone() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let (start, end) = day_bounds(tz, NaiveDate::from_ymd_opt(2026, 3, 9).unwrap());
+        assert_eq!(start.to_rfc3339(), "2026-03-09T04:00:00+00:00");
+        assert_eq!(end - start, Duration::hours(24));
+    }
+}
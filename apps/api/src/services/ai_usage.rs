@@ -0,0 +1,388 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::AiBudgetStatus;
+use crate::services::LlmUsage;
+use chrono::{Datelike, Duration, Utc};
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// USD per million tokens. Model names are matched by longest prefix, so
+/// `claude-sonnet-4` covers dated snapshots like `claude-sonnet-4-20250514`.
+const DEFAULT_PRICES: &[(&str, &str, &str)] = &[
+    ("claude-opus-4", "15", "75"),
+    ("claude-sonnet-4", "3", "15"),
+    ("claude-3-7-sonnet", "3", "15"),
+    ("claude-3-5-sonnet", "3", "15"),
+    ("claude-3-5-haiku", "0.80", "4"),
+    ("claude-haiku-4", "1", "5"),
+    ("gpt-4o-mini", "0.15", "0.60"),
+    ("gpt-4o", "2.50", "10"),
+    ("gpt-4.1-mini", "0.40", "1.60"),
+    ("gpt-4.1", "2", "8"),
+    ("fake", "0", "0"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelPrice {
+    pub input_per_mtok: Decimal,
+    pub output_per_mtok: Decimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct PriceTable {
+    /// Longest prefix first.
+    entries: Vec<(String, ModelPrice)>,
+}
+
+impl PriceTable {
+    /// Built-in prices with `overrides` applied. The override format is
+    /// `model=input/output` pairs separated by commas, e.g.
+    /// `llama-3.1=0/0,gpt-4o=2.5/10`. A `*` entry prices unknown models.
+    pub fn parse(overrides: Option<&str>) -> Result<Self, String> {
+        let mut entries = DEFAULT_PRICES
+            .iter()
+            .map(|(model, input, output)| {
+                (
+                    model.to_string(),
+                    ModelPrice {
+                        input_per_mtok: Decimal::from_str(input).unwrap_or_default(),
+                        output_per_mtok: Decimal::from_str(output).unwrap_or_default(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for entry in overrides.unwrap_or_default().split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (model, prices) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid price entry '{}': expected model=input/output", entry))?;
+            let (input, output) = prices
+                .split_once('/')
+                .ok_or_else(|| format!("Invalid price entry '{}': expected model=input/output", entry))?;
+            let parse = |v: &str| {
+                Decimal::from_str(v.trim())
+                    .ok()
+                    .filter(|d| !d.is_sign_negative())
+                    .ok_or_else(|| format!("Invalid price '{}' for model '{}'", v.trim(), model.trim()))
+            };
+            let price = ModelPrice {
+                input_per_mtok: parse(input)?,
+                output_per_mtok: parse(output)?,
+            };
+
+            let model = model.trim().to_string();
+            entries.retain(|(m, _)| *m != model);
+            entries.push((model, price));
+        }
+
+        entries.sort_by_key(|(model, _)| std::cmp::Reverse(model.len()));
+        Ok(Self { entries })
+    }
+
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        self.entries
+            .iter()
+            .find(|(prefix, _)| prefix != "*" && model.starts_with(prefix.as_str()))
+            .or_else(|| self.entries.iter().find(|(prefix, _)| prefix == "*"))
+            .map(|(_, price)| *price)
+    }
+
+    /// Cost of one call, rounded to a millionth of a dollar. Unknown models
+    /// cost nothing (and are logged) rather than failing the request.
+    pub fn cost(&self, usage: &LlmUsage) -> Decimal {
+        let Some(price) = self.price_for(&usage.model) else {
+            tracing::warn!(model = %usage.model, "No price configured for AI model; recording zero cost");
+            return Decimal::ZERO;
+        };
+        let million = Decimal::from(1_000_000);
+        ((Decimal::from(usage.input_tokens) * price.input_per_mtok
+            + Decimal::from(usage.output_tokens) * price.output_per_mtok)
+            / million)
+            .round_dp(6)
+    }
+}
+
+/// Meters AI calls and enforces per-user daily and monthly spend limits.
+/// Days and months are UTC. A server budget of zero disables that limit.
+pub struct AiUsageService {
+    prices: PriceTable,
+    daily_budget: Decimal,
+    monthly_budget: Decimal,
+}
+
+impl AiUsageService {
+    pub fn new(config: &Config) -> Self {
+        let prices = PriceTable::parse(config.ai_price_table.as_deref()).unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Invalid AI_PRICE_TABLE; using built-in prices");
+            PriceTable::parse(None).unwrap_or(PriceTable { entries: Vec::new() })
+        });
+        Self {
+            prices,
+            daily_budget: config.ai_daily_budget_usd,
+            monthly_budget: config.ai_monthly_budget_usd,
+        }
+    }
+
+    pub fn server_budgets(&self) -> (Decimal, Decimal) {
+        (self.daily_budget, self.monthly_budget)
+    }
+
+    /// Applies a user's override to a server limit. Overrides can only
+    /// tighten a limit; zero means unlimited on the server side only.
+    pub fn effective_budget(server: Decimal, user_override: Option<Decimal>) -> Decimal {
+        match user_override {
+            Some(limit) if server.is_zero() || limit < server => limit,
+            _ => server,
+        }
+    }
+
+    pub fn validate_override(name: &str, server: Decimal, value: Option<Decimal>) -> Result<(), String> {
+        match value {
+            Some(v) if v <= Decimal::ZERO => Err(format!("{} must be greater than 0", name)),
+            Some(v) if !server.is_zero() && v > server => {
+                Err(format!("{} cannot exceed the server limit of ${}", name, server))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn status(&self, pool: &PgPool, user_id: Uuid) -> AppResult<AiBudgetStatus> {
+        let overrides = sqlx::query_as::<_, (Option<Decimal>, Option<Decimal>)>(
+            "SELECT ai_daily_budget_usd, ai_monthly_budget_usd FROM user_profiles WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or((None, None));
+
+        let (spent_today, spent_month) = sqlx::query_as::<_, (Decimal, Decimal)>(
+            r#"
+            SELECT
+                COALESCE(SUM(cost_usd) FILTER (WHERE created_at >= date_trunc('day', NOW() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'), 0),
+                COALESCE(SUM(cost_usd), 0)
+            FROM ai_usage_events
+            WHERE user_id = $1
+              AND created_at >= date_trunc('month', NOW() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(AiBudgetStatus {
+            daily_budget_usd: Self::effective_budget(self.daily_budget, overrides.0),
+            monthly_budget_usd: Self::effective_budget(self.monthly_budget, overrides.1),
+            spent_today_usd: spent_today,
+            spent_this_month_usd: spent_month,
+        })
+    }
+
+    /// Rejects the request with `RateLimited` once either budget is spent.
+    /// Checked before each call, so one in-flight request may overshoot.
+    pub async fn ensure_within_budget(&self, pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+        let status = self.status(pool, user_id).await?;
+        Self::check_budget(&status)
+    }
+
+    pub fn check_budget(status: &AiBudgetStatus) -> AppResult<()> {
+        let now = Utc::now().date_naive();
+        if !status.daily_budget_usd.is_zero() && status.spent_today_usd >= status.daily_budget_usd {
+            return Err(AppError::RateLimited(format!(
+                "Daily AI budget of ${} reached. It resets at 00:00 UTC ({}).",
+                status.daily_budget_usd,
+                now + Duration::days(1)
+            )));
+        }
+        if !status.monthly_budget_usd.is_zero() && status.spent_this_month_usd >= status.monthly_budget_usd {
+            let next_month = now
+                .with_day(1)
+                .and_then(|d| d.checked_add_months(chrono::Months::new(1)))
+                .unwrap_or(now);
+            return Err(AppError::RateLimited(format!(
+                "Monthly AI budget of ${} reached. It resets on {}.",
+                status.monthly_budget_usd, next_month
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn cost(&self, usage: &[LlmUsage]) -> Decimal {
+        usage.iter().map(|u| self.prices.cost(u)).sum()
+    }
+
+    /// Writes one ledger row per call and returns their total cost.
+    pub async fn record<'e, E>(
+        &self,
+        executor: E,
+        user_id: Uuid,
+        review_id: Option<Uuid>,
+        feature: &str,
+        provider: &str,
+        usage: &[LlmUsage],
+    ) -> AppResult<Decimal>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        if usage.is_empty() {
+            return Ok(Decimal::ZERO);
+        }
+
+        let costs = usage.iter().map(|u| self.prices.cost(u)).collect::<Vec<_>>();
+        sqlx::query(
+            r#"
+            INSERT INTO ai_usage_events (
+                user_id, review_id, feature, provider, model, input_tokens, output_tokens, cost_usd
+            )
+            SELECT $1, $2, $3, $4, * FROM UNNEST($5::text[], $6::int[], $7::int[], $8::numeric[])
+            "#,
+        )
+        .bind(user_id)
+        .bind(review_id)
+        .bind(feature)
+        .bind(provider)
+        .bind(usage.iter().map(|u| u.model.clone()).collect::<Vec<_>>())
+        .bind(usage.iter().map(|u| u.input_tokens).collect::<Vec<_>>())
+        .bind(usage.iter().map(|u| u.output_tokens).collect::<Vec<_>>())
+        .bind(&costs)
+        .execute(executor)
+        .await?;
+
+        Ok(costs.iter().copied().sum())
+    }
+
+    /// Passes `result` through, first recording `usage` if it failed: calls
+    /// whose output was rejected or abandoned were still paid for. A ledger
+    /// write failure is logged rather than replacing the original error.
+    pub async fn record_on_error<T>(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        feature: &str,
+        provider: &str,
+        usage: &[LlmUsage],
+        result: AppResult<T>,
+    ) -> AppResult<T> {
+        if result.is_err() {
+            self.record_discarded(pool, user_id, None, feature, provider, usage).await;
+        }
+        result
+    }
+
+    /// Records calls that produced nothing to save, logging instead of
+    /// failing.
+    pub async fn record_discarded(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        review_id: Option<Uuid>,
+        feature: &str,
+        provider: &str,
+        usage: &[LlmUsage],
+    ) {
+        if let Err(e) = self.record(pool, user_id, review_id, feature, provider, usage).await {
+            tracing::error!(error = %e, feature, "Failed to record AI usage");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(model: &str, input: i32, output: i32) -> LlmUsage {
+        LlmUsage {
+            model: model.to_string(),
+            input_tokens: input,
+            output_tokens: output,
+        }
+    }
+
+    fn status(daily: i64, monthly: i64, today: i64, month: i64) -> AiBudgetStatus {
+        AiBudgetStatus {
+            daily_budget_usd: Decimal::from(daily),
+            monthly_budget_usd: Decimal::from(monthly),
+            spent_today_usd: Decimal::from(today),
+            spent_this_month_usd: Decimal::from(month),
+        }
+    }
+
+    #[test]
+    fn test_price_table_matches_longest_prefix() {
+        let table = PriceTable::parse(None).unwrap();
+        // 1,200 input at $3/M + 300 output at $15/M
+        assert_eq!(
+            table.cost(&usage("claude-sonnet-4-20250514", 1200, 300)),
+            Decimal::from_str("0.0081").unwrap()
+        );
+        // gpt-4o-mini must not be priced as gpt-4o
+        assert_eq!(
+            table.price_for("gpt-4o-mini-2024-07-18").unwrap().input_per_mtok,
+            Decimal::from_str("0.15").unwrap()
+        );
+        assert_eq!(table.cost(&usage("unknown-model", 1000, 1000)), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_price_table_overrides() {
+        let table = PriceTable::parse(Some("gpt-4o=5/20, *=1/2")).unwrap();
+        assert_eq!(table.price_for("gpt-4o").unwrap().output_per_mtok, Decimal::from(20));
+        assert_eq!(table.price_for("llama-3.1-8b").unwrap().input_per_mtok, Decimal::ONE);
+        assert_eq!(table.cost(&usage("llama-3.1-8b", 500_000, 250_000)), Decimal::ONE);
+
+        assert!(PriceTable::parse(Some("gpt-4o=5")).is_err());
+        assert!(PriceTable::parse(Some("gpt-4o=-1/2")).is_err());
+    }
+
+    #[test]
+    fn test_budget_checks() {
+        assert!(AiUsageService::check_budget(&status(2, 20, 1, 10)).is_ok());
+        assert!(matches!(
+            AiUsageService::check_budget(&status(2, 20, 2, 10)),
+            Err(AppError::RateLimited(msg)) if msg.contains("Daily")
+        ));
+        assert!(matches!(
+            AiUsageService::check_budget(&status(2, 20, 0, 25)),
+            Err(AppError::RateLimited(msg)) if msg.contains("Monthly")
+        ));
+        // Zero disables the limit
+        assert!(AiUsageService::check_budget(&status(0, 0, 50, 500)).is_ok());
+
+        let server = Decimal::from(5);
+        assert_eq!(AiUsageService::effective_budget(server, Some(Decimal::from(2))), Decimal::from(2));
+        assert_eq!(AiUsageService::effective_budget(server, Some(Decimal::from(9))), server);
+        assert_eq!(AiUsageService::effective_budget(Decimal::ZERO, Some(Decimal::ONE)), Decimal::ONE);
+    }
+
+    #[tokio::test]
+    async fn test_record_on_error_meters_only_failed_calls() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let user_id = db.insert_user("usage@example.com").await;
+        let service = AiUsageService::new(&Config::for_tests());
+        let calls = [usage("fake-deterministic", 100, 20), usage("fake-deterministic", 120, 20)];
+
+        let failed: AppResult<()> = Err(AppError::AiError("AI returned an invalid review.".to_string()));
+        let result = service
+            .record_on_error(&db.pool, user_id, "trade_review", "fake", &calls, failed)
+            .await;
+        assert!(matches!(result, Err(AppError::AiError(_))));
+
+        // Successful results are recorded by the caller alongside what they save
+        service
+            .record_on_error(&db.pool, user_id, "trade_review", "fake", &calls, Ok(()))
+            .await
+            .unwrap();
+
+        let tokens: Vec<(i32, i32)> = sqlx::query_as(
+            "SELECT input_tokens, output_tokens FROM ai_usage_events WHERE user_id = $1 ORDER BY input_tokens",
+        )
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        db.drop().await;
+
+        assert_eq!(tokens, vec![(100, 20), (120, 20)]);
+    }
+}
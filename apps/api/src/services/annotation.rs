@@ -0,0 +1,633 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AnnotationShape, ChartAnnotation, CreateAnnotationRequest, UpdateAnnotationRequest,
+};
+use chrono::Utc;
+use image::{Rgba, RgbaImage};
+use std::io::Cursor;
+use uuid::Uuid;
+
+pub const MAX_ANNOTATIONS_PER_MEDIA: usize = 200;
+const MAX_TEXT_LEN: usize = 500;
+const MAX_NOTE_LEN: usize = 2000;
+const DEFAULT_COLOR: [u8; 4] = [0xF5, 0x9E, 0x0B, 0xFF];
+const DEFAULT_STROKE_WIDTH: f64 = 0.003;
+const MIN_STROKE_WIDTH: f64 = 0.0005;
+const MAX_STROKE_WIDTH: f64 = 0.05;
+const DEFAULT_FONT_SIZE: f64 = 0.03;
+const MIN_FONT_SIZE: f64 = 0.005;
+const MAX_FONT_SIZE: f64 = 0.25;
+const TEXT_BACKGROUND: [u8; 4] = [0, 0, 0, 140];
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// 5x7 bitmap font for annotation labels. Lowercase letters render as
+/// uppercase and anything else missing falls back to `?`.
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('"', [0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('#', [0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010]),
+    ('$', [0b00100, 0b01111, 0b10100, 0b01110, 0b00101, 0b11110, 0b00100]),
+    ('%', [0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011]),
+    ('&', [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101]),
+    ('\'', [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('(', [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010]),
+    (')', [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000]),
+    ('*', [0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    ('/', [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    (';', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('<', [0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010]),
+    ('=', [0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000]),
+    ('>', [0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    ('@', [0b01110, 0b10001, 0b00001, 0b01101, 0b10101, 0b10101, 0b01110]),
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('[', [0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110]),
+    (']', [0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110]),
+    ('_', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111]),
+];
+
+fn check_unit(name: &str, value: f64) -> Result<(), String> {
+    if !value.is_finite() || !(0.0..=1.0).contains(&value) {
+        return Err(format!("{} must be between 0 and 1", name));
+    }
+    Ok(())
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA`.
+pub fn parse_color(color: &str) -> Result<[u8; 4], String> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|h| (h.len() == 6 || h.len() == 8) && h.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("Invalid color '{}'. Use #RRGGBB or #RRGGBBAA", color))?;
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    let alpha = if hex.len() == 8 { channel(6) } else { 0xFF };
+    Ok([channel(0), channel(2), channel(4), alpha])
+}
+
+pub fn validate_shape(shape: &AnnotationShape) -> Result<(), String> {
+    match shape {
+        AnnotationShape::Line { x1, y1, x2, y2 } | AnnotationShape::Arrow { x1, y1, x2, y2 } => {
+            check_unit("x1", *x1)?;
+            check_unit("y1", *y1)?;
+            check_unit("x2", *x2)?;
+            check_unit("y2", *y2)?;
+            if x1 == x2 && y1 == y2 {
+                return Err("Start and end points must differ".to_string());
+            }
+        }
+        AnnotationShape::Rectangle { x, y, width, height, fill } => {
+            check_unit("x", *x)?;
+            check_unit("y", *y)?;
+            check_unit("width", *width)?;
+            check_unit("height", *height)?;
+            if *width <= 0.0 || *height <= 0.0 {
+                return Err("Rectangle width and height must be positive".to_string());
+            }
+            if x + width > 1.0 + f64::EPSILON || y + height > 1.0 + f64::EPSILON {
+                return Err("Rectangle must fit inside the image".to_string());
+            }
+            if let Some(fill) = fill {
+                parse_color(fill)?;
+            }
+        }
+        AnnotationShape::Text { x, y, text, font_size } => {
+            check_unit("x", *x)?;
+            check_unit("y", *y)?;
+            if text.trim().is_empty() || text.len() > MAX_TEXT_LEN {
+                return Err(format!("Text must be between 1 and {} characters", MAX_TEXT_LEN));
+            }
+            if let Some(size) = font_size {
+                if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(size) {
+                    return Err(format!(
+                        "font_size must be between {} and {}",
+                        MIN_FONT_SIZE, MAX_FONT_SIZE
+                    ));
+                }
+            }
+        }
+        AnnotationShape::PriceLevel { y, label, .. } => {
+            check_unit("y", *y)?;
+            if label.as_ref().is_some_and(|l| l.len() > 100) {
+                return Err("Label must be 100 characters or fewer".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_style(
+    color: &Option<String>,
+    stroke_width: Option<f64>,
+    note: &Option<String>,
+) -> Result<(), String> {
+    if let Some(color) = color {
+        parse_color(color)?;
+    }
+    if let Some(width) = stroke_width {
+        if !(MIN_STROKE_WIDTH..=MAX_STROKE_WIDTH).contains(&width) {
+            return Err(format!(
+                "stroke_width must be between {} and {}",
+                MIN_STROKE_WIDTH, MAX_STROKE_WIDTH
+            ));
+        }
+    }
+    if note.as_ref().is_some_and(|n| n.len() > MAX_NOTE_LEN) {
+        return Err(format!("Note must be {} characters or fewer", MAX_NOTE_LEN));
+    }
+    Ok(())
+}
+
+pub struct AnnotationService;
+
+impl AnnotationService {
+    /// Reads the stored annotation list. A NULL column is an empty list.
+    pub fn parse_stored(value: Option<serde_json::Value>) -> AppResult<Vec<ChartAnnotation>> {
+        match value {
+            None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+            Some(value) => serde_json::from_value(value).map_err(|e| {
+                tracing::warn!("Unreadable trade_media.annotations: {}", e);
+                AppError::Conflict(
+                    "Existing annotations are in an unsupported format and can't be edited".to_string(),
+                )
+            }),
+        }
+    }
+
+    pub fn create(
+        annotations: &mut Vec<ChartAnnotation>,
+        req: CreateAnnotationRequest,
+        user_id: Uuid,
+    ) -> AppResult<ChartAnnotation> {
+        validate_shape(&req.shape).map_err(AppError::Validation)?;
+        validate_style(&req.color, req.stroke_width, &req.note).map_err(AppError::Validation)?;
+        if annotations.len() >= MAX_ANNOTATIONS_PER_MEDIA {
+            return Err(AppError::Validation(format!(
+                "A screenshot can have at most {} annotations",
+                MAX_ANNOTATIONS_PER_MEDIA
+            )));
+        }
+
+        let now = Utc::now();
+        let annotation = ChartAnnotation {
+            id: Uuid::new_v4(),
+            version: 1,
+            shape: req.shape,
+            color: req.color,
+            stroke_width: req.stroke_width,
+            note: req.note,
+            created_by: user_id,
+            created_at: now,
+            updated_at: now,
+        };
+        annotations.push(annotation.clone());
+        Ok(annotation)
+    }
+
+    fn find_versioned(
+        annotations: &mut [ChartAnnotation],
+        annotation_id: Uuid,
+        version: i32,
+    ) -> AppResult<&mut ChartAnnotation> {
+        let annotation = annotations
+            .iter_mut()
+            .find(|a| a.id == annotation_id)
+            .ok_or_else(|| AppError::NotFound("Annotation not found".to_string()))?;
+
+        if annotation.version != version {
+            return Err(AppError::Conflict(format!(
+                "Annotation was modified by another request (current version {})",
+                annotation.version
+            )));
+        }
+        Ok(annotation)
+    }
+
+    pub fn update(
+        annotations: &mut [ChartAnnotation],
+        annotation_id: Uuid,
+        req: UpdateAnnotationRequest,
+    ) -> AppResult<ChartAnnotation> {
+        if let Some(ref shape) = req.shape {
+            validate_shape(shape).map_err(AppError::Validation)?;
+        }
+        validate_style(&req.color, req.stroke_width, &req.note).map_err(AppError::Validation)?;
+
+        let annotation = Self::find_versioned(annotations, annotation_id, req.version)?;
+        if let Some(shape) = req.shape {
+            annotation.shape = shape;
+        }
+        if req.color.is_some() {
+            annotation.color = req.color;
+        }
+        if req.stroke_width.is_some() {
+            annotation.stroke_width = req.stroke_width;
+        }
+        if req.note.is_some() {
+            annotation.note = req.note;
+        }
+        annotation.version += 1;
+        annotation.updated_at = Utc::now();
+        Ok(annotation.clone())
+    }
+
+    pub fn remove(annotations: &mut Vec<ChartAnnotation>, annotation_id: Uuid, version: i32) -> AppResult<()> {
+        Self::find_versioned(annotations, annotation_id, version)?;
+        annotations.retain(|a| a.id != annotation_id);
+        Ok(())
+    }
+
+    /// Draws the annotations onto the image and returns a flattened PNG.
+    /// CPU-bound; call from `spawn_blocking`.
+    pub fn render(image_bytes: &[u8], annotations: &[ChartAnnotation]) -> Result<Vec<u8>, String> {
+        let img = image::load_from_memory(image_bytes)
+            .map_err(|e| format!("Could not decode image: {}", e))?
+            .to_rgba8();
+        let mut canvas = Canvas { img };
+
+        for annotation in annotations {
+            canvas.draw(annotation);
+        }
+
+        let mut out = Cursor::new(Vec::new());
+        canvas
+            .img
+            .write_to(&mut out, image::ImageFormat::Png)
+            .map_err(|e| format!("Could not encode PNG: {}", e))?;
+        Ok(out.into_inner())
+    }
+}
+
+fn glyph(c: char) -> &'static [u8; 7] {
+    let upper = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(g, _)| *g == upper)
+        .or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+        .map(|(_, rows)| rows)
+        .unwrap_or(&[0; 7])
+}
+
+struct Canvas {
+    img: RgbaImage,
+}
+
+impl Canvas {
+    fn width(&self) -> f64 {
+        self.img.width() as f64
+    }
+
+    fn height(&self) -> f64 {
+        self.img.height() as f64
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: [u8; 4], coverage: f64) {
+        if x < 0 || y < 0 || x >= self.img.width() as i64 || y >= self.img.height() as i64 {
+            return;
+        }
+        let alpha = (color[3] as f64 / 255.0) * coverage.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let Rgba(px) = self.img.get_pixel_mut(x as u32, y as u32);
+        for c in 0..3 {
+            px[c] = (color[c] as f64 * alpha + px[c] as f64 * (1.0 - alpha)).round() as u8;
+        }
+        px[3] = (255.0 * alpha + px[3] as f64 * (1.0 - alpha)).round() as u8;
+    }
+
+    /// Anti-aliased thick segment between two pixel-space points.
+    fn segment(&mut self, from: (f64, f64), to: (f64, f64), width: f64, color: [u8; 4]) {
+        let half = width / 2.0;
+        let (min_x, max_x) = (from.0.min(to.0) - half - 1.0, from.0.max(to.0) + half + 1.0);
+        let (min_y, max_y) = (from.1.min(to.1) - half - 1.0, from.1.max(to.1) + half + 1.0);
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let len_sq = dx * dx + dy * dy;
+
+        for y in (min_y.floor().max(0.0) as i64)..=(max_y.ceil().min(self.height() - 1.0) as i64) {
+            for x in (min_x.floor().max(0.0) as i64)..=(max_x.ceil().min(self.width() - 1.0) as i64) {
+                let (px, py) = (x as f64 + 0.5, y as f64 + 0.5);
+                let t = if len_sq > 0.0 {
+                    (((px - from.0) * dx + (py - from.1) * dy) / len_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (cx, cy) = (from.0 + t * dx, from.1 + t * dy);
+                let distance = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+                self.blend(x, y, color, half + 0.5 - distance);
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, color: [u8; 4]) {
+        for y in (y0.floor().max(0.0) as i64)..(y1.ceil().min(self.height()) as i64) {
+            for x in (x0.floor().max(0.0) as i64)..(x1.ceil().min(self.width()) as i64) {
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    /// Draws text with its top-left corner at (x, y) on a translucent
+    /// backdrop. Returns the rendered height in pixels.
+    fn text(&mut self, x: f64, y: f64, text: &str, font_size: f64, color: [u8; 4]) -> f64 {
+        let scale = ((font_size * self.height()) / GLYPH_HEIGHT as f64).round().max(1.0) as i64;
+        let advance = (GLYPH_WIDTH as i64 + 1) * scale;
+        let line_height = (GLYPH_HEIGHT as i64 + 2) * scale;
+        let lines: Vec<&str> = text.lines().collect();
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i64;
+
+        let padding = scale as f64;
+        let block_height = (lines.len() as i64 * line_height) as f64;
+        self.fill_rect(
+            x - padding,
+            y - padding,
+            x + (longest * advance) as f64 + padding,
+            y + block_height,
+            TEXT_BACKGROUND,
+        );
+
+        for (row, line) in lines.iter().enumerate() {
+            let top = y.round() as i64 + row as i64 * line_height;
+            for (col, c) in line.chars().enumerate() {
+                let left = x.round() as i64 + col as i64 * advance;
+                for (gy, bits) in glyph(c).iter().enumerate() {
+                    for gx in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - gx)) == 0 {
+                            continue;
+                        }
+                        for sy in 0..scale {
+                            for sx in 0..scale {
+                                self.blend(
+                                    left + gx as i64 * scale + sx,
+                                    top + gy as i64 * scale + sy,
+                                    color,
+                                    1.0,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        block_height
+    }
+
+    fn draw(&mut self, annotation: &ChartAnnotation) {
+        let (w, h) = (self.width(), self.height());
+        let color = annotation
+            .color
+            .as_deref()
+            .and_then(|c| parse_color(c).ok())
+            .unwrap_or(DEFAULT_COLOR);
+        let stroke = (annotation.stroke_width.unwrap_or(DEFAULT_STROKE_WIDTH) * w).max(1.0);
+
+        match &annotation.shape {
+            AnnotationShape::Line { x1, y1, x2, y2 } => {
+                self.segment((x1 * w, y1 * h), (x2 * w, y2 * h), stroke, color);
+            }
+            AnnotationShape::Arrow { x1, y1, x2, y2 } => {
+                let (from, tip) = ((x1 * w, y1 * h), (x2 * w, y2 * h));
+                self.segment(from, tip, stroke, color);
+
+                let angle = (tip.1 - from.1).atan2(tip.0 - from.0);
+                let head = (stroke * 5.0).max(10.0);
+                for spread in [-0.45_f64, 0.45] {
+                    let back = angle + std::f64::consts::PI + spread;
+                    let end = (tip.0 + head * back.cos(), tip.1 + head * back.sin());
+                    self.segment(tip, end, stroke, color);
+                }
+            }
+            AnnotationShape::Rectangle { x, y, width, height, fill } => {
+                let (x0, y0, x1, y1) = (x * w, y * h, (x + width) * w, (y + height) * h);
+                if let Some(fill) = fill.as_deref().and_then(|f| parse_color(f).ok()) {
+                    self.fill_rect(x0, y0, x1, y1, fill);
+                }
+                self.segment((x0, y0), (x1, y0), stroke, color);
+                self.segment((x1, y0), (x1, y1), stroke, color);
+                self.segment((x1, y1), (x0, y1), stroke, color);
+                self.segment((x0, y1), (x0, y0), stroke, color);
+            }
+            AnnotationShape::Text { x, y, text, font_size } => {
+                self.text(x * w, y * h, text, font_size.unwrap_or(DEFAULT_FONT_SIZE), color);
+            }
+            AnnotationShape::PriceLevel { y, price, label } => {
+                let line_y = y * h;
+                let dash = (stroke * 6.0).max(6.0);
+                let mut x = 0.0;
+                while x < w {
+                    self.segment((x, line_y), ((x + dash).min(w), line_y), stroke, color);
+                    x += dash * 2.0;
+                }
+
+                let caption = [label.clone(), price.map(|p| p.normalize().to_string())]
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if !caption.is_empty() {
+                    let text_height = (DEFAULT_FONT_SIZE * h).max(GLYPH_HEIGHT as f64);
+                    let gap = stroke + 2.0;
+                    // Label sits above the line unless that would run off the top
+                    let top = if line_y - text_height - gap >= 0.0 {
+                        line_y - text_height - gap
+                    } else {
+                        line_y + gap
+                    };
+                    self.text(4.0, top, &caption, DEFAULT_FONT_SIZE, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageBuffer;
+
+    fn blank_png(width: u32, height: u32) -> Vec<u8> {
+        let img: RgbaImage = ImageBuffer::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn line_request(y: f64) -> CreateAnnotationRequest {
+        CreateAnnotationRequest {
+            shape: AnnotationShape::Line { x1: 0.1, y1: y, x2: 0.9, y2: y },
+            color: Some("#FF0000".to_string()),
+            stroke_width: Some(0.04),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000").unwrap(), [255, 128, 0, 255]);
+        assert_eq!(parse_color("#00000080").unwrap(), [0, 0, 0, 128]);
+        assert!(parse_color("red").is_err());
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("#GGGGGG").is_err());
+    }
+
+    #[test]
+    fn test_validate_shape() {
+        assert!(validate_shape(&AnnotationShape::Line { x1: 0.0, y1: 0.0, x2: 1.0, y2: 1.0 }).is_ok());
+        assert!(validate_shape(&AnnotationShape::Line { x1: 0.0, y1: 0.0, x2: 1.2, y2: 1.0 }).is_err());
+        assert!(validate_shape(&AnnotationShape::Arrow { x1: 0.5, y1: 0.5, x2: 0.5, y2: 0.5 }).is_err());
+        assert!(validate_shape(&AnnotationShape::Line { x1: f64::NAN, y1: 0.0, x2: 1.0, y2: 1.0 }).is_err());
+
+        let rect = |x, width, fill: Option<&str>| AnnotationShape::Rectangle {
+            x,
+            y: 0.1,
+            width,
+            height: 0.2,
+            fill: fill.map(str::to_string),
+        };
+        assert!(validate_shape(&rect(0.5, 0.5, Some("#00FF0033"))).is_ok());
+        assert!(validate_shape(&rect(0.6, 0.5, None)).is_err());
+        assert!(validate_shape(&rect(0.1, 0.0, None)).is_err());
+        assert!(validate_shape(&rect(0.1, 0.2, Some("green"))).is_err());
+
+        let text = |t: &str| AnnotationShape::Text { x: 0.1, y: 0.1, text: t.to_string(), font_size: None };
+        assert!(validate_shape(&text("Entry")).is_ok());
+        assert!(validate_shape(&text("   ")).is_err());
+    }
+
+    #[test]
+    fn test_shape_serializes_with_kind_tag() {
+        let json = serde_json::json!({
+            "kind": "price_level",
+            "y": 0.25,
+            "price": "4512.25",
+            "label": "VWAP",
+            "color": "#3B82F6"
+        });
+        let req: CreateAnnotationRequest = serde_json::from_value(json).unwrap();
+        assert!(matches!(req.shape, AnnotationShape::PriceLevel { y, .. } if y == 0.25));
+
+        let mut list = Vec::new();
+        let created = AnnotationService::create(&mut list, req, Uuid::new_v4()).unwrap();
+        let stored = serde_json::to_value(&list).unwrap();
+        assert_eq!(stored[0]["kind"], "price_level");
+        assert_eq!(AnnotationService::parse_stored(Some(stored)).unwrap(), vec![created]);
+        assert!(AnnotationService::parse_stored(None).unwrap().is_empty());
+        assert!(AnnotationService::parse_stored(Some(serde_json::json!({"legacy": true}))).is_err());
+    }
+
+    #[test]
+    fn test_optimistic_concurrency() {
+        let mut list = Vec::new();
+        let created = AnnotationService::create(&mut list, line_request(0.5), Uuid::new_v4()).unwrap();
+        assert_eq!(created.version, 1);
+
+        let update = |version| UpdateAnnotationRequest {
+            version,
+            shape: None,
+            color: Some("#00FF00".to_string()),
+            stroke_width: None,
+            note: Some("moved stop".to_string()),
+        };
+
+        let updated = AnnotationService::update(&mut list, created.id, update(1)).unwrap();
+        assert_eq!(updated.version, 2);
+        assert_eq!(updated.color.as_deref(), Some("#00FF00"));
+
+        // A client still holding version 1 loses the race
+        assert!(matches!(
+            AnnotationService::update(&mut list, created.id, update(1)),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            AnnotationService::remove(&mut list, created.id, 1),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            AnnotationService::remove(&mut list, Uuid::new_v4(), 2),
+            Err(AppError::NotFound(_))
+        ));
+
+        AnnotationService::remove(&mut list, created.id, 2).unwrap();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_render_flattens_annotations() {
+        let mut list = Vec::new();
+        let user = Uuid::new_v4();
+        AnnotationService::create(&mut list, line_request(0.5), user).unwrap();
+        AnnotationService::create(
+            &mut list,
+            CreateAnnotationRequest {
+                shape: AnnotationShape::Text {
+                    x: 0.05,
+                    y: 0.05,
+                    text: "Entry 1.5R".to_string(),
+                    font_size: Some(0.2),
+                },
+                color: None,
+                stroke_width: None,
+                note: None,
+            },
+            user,
+        )
+        .unwrap();
+
+        let png = AnnotationService::render(&blank_png(200, 100), &list).unwrap();
+        let rendered = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(rendered.dimensions(), (200, 100));
+
+        // Middle of the red line
+        assert_eq!(rendered.get_pixel(100, 50).0, [255, 0, 0, 255]);
+        // Outside any annotation stays untouched
+        assert_eq!(rendered.get_pixel(190, 90).0, [255, 255, 255, 255]);
+        // Text backdrop darkens the top-left corner
+        assert!(rendered.get_pixel(12, 8).0[0] < 255);
+
+        assert!(AnnotationService::render(b"not an image", &list).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{ApiKey, AuthUser, CreateApiKeyRequest, CreatedApiKey};
+use crate::services::AuthService;
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const API_SCOPE_TRADES_READ: &str = "trades:read";
+pub const API_SCOPE_TRADES_WRITE: &str = "trades:write";
+pub const API_SCOPE_ANALYTICS_READ: &str = "analytics:read";
+pub const API_SCOPE_ANALYTICS_WRITE: &str = "analytics:write";
+pub const API_SCOPE_AI_USE: &str = "ai:use";
+pub const API_KEY_SCOPES: &[&str] = &[
+    API_SCOPE_TRADES_READ,
+    API_SCOPE_TRADES_WRITE,
+    API_SCOPE_ANALYTICS_READ,
+    API_SCOPE_ANALYTICS_WRITE,
+    API_SCOPE_AI_USE,
+];
+
+/// Marks a bearer token as an API key rather than a JWT.
+pub const API_KEY_PREFIX: &str = "tmk_";
+const API_KEY_RANDOM_CHARS: usize = 40;
+const API_KEY_DISPLAY_CHARS: usize = 12;
+const MAX_API_KEY_NAME_CHARS: usize = 100;
+const MAX_ACTIVE_API_KEYS: i64 = 20;
+/// `last_used_at` is only rewritten once it is this stale, so busy scripts
+/// don't turn every request into a write.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+pub fn generate_api_key() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_RANDOM_CHARS)
+        .map(char::from)
+        .collect();
+    format!("{}{}", API_KEY_PREFIX, random)
+}
+
+/// Validated, de-duplicated scopes in canonical order.
+pub fn normalize_scopes(scopes: &[String]) -> Result<Vec<String>, String> {
+    if let Some(unknown) = scopes
+        .iter()
+        .find(|s| !API_KEY_SCOPES.contains(&s.trim()))
+    {
+        return Err(format!(
+            "Unknown scope '{}'; expected one of: {}",
+            unknown,
+            API_KEY_SCOPES.join(", ")
+        ));
+    }
+
+    let normalized: Vec<String> = API_KEY_SCOPES
+        .iter()
+        .filter(|scope| scopes.iter().any(|s| s.trim() == **scope))
+        .map(|scope| scope.to_string())
+        .collect();
+
+    if normalized.is_empty() {
+        return Err("An API key needs at least one scope".to_string());
+    }
+
+    Ok(normalized)
+}
+
+/// Personal API keys: created and revoked from a login session, then used
+/// as bearer tokens limited to their scopes.
+pub struct ApiKeyService;
+
+impl ApiKeyService {
+    pub async fn create(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        req: &CreateApiKeyRequest,
+    ) -> AppResult<CreatedApiKey> {
+        let name = req.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_API_KEY_NAME_CHARS {
+            return Err(AppError::Validation(format!(
+                "Name must be 1-{} characters",
+                MAX_API_KEY_NAME_CHARS
+            )));
+        }
+
+        let scopes = normalize_scopes(&req.scopes).map_err(AppError::Validation)?;
+
+        if req.expires_at.is_some_and(|at| at <= Utc::now()) {
+            return Err(AppError::Validation("expires_at must be in the future".to_string()));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        // Serialise concurrent creates so the cap holds
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let active = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM api_keys
+            WHERE user_id = $1 AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if active >= MAX_ACTIVE_API_KEYS {
+            return Err(AppError::Conflict(format!(
+                "You can have at most {} active API keys; revoke one first",
+                MAX_ACTIVE_API_KEYS
+            )));
+        }
+
+        let key = generate_api_key();
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (user_id, name, key_prefix, key_hash, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, key_prefix, scopes, expires_at, last_used_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(&key[..API_KEY_DISPLAY_CHARS])
+        .bind(auth_service.hash_token(&key))
+        .bind(&scopes)
+        .bind(req.expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(CreatedApiKey { api_key, key })
+    }
+
+    /// Keys that have not been revoked, including expired ones so users can
+    /// see why a script stopped working.
+    pub async fn list(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<ApiKey>> {
+        Ok(sqlx::query_as::<_, ApiKey>(
+            r#"
+            SELECT id, name, key_prefix, scopes, expires_at, last_used_at, created_at
+            FROM api_keys
+            WHERE user_id = $1 AND revoked_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    pub async fn revoke(pool: &PgPool, user_id: Uuid, key_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE api_keys SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(key_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("API key not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Revokes every key a user holds, for when their password is replaced
+    /// without proof of the old one.
+    pub async fn revoke_all<'e, E>(executor: E, user_id: Uuid) -> AppResult<u64>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Resolves a presented key to its user and scopes.
+    pub async fn authenticate(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        key: &str,
+    ) -> AppResult<AuthUser> {
+        let (key_id, user_id, email, scopes, last_used_at) = sqlx::query_as::<
+            _,
+            (Uuid, Uuid, String, Vec<String>, Option<DateTime<Utc>>),
+        >(
+            r#"
+            SELECT k.id, k.user_id, u.email, k.scopes, k.last_used_at
+            FROM api_keys k
+            JOIN users u ON u.id = k.user_id
+            WHERE k.key_hash = $1 AND k.revoked_at IS NULL
+                AND (k.expires_at IS NULL OR k.expires_at > NOW())
+            "#,
+        )
+        .bind(auth_service.hash_token(key))
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired API key".to_string()))?;
+
+        let stale = last_used_at
+            .is_none_or(|at| (Utc::now() - at).num_seconds() >= LAST_USED_RESOLUTION_SECONDS);
+        if stale {
+            sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+                .bind(key_id)
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(AuthUser {
+            user_id,
+            email,
+            session_id: None,
+            scopes: Some(scopes),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn scopes(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn generated_keys_are_prefixed_and_unique() {
+        let key = generate_api_key();
+        assert!(is_api_key(&key));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + API_KEY_RANDOM_CHARS);
+        assert!(key[API_KEY_PREFIX.len()..].chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(key, generate_api_key());
+    }
+
+    #[test]
+    fn jwts_are_not_mistaken_for_api_keys() {
+        assert!(!is_api_key("eyJhbGciOiJIUzI1NiJ9.e30.sig"));
+    }
+
+    #[test]
+    fn scopes_are_deduplicated_in_canonical_order() {
+        assert_eq!(
+            normalize_scopes(&scopes(&["ai:use", " trades:read", "ai:use"])).unwrap(),
+            scopes(&[API_SCOPE_TRADES_READ, API_SCOPE_AI_USE])
+        );
+    }
+
+    #[test]
+    fn unknown_or_missing_scopes_are_rejected() {
+        assert!(normalize_scopes(&scopes(&["trades:read", "admin"]))
+            .unwrap_err()
+            .contains("'admin'"));
+        assert!(normalize_scopes(&[]).is_err());
+    }
+
+    #[test]
+    fn session_users_have_every_scope() {
+        let mut user = AuthUser {
+            user_id: Uuid::new_v4(),
+            email: "trader@example.com".to_string(),
+            session_id: Some(Uuid::new_v4()),
+            scopes: None,
+        };
+        assert!(API_KEY_SCOPES.iter().all(|scope| user.has_scope(scope)));
+
+        user.scopes = Some(scopes(&[API_SCOPE_TRADES_READ]));
+        assert!(user.has_scope(API_SCOPE_TRADES_READ));
+        assert!(!user.has_scope(API_SCOPE_TRADES_WRITE));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_stops_every_key_of_one_user() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let auth_service = AuthService::new(&Config::for_tests());
+        let owner = db.insert_user("owner@example.com").await;
+        let other = db.insert_user("other@example.com").await;
+        let create = |user_id, name: &'static str| {
+            let req = CreateApiKeyRequest {
+                name: name.to_string(),
+                scopes: scopes(&[API_SCOPE_TRADES_READ]),
+                expires_at: None,
+            };
+            let (pool, auth_service) = (db.pool.clone(), &auth_service);
+            async move { ApiKeyService::create(&pool, auth_service, user_id, &req).await.unwrap().key }
+        };
+        let owner_keys = [create(owner, "a").await, create(owner, "b").await];
+        let other_key = create(other, "c").await;
+
+        let revoked = ApiKeyService::revoke_all(&db.pool, owner).await.unwrap();
+        let mut owner_auth = Vec::new();
+        for key in &owner_keys {
+            owner_auth.push(ApiKeyService::authenticate(&db.pool, &auth_service, key).await);
+        }
+        let other_auth = ApiKeyService::authenticate(&db.pool, &auth_service, &other_key).await;
+        db.drop().await;
+
+        assert_eq!(revoked, 2);
+        assert!(owner_auth.iter().all(|r| matches!(r, Err(AppError::Unauthorized(_)))));
+        assert_eq!(other_auth.unwrap().user_id, other);
+    }
+}
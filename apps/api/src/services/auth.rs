@@ -1,15 +1,28 @@
 use crate::config::Config;
 use crate::error::{AppError, AppResult};
-use crate::models::{AuthUser, Claims};
+use crate::models::{AuthUser, Claims, EmailTokenClaims, TwoFactorChallengeClaims};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
 use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
 use uuid::Uuid;
 
+const TOTP_ISSUER: &str = "TradeMaster AI";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_SECRET_BYTES: usize = 20;
+const TWO_FACTOR_CHALLENGE_PURPOSE: &str = "two_factor_challenge";
+const TWO_FACTOR_CHALLENGE_TTL_SECONDS: i64 = 5 * 60;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// No 0/o, 1/l/i, so codes survive being read aloud or written down
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_HALF_LEN: usize = 5;
+
 pub struct AuthService {
     jwt_secret: String,
     access_expiry_seconds: i64,
@@ -44,14 +57,20 @@ impl AuthService {
         Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
     }
 
-    /// Generate an access token (JWT)
-    pub fn generate_access_token(&self, user_id: Uuid, email: &str) -> AppResult<String> {
+    /// Generate an access token (JWT) bound to a session
+    pub fn generate_access_token(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        session_id: Uuid,
+    ) -> AppResult<String> {
         let now = Utc::now().timestamp();
         let claims = Claims {
             sub: user_id.to_string(),
             email: email.to_string(),
             exp: now + self.access_expiry_seconds,
             iat: now,
+            sid: Some(session_id.to_string()),
         };
 
         let token = encode(
@@ -88,48 +107,208 @@ impl AuthService {
         let user_id = Uuid::parse_str(&token_data.claims.sub)
             .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))?;
 
+        let session_id = token_data
+            .claims
+            .sid
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| AppError::Unauthorized("Invalid session ID in token".to_string()))?;
+
         Ok(AuthUser {
             user_id,
             email: token_data.claims.email,
+            session_id,
+            scopes: None,
         })
     }
 
+    /// Sign an email link token. `token_id` is the `email_tokens` row that
+    /// makes it single-use; the signature only proves we issued it.
+    pub fn generate_email_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+        purpose: &str,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<String> {
+        let claims = EmailTokenClaims {
+            sub: user_id.to_string(),
+            jti: token_id.to_string(),
+            purpose: purpose.to_string(),
+            exp: expires_at.timestamp(),
+            iat: Utc::now().timestamp(),
+        };
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )?;
+
+        Ok(token)
+    }
+
+    /// Verify an email link token's signature, expiry and purpose. Returns
+    /// `(user_id, token_id)`; the caller still has to consume the row.
+    pub fn verify_email_token(&self, token: &str, purpose: &str) -> AppResult<(Uuid, Uuid)> {
+        let invalid = || AppError::BadRequest("This link is invalid or has expired".to_string());
+
+        let token_data = decode::<EmailTokenClaims>(
+            token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .map_err(|e| {
+            tracing::debug!(error = %e, "Email token validation failed");
+            invalid()
+        })?;
+
+        if token_data.claims.purpose != purpose {
+            return Err(invalid());
+        }
+
+        let user_id = Uuid::parse_str(&token_data.claims.sub).map_err(|_| invalid())?;
+        let token_id = Uuid::parse_str(&token_data.claims.jti).map_err(|_| invalid())?;
+
+        Ok((user_id, token_id))
+    }
+
+    /// New random TOTP secret, base32 encoded as authenticator apps expect.
+    pub fn generate_totp_secret(&self) -> String {
+        let mut bytes = [0u8; TOTP_SECRET_BYTES];
+        rand::thread_rng().fill(&mut bytes[..]);
+        Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+    }
+
+    fn totp(&self, secret: &str, account_name: String) -> AppResult<TOTP> {
+        let bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| AppError::Internal(format!("Invalid TOTP secret: {:?}", e)))?;
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            bytes,
+            Some(TOTP_ISSUER.to_string()),
+            account_name,
+        )
+        .map_err(|e| AppError::Internal(format!("Invalid TOTP parameters: {}", e)))
+    }
+
+    /// `otpauth://` URI for the enrolment QR code.
+    pub fn totp_provisioning_uri(&self, secret: &str, email: &str) -> AppResult<String> {
+        // A colon would split the label into issuer and account
+        Ok(self.totp(secret, email.replace(':', ""))?.get_url())
+    }
+
+    /// Checks a code against the previous, current and next 30-second steps
+    /// and returns the matching step. Steps at or before `last_used_step` are
+    /// skipped so an observed code cannot be replayed.
+    pub fn verify_totp(
+        &self,
+        secret: &str,
+        code: &str,
+        unix_time: u64,
+        last_used_step: Option<i64>,
+    ) -> AppResult<Option<i64>> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+        if code.len() != TOTP_DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+
+        let totp = self.totp(secret, String::new())?;
+        let current = unix_time / TOTP_STEP_SECONDS;
+        for step in current.saturating_sub(1)..=current + 1 {
+            if last_used_step.is_some_and(|last| step as i64 <= last) {
+                continue;
+            }
+            if totp.check(&code, step * TOTP_STEP_SECONDS) {
+                return Ok(Some(step as i64));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fresh one-time recovery codes in `xxxxx-xxxxx` form, shown once.
+    pub fn generate_recovery_codes(&self) -> Vec<String> {
+        let mut rng = rand::thread_rng();
+        let mut half = || -> String {
+            (0..RECOVERY_CODE_HALF_LEN)
+                .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect()
+        };
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| format!("{}-{}", half(), half()))
+            .collect()
+    }
+
+    /// Hash a recovery code for storage. Case, spaces and dashes are ignored.
+    pub fn hash_recovery_code(&self, code: &str) -> AppResult<String> {
+        self.hash_password(&normalize_recovery_code(code))
+    }
+
+    pub fn verify_recovery_code(&self, code: &str, hash: &str) -> AppResult<bool> {
+        self.verify_password(&normalize_recovery_code(code), hash)
+    }
+
+    /// Token proving the password step of a 2FA login succeeded.
+    pub fn generate_two_factor_challenge(&self, user_id: Uuid) -> AppResult<(String, DateTime<Utc>)> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::seconds(TWO_FACTOR_CHALLENGE_TTL_SECONDS);
+        let claims = TwoFactorChallengeClaims {
+            sub: user_id.to_string(),
+            purpose: TWO_FACTOR_CHALLENGE_PURPOSE.to_string(),
+            exp: expires_at.timestamp(),
+            iat: now.timestamp(),
+        };
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )?;
+
+        Ok((token, expires_at))
+    }
+
+    pub fn verify_two_factor_challenge(&self, token: &str) -> AppResult<Uuid> {
+        let token_data = decode::<TwoFactorChallengeClaims>(
+            token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )?;
+
+        if token_data.claims.purpose != TWO_FACTOR_CHALLENGE_PURPOSE {
+            return Err(AppError::Unauthorized("Invalid challenge token".to_string()));
+        }
+
+        Uuid::parse_str(&token_data.claims.sub)
+            .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))
+    }
+
     /// Get refresh token expiry timestamp
     pub fn get_refresh_token_expiry(&self) -> chrono::DateTime<Utc> {
         Utc::now() + chrono::Duration::seconds(self.refresh_expiry_seconds)
     }
 }
 
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn test_config() -> Config {
-        Config {
-            database_url: "".to_string(),
-            port: 3000,
-            cors_origins: vec![],
-            jwt_secret: "test_secret_key_at_least_32_characters_long".to_string(),
-            jwt_access_expiry_seconds: 900,
-            jwt_refresh_expiry_seconds: 2592000,
-            anthropic_api_key: None,
-            s3_endpoint: "".to_string(),
-            s3_region: "".to_string(),
-            s3_bucket: "".to_string(),
-            s3_access_key: "".to_string(),
-            s3_secret_key: "".to_string(),
-            max_pool_connections: 10,
-            smtp_host: None,
-            smtp_port: None,
-            smtp_username: None,
-            smtp_password: None,
-            smtp_from_email: None,
-        }
-    }
-
     #[test]
     fn test_password_hashing() {
-        let service = AuthService::new(&test_config());
+        let service = AuthService::new(&Config::for_tests());
         let password = "test_password_123";
         
         let hash = service.hash_password(password).unwrap();
@@ -139,23 +318,153 @@ mod tests {
 
     #[test]
     fn test_token_generation_and_verification() {
-        let service = AuthService::new(&test_config());
+        let service = AuthService::new(&Config::for_tests());
         let user_id = Uuid::new_v4();
         let email = "test@example.com";
 
-        let token = service.generate_access_token(user_id, email).unwrap();
+        let session_id = Uuid::new_v4();
+
+        let token = service.generate_access_token(user_id, email, session_id).unwrap();
         let auth_user = service.verify_access_token(&token).unwrap();
 
         assert_eq!(auth_user.user_id, user_id);
         assert_eq!(auth_user.email, email);
+        assert_eq!(auth_user.session_id, Some(session_id));
+    }
+
+    #[test]
+    fn test_access_token_without_session_still_verifies() {
+        let service = AuthService::new(&Config::for_tests());
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: Uuid::new_v4().to_string(),
+            email: "test@example.com".to_string(),
+            exp: now + 60,
+            iat: now,
+            sid: None,
+        };
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(Config::for_tests().jwt_secret.as_bytes()),
+        )
+        .unwrap();
+
+        assert_eq!(service.verify_access_token(&token).unwrap().session_id, None);
     }
 
     #[test]
     fn test_refresh_token_hashing() {
-        let service = AuthService::new(&test_config());
+        let service = AuthService::new(&Config::for_tests());
         let (token, hash) = service.generate_refresh_token();
         
         assert_ne!(token, hash);
         assert_eq!(hash, service.hash_token(&token));
     }
+
+    #[test]
+    fn test_email_token_round_trip() {
+        let service = AuthService::new(&Config::for_tests());
+        let (user_id, token_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+
+        let token = service
+            .generate_email_token(user_id, token_id, "verify_email", expires_at)
+            .unwrap();
+
+        assert_eq!(
+            service.verify_email_token(&token, "verify_email").unwrap(),
+            (user_id, token_id)
+        );
+        assert!(service.verify_email_token(&token, "password_reset").is_err());
+        // Not interchangeable with access tokens in either direction
+        assert!(service.verify_access_token(&token).is_err());
+        let access = service.generate_access_token(user_id, "a@b.com", token_id).unwrap();
+        assert!(service.verify_email_token(&access, "verify_email").is_err());
+    }
+
+    #[test]
+    fn test_totp_verification_and_replay() {
+        let service = AuthService::new(&Config::for_tests());
+        let secret = service.generate_totp_secret();
+        let now = 1_760_000_000u64;
+
+        let totp = service.totp(&secret, String::new()).unwrap();
+        let code = totp.generate(now);
+        let step = (now / TOTP_STEP_SECONDS) as i64;
+
+        assert_eq!(service.verify_totp(&secret, &code, now, None).unwrap(), Some(step));
+        // Clock drift of one step either way is tolerated
+        assert_eq!(service.verify_totp(&secret, &code, now + 30, None).unwrap(), Some(step));
+        assert_eq!(service.verify_totp(&secret, &code, now - 30, None).unwrap(), Some(step));
+        assert_eq!(service.verify_totp(&secret, &code, now + 90, None).unwrap(), None);
+        // A code already used cannot be replayed
+        assert_eq!(service.verify_totp(&secret, &code, now, Some(step)).unwrap(), None);
+        assert_eq!(service.verify_totp(&secret, "12345", now, None).unwrap(), None);
+        assert_eq!(service.verify_totp(&secret, "abcdef", now, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_totp_provisioning_uri() {
+        let service = AuthService::new(&Config::for_tests());
+        let secret = service.generate_totp_secret();
+        let uri = service.totp_provisioning_uri(&secret, "trader@example.com").unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/TradeMaster%20AI:trader%40example.com?"));
+        assert!(uri.contains(&format!("secret={}", secret)));
+        assert!(uri.contains("issuer=TradeMaster%20AI"));
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let service = AuthService::new(&Config::for_tests());
+        let codes = service.generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|c| c.len() == 11 && c.as_bytes()[5] == b'-'));
+
+        let hash = service.hash_recovery_code(&codes[0]).unwrap();
+        assert!(service.verify_recovery_code(&codes[0], &hash).unwrap());
+        assert!(service
+            .verify_recovery_code(&format!(" {} ", codes[0].to_uppercase().replace('-', "")), &hash)
+            .unwrap());
+        assert!(!service.verify_recovery_code(&codes[1], &hash).unwrap());
+    }
+
+    #[test]
+    fn test_two_factor_challenge_round_trip() {
+        let service = AuthService::new(&Config::for_tests());
+        let user_id = Uuid::new_v4();
+
+        let (challenge, _) = service.generate_two_factor_challenge(user_id).unwrap();
+        assert_eq!(service.verify_two_factor_challenge(&challenge).unwrap(), user_id);
+        // Not an access token, and other signed tokens are not challenges
+        assert!(service.verify_access_token(&challenge).is_err());
+        let email_token = service
+            .generate_email_token(user_id, Uuid::new_v4(), "verify_email", Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        assert!(service.verify_two_factor_challenge(&email_token).is_err());
+    }
+
+    #[test]
+    fn test_email_token_rejects_tampering_and_expiry() {
+        let service = AuthService::new(&Config::for_tests());
+        let (user_id, token_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let expired = service
+            .generate_email_token(user_id, token_id, "password_reset", Utc::now() - chrono::Duration::hours(1))
+            .unwrap();
+        assert!(service.verify_email_token(&expired, "password_reset").is_err());
+
+        let token = service
+            .generate_email_token(user_id, token_id, "password_reset", Utc::now() + chrono::Duration::hours(1))
+            .unwrap();
+        let mut tampered = token.clone();
+        tampered.push('x');
+        assert!(service.verify_email_token(&tampered, "password_reset").is_err());
+
+        let mut other_config = Config::for_tests();
+        other_config.jwt_secret = "another_secret_key_at_least_32_characters".to_string();
+        let other = AuthService::new(&other_config);
+        assert!(other.verify_email_token(&token, "password_reset").is_err());
+    }
 }
@@ -0,0 +1,200 @@
+use crate::error::{AppError, AppResult};
+use crate::models::AccountabilityLink;
+use crate::services::{AccountabilityService, CoachResource, NotificationKind};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Journal entries that can carry a comment thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentSubject {
+    Trade,
+    DailyPlan,
+    PeriodicReview,
+}
+
+impl CommentSubject {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "trade" => Ok(CommentSubject::Trade),
+            "daily_plan" => Ok(CommentSubject::DailyPlan),
+            "periodic_review" => Ok(CommentSubject::PeriodicReview),
+            other => Err(format!(
+                "Invalid subject_type '{}'. Must be one of: trade, daily_plan, periodic_review",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentSubject::Trade => "trade",
+            CommentSubject::DailyPlan => "daily_plan",
+            CommentSubject::PeriodicReview => "periodic_review",
+        }
+    }
+
+    fn table(&self) -> &'static str {
+        match self {
+            CommentSubject::Trade => "trades",
+            CommentSubject::DailyPlan => "daily_plans",
+            CommentSubject::PeriodicReview => "periodic_reviews",
+        }
+    }
+
+    /// The accountability permission a coach needs to see and comment on this subject.
+    pub fn coach_resource(&self) -> CoachResource {
+        match self {
+            CommentSubject::Trade => CoachResource::Trades,
+            CommentSubject::DailyPlan => CoachResource::Plans,
+            CommentSubject::PeriodicReview => CoachResource::Reviews,
+        }
+    }
+
+    fn not_found(&self) -> AppError {
+        AppError::NotFound(match self {
+            CommentSubject::Trade => "Trade not found".to_string(),
+            CommentSubject::DailyPlan => "Daily plan not found".to_string(),
+            CommentSubject::PeriodicReview => "Review not found".to_string(),
+        })
+    }
+}
+
+pub struct CommentService;
+
+impl CommentService {
+    /// Checks that `user_id` may read and comment on the subject: either they
+    /// own it, or they coach its owner with the matching permission. Returns
+    /// the owning trader's id.
+    pub async fn authorize(
+        pool: &PgPool,
+        user_id: Uuid,
+        subject: CommentSubject,
+        subject_id: Uuid,
+    ) -> AppResult<Uuid> {
+        let sql = format!("SELECT user_id FROM {} WHERE id = $1", subject.table());
+        let trader_user_id = sqlx::query_scalar::<_, Uuid>(&sql)
+            .bind(subject_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| subject.not_found())?;
+
+        if trader_user_id != user_id {
+            // Without an active link, hide that the subject exists at all.
+            AccountabilityService::authorize_coach_access(
+                pool,
+                user_id,
+                trader_user_id,
+                subject.coach_resource(),
+                Some(subject_id),
+            )
+            .await
+            .map_err(|e| match e {
+                AppError::NotFound(_) => subject.not_found(),
+                other => other,
+            })?;
+        }
+
+        Ok(trader_user_id)
+    }
+
+    /// Everyone who can see a thread: the trader plus each active coach whose
+    /// link grants the subject's permission.
+    pub async fn participants(
+        pool: &PgPool,
+        trader_user_id: Uuid,
+        subject: CommentSubject,
+    ) -> AppResult<Vec<Uuid>> {
+        let links = sqlx::query_as::<_, AccountabilityLink>(
+            "SELECT * FROM accountability_links WHERE trader_user_id = $1 AND status = 'active'",
+        )
+        .bind(trader_user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let resource = subject.coach_resource();
+        let mut participants = vec![trader_user_id];
+        participants.extend(
+            links
+                .iter()
+                .filter(|link| resource.is_permitted(link))
+                .map(|link| link.coach_user_id),
+        );
+
+        Ok(participants)
+    }
+
+    /// Mentions may only name people who can already see the thread.
+    pub fn validate_mentions(participants: &[Uuid], mentions: &[Uuid]) -> Result<(), String> {
+        match mentions.iter().find(|m| !participants.contains(m)) {
+            Some(id) => Err(format!("Mentioned user {} cannot see this thread", id)),
+            None => Ok(()),
+        }
+    }
+
+    /// Who to notify about a new comment. Mentioned users get a mention
+    /// notification; every other participant gets a plain comment notification.
+    /// The author is never notified of their own comment.
+    pub fn notification_recipients(
+        author_user_id: Uuid,
+        participants: &[Uuid],
+        mentions: &[Uuid],
+    ) -> Vec<(Uuid, NotificationKind)> {
+        let mut recipients: Vec<(Uuid, NotificationKind)> = Vec::new();
+
+        for user_id in participants {
+            if *user_id == author_user_id || recipients.iter().any(|(id, _)| id == user_id) {
+                continue;
+            }
+            let kind = if mentions.contains(user_id) {
+                NotificationKind::CommentMention
+            } else {
+                NotificationKind::Comment
+            };
+            recipients.push((*user_id, kind));
+        }
+
+        recipients
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_parse_round_trip() {
+        for s in ["trade", "daily_plan", "periodic_review"] {
+            assert_eq!(CommentSubject::parse(s).unwrap().as_str(), s);
+        }
+        assert!(CommentSubject::parse("mood_log").is_err());
+    }
+
+    #[test]
+    fn test_validate_mentions_rejects_outsiders() {
+        let trader = Uuid::new_v4();
+        let coach = Uuid::new_v4();
+        let participants = vec![trader, coach];
+
+        assert!(CommentService::validate_mentions(&participants, &[coach]).is_ok());
+        assert!(CommentService::validate_mentions(&participants, &[Uuid::new_v4()]).is_err());
+    }
+
+    #[test]
+    fn test_notification_recipients_skip_author_and_flag_mentions() {
+        let trader = Uuid::new_v4();
+        let coach_a = Uuid::new_v4();
+        let coach_b = Uuid::new_v4();
+        let participants = vec![trader, coach_a, coach_b, coach_a];
+
+        let recipients =
+            CommentService::notification_recipients(coach_a, &participants, &[coach_b]);
+
+        assert_eq!(
+            recipients,
+            vec![
+                (trader, NotificationKind::Comment),
+                (coach_b, NotificationKind::CommentMention),
+            ]
+        );
+    }
+}
@@ -0,0 +1,715 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{EconomicEvent, EconomicEventImportError};
+use crate::services::StreakService;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Trades entered within this many minutes of a high-impact release are tagged.
+pub const DEFAULT_PROXIMITY_MINUTES: i32 = 30;
+pub const MAX_PROXIMITY_MINUTES: i32 = 24 * 60;
+
+const NEWS_TAG_NAME: &str = "High-Impact News";
+
+/// VEVENT properties by name: (parameters, raw value).
+type IcsProperties = HashMap<String, (HashMap<String, String>, String)>;
+
+/// An event parsed from an import file, before it is stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEvent {
+    pub event_date: NaiveDate,
+    pub event_time: Option<NaiveTime>,
+    pub timezone: Tz,
+    pub title: String,
+    pub currency: Option<String>,
+    pub impact: Option<String>,
+    pub description: Option<String>,
+    pub external_uid: String,
+}
+
+impl ParsedEvent {
+    pub fn event_at(&self) -> Option<DateTime<Utc>> {
+        EconomicCalendarService::resolve_event_at(self.event_date, self.event_time, self.timezone)
+    }
+}
+
+pub struct EconomicCalendarService;
+
+impl EconomicCalendarService {
+    pub fn parse_timezone(name: &str) -> AppResult<Tz> {
+        name.parse::<Tz>()
+            .map_err(|_| AppError::Validation(format!("Unknown timezone '{}'", name)))
+    }
+
+    /// The UTC instant for a local event time. DST gaps resolve to the later
+    /// valid instant; all-day events have none.
+    pub fn resolve_event_at(
+        date: NaiveDate,
+        time: Option<NaiveTime>,
+        tz: Tz,
+    ) -> Option<DateTime<Utc>> {
+        let local = date.and_time(time?);
+        tz.from_local_datetime(&local)
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(local + chrono::Duration::hours(1))).earliest())
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+
+    /// Accepts high/medium/low and the common aliases used by calendar exports.
+    pub fn normalize_impact(value: &str) -> Option<String> {
+        let v = value.trim().to_lowercase();
+        let level = match v.as_str() {
+            "high" | "h" | "3" | "red" => "high",
+            "medium" | "med" | "moderate" | "m" | "2" | "orange" => "medium",
+            "low" | "l" | "1" | "yellow" => "low",
+            _ if v.contains("high") => "high",
+            _ if v.contains("medium") || v.contains("moderate") => "medium",
+            _ if v.contains("low") => "low",
+            _ => return None,
+        };
+        Some(level.to_string())
+    }
+
+    fn normalize_currency(value: &str) -> Option<String> {
+        let v = value.trim().to_uppercase();
+        (v.len() == 3 && v.chars().all(|c| c.is_ascii_alphabetic())).then_some(v)
+    }
+
+    fn csv_uid(date: NaiveDate, time: Option<NaiveTime>, title: &str, currency: Option<&str>) -> String {
+        format!(
+            "csv:{}:{}:{}:{}",
+            date,
+            time.map(|t| t.format("%H:%M").to_string()).unwrap_or_default(),
+            title.to_lowercase(),
+            currency.unwrap_or_default()
+        )
+    }
+
+    /// Parses VEVENT blocks from an iCalendar file. Handles folded lines,
+    /// UTC/TZID/floating/all-day DTSTART values, PRIORITY as impact, and
+    /// CATEGORIES carrying an impact word or a currency code.
+    pub fn parse_ics(
+        content: &str,
+        default_tz: Tz,
+        default_currency: Option<&str>,
+    ) -> (Vec<ParsedEvent>, Vec<EconomicEventImportError>) {
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        let mut current: Option<IcsProperties> = None;
+        let mut event_index = 0;
+
+        for line in Self::unfold_ics(content) {
+            if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+                current = Some(HashMap::new());
+                event_index += 1;
+                continue;
+            }
+            if line.eq_ignore_ascii_case("END:VEVENT") {
+                if let Some(props) = current.take() {
+                    match Self::ics_event(&props, default_tz, default_currency) {
+                        Ok(event) => events.push(event),
+                        Err(error) => errors.push(EconomicEventImportError {
+                            row: event_index,
+                            error,
+                        }),
+                    }
+                }
+                continue;
+            }
+            if let (Some(props), Some((name, params, value))) =
+                (current.as_mut(), Self::split_ics_property(&line))
+            {
+                props.entry(name).or_insert((params, value));
+            }
+        }
+
+        (events, errors)
+    }
+
+    fn unfold_ics(content: &str) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for raw in content.lines() {
+            let raw = raw.trim_end_matches('\r');
+            if let Some(rest) = raw.strip_prefix(' ').or_else(|| raw.strip_prefix('\t')) {
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(rest);
+                    continue;
+                }
+            }
+            lines.push(raw.to_string());
+        }
+        lines
+    }
+
+    /// `NAME;PARAM=VALUE;...:VALUE`, ignoring colons inside quoted parameters.
+    fn split_ics_property(line: &str) -> Option<(String, HashMap<String, String>, String)> {
+        let mut in_quotes = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                None
+            }
+            ':' if !in_quotes => Some(i),
+            _ => None,
+        })?;
+
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.trim().to_uppercase(), v.trim_matches('"').to_string()))
+            .collect();
+
+        Some((name, params, value.to_string()))
+    }
+
+    fn unescape_ics(value: &str) -> String {
+        let mut out = String::with_capacity(value.len());
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') | Some('N') => out.push('\n'),
+                    Some(other) => out.push(other),
+                    None => {}
+                }
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    fn ics_event(
+        props: &IcsProperties,
+        default_tz: Tz,
+        default_currency: Option<&str>,
+    ) -> Result<ParsedEvent, String> {
+        let title = props
+            .get("SUMMARY")
+            .map(|(_, v)| Self::unescape_ics(v).trim().to_string())
+            .filter(|s| !s.is_empty())
+            .ok_or("Event has no SUMMARY")?;
+
+        let (params, dtstart) = props.get("DTSTART").ok_or("Event has no DTSTART")?;
+        let dtstart = dtstart.trim();
+
+        let (event_date, event_time, timezone) = if params
+            .get("VALUE")
+            .is_some_and(|v| v.eq_ignore_ascii_case("DATE"))
+            || dtstart.len() == 8
+        {
+            let date = NaiveDate::parse_from_str(dtstart, "%Y%m%d")
+                .map_err(|_| format!("Invalid DTSTART '{}'", dtstart))?;
+            (date, None, default_tz)
+        } else if let Some(utc) = dtstart.strip_suffix('Z') {
+            let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                .map_err(|_| format!("Invalid DTSTART '{}'", dtstart))?;
+            // Keep the wall time in the importer's zone so daily views line up.
+            let local = Utc.from_utc_datetime(&naive).with_timezone(&default_tz);
+            (local.date_naive(), Some(local.time()), default_tz)
+        } else {
+            let tz = match params.get("TZID") {
+                Some(id) => id
+                    .parse::<Tz>()
+                    .map_err(|_| format!("Unknown TZID '{}'", id))?,
+                None => default_tz,
+            };
+            let naive = NaiveDateTime::parse_from_str(dtstart, "%Y%m%dT%H%M%S")
+                .map_err(|_| format!("Invalid DTSTART '{}'", dtstart))?;
+            (naive.date(), Some(naive.time()), tz)
+        };
+
+        let mut impact = props.get("PRIORITY").and_then(|(_, v)| {
+            match v.trim().parse::<u8>().ok()? {
+                1..=4 => Some("high".to_string()),
+                5 => Some("medium".to_string()),
+                6..=9 => Some("low".to_string()),
+                _ => None,
+            }
+        });
+        let mut currency = None;
+        if let Some((_, categories)) = props.get("CATEGORIES") {
+            for category in Self::unescape_ics(categories).split(',') {
+                if currency.is_none() {
+                    currency = Self::normalize_currency(category);
+                }
+                if impact.is_none() {
+                    impact = Self::normalize_impact(category);
+                }
+            }
+        }
+        let currency = currency.or_else(|| default_currency.and_then(Self::normalize_currency));
+
+        let description = props
+            .get("DESCRIPTION")
+            .map(|(_, v)| Self::unescape_ics(v).trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        let external_uid = props
+            .get("UID")
+            .map(|(_, v)| format!("ics:{}", v.trim()))
+            .unwrap_or_else(|| Self::csv_uid(event_date, event_time, &title, currency.as_deref()));
+
+        Ok(ParsedEvent {
+            event_date,
+            event_time,
+            timezone,
+            title,
+            currency,
+            impact,
+            description,
+            external_uid,
+        })
+    }
+
+    /// Parses a CSV with a header row. Required columns: `date` and `title`
+    /// (or `event`). Optional: `time`, `timezone`, `currency`, `impact`,
+    /// `description`. Rows are numbered from 1, excluding the header.
+    pub fn parse_csv(
+        content: &str,
+        default_tz: Tz,
+        default_currency: Option<&str>,
+    ) -> (Vec<ParsedEvent>, Vec<EconomicEventImportError>) {
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(content.as_bytes());
+
+        let headers: Vec<String> = match reader.headers() {
+            Ok(h) => h.iter().map(|s| s.to_lowercase()).collect(),
+            Err(e) => {
+                errors.push(EconomicEventImportError {
+                    row: 0,
+                    error: format!("Invalid CSV header: {}", e),
+                });
+                return (events, errors);
+            }
+        };
+        let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+        let date_col = column(&["date", "event_date"]);
+        let title_col = column(&["title", "event", "name"]);
+        let time_col = column(&["time", "event_time"]);
+        let tz_col = column(&["timezone", "tz"]);
+        let currency_col = column(&["currency", "ccy"]);
+        let impact_col = column(&["impact", "importance"]);
+        let description_col = column(&["description", "notes"]);
+
+        let (Some(date_col), Some(title_col)) = (date_col, title_col) else {
+            errors.push(EconomicEventImportError {
+                row: 0,
+                error: "CSV must have 'date' and 'title' columns".to_string(),
+            });
+            return (events, errors);
+        };
+
+        for (index, record) in reader.records().enumerate() {
+            let row = index + 1;
+            let record = match record {
+                Ok(r) => r,
+                Err(e) => {
+                    errors.push(EconomicEventImportError { row, error: e.to_string() });
+                    continue;
+                }
+            };
+            let field = |col: Option<usize>| {
+                col.and_then(|c| record.get(c))
+                    .map(str::to_string)
+                    .filter(|s| !s.is_empty())
+            };
+
+            match Self::csv_event(
+                field(Some(date_col)),
+                field(Some(title_col)),
+                field(time_col),
+                field(tz_col),
+                field(currency_col).or_else(|| default_currency.map(str::to_string)),
+                field(impact_col),
+                field(description_col),
+                default_tz,
+            ) {
+                Ok(event) => events.push(event),
+                Err(error) => errors.push(EconomicEventImportError { row, error }),
+            }
+        }
+
+        (events, errors)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn csv_event(
+        date: Option<String>,
+        title: Option<String>,
+        time: Option<String>,
+        timezone: Option<String>,
+        currency: Option<String>,
+        impact: Option<String>,
+        description: Option<String>,
+        default_tz: Tz,
+    ) -> Result<ParsedEvent, String> {
+        let date = date.ok_or("Missing date")?;
+        let event_date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(&date, "%m/%d/%Y"))
+            .map_err(|_| format!("Invalid date '{}'", date))?;
+        let title = title.ok_or("Missing title")?;
+        if title.len() > 255 {
+            return Err("Title must be 255 characters or fewer".to_string());
+        }
+
+        let event_time = match time.as_deref() {
+            None => None,
+            Some(t) if t.eq_ignore_ascii_case("all day") || t.eq_ignore_ascii_case("tentative") => {
+                None
+            }
+            Some(t) => Some(
+                NaiveTime::parse_from_str(t, "%H:%M")
+                    .or_else(|_| NaiveTime::parse_from_str(t, "%H:%M:%S"))
+                    .or_else(|_| NaiveTime::parse_from_str(&t.to_uppercase(), "%I:%M%p"))
+                    .map_err(|_| format!("Invalid time '{}'", t))?,
+            ),
+        };
+
+        let timezone = match timezone {
+            Some(name) => name
+                .parse::<Tz>()
+                .map_err(|_| format!("Unknown timezone '{}'", name))?,
+            None => default_tz,
+        };
+
+        let currency = match currency {
+            Some(c) => Some(
+                Self::normalize_currency(&c).ok_or_else(|| format!("Invalid currency '{}'", c))?,
+            ),
+            None => None,
+        };
+
+        let impact = match impact {
+            Some(i) => Some(Self::normalize_impact(&i).ok_or_else(|| format!("Invalid impact '{}'", i))?),
+            None => None,
+        };
+
+        Ok(ParsedEvent {
+            external_uid: Self::csv_uid(event_date, event_time, &title, currency.as_deref()),
+            event_date,
+            event_time,
+            timezone,
+            title,
+            currency,
+            impact,
+            description,
+        })
+    }
+
+    /// Upserts parsed events for a user. Returns (inserted, updated).
+    pub async fn store_events(
+        pool: &PgPool,
+        user_id: Uuid,
+        source: &str,
+        events: &[ParsedEvent],
+    ) -> AppResult<(usize, usize)> {
+        let mut tx = pool.begin().await?;
+        let (mut inserted, mut updated) = (0, 0);
+
+        for event in events {
+            let was_inserted = sqlx::query_scalar::<_, bool>(
+                r#"
+                INSERT INTO economic_events (
+                    user_id, event_date, event_time, timezone, event_at,
+                    title, currency, impact, description, source, external_uid
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (user_id, external_uid) DO UPDATE SET
+                    event_date = EXCLUDED.event_date,
+                    event_time = EXCLUDED.event_time,
+                    timezone = EXCLUDED.timezone,
+                    event_at = EXCLUDED.event_at,
+                    title = EXCLUDED.title,
+                    currency = EXCLUDED.currency,
+                    impact = EXCLUDED.impact,
+                    description = EXCLUDED.description
+                RETURNING (xmax = 0)
+                "#,
+            )
+            .bind(user_id)
+            .bind(event.event_date)
+            .bind(event.event_time)
+            .bind(event.timezone.name())
+            .bind(event.event_at())
+            .bind(&event.title)
+            .bind(&event.currency)
+            .bind(&event.impact)
+            .bind(&event.description)
+            .bind(source)
+            .bind(&event.external_uid)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if was_inserted {
+                inserted += 1;
+            } else {
+                updated += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok((inserted, updated))
+    }
+
+    /// Links trades to high-impact events within `window_minutes` of entry and
+    /// applies the "High-Impact News" tag. Existing links in scope are rebuilt,
+    /// so rerunning with a narrower window also removes stale tags. Scope is a
+    /// single trade, or all of the user's trades when `trade_id` is `None`.
+    /// Returns the number of trades tagged.
+    pub async fn tag_trades(
+        pool: &PgPool,
+        user_id: Uuid,
+        trade_id: Option<Uuid>,
+        window_minutes: i32,
+    ) -> AppResult<u64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM trade_economic_events tee
+            USING trades t
+            WHERE tee.trade_id = t.id AND t.user_id = $1 AND ($2::uuid IS NULL OR t.id = $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO trade_economic_events (trade_id, event_id, minutes_from_event)
+            SELECT
+                t.id,
+                e.id,
+                ROUND(EXTRACT(EPOCH FROM (t.entry_date - e.event_at)) / 60)::INTEGER
+            FROM trades t
+            JOIN economic_events e
+                ON (e.user_id = t.user_id OR e.user_id IS NULL)
+               AND e.impact = 'high'
+               AND e.event_at BETWEEN t.entry_date - make_interval(mins => $3)
+                                  AND t.entry_date + make_interval(mins => $3)
+            WHERE t.user_id = $1 AND ($2::uuid IS NULL OR t.id = $2)
+            ON CONFLICT (trade_id, event_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade_id)
+        .bind(window_minutes)
+        .execute(&mut *tx)
+        .await?;
+
+        let tag_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO tags (user_id, name, category, color, icon)
+            VALUES ($1, $2, 'market_condition', '#dc2626', 'Newspaper')
+            ON CONFLICT (user_id, name) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(NEWS_TAG_NAME)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM trade_tags tt
+            USING trades t
+            WHERE tt.trade_id = t.id AND tt.tag_id = $3
+              AND t.user_id = $1 AND ($2::uuid IS NULL OR t.id = $2)
+              AND NOT EXISTS (SELECT 1 FROM trade_economic_events tee WHERE tee.trade_id = t.id)
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade_id)
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO trade_tags (trade_id, tag_id)
+            SELECT DISTINCT tee.trade_id, $3
+            FROM trade_economic_events tee
+            JOIN trades t ON t.id = tee.trade_id
+            WHERE t.user_id = $1 AND ($2::uuid IS NULL OR t.id = $2)
+            ON CONFLICT (trade_id, tag_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade_id)
+        .bind(tag_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let tagged = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(DISTINCT tee.trade_id)
+            FROM trade_economic_events tee
+            JOIN trades t ON t.id = tee.trade_id
+            WHERE t.user_id = $1 AND ($2::uuid IS NULL OR t.id = $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(trade_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(tagged as u64)
+    }
+
+    /// Tags a newly written trade. Failures are logged rather than surfaced
+    /// so tagging never fails the trade write itself.
+    pub async fn tag_trade(pool: &PgPool, user_id: Uuid, trade_id: Uuid) {
+        if let Err(e) = Self::tag_trades(pool, user_id, Some(trade_id), DEFAULT_PROXIMITY_MINUTES).await {
+            tracing::warn!(trade_id = %trade_id, error = %e, "Failed to tag trade with economic events");
+        }
+    }
+
+    /// Events falling on `date` in the user's timezone, for daily plans.
+    pub async fn events_for_day(
+        pool: &PgPool,
+        user_id: Uuid,
+        date: NaiveDate,
+    ) -> AppResult<Vec<EconomicEvent>> {
+        let tz = StreakService::user_timezone(pool, user_id).await?;
+
+        let events = sqlx::query_as::<_, EconomicEvent>(
+            r#"
+            SELECT * FROM economic_events
+            WHERE (user_id = $1 OR user_id IS NULL)
+              AND (
+                (event_at IS NOT NULL AND (event_at AT TIME ZONE $2)::date = $3)
+                OR (event_at IS NULL AND event_date = $3)
+              )
+            ORDER BY event_at NULLS FIRST, title
+            "#,
+        )
+        .bind(user_id)
+        .bind(tz.name())
+        .bind(date)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::New_York;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:cpi-2026-10\r\n\
+DTSTART:20261014T123000Z\r\n\
+SUMMARY:CPI m/m\r\n\
+CATEGORIES:USD,High\r\n\
+DESCRIPTION:Consumer prices\\, all items\r\n\
+\x20 including food\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART;TZID=Europe/London:20261015T090000\r\n\
+SUMMARY:GDP q/q\r\n\
+PRIORITY:5\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+DTSTART;VALUE=DATE:20261012\r\n\
+SUMMARY:Bank Holiday\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Missing start\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_parse_ics_events() {
+        let (events, errors) = EconomicCalendarService::parse_ics(ICS, New_York, None);
+        assert_eq!(events.len(), 3);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 4);
+
+        let cpi = &events[0];
+        assert_eq!(cpi.title, "CPI m/m");
+        assert_eq!(cpi.currency.as_deref(), Some("USD"));
+        assert_eq!(cpi.impact.as_deref(), Some("high"));
+        assert_eq!(cpi.event_time, NaiveTime::from_hms_opt(8, 30, 0));
+        assert_eq!(
+            cpi.description.as_deref(),
+            Some("Consumer prices, all items including food")
+        );
+        assert_eq!(cpi.external_uid, "ics:cpi-2026-10");
+        assert_eq!(
+            cpi.event_at(),
+            Some(Utc.with_ymd_and_hms(2026, 10, 14, 12, 30, 0).unwrap())
+        );
+
+        let gdp = &events[1];
+        assert_eq!(gdp.timezone, chrono_tz::Europe::London);
+        assert_eq!(gdp.impact.as_deref(), Some("medium"));
+        assert_eq!(
+            gdp.event_at(),
+            Some(Utc.with_ymd_and_hms(2026, 10, 15, 8, 0, 0).unwrap())
+        );
+
+        assert_eq!(events[2].event_time, None);
+        assert_eq!(events[2].event_at(), None);
+    }
+
+    #[test]
+    fn test_parse_csv_events() {
+        let csv = "Date,Time,Event,Currency,Impact\n\
+2026-10-14,08:30,CPI m/m,usd,High\n\
+10/29/2026,2:00pm,FOMC Statement,USD,3\n\
+2026-10-30,All Day,Bank Holiday,JPY,low\n\
+2026-13-01,08:30,Bad date,USD,high\n\
+2026-10-31,08:30,Bad impact,USD,extreme\n";
+
+        let (events, errors) = EconomicCalendarService::parse_csv(csv, New_York, None);
+        assert_eq!(events.len(), 3);
+        assert_eq!(errors.iter().map(|e| e.row).collect::<Vec<_>>(), vec![4, 5]);
+
+        assert_eq!(events[0].currency.as_deref(), Some("USD"));
+        assert_eq!(events[1].event_time, NaiveTime::from_hms_opt(14, 0, 0));
+        assert_eq!(events[1].impact.as_deref(), Some("high"));
+        assert_eq!(events[2].event_time, None);
+        assert_eq!(events[0].external_uid, "csv:2026-10-14:08:30:cpi m/m:USD");
+    }
+
+    #[test]
+    fn test_parse_csv_requires_columns() {
+        let (events, errors) =
+            EconomicCalendarService::parse_csv("when,what\n2026-10-14,CPI\n", New_York, None);
+        assert!(events.is_empty());
+        assert_eq!(errors[0].row, 0);
+    }
+
+    #[test]
+    fn test_normalize_impact() {
+        assert_eq!(EconomicCalendarService::normalize_impact("High Impact Expected").as_deref(), Some("high"));
+        assert_eq!(EconomicCalendarService::normalize_impact("2").as_deref(), Some("medium"));
+        assert_eq!(EconomicCalendarService::normalize_impact("none"), None);
+    }
+
+    #[test]
+    fn test_resolve_event_at_handles_dst_gap() {
+        // 02:30 on 2026-03-08 does not exist in New York.
+        let date = NaiveDate::from_ymd_opt(2026, 3, 8).unwrap();
+        let at = EconomicCalendarService::resolve_event_at(date, NaiveTime::from_hms_opt(2, 30, 0), New_York);
+        assert_eq!(at, Some(Utc.with_ymd_and_hms(2026, 3, 8, 7, 30, 0).unwrap()));
+    }
+}
@@ -0,0 +1,307 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::services::escape_html;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+/// `SMTP_TLS` values: STARTTLS upgrade (port 587), implicit TLS (port 465),
+/// or plaintext for local sinks such as Mailpit.
+pub const SMTP_TLS_MODES: &[&str] = &["starttls", "tls", "none"];
+const DEFAULT_FROM: &str = "TradeMaster AI <noreply@trademaster.ai>";
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Transactional emails the API sends. Bodies are rendered as plain text
+/// plus an equivalent HTML alternative.
+#[derive(Debug, Clone, Copy)]
+pub enum EmailTemplate<'a> {
+    VerifyEmail { link: &'a str },
+    PasswordReset { link: &'a str },
+    PasswordChanged,
+}
+
+impl EmailTemplate<'_> {
+    pub fn subject(&self) -> &'static str {
+        match self {
+            Self::VerifyEmail { .. } => "Verify your TradeMaster AI email address",
+            Self::PasswordReset { .. } => "Reset your TradeMaster AI password",
+            Self::PasswordChanged => "Your TradeMaster AI password was changed",
+        }
+    }
+
+    /// Paragraphs of the message and the optional call-to-action link.
+    fn content(&self) -> (Vec<&'static str>, Option<(&'static str, &str)>) {
+        match self {
+            Self::VerifyEmail { link } => (
+                vec![
+                    "Confirm this address to finish setting up your journal.",
+                    "The link expires in 24 hours. If you did not sign up, ignore this email.",
+                ],
+                Some(("Verify email", *link)),
+            ),
+            Self::PasswordReset { link } => (
+                vec![
+                    "Someone asked to reset the password for this account.",
+                    "The link expires in 1 hour and works once. If it was not you, ignore this email.",
+                ],
+                Some(("Reset password", *link)),
+            ),
+            Self::PasswordChanged => (
+                vec![
+                    "The password for this account was just changed and all sessions were signed out.",
+                    "If this was not you, reset your password immediately and contact support.",
+                ],
+                None,
+            ),
+        }
+    }
+
+    pub fn text_body(&self) -> String {
+        let (paragraphs, action) = self.content();
+        let mut body = String::from("Hi,\n\n");
+        for paragraph in paragraphs {
+            body.push_str(paragraph);
+            body.push_str("\n\n");
+        }
+        if let Some((label, link)) = action {
+            body.push_str(&format!("{}:\n{}\n\n", label, link));
+        }
+        body.push_str("- TradeMaster AI\n");
+        body
+    }
+
+    pub fn html_body(&self) -> String {
+        let (paragraphs, action) = self.content();
+        let mut body = String::from("<!DOCTYPE html>\n<html>\n<body>\n<p>Hi,</p>\n");
+        for paragraph in paragraphs {
+            body.push_str(&format!("<p>{}</p>\n", escape_html(paragraph)));
+        }
+        if let Some((label, link)) = action {
+            body.push_str(&format!(
+                "<p><a href=\"{}\">\n{}</a></p>\n",
+                escape_html(link),
+                escape_html(label)
+            ));
+        }
+        body.push_str("<p>- TradeMaster AI</p>\n</body>\n</html>\n");
+        body
+    }
+}
+
+/// Sends templated transactional email over SMTP. Without `SMTP_HOST` the
+/// messages are logged instead, so local development works without a relay.
+pub struct EmailService {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    app_base_url: String,
+}
+
+impl EmailService {
+    pub fn new(config: &Config) -> Self {
+        let transport = config.smtp_host.as_deref().and_then(|host| {
+            let builder = match config.smtp_tls.as_str() {
+                "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+                "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+                _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            };
+            let mut builder = match builder {
+                Ok(builder) => builder.timeout(Some(SMTP_TIMEOUT)),
+                Err(e) => {
+                    tracing::error!(host = %host, error = %e, "Invalid SMTP relay, email disabled");
+                    return None;
+                }
+            };
+            if let Some(port) = config.smtp_port {
+                builder = builder.port(port);
+            }
+            if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+                builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+            Some(builder.build())
+        });
+
+        Self {
+            transport,
+            // Config::validate has already rejected unparseable senders
+            from: Self::parse_from(config.smtp_from_email.as_deref())
+                .unwrap_or_else(|_| DEFAULT_FROM.parse().expect("default sender is valid")),
+            app_base_url: config.app_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Parse `SMTP_FROM_EMAIL`, either a bare address or `Name <address>`.
+    pub fn parse_from(from: Option<&str>) -> Result<Mailbox, String> {
+        from.unwrap_or(DEFAULT_FROM)
+            .parse()
+            .map_err(|e| format!("invalid sender address: {}", e))
+    }
+
+    /// Link into the web app carrying a signed token, e.g. `/verify-email`.
+    pub fn link(&self, path: &str, token: &str) -> String {
+        format!("{}{}?token={}", self.app_base_url, path, token)
+    }
+
+    pub async fn send(&self, to: &str, template: &EmailTemplate<'_>) -> AppResult<()> {
+        let recipient: Mailbox = to
+            .parse()
+            .map_err(|_| AppError::Validation("Invalid email address".to_string()))?;
+
+        let Some(transport) = &self.transport else {
+            tracing::warn!(to = %to, subject = template.subject(), "SMTP is not configured; email not sent");
+            tracing::debug!(body = %template.text_body(), "Unsent email body");
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(recipient)
+            .subject(template.subject())
+            .multipart(MultiPart::alternative_plain_html(
+                template.text_body(),
+                template.html_body(),
+            ))
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        transport.send(message).await.map_err(|e| {
+            tracing::error!(to = %to, error = %e, "SMTP send failed");
+            AppError::Internal(format!("Failed to send email: {}", e))
+        })?;
+
+        tracing::info!(to = %to, subject = template.subject(), "Email sent");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal SMTP sink: accepts every envelope and keeps the raw DATA of
+    /// each message.
+    async fn smtp_sink() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let received = messages.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    let mut data: Option<String> = None;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if let Some(body) = data.as_mut() {
+                            if line == "." {
+                                received.lock().unwrap().push(data.take().unwrap());
+                                writer.write_all(b"250 queued\r\n").await.unwrap();
+                            } else {
+                                body.push_str(&line);
+                                body.push('\n');
+                            }
+                            continue;
+                        }
+                        let reply: &[u8] = match line.get(..4).map(|c| c.to_ascii_uppercase()).as_deref() {
+                            Some("EHLO") => b"250-sink\r\n250 8BITMIME\r\n",
+                            Some("DATA") => {
+                                data = Some(String::new());
+                                b"354 go ahead\r\n"
+                            }
+                            Some("QUIT") => {
+                                writer.write_all(b"221 bye\r\n").await.unwrap();
+                                break;
+                            }
+                            _ => b"250 ok\r\n",
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (port, messages)
+    }
+
+    fn sink_config(port: u16) -> Config {
+        Config {
+            smtp_host: Some("127.0.0.1".to_string()),
+            smtp_port: Some(port),
+            smtp_from_email: Some("Journal <journal@example.com>".to_string()),
+            smtp_tls: "none".to_string(),
+            app_base_url: "http://app.test/".to_string(),
+            ..Config::for_tests()
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_verification_email_to_smtp_sink() {
+        let (port, messages) = smtp_sink().await;
+        let service = EmailService::new(&sink_config(port));
+        let link = service.link("/verify-email", "abc.def");
+        assert_eq!(link, "http://app.test/verify-email?token=abc.def");
+
+        service
+            .send("trader@example.com", &EmailTemplate::VerifyEmail { link: &link })
+            .await
+            .unwrap();
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        let raw = &messages[0];
+        assert!(raw.contains("From: Journal <journal@example.com>"));
+        assert!(raw.contains("To: trader@example.com"));
+        assert!(raw.contains("Subject: Verify your TradeMaster AI email address"));
+        assert!(raw.contains("multipart/alternative"));
+        assert!(raw.contains("http://app.test/verify-email?token=abc.def"));
+        // The HTML part is quoted-printable; undo soft breaks and `=3D`
+        let html = raw.replace("=\n", "").replace("=3D", "=");
+        assert!(html.contains("<a href=\"http://app.test/verify-email?token=abc.def\">"));
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_recipient_without_sending() {
+        let (port, messages) = smtp_sink().await;
+        let service = EmailService::new(&sink_config(port));
+
+        let result = service.send("not an address", &EmailTemplate::PasswordChanged).await;
+        assert!(matches!(result, Err(AppError::Validation(_))));
+        assert!(messages.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn logs_instead_of_sending_without_smtp_host() {
+        let mut config = sink_config(25);
+        config.smtp_host = None;
+        let service = EmailService::new(&config);
+
+        assert!(service.send("trader@example.com", &EmailTemplate::PasswordChanged).await.is_ok());
+    }
+
+    #[test]
+    fn templates_escape_links_and_omit_missing_actions() {
+        let link = "http://app.test/reset-password?token=a&b=<c>";
+        let template = EmailTemplate::PasswordReset { link };
+        assert!(template.text_body().contains(link));
+        assert!(template
+            .html_body()
+            .contains("href=\"http://app.test/reset-password?token=a&amp;b=&lt;c&gt;\""));
+
+        let changed = EmailTemplate::PasswordChanged;
+        assert!(!changed.html_body().contains("<a "));
+        assert!(changed.text_body().contains("sessions were signed out"));
+    }
+
+    #[test]
+    fn parses_sender_addresses() {
+        assert!(EmailService::parse_from(None).is_ok());
+        assert!(EmailService::parse_from(Some("noreply@example.com")).is_ok());
+        assert!(EmailService::parse_from(Some("Desk <desk@example.com>")).is_ok());
+        assert!(EmailService::parse_from(Some("not an address")).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{EmailToken, EMAIL_TOKEN_PASSWORD_RESET};
+use crate::services::AuthService;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const VERIFY_EMAIL_TTL_HOURS: i64 = 24;
+const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+/// Per user and purpose: one email a minute, five a day.
+const MIN_SEND_INTERVAL_SECONDS: i64 = 60;
+const MAX_SENDS_PER_DAY: usize = 5;
+
+pub fn email_token_ttl(purpose: &str) -> Duration {
+    if purpose == EMAIL_TOKEN_PASSWORD_RESET {
+        Duration::minutes(PASSWORD_RESET_TTL_MINUTES)
+    } else {
+        Duration::hours(VERIFY_EMAIL_TTL_HOURS)
+    }
+}
+
+/// `recent` holds the creation times of tokens issued in the last day.
+pub fn check_send_rate(recent: &[DateTime<Utc>], now: DateTime<Utc>) -> Result<(), String> {
+    if let Some(last) = recent.iter().max() {
+        let wait = MIN_SEND_INTERVAL_SECONDS - (now - *last).num_seconds();
+        if wait > 0 {
+            return Err(format!("Please wait {} seconds before requesting another email", wait));
+        }
+    }
+
+    let today = recent.iter().filter(|t| now - **t < Duration::days(1)).count();
+    if today >= MAX_SENDS_PER_DAY {
+        return Err("Too many emails requested today; try again tomorrow".to_string());
+    }
+
+    Ok(())
+}
+
+/// Issues and consumes the signed, single-use links sent for email
+/// verification and password resets.
+pub struct EmailTokenService;
+
+impl EmailTokenService {
+    /// Record a new token and return its signed form. Earlier unused tokens
+    /// for the same purpose stop working, so only the latest link is valid.
+    pub async fn issue(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        email: &str,
+        purpose: &str,
+    ) -> AppResult<String> {
+        let mut tx = pool.begin().await?;
+
+        // Serialise concurrent requests for the same user so the rate limit holds
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let recent = sqlx::query_scalar::<_, DateTime<Utc>>(
+            r#"
+            SELECT created_at FROM email_tokens
+            WHERE user_id = $1 AND purpose = $2 AND created_at > NOW() - INTERVAL '1 day'
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        check_send_rate(&recent, Utc::now()).map_err(AppError::RateLimited)?;
+
+        sqlx::query(
+            r#"
+            UPDATE email_tokens SET used_at = NOW()
+            WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose)
+        .execute(&mut *tx)
+        .await?;
+
+        let expires_at = Utc::now() + email_token_ttl(purpose);
+        let token_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO email_tokens (user_id, purpose, email, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(purpose)
+        .bind(email)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let token = auth_service.generate_email_token(user_id, token_id, purpose, expires_at)?;
+        tx.commit().await?;
+
+        Ok(token)
+    }
+
+    /// Verify the signature and mark the token used in one statement, so a
+    /// link can only ever be redeemed once.
+    pub async fn consume(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        token: &str,
+        purpose: &str,
+    ) -> AppResult<EmailToken> {
+        let (user_id, token_id) = auth_service.verify_email_token(token, purpose)?;
+
+        sqlx::query_as::<_, EmailToken>(
+            r#"
+            UPDATE email_tokens SET used_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND purpose = $3
+                AND used_at IS NULL AND expires_at > NOW()
+            RETURNING user_id, email
+            "#,
+        )
+        .bind(token_id)
+        .bind(user_id)
+        .bind(purpose)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("This link is invalid or has expired".to_string()))
+    }
+
+    /// Guard for sensitive actions that need a confirmed address.
+    pub async fn require_verified_email(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+        let verified = sqlx::query_scalar::<_, Option<bool>>(
+            "SELECT email_verified FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten()
+        .unwrap_or(false);
+
+        if !verified {
+            return Err(AppError::EmailNotVerified(
+                "Verify your email address to continue".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::EMAIL_TOKEN_VERIFY_EMAIL;
+
+    #[test]
+    fn reset_links_expire_sooner_than_verification_links() {
+        assert_eq!(email_token_ttl(EMAIL_TOKEN_PASSWORD_RESET), Duration::hours(1));
+        assert_eq!(email_token_ttl(EMAIL_TOKEN_VERIFY_EMAIL), Duration::hours(24));
+    }
+
+    #[test]
+    fn send_rate_allows_first_email() {
+        assert!(check_send_rate(&[], Utc::now()).is_ok());
+    }
+
+    #[test]
+    fn send_rate_enforces_minimum_interval() {
+        let now = Utc::now();
+        let err = check_send_rate(&[now - Duration::seconds(20)], now).unwrap_err();
+        assert!(err.contains("40 seconds"));
+        assert!(check_send_rate(&[now - Duration::seconds(61)], now).is_ok());
+    }
+
+    #[test]
+    fn send_rate_caps_daily_volume() {
+        let now = Utc::now();
+        let mut sent: Vec<_> = (1..=4).map(|h| now - Duration::hours(h)).collect();
+        assert!(check_send_rate(&sent, now).is_ok());
+
+        sent.push(now - Duration::hours(5));
+        assert!(check_send_rate(&sent, now).unwrap_err().contains("today"));
+
+        // Older sends fall out of the window
+        sent[4] = now - Duration::hours(25);
+        assert!(check_send_rate(&sent, now).is_ok());
+    }
+}
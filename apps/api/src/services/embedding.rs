@@ -0,0 +1,273 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::services::llm::{http_clients, send_checked};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use std::sync::Arc;
+
+pub const LOCAL_EMBEDDING_MODEL: &str = "local-hash-384";
+pub const DEFAULT_OPENAI_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+pub const EMBEDDING_PROVIDERS: &[&str] = &["local", "openai", "none"];
+
+const LOCAL_DIMENSIONS: usize = 384;
+/// Inputs per request to a remote embedding API.
+const MAX_BATCH: usize = 64;
+
+/// Short words that carry no meaning for matching journal notes.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "had", "has", "have",
+    "i", "if", "in", "into", "is", "it", "its", "me", "my", "of", "on", "or", "so", "that",
+    "the", "then", "there", "this", "to", "was", "were", "when", "where", "which", "while",
+    "with", "you", "your",
+];
+
+/// Turns text into vectors for semantic search. Vectors from different
+/// models are not comparable, so each stored embedding records `model()`.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn model(&self) -> &str;
+
+    async fn embed(&self, texts: &[String]) -> AppResult<Vec<Vec<f32>>>;
+}
+
+/// `None` when semantic search is disabled (`EMBEDDING_PROVIDER=none`).
+pub fn embedder_from_config(config: &Config) -> Option<Arc<dyn Embedder>> {
+    match config.embedding_provider.as_str() {
+        "openai" => Some(Arc::new(OpenAiEmbedder::new(
+            config.openai_api_key.clone(),
+            &config.openai_base_url,
+            config
+                .embedding_model
+                .clone()
+                .unwrap_or_else(|| DEFAULT_OPENAI_EMBEDDING_MODEL.to_string()),
+        ))),
+        "none" => None,
+        _ => Some(Arc::new(LocalEmbedder)),
+    }
+}
+
+/// Lowercased word stems with stopwords removed. Shared with snippet
+/// highlighting so both agree on what counts as a match.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .map(|w| stem(&w))
+        .collect()
+}
+
+/// Crude suffix stripping so "chased", "chasing" and "chase" collide.
+fn stem(word: &str) -> String {
+    for suffix in ["ing", "ed", "es", "s"] {
+        if let Some(base) = word.strip_suffix(suffix) {
+            if base.chars().count() >= 3 {
+                return base.trim_end_matches('e').to_string();
+            }
+        }
+    }
+    word.trim_end_matches('e').to_string()
+}
+
+/// FNV-1a, chosen because it is stable across builds and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Deterministic, offline embedder: hashed word stems and word pairs,
+/// L2-normalized. It only captures shared vocabulary, but needs no network
+/// and gives identical vectors on every run, which is what tests need.
+pub struct LocalEmbedder;
+
+impl LocalEmbedder {
+    pub fn embed_one(text: &str) -> Vec<f32> {
+        let mut vector = vec![0f32; LOCAL_DIMENSIONS];
+        let tokens = tokenize(text);
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % LOCAL_DIMENSIONS as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign * weight;
+        };
+        for token in &tokens {
+            add(token, 1.0);
+        }
+        for pair in tokens.windows(2) {
+            add(&format!("{} {}", pair[0], pair[1]), 0.5);
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl Embedder for LocalEmbedder {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn model(&self) -> &str {
+        LOCAL_EMBEDDING_MODEL
+    }
+
+    async fn embed(&self, texts: &[String]) -> AppResult<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|t| Self::embed_one(t)).collect())
+    }
+}
+
+/// Any OpenAI-compatible `/embeddings` endpoint, sharing the chat
+/// provider's key and base URL.
+pub struct OpenAiEmbedder {
+    api_key: Option<String>,
+    base_url: String,
+    model: String,
+    client: Client,
+}
+
+impl OpenAiEmbedder {
+    pub fn new(api_key: Option<String>, base_url: &str, model: String) -> Self {
+        let (client, _) = http_clients();
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiEmbedder {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> AppResult<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            let mut request = self
+                .client
+                .post(format!("{}/embeddings", self.base_url))
+                .json(&json!({"model": self.model, "input": batch}));
+            if let Some(key) = self.api_key.as_deref().filter(|k| !k.is_empty()) {
+                request = request.bearer_auth(key);
+            }
+
+            let body: serde_json::Value = send_checked(request, "openai")
+                .await?
+                .json()
+                .await
+                .map_err(|e| AppError::AiError(format!("Invalid embedding response: {}", e)))?;
+
+            let mut data = body["data"]
+                .as_array()
+                .cloned()
+                .ok_or_else(|| AppError::AiError("Embedding response has no data".to_string()))?;
+            data.sort_by_key(|item| item["index"].as_u64().unwrap_or(0));
+            if data.len() != batch.len() {
+                return Err(AppError::AiError(format!(
+                    "Expected {} embeddings, got {}",
+                    batch.len(),
+                    data.len()
+                )));
+            }
+            for item in data {
+                let vector = item["embedding"]
+                    .as_array()
+                    .ok_or_else(|| AppError::AiError("Embedding is missing".to_string()))?
+                    .iter()
+                    .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+                    .collect();
+                vectors.push(vector);
+            }
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+
+    #[test]
+    fn test_local_embedder_is_deterministic_and_normalized() {
+        let a = LocalEmbedder::embed_one("Chased the open on NVDA and got stopped out");
+        let b = LocalEmbedder::embed_one("Chased the open on NVDA and got stopped out");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), LOCAL_DIMENSIONS);
+        let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert!(LocalEmbedder::embed_one("the and of").iter().all(|x| *x == 0.0));
+    }
+
+    #[test]
+    fn test_local_embedder_ranks_shared_vocabulary_higher() {
+        let query = LocalEmbedder::embed_one("trades where I chased the open");
+        let related = LocalEmbedder::embed_one("I was chasing the opening candle again, entered late");
+        let unrelated = LocalEmbedder::embed_one("Slept badly and skipped the gym");
+        assert!(cosine_similarity(&query, &related) > cosine_similarity(&query, &unrelated));
+    }
+
+    #[test]
+    fn test_tokenize_stems_and_drops_stopwords() {
+        assert_eq!(tokenize("I chased the Open"), vec!["chas", "open"]);
+        assert_eq!(tokenize("chasing"), tokenize("chased"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_embedder_against_mock_api() {
+        let app = Router::new().route(
+            "/v1/embeddings",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                let inputs = body["input"].as_array().unwrap().len();
+                // Returned out of order; the embedder must sort by index.
+                let data = (0..inputs)
+                    .rev()
+                    .map(|i| json!({"index": i, "embedding": [i as f32, 1.0]}))
+                    .collect::<Vec<_>>();
+                Json(json!({"data": data, "model": body["model"]}))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let embedder = OpenAiEmbedder::new(
+            None,
+            &format!("http://{}/v1/", addr),
+            DEFAULT_OPENAI_EMBEDDING_MODEL.to_string(),
+        );
+        let vectors = embedder
+            .embed(&["one".to_string(), "two".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(vectors, vec![vec![0.0, 1.0], vec![1.0, 1.0]]);
+    }
+}
@@ -0,0 +1,946 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::services::ai_context::estimate_tokens;
+use crate::models::{
+    ClaudeContentBlock, ClaudeMessage, ClaudeMessageContent, ClaudeRequest, ClaudeResponse,
+};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Streams have no overall deadline; instead the connection is dropped if
+/// no bytes (including pings) arrive for this long.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const DEFAULT_ANTHROPIC_MODEL: &str = "claude-sonnet-4-20250514";
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+pub const FAKE_MODEL: &str = "fake-deterministic";
+pub const AI_PROVIDERS: &[&str] = &["anthropic", "openai", "fake"];
+
+/// One model call. The provider supplies the model name.
+#[derive(Debug, Clone)]
+pub struct LlmRequest {
+    pub messages: Vec<ClaudeMessage>,
+    pub max_tokens: i32,
+}
+
+impl LlmRequest {
+    /// Text of every message, images left out.
+    pub fn prompt_text(&self) -> String {
+        self.messages
+            .iter()
+            .map(|m| match &m.content {
+                ClaudeMessageContent::Text(text) => text.clone(),
+                ClaudeMessageContent::Blocks(blocks) => blocks
+                    .iter()
+                    .filter_map(|b| match b {
+                        ClaudeContentBlock::Text { text } => Some(text.as_str()),
+                        ClaudeContentBlock::Image { .. } => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlmCompletion {
+    pub text: String,
+    /// Model reported by the provider, falling back to the configured one.
+    pub model: String,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+}
+
+impl LlmCompletion {
+    pub fn tokens(&self) -> i32 {
+        self.input_tokens + self.output_tokens
+    }
+
+    pub fn usage(&self) -> LlmUsage {
+        LlmUsage {
+            model: self.model.clone(),
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+        }
+    }
+}
+
+/// Token counts for one call, as metered by `AiUsageService`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlmUsage {
+    pub model: String,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+}
+
+impl LlmUsage {
+    pub fn tokens(&self) -> i32 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChatStreamOutcome {
+    Completed(LlmCompletion),
+    /// The consumer stopped accepting deltas; the upstream request was
+    /// cancelled and nothing should be persisted. Carries the usage so far,
+    /// estimated where the provider hadn't reported it yet, so the partial
+    /// call is still metered.
+    ClientDisconnected(LlmUsage),
+}
+
+/// A chat-completion backend. Retries and the circuit breaker live in
+/// `AiService`; providers make exactly one attempt per call.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Stable identifier recorded alongside each review.
+    fn name(&self) -> &'static str;
+
+    fn model(&self) -> &str;
+
+    /// Fails with a user-facing error when required credentials are missing.
+    fn check_configured(&self) -> AppResult<()>;
+
+    async fn complete(&self, request: &LlmRequest) -> AppResult<LlmCompletion>;
+
+    /// Calls `on_delta` with each text fragment as it arrives. Returning
+    /// false from `on_delta` cancels the request. (The explicit `for<'a>`
+    /// stops `async_trait` from tying fragments to the call's lifetime.)
+    async fn stream(
+        &self,
+        request: &LlmRequest,
+        on_delta: &mut (dyn for<'a> FnMut(&'a str) -> bool + Send),
+    ) -> AppResult<ChatStreamOutcome>;
+}
+
+/// Builds the provider selected by `AI_PROVIDER`.
+pub fn provider_from_config(config: &Config) -> Arc<dyn LlmProvider> {
+    let model = config.ai_model.clone();
+    match config.ai_provider.as_str() {
+        "openai" => Arc::new(OpenAiProvider::new(
+            config.openai_api_key.clone(),
+            &config.openai_base_url,
+            model.unwrap_or_else(|| DEFAULT_OPENAI_MODEL.to_string()),
+        )),
+        "fake" => Arc::new(FakeProvider::new(model.unwrap_or_else(|| FAKE_MODEL.to_string()))),
+        _ => Arc::new(AnthropicProvider::new(
+            config.anthropic_api_key.clone(),
+            &config.anthropic_base_url,
+            model.unwrap_or_else(|| DEFAULT_ANTHROPIC_MODEL.to_string()),
+        )),
+    }
+}
+
+/// Splits a server-sent event byte stream into `data` payloads. Bytes are
+/// buffered until a blank line so multi-byte characters split across
+/// chunks survive.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend(chunk.iter().filter(|b| **b != b'\r'));
+
+        let mut payloads = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let event = self.buffer.drain(..end + 2).collect::<Vec<_>>();
+            let data = String::from_utf8_lossy(&event)
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|d| d.strip_prefix(' ').unwrap_or(d))
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !data.is_empty() {
+                payloads.push(data);
+            }
+        }
+        payloads
+    }
+}
+
+/// Accumulated state of one streamed message.
+#[derive(Debug, Default)]
+pub struct StreamState {
+    pub text: String,
+    pub model: Option<String>,
+    pub input_tokens: i32,
+    pub output_tokens: i32,
+    pub finished: bool,
+}
+
+fn parse_event(data: &str) -> AppResult<serde_json::Value> {
+    serde_json::from_str(data).map_err(|e| {
+        tracing::error!(error = %e, "Failed to parse AI stream event");
+        AppError::AiError("Unexpected response from AI service.".to_string())
+    })
+}
+
+fn as_i32(value: &serde_json::Value) -> i32 {
+    value.as_i64().unwrap_or(0) as i32
+}
+
+impl StreamState {
+    /// Applies one Anthropic streaming event and returns any new text.
+    pub fn apply_anthropic(&mut self, data: &str) -> AppResult<Option<String>> {
+        let event = parse_event(data)?;
+
+        match event["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                self.model = event["message"]["model"].as_str().map(str::to_string);
+                self.input_tokens = as_i32(&event["message"]["usage"]["input_tokens"]);
+                self.output_tokens = as_i32(&event["message"]["usage"]["output_tokens"]);
+            }
+            "content_block_delta" if event["delta"]["type"] == "text_delta" => {
+                return Ok(self.push_text(event["delta"]["text"].as_str()));
+            }
+            "message_delta" => {
+                self.output_tokens = as_i32(&event["usage"]["output_tokens"]);
+            }
+            "message_stop" => self.finished = true,
+            "error" => {
+                tracing::error!(event = %data, "Anthropic stream error");
+                let message = if event["error"]["type"] == "overloaded_error" {
+                    "AI service is overloaded. Please try again shortly."
+                } else {
+                    "AI service returned an error. Please try again."
+                };
+                return Err(AppError::AiError(message.to_string()));
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Applies one OpenAI-style `chat.completion.chunk` and returns any new
+    /// text. Usage arrives in a final chunk with no choices.
+    pub fn apply_openai(&mut self, data: &str) -> AppResult<Option<String>> {
+        if data.trim() == "[DONE]" {
+            self.finished = true;
+            return Ok(None);
+        }
+
+        let event = parse_event(data)?;
+        if event.get("error").is_some() {
+            tracing::error!(event = %data, "OpenAI-compatible stream error");
+            return Err(AppError::AiError(
+                "AI service returned an error. Please try again.".to_string(),
+            ));
+        }
+        if let Some(model) = event["model"].as_str() {
+            self.model = Some(model.to_string());
+        }
+        if event["usage"].is_object() {
+            self.input_tokens = as_i32(&event["usage"]["prompt_tokens"]);
+            self.output_tokens = as_i32(&event["usage"]["completion_tokens"]);
+        }
+        Ok(self.push_text(event["choices"][0]["delta"]["content"].as_str()))
+    }
+
+    fn push_text(&mut self, text: Option<&str>) -> Option<String> {
+        let text = text.filter(|t| !t.is_empty())?;
+        self.text.push_str(text);
+        Some(text.to_string())
+    }
+
+    /// Usage of a stream cut short. Anthropic reports input tokens up front
+    /// but OpenAI-style servers only at the end, so missing counts are
+    /// estimated from the prompt and the text received.
+    fn usage_so_far(&self, request: &LlmRequest, configured_model: &str) -> LlmUsage {
+        let input_tokens = if self.input_tokens > 0 {
+            self.input_tokens
+        } else {
+            estimate_tokens(&request.prompt_text()) as i32
+        };
+        LlmUsage {
+            model: self.model.clone().unwrap_or_else(|| configured_model.to_string()),
+            input_tokens,
+            output_tokens: self.output_tokens.max(estimate_tokens(&self.text) as i32),
+        }
+    }
+
+    fn into_completion(self, configured_model: &str) -> AppResult<LlmCompletion> {
+        if !self.finished || self.text.is_empty() {
+            return Err(AppError::AiError(
+                "AI response ended unexpectedly. Please try again.".to_string(),
+            ));
+        }
+        Ok(LlmCompletion {
+            text: self.text,
+            model: self.model.unwrap_or_else(|| configured_model.to_string()),
+            input_tokens: self.input_tokens,
+            output_tokens: self.output_tokens,
+        })
+    }
+}
+
+pub fn http_clients() -> (Client, Client) {
+    let client = Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .connect_timeout(Duration::from_secs(10))
+        .pool_max_idle_per_host(4)
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    let stream_client = Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .pool_max_idle_per_host(4)
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    (client, stream_client)
+}
+
+/// Sends the request and maps HTTP-level failures to safe errors.
+pub async fn send_checked(request: reqwest::RequestBuilder, provider: &str) -> AppResult<reqwest::Response> {
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            AppError::AiError("AI request timed out. Please try again.".to_string())
+        } else if e.is_connect() {
+            AppError::AiError("Could not reach AI service.".to_string())
+        } else {
+            AppError::AiError(format!("AI request failed: {}", e))
+        }
+    })?;
+
+    let status = response.status();
+
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+
+        // Log the full error server-side, return a safe message to the client
+        tracing::error!(provider, status = %status, body = %error_text, "AI API error");
+
+        return match status.as_u16() {
+            401 => Err(AppError::AiError(
+                "AI service authentication failed. Check API key.".to_string(),
+            )),
+            429 => Err(AppError::AiError(
+                "AI rate limit reached. Please wait and try again.".to_string(),
+            )),
+            400 => Err(AppError::Validation(
+                "Invalid request to AI service.".to_string(),
+            )),
+            _ => Err(AppError::AiError(
+                "AI service returned an error. Please try again.".to_string(),
+            )),
+        };
+    }
+
+    Ok(response)
+}
+
+/// Pulls `data` payloads off a streaming response, enforcing the idle timeout.
+struct SseReader {
+    response: reqwest::Response,
+    parser: SseParser,
+    pending: VecDeque<String>,
+}
+
+impl SseReader {
+    fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Next payload, or `None` once the body ends.
+    async fn next(&mut self) -> AppResult<Option<String>> {
+        while self.pending.is_empty() {
+            match tokio::time::timeout(STREAM_IDLE_TIMEOUT, self.response.chunk()).await {
+                Ok(Ok(Some(chunk))) => self.pending.extend(self.parser.push(&chunk)),
+                Ok(Ok(None)) => return Ok(None),
+                Ok(Err(e)) => {
+                    tracing::warn!(error = %e, "AI stream interrupted");
+                    return Err(AppError::AiError("AI response was interrupted. Please try again.".to_string()));
+                }
+                Err(_) => {
+                    return Err(AppError::AiError("AI response stalled. Please try again.".to_string()));
+                }
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Anthropic Messages API.
+pub struct AnthropicProvider {
+    api_key: Option<String>,
+    base_url: String,
+    model: String,
+    client: Client,
+    stream_client: Client,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: Option<String>, base_url: &str, model: String) -> Self {
+        let (client, stream_client) = http_clients();
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            client,
+            stream_client,
+        }
+    }
+
+    fn request(&self, client: &Client, request: &LlmRequest, stream: bool) -> reqwest::RequestBuilder {
+        client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", self.api_key.as_deref().unwrap_or_default())
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(&ClaudeRequest {
+                model: self.model.clone(),
+                max_tokens: request.max_tokens,
+                messages: request.messages.clone(),
+                stream,
+            })
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn check_configured(&self) -> AppResult<()> {
+        self.api_key.as_ref().map(|_| ()).ok_or_else(|| {
+            AppError::AiError("AI service is not configured. Set ANTHROPIC_API_KEY.".to_string())
+        })
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> AppResult<LlmCompletion> {
+        let response = send_checked(self.request(&self.client, request, false), self.name()).await?;
+
+        let claude_response: ClaudeResponse = response.json().await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to parse Claude response");
+            AppError::AiError("Unexpected response from AI service.".to_string())
+        })?;
+
+        let text = claude_response
+            .content
+            .iter()
+            .filter(|c| c.content_type == "text")
+            .map(|c| c.text.as_str())
+            .collect::<String>();
+
+        if text.is_empty() {
+            return Err(AppError::AiError(
+                "AI returned an empty response.".to_string(),
+            ));
+        }
+
+        Ok(LlmCompletion {
+            text,
+            model: claude_response.model.unwrap_or_else(|| self.model.clone()),
+            input_tokens: claude_response.usage.input_tokens,
+            output_tokens: claude_response.usage.output_tokens,
+        })
+    }
+
+    async fn stream(
+        &self,
+        request: &LlmRequest,
+        on_delta: &mut (dyn for<'a> FnMut(&'a str) -> bool + Send),
+    ) -> AppResult<ChatStreamOutcome> {
+        let response =
+            send_checked(self.request(&self.stream_client, request, true), self.name()).await?;
+
+        let mut reader = SseReader::new(response);
+        let mut state = StreamState::default();
+        while !state.finished {
+            let Some(data) = reader.next().await? else { break };
+            if let Some(delta) = state.apply_anthropic(&data)? {
+                if !on_delta(&delta) {
+                    return Ok(ChatStreamOutcome::ClientDisconnected(
+                        state.usage_so_far(request, &self.model),
+                    ));
+                }
+            }
+        }
+        Ok(ChatStreamOutcome::Completed(state.into_completion(&self.model)?))
+    }
+}
+
+/// Any server speaking the OpenAI chat completions API (OpenAI itself,
+/// vLLM, Ollama, llama.cpp, LM Studio...). The API key is optional because
+/// self-hosted servers usually don't check one.
+pub struct OpenAiProvider {
+    api_key: Option<String>,
+    base_url: String,
+    model: String,
+    client: Client,
+    stream_client: Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_key: Option<String>, base_url: &str, model: String) -> Self {
+        let (client, stream_client) = http_clients();
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            client,
+            stream_client,
+        }
+    }
+
+    /// Converts messages to the chat completions format; images become
+    /// base64 data URLs.
+    pub fn to_openai_messages(messages: &[ClaudeMessage]) -> Vec<serde_json::Value> {
+        messages
+            .iter()
+            .map(|message| {
+                let content = match &message.content {
+                    ClaudeMessageContent::Text(text) => json!(text),
+                    ClaudeMessageContent::Blocks(blocks) => json!(blocks
+                        .iter()
+                        .map(|block| match block {
+                            ClaudeContentBlock::Text { text } => json!({"type": "text", "text": text}),
+                            ClaudeContentBlock::Image { source } => json!({
+                                "type": "image_url",
+                                "image_url": {
+                                    "url": format!("data:{};base64,{}", source.media_type, source.data)
+                                }
+                            }),
+                        })
+                        .collect::<Vec<_>>()),
+                };
+                json!({"role": message.role, "content": content})
+            })
+            .collect()
+    }
+
+    fn request(&self, client: &Client, request: &LlmRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": request.max_tokens,
+            "messages": Self::to_openai_messages(&request.messages),
+        });
+        if stream {
+            body["stream"] = json!(true);
+            body["stream_options"] = json!({"include_usage": true});
+        }
+
+        let builder = client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&body);
+        match self.api_key {
+            Some(ref key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn check_configured(&self) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> AppResult<LlmCompletion> {
+        let response = send_checked(self.request(&self.client, request, false), self.name()).await?;
+
+        let body: serde_json::Value = response.json().await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to parse chat completion response");
+            AppError::AiError("Unexpected response from AI service.".to_string())
+        })?;
+
+        let text = body["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        if text.is_empty() {
+            return Err(AppError::AiError(
+                "AI returned an empty response.".to_string(),
+            ));
+        }
+
+        Ok(LlmCompletion {
+            text,
+            model: body["model"].as_str().unwrap_or(&self.model).to_string(),
+            input_tokens: as_i32(&body["usage"]["prompt_tokens"]),
+            output_tokens: as_i32(&body["usage"]["completion_tokens"]),
+        })
+    }
+
+    async fn stream(
+        &self,
+        request: &LlmRequest,
+        on_delta: &mut (dyn for<'a> FnMut(&'a str) -> bool + Send),
+    ) -> AppResult<ChatStreamOutcome> {
+        let response =
+            send_checked(self.request(&self.stream_client, request, true), self.name()).await?;
+
+        let mut reader = SseReader::new(response);
+        let mut state = StreamState::default();
+        while !state.finished {
+            let Some(data) = reader.next().await? else { break };
+            if let Some(delta) = state.apply_openai(&data)? {
+                if !on_delta(&delta) {
+                    return Ok(ChatStreamOutcome::ClientDisconnected(
+                        state.usage_so_far(request, &self.model),
+                    ));
+                }
+            }
+        }
+        Ok(ChatStreamOutcome::Completed(state.into_completion(&self.model)?))
+    }
+}
+
+/// Deterministic offline backend. Replies come from a queue when one is
+/// supplied, otherwise from canned answers keyed on the prompt, so the app
+/// runs end to end without network access. Token counts are estimated at
+/// four characters per token.
+pub struct FakeProvider {
+    model: String,
+    replies: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<LlmRequest>>,
+}
+
+impl FakeProvider {
+    pub fn new(model: String) -> Self {
+        Self {
+            model,
+            replies: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_replies(replies: Vec<String>) -> Self {
+        let provider = Self::new(FAKE_MODEL.to_string());
+        *provider.replies.lock().unwrap() = replies.into();
+        provider
+    }
+
+    /// Every request received so far, oldest first.
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<LlmRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn canned_reply(prompt: &str) -> String {
+        if prompt.contains("\"overall_score\"") {
+            json!({
+                "overall_score": 6.5,
+                "execution_quality_score": 6.0,
+                "risk_management_score": 7.0,
+                "plan_adherence_score": 6.5,
+                "thesis_alignment_score": null,
+                "strengths": ["Position size stayed within plan"],
+                "weaknesses": ["Entry was taken before confirmation"],
+                "key_lesson": "Wait for the setup's trigger before entering.",
+                "actionable_fixes": ["Write the entry trigger down before the open"],
+                "alternative_scenario": null,
+                "emotional_state_detected": "neutral"
+            })
+            .to_string()
+        } else if prompt.contains("\"key_levels\"") {
+            json!({
+                "pattern": {"name": "range", "confidence": 0.5, "description": "Sideways consolidation."},
+                "entry_quality": {"rating": "fair", "assessment": "Entry near the middle of the range."},
+                "stop_placement": {"rating": "fair", "assessment": "Stop sits inside recent noise.", "suggested_stop": null},
+                "key_levels": [],
+                "summary": "Offline analysis: no chart was inspected."
+            })
+            .to_string()
+        } else if prompt.contains("\"aggregation\"") {
+            json!({
+                "filters": {"status": "Closed"},
+                "aggregation": {"kind": "stats"},
+                "explanation": "Offline interpretation: statistics over all closed trades."
+            })
+            .to_string()
+        } else if prompt.contains("\"goals_next_period\"") {
+            json!({
+                "what_went_well": "Offline draft: I kept position sizes within plan.",
+                "what_to_improve": "Offline draft: wait for confirmation before entering.",
+                "key_lessons": ["Review the journal before drafting goals"],
+                "rules_broken": [],
+                "goals_next_period": ["Write the entry trigger down before the open"]
+            })
+            .to_string()
+        } else {
+            format!(
+                "Offline reply from the fake AI provider ({} characters of context received).",
+                prompt.chars().count()
+            )
+        }
+    }
+
+    fn respond(&self, request: &LlmRequest) -> LlmCompletion {
+        self.requests.lock().unwrap().push(request.clone());
+        let prompt = request.prompt_text();
+        let text = self
+            .replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Self::canned_reply(&prompt));
+
+        LlmCompletion {
+            input_tokens: estimate_tokens(&prompt) as i32,
+            output_tokens: estimate_tokens(&text) as i32,
+            model: self.model.clone(),
+            text,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for FakeProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn check_configured(&self) -> AppResult<()> {
+        Ok(())
+    }
+
+    async fn complete(&self, request: &LlmRequest) -> AppResult<LlmCompletion> {
+        Ok(self.respond(request))
+    }
+
+    async fn stream(
+        &self,
+        request: &LlmRequest,
+        on_delta: &mut (dyn for<'a> FnMut(&'a str) -> bool + Send),
+    ) -> AppResult<ChatStreamOutcome> {
+        let completion = self.respond(request);
+        let mut forwarded = 0;
+        for word in completion.text.split_inclusive(' ') {
+            forwarded += word.len();
+            if !on_delta(word) {
+                return Ok(ChatStreamOutcome::ClientDisconnected(LlmUsage {
+                    output_tokens: estimate_tokens(&completion.text[..forwarded]) as i32,
+                    ..completion.usage()
+                }));
+            }
+        }
+        Ok(ChatStreamOutcome::Completed(completion))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{header, HeaderMap},
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+
+    type Captured = Arc<Mutex<Vec<(HeaderMap, serde_json::Value)>>>;
+
+    fn request(text: &str) -> LlmRequest {
+        LlmRequest {
+            messages: vec![ClaudeMessage::text("user", text)],
+            max_tokens: 256,
+        }
+    }
+
+    /// Stand-in for an OpenAI-compatible server.
+    async fn mock_chat_completions(
+        State(captured): State<Captured>,
+        headers: HeaderMap,
+        Json(body): Json<serde_json::Value>,
+    ) -> Response {
+        let stream = body["stream"] == true;
+        captured.lock().unwrap().push((headers, body));
+        if !stream {
+            return Json(json!({
+                "model": "llama-3.1-8b-instruct",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Size down."}}],
+                "usage": {"prompt_tokens": 40, "completion_tokens": 3}
+            }))
+            .into_response();
+        }
+
+        let chunks = [
+            json!({"model": "llama-3.1-8b-instruct", "choices": [{"delta": {"role": "assistant"}}]}),
+            json!({"choices": [{"delta": {"content": "Size "}}]}),
+            json!({"choices": [{"delta": {"content": "down."}, "finish_reason": "stop"}]}),
+            json!({"choices": [], "usage": {"prompt_tokens": 40, "completion_tokens": 3}}),
+        ];
+        let mut body = chunks
+            .iter()
+            .map(|c| format!("data: {}\n\n", c))
+            .collect::<String>();
+        body.push_str("data: [DONE]\n\n");
+        ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response()
+    }
+
+    async fn mock_openai() -> (OpenAiProvider, Captured) {
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route("/v1/chat/completions", post(mock_chat_completions))
+            .with_state(captured.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let provider = OpenAiProvider::new(
+            Some("local".to_string()),
+            &format!("http://{}/v1/", addr),
+            "llama-3.1-8b-instruct".to_string(),
+        );
+        (provider, captured)
+    }
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"event: ping\r\ndata: {\"type\":").is_empty());
+        let payloads = parser.push(b"\"ping\"}\r\n\r\n: comment\n\ndata: \xC3");
+        assert_eq!(payloads, vec![r#"{"type":"ping"}"#.to_string()]);
+        // A multi-byte character split across chunks is reassembled.
+        assert_eq!(parser.push(b"\xA9\n\n"), vec!["\u{e9}".to_string()]);
+    }
+
+    #[test]
+    fn test_stream_state_accumulates_and_maps_errors() {
+        let mut state = StreamState::default();
+        state
+            .apply_anthropic(r#"{"type":"message_start","message":{"model":"claude-x","usage":{"input_tokens":10,"output_tokens":1}}}"#)
+            .unwrap();
+        let delta = state
+            .apply_anthropic(r#"{"type":"content_block_delta","delta":{"type":"text_delta","text":"Hi"}}"#)
+            .unwrap();
+        assert_eq!(delta.as_deref(), Some("Hi"));
+        state.apply_anthropic(r#"{"type":"message_delta","usage":{"output_tokens":7}}"#).unwrap();
+        state.apply_anthropic(r#"{"type":"message_stop"}"#).unwrap();
+        let completion = state.into_completion("configured").unwrap();
+        assert_eq!(
+            (completion.text.as_str(), completion.model.as_str(), completion.tokens()),
+            ("Hi", "claude-x", 17)
+        );
+
+        let err = StreamState::default()
+            .apply_anthropic(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#)
+            .unwrap_err();
+        assert!(matches!(err, AppError::AiError(msg) if msg.contains("overloaded")));
+    }
+
+    #[test]
+    fn test_openai_messages_inline_images() {
+        let messages = vec![ClaudeMessage {
+            role: "user".to_string(),
+            content: ClaudeMessageContent::Blocks(vec![
+                ClaudeContentBlock::Image {
+                    source: crate::models::ClaudeImageSource {
+                        source_type: "base64".to_string(),
+                        media_type: "image/png".to_string(),
+                        data: "AAAA".to_string(),
+                    },
+                },
+                ClaudeContentBlock::Text { text: "Describe".to_string() },
+            ]),
+        }];
+        let converted = OpenAiProvider::to_openai_messages(&messages);
+        assert_eq!(converted[0]["content"][0]["image_url"]["url"], "data:image/png;base64,AAAA");
+        assert_eq!(converted[0]["content"][1], json!({"type": "text", "text": "Describe"}));
+    }
+
+    #[tokio::test]
+    async fn test_openai_provider_complete_and_stream() {
+        let (provider, captured) = mock_openai().await;
+
+        let completion = provider.complete(&request("Review")).await.unwrap();
+        assert_eq!(completion.text, "Size down.");
+        assert_eq!((completion.input_tokens, completion.output_tokens), (40, 3));
+
+        let mut deltas = Vec::new();
+        let outcome = provider
+            .stream(&request("Review"), &mut |text| {
+                deltas.push(text.to_string());
+                true
+            })
+            .await
+            .unwrap();
+        assert_eq!(outcome, ChatStreamOutcome::Completed(completion));
+        assert_eq!(deltas, vec!["Size ", "down."]);
+
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured[0].0["authorization"], "Bearer local");
+        assert_eq!(captured[0].1["model"], "llama-3.1-8b-instruct");
+        assert_eq!(captured[0].1["messages"][0]["content"], "Review");
+        assert_eq!(captured[1].1["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn test_fake_provider_is_deterministic() {
+        let provider = FakeProvider::new(FAKE_MODEL.to_string());
+        let first = provider.complete(&request("How did I do?")).await.unwrap();
+        let second = provider.complete(&request("How did I do?")).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.input_tokens, 4);
+        assert_eq!(provider.requests().len(), 2);
+
+        let review = provider
+            .complete(&request("Respond with JSON: {\"overall_score\": ...}"))
+            .await
+            .unwrap();
+        assert!(crate::services::AiService::parse_trade_review(&review.text).is_ok());
+
+        let draft = provider
+            .complete(&request("Respond with JSON: {\"goals_next_period\": ...}"))
+            .await
+            .unwrap();
+        assert!(crate::services::AiService::parse_review_draft(&draft.text).is_ok());
+
+        let query = provider
+            .complete(&request("Respond with JSON: {\"aggregation\": ...}"))
+            .await
+            .unwrap();
+        assert!(crate::services::AiService::parse_trade_query(&query.text).is_ok());
+
+        let scripted = FakeProvider::with_replies(vec!["one two three".to_string()]);
+        let mut received = 0;
+        let outcome = scripted
+            .stream(&request("x"), &mut |_| {
+                received += 1;
+                received < 2
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            ChatStreamOutcome::ClientDisconnected(LlmUsage {
+                model: FAKE_MODEL.to_string(),
+                input_tokens: 1,
+                output_tokens: 2,
+            })
+        );
+    }
+}
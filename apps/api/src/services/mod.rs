@@ -1,9 +1,55 @@
 pub mod auth;
 pub mod trade;
 pub mod ai;
+pub mod llm;
+pub mod ai_usage;
+pub mod ai_context;
 pub mod risk;
+pub mod ruleset;
+pub mod accountability;
+pub mod notification;
+pub mod comment;
+pub mod streak;
+pub mod economic_calendar;
+pub mod storage;
+pub mod annotation;
+pub mod embedding;
+pub mod search;
+pub mod trade_query;
+pub mod pattern_mining;
+pub mod email;
+pub mod email_token;
+pub mod session;
+pub mod two_factor;
+pub mod oauth;
+pub mod api_key;
+pub mod profile;
+pub mod session_analytics;
 
 pub use auth::*;
 pub use trade::*;
 pub use ai::*;
+pub use llm::*;
+pub use ai_usage::*;
+pub use ai_context::*;
 pub use risk::*;
+pub use ruleset::*;
+pub use accountability::*;
+pub use notification::*;
+pub use comment::*;
+pub use streak::*;
+pub use economic_calendar::*;
+pub use storage::*;
+pub use annotation::*;
+pub use embedding::*;
+pub use search::*;
+pub use trade_query::*;
+pub use pattern_mining::*;
+pub use email::*;
+pub use email_token::*;
+pub use session::*;
+pub use two_factor::*;
+pub use oauth::*;
+pub use api_key::*;
+pub use profile::*;
+pub use session_analytics::*;
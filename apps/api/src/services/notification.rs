@@ -0,0 +1,68 @@
+use crate::error::AppResult;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Kinds of in-app notification. Stored as the `kind` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Comment,
+    CommentMention,
+    ReviewRequested,
+    ReviewCleared,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Comment => "comment",
+            NotificationKind::CommentMention => "comment_mention",
+            NotificationKind::ReviewRequested => "review_requested",
+            NotificationKind::ReviewCleared => "review_cleared",
+        }
+    }
+}
+
+pub struct NotificationService;
+
+impl NotificationService {
+    /// Queues one notification per recipient. Delivery failures are logged
+    /// rather than surfaced so they never fail the action that triggered them.
+    pub async fn notify(
+        pool: &PgPool,
+        recipients: &[(Uuid, NotificationKind)],
+        title: &str,
+        body: Option<&str>,
+        data: serde_json::Value,
+    ) {
+        for (user_id, kind) in recipients {
+            if let Err(e) = Self::insert(pool, *user_id, *kind, title, body, &data).await {
+                tracing::warn!(user_id = %user_id, error = %e, "Failed to deliver notification");
+            }
+        }
+    }
+
+    async fn insert(
+        pool: &PgPool,
+        user_id: Uuid,
+        kind: NotificationKind,
+        title: &str,
+        body: Option<&str>,
+        data: &serde_json::Value,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO notifications (user_id, kind, title, body, data)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(user_id)
+        .bind(kind.as_str())
+        .bind(title)
+        .bind(body)
+        .bind(data)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
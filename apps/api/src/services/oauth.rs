@@ -0,0 +1,927 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::{ExternalIdentity, OAuthAuthorizeResponse, OAuthIdentity, OAuthProviderInfo, User};
+use crate::services::{ApiKeyService, AuthService, SessionService, SESSION_REVOKED_PASSWORD_RESET};
+use chrono::{Duration, Utc};
+use oauth2::basic::BasicClient;
+use oauth2::reqwest::async_http_client;
+use oauth2::{
+    AuthType, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse, TokenUrl,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+pub const OAUTH_PROVIDER_GOOGLE: &str = "google";
+pub const OAUTH_PROVIDER_GITHUB: &str = "github";
+pub const OAUTH_PROVIDER_OIDC: &str = "oidc";
+
+const OAUTH_STATE_TTL_MINUTES: i64 = 10;
+const HTTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+const GITHUB_AUTH_URL: &str = "https://github.com/login/oauth/authorize";
+const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+#[derive(Debug, Clone)]
+struct ProviderEndpoints {
+    auth_url: String,
+    token_url: String,
+    userinfo_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OidcDiscovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+enum ProviderKind {
+    /// Endpoints come from `{issuer}/.well-known/openid-configuration`,
+    /// fetched on first use.
+    Oidc {
+        issuer: String,
+        endpoints: OnceCell<ProviderEndpoints>,
+    },
+    /// GitHub OAuth apps aren't OIDC; the profile and verified emails come
+    /// from its REST API.
+    GitHub {
+        auth_url: String,
+        token_url: String,
+        api_url: String,
+    },
+}
+
+pub struct OAuthProvider {
+    id: String,
+    name: String,
+    client_id: String,
+    client_secret: Option<String>,
+    kind: ProviderKind,
+}
+
+impl OAuthProvider {
+    fn oidc(id: &str, name: &str, issuer: &str, client_id: &str, client_secret: Option<&str>) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.map(str::to_string),
+            kind: ProviderKind::Oidc {
+                issuer: issuer.trim_end_matches('/').to_string(),
+                endpoints: OnceCell::new(),
+            },
+        }
+    }
+
+    fn github(
+        client_id: &str,
+        client_secret: Option<&str>,
+        auth_url: &str,
+        token_url: &str,
+        api_url: &str,
+    ) -> Self {
+        Self {
+            id: OAUTH_PROVIDER_GITHUB.to_string(),
+            name: "GitHub".to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.map(str::to_string),
+            kind: ProviderKind::GitHub {
+                auth_url: auth_url.to_string(),
+                token_url: token_url.to_string(),
+                api_url: api_url.trim_end_matches('/').to_string(),
+            },
+        }
+    }
+
+    fn scopes(&self) -> &'static [&'static str] {
+        match self.kind {
+            ProviderKind::Oidc { .. } => &["openid", "email", "profile"],
+            ProviderKind::GitHub { .. } => &["read:user", "user:email"],
+        }
+    }
+
+    async fn endpoints(&self, http: &reqwest::Client) -> AppResult<ProviderEndpoints> {
+        match &self.kind {
+            ProviderKind::Oidc { issuer, endpoints } => endpoints
+                .get_or_try_init(|| discover(http, issuer))
+                .await
+                .cloned(),
+            ProviderKind::GitHub {
+                auth_url,
+                token_url,
+                api_url,
+            } => Ok(ProviderEndpoints {
+                auth_url: auth_url.clone(),
+                token_url: token_url.clone(),
+                userinfo_url: format!("{}/user", api_url),
+            }),
+        }
+    }
+
+    fn client(&self, endpoints: &ProviderEndpoints, redirect_url: &str) -> AppResult<BasicClient> {
+        let invalid = |e: oauth2::url::ParseError| {
+            AppError::Internal(format!("Invalid OAuth URL for {}: {}", self.id, e))
+        };
+
+        Ok(BasicClient::new(
+            ClientId::new(self.client_id.clone()),
+            self.client_secret.clone().map(ClientSecret::new),
+            AuthUrl::new(endpoints.auth_url.clone()).map_err(invalid)?,
+            Some(TokenUrl::new(endpoints.token_url.clone()).map_err(invalid)?),
+        )
+        .set_auth_type(AuthType::RequestBody)
+        .set_redirect_uri(RedirectUrl::new(redirect_url.to_string()).map_err(invalid)?))
+    }
+
+    async fn fetch_identity(
+        &self,
+        http: &reqwest::Client,
+        endpoints: &ProviderEndpoints,
+        access_token: &str,
+    ) -> AppResult<ExternalIdentity> {
+        let profile = http
+            .get(&endpoints.userinfo_url)
+            .bearer_auth(access_token)
+            .header("Accept", "application/json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        match &self.kind {
+            ProviderKind::Oidc { .. } => {
+                let subject = profile["sub"]
+                    .as_str()
+                    .ok_or_else(|| AppError::Internal("OIDC userinfo has no subject".to_string()))?;
+                // Some providers send the flag as a string
+                let email_verified = match &profile["email_verified"] {
+                    Value::Bool(verified) => *verified,
+                    Value::String(verified) => verified == "true",
+                    _ => false,
+                };
+                Ok(ExternalIdentity {
+                    provider: self.id.clone(),
+                    subject: subject.to_string(),
+                    email: profile["email"].as_str().map(str::to_string),
+                    email_verified,
+                })
+            }
+            ProviderKind::GitHub { api_url, .. } => {
+                let subject = profile["id"]
+                    .as_i64()
+                    .ok_or_else(|| AppError::Internal("GitHub user has no id".to_string()))?;
+
+                let emails = http
+                    .get(format!("{}/user/emails", api_url))
+                    .bearer_auth(access_token)
+                    .header("Accept", "application/vnd.github+json")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Vec<GitHubEmail>>()
+                    .await?;
+
+                let email = emails
+                    .iter()
+                    .filter(|e| e.verified)
+                    .max_by_key(|e| e.primary)
+                    .map(|e| e.email.clone());
+
+                Ok(ExternalIdentity {
+                    provider: self.id.clone(),
+                    subject: subject.to_string(),
+                    email_verified: email.is_some(),
+                    email: email.or_else(|| profile["email"].as_str().map(str::to_string)),
+                })
+            }
+        }
+    }
+}
+
+async fn discover(http: &reqwest::Client, issuer: &str) -> AppResult<ProviderEndpoints> {
+    let document = http
+        .get(format!("{}/.well-known/openid-configuration", issuer))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<OidcDiscovery>()
+        .await?;
+
+    // Per OIDC discovery, the document must name the issuer it was fetched for
+    if document.issuer.trim_end_matches('/') != issuer {
+        return Err(AppError::Internal(format!(
+            "OIDC discovery issuer mismatch: expected {}, got {}",
+            issuer, document.issuer
+        )));
+    }
+
+    Ok(ProviderEndpoints {
+        auth_url: document.authorization_endpoint,
+        token_url: document.token_endpoint,
+        userinfo_url: document.userinfo_endpoint,
+    })
+}
+
+fn hash_state(state: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(state.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// A provider redirect ready to send the browser to.
+#[derive(Debug)]
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub pkce_verifier: String,
+}
+
+/// OAuth2 authorization code flow with PKCE for Google, GitHub and one
+/// generic OIDC provider, plus linking the resulting identities to users.
+pub struct OAuthService {
+    providers: Vec<OAuthProvider>,
+    redirect_url: String,
+    http: reqwest::Client,
+}
+
+impl OAuthService {
+    pub fn new(config: &Config) -> Self {
+        let mut providers = Vec::new();
+
+        if let Some(client_id) = &config.google_client_id {
+            providers.push(OAuthProvider::oidc(
+                OAUTH_PROVIDER_GOOGLE,
+                "Google",
+                GOOGLE_ISSUER,
+                client_id,
+                config.google_client_secret.as_deref(),
+            ));
+        }
+
+        if let Some(client_id) = &config.github_client_id {
+            providers.push(OAuthProvider::github(
+                client_id,
+                config.github_client_secret.as_deref(),
+                GITHUB_AUTH_URL,
+                GITHUB_TOKEN_URL,
+                GITHUB_API_URL,
+            ));
+        }
+
+        if let (Some(issuer), Some(client_id)) = (&config.oidc_issuer_url, &config.oidc_client_id) {
+            providers.push(OAuthProvider::oidc(
+                OAUTH_PROVIDER_OIDC,
+                &config.oidc_display_name,
+                issuer,
+                client_id,
+                config.oidc_client_secret.as_deref(),
+            ));
+        }
+
+        Self::with_providers(providers, &config.oauth_redirect_url)
+    }
+
+    fn with_providers(providers: Vec<OAuthProvider>, redirect_url: &str) -> Self {
+        Self {
+            providers,
+            redirect_url: redirect_url.to_string(),
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .user_agent(concat!("trademaster-api/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .expect("HTTP client configuration is valid"),
+        }
+    }
+
+    /// Configured providers, for rendering sign-in buttons.
+    pub fn providers(&self) -> Vec<OAuthProviderInfo> {
+        self.providers
+            .iter()
+            .map(|p| OAuthProviderInfo {
+                id: p.id.clone(),
+                name: p.name.clone(),
+            })
+            .collect()
+    }
+
+    fn provider(&self, id: &str) -> AppResult<&OAuthProvider> {
+        self.providers
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Sign-in provider '{}' is not configured", id)))
+    }
+
+    pub async fn authorization_url(&self, provider_id: &str) -> AppResult<AuthorizationRequest> {
+        let provider = self.provider(provider_id)?;
+        let endpoints = provider.endpoints(&self.http).await?;
+        let client = provider.client(&endpoints, &self.redirect_url)?;
+
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        let mut request = client
+            .authorize_url(CsrfToken::new_random)
+            .set_pkce_challenge(challenge);
+        for scope in provider.scopes() {
+            request = request.add_scope(Scope::new(scope.to_string()));
+        }
+        let (url, state) = request.url();
+
+        Ok(AuthorizationRequest {
+            url: url.to_string(),
+            state: state.secret().clone(),
+            pkce_verifier: verifier.secret().clone(),
+        })
+    }
+
+    pub async fn exchange_code(
+        &self,
+        provider_id: &str,
+        code: &str,
+        pkce_verifier: &str,
+    ) -> AppResult<ExternalIdentity> {
+        let provider = self.provider(provider_id)?;
+        let endpoints = provider.endpoints(&self.http).await?;
+        let client = provider.client(&endpoints, &self.redirect_url)?;
+
+        let token = client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.to_string()))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| {
+                tracing::warn!(provider = %provider_id, error = %e, "OAuth code exchange failed");
+                AppError::BadRequest("Sign-in with the provider failed; please try again".to_string())
+            })?;
+
+        provider
+            .fetch_identity(&self.http, &endpoints, token.access_token().secret())
+            .await
+    }
+
+    /// Stores state and PKCE verifier and returns where to send the browser,
+    /// along with a nonce only that browser knows. `link_user_id` marks a
+    /// link from account settings rather than a login.
+    pub async fn begin(
+        &self,
+        pool: &PgPool,
+        provider_id: &str,
+        link_user_id: Option<Uuid>,
+    ) -> AppResult<OAuthAuthorizeResponse> {
+        let request = self.authorization_url(provider_id).await?;
+        let browser_nonce = CsrfToken::new_random().secret().clone();
+
+        sqlx::query("DELETE FROM oauth_states WHERE expires_at < NOW()")
+            .execute(pool)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_states (
+                state_hash, provider, pkce_verifier, link_user_id, browser_nonce_hash, expires_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(hash_state(&request.state))
+        .bind(provider_id)
+        .bind(&request.pkce_verifier)
+        .bind(link_user_id)
+        .bind(hash_state(&browser_nonce))
+        .bind(Utc::now() + Duration::minutes(OAUTH_STATE_TTL_MINUTES))
+        .execute(pool)
+        .await?;
+
+        Ok(OAuthAuthorizeResponse {
+            authorization_url: request.url,
+            browser_nonce,
+        })
+    }
+
+    /// Consumes the state (once, unexpired, same flow, same browser) and
+    /// exchanges the code. Without the browser check, an attacker could stop
+    /// their own flow before the callback and get a victim to submit its
+    /// `code` and `state`, signing the victim into the attacker's account.
+    pub async fn complete(
+        &self,
+        pool: &PgPool,
+        state: &str,
+        code: &str,
+        browser_nonce: &str,
+        link_user_id: Option<Uuid>,
+    ) -> AppResult<ExternalIdentity> {
+        let invalid = || {
+            AppError::BadRequest("This sign-in attempt is invalid or has expired".to_string())
+        };
+
+        let (provider_id, pkce_verifier, stored_link_user_id, browser_nonce_hash) =
+            sqlx::query_as::<_, (String, String, Option<Uuid>, String)>(
+                r#"
+                DELETE FROM oauth_states
+                WHERE state_hash = $1 AND expires_at > NOW()
+                RETURNING provider, pkce_verifier, link_user_id, browser_nonce_hash
+                "#,
+            )
+            .bind(hash_state(state))
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(invalid)?;
+
+        if stored_link_user_id != link_user_id || browser_nonce_hash != hash_state(browser_nonce) {
+            return Err(invalid());
+        }
+
+        self.exchange_code(&provider_id, code, &pkce_verifier).await
+    }
+
+    /// Finds or creates the user for a provider login. Unknown identities
+    /// attach to an existing account only when the provider verified the
+    /// email address.
+    pub async fn resolve_login(
+        &self,
+        pool: &PgPool,
+        auth_service: &AuthService,
+        identity: &ExternalIdentity,
+    ) -> AppResult<User> {
+        let mut tx = pool.begin().await?;
+
+        let linked = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE oauth_identities i
+            SET last_login_at = NOW(), email = COALESCE($3, i.email)
+            FROM users u
+            WHERE i.provider = $1 AND i.subject = $2 AND u.id = i.user_id
+            RETURNING u.id, u.email, u.password_hash, u.email_verified, u.created_at, u.updated_at
+            "#,
+        )
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(user) = linked {
+            tx.commit().await?;
+            return Ok(user);
+        }
+
+        let provider_name = self.provider(&identity.provider)?.name.clone();
+        let email = identity.email.as_deref().ok_or_else(|| {
+            AppError::BadRequest(format!("{} did not share an email address", provider_name))
+        })?;
+
+        let existing = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, email, password_hash, email_verified, created_at, updated_at
+            FROM users
+            WHERE LOWER(email) = LOWER($1)
+            FOR UPDATE
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // A random, never-disclosed password: the account signs in through
+        // the provider until the user sets one via password reset
+        let unusable_password = auth_service.hash_password(&format!("{}{}", Uuid::new_v4(), Uuid::new_v4()))?;
+
+        let user = match existing {
+            Some(user) if !identity.email_verified => {
+                return Err(AppError::Conflict(format!(
+                    "An account with {} already exists. Sign in with your password and link {} from account settings.",
+                    user.email, provider_name
+                )));
+            }
+            Some(user) if !user.email_verified => {
+                // Whoever registered this address never proved they own it and
+                // the provider says our user does: drop their password, sessions and API keys
+                tracing::warn!(user_id = %user.id, provider = %identity.provider, "Unverified account claimed through OAuth");
+                SessionService::revoke_all(&mut *tx, user.id, None, SESSION_REVOKED_PASSWORD_RESET)
+                    .await?;
+                ApiKeyService::revoke_all(&mut *tx, user.id).await?;
+                sqlx::query_as::<_, User>(
+                    r#"
+                    UPDATE users
+                    SET email_verified = true, password_hash = $2, password_set = false, updated_at = NOW()
+                    WHERE id = $1
+                    RETURNING id, email, password_hash, email_verified, created_at, updated_at
+                    "#,
+                )
+                .bind(user.id)
+                .bind(&unusable_password)
+                .fetch_one(&mut *tx)
+                .await?
+            }
+            Some(user) => user,
+            None => {
+                let user = sqlx::query_as::<_, User>(
+                    r#"
+                    INSERT INTO users (email, password_hash, email_verified, password_set)
+                    VALUES ($1, $2, $3, false)
+                    RETURNING id, email, password_hash, email_verified, created_at, updated_at
+                    "#,
+                )
+                .bind(email)
+                .bind(&unusable_password)
+                .bind(identity.email_verified)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                sqlx::query("INSERT INTO user_profiles (user_id) VALUES ($1)")
+                    .bind(user.id)
+                    .execute(&mut *tx)
+                    .await?;
+
+                tracing::info!(user_id = %user.id, provider = %identity.provider, "User registered through OAuth");
+                user
+            }
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_identities (user_id, provider, subject, email, last_login_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            "#,
+        )
+        .bind(user.id)
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => AppError::Conflict(
+                format!("Another {} account is already linked to this user", provider_name),
+            ),
+            _ => AppError::from(e),
+        })?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    /// Attaches an identity to a signed-in user from account settings.
+    pub async fn link(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        identity: &ExternalIdentity,
+    ) -> AppResult<()> {
+        let provider_name = &self.provider(&identity.provider)?.name;
+
+        let owner = sqlx::query_scalar::<_, Uuid>(
+            "SELECT user_id FROM oauth_identities WHERE provider = $1 AND subject = $2",
+        )
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .fetch_optional(pool)
+        .await?;
+
+        match owner {
+            Some(owner) if owner == user_id => return Ok(()),
+            Some(_) => {
+                return Err(AppError::Conflict(format!(
+                    "This {} account is linked to a different user",
+                    provider_name
+                )))
+            }
+            None => {}
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO oauth_identities (user_id, provider, subject, email)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .execute(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => AppError::Conflict(
+                format!("A {} account is already linked; unlink it first", provider_name),
+            ),
+            _ => AppError::from(e),
+        })?;
+
+        tracing::info!(user_id = %user_id, provider = %identity.provider, "OAuth identity linked");
+        Ok(())
+    }
+
+    pub async fn identities(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<OAuthIdentity>> {
+        Ok(sqlx::query_as::<_, OAuthIdentity>(
+            r#"
+            SELECT * FROM oauth_identities WHERE user_id = $1 ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Refuses to remove the last way into an account without a password.
+    pub async fn unlink(pool: &PgPool, user_id: Uuid, identity_id: Uuid) -> AppResult<()> {
+        let mut tx = pool.begin().await?;
+
+        let password_set = sqlx::query_scalar::<_, bool>(
+            "SELECT password_set FROM users WHERE id = $1 FOR UPDATE",
+        )
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let remaining = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM oauth_identities WHERE user_id = $1 AND id <> $2",
+        )
+        .bind(user_id)
+        .bind(identity_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !password_set && remaining == 0 {
+            return Err(AppError::BadRequest(
+                "Set a password (via forgot password) before unlinking your only sign-in method"
+                    .to_string(),
+            ));
+        }
+
+        let result = sqlx::query("DELETE FROM oauth_identities WHERE id = $1 AND user_id = $2")
+            .bind(identity_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Linked account not found".to_string()));
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::State,
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    const REDIRECT_URL: &str = "http://app.test/oauth/callback";
+
+    /// PKCE challenge the mock IdP expects on the next token request.
+    type Challenge = Arc<Mutex<Option<String>>>;
+
+    async fn token(
+        State(challenge): State<Challenge>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> (StatusCode, Json<Value>) {
+        let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+        let computed = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        let expected = challenge.lock().unwrap().clone();
+
+        if form.get("grant_type").map(String::as_str) != Some("authorization_code")
+            || form.get("code").map(String::as_str) != Some("good-code")
+            || form.get("client_id").map(String::as_str) != Some("client-1")
+            || form.get("redirect_uri").map(String::as_str) != Some(REDIRECT_URL)
+            || expected.as_deref() != Some(computed.as_str())
+        {
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid_grant" })));
+        }
+
+        (
+            StatusCode::OK,
+            Json(json!({ "access_token": "at-123", "token_type": "Bearer", "expires_in": 3600 })),
+        )
+    }
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers.get("authorization").and_then(|h| h.to_str().ok()) == Some("Bearer at-123")
+    }
+
+    /// Local IdP speaking OIDC discovery/userinfo and GitHub's REST shape.
+    async fn mock_idp(issuer_override: Option<&'static str>) -> (String, Challenge) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let challenge: Challenge = Arc::new(Mutex::new(None));
+
+        let discovery_base = base.clone();
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move {
+                    Json(json!({
+                        "issuer": issuer_override.map(str::to_string).unwrap_or(discovery_base.clone()),
+                        "authorization_endpoint": format!("{}/authorize", discovery_base),
+                        "token_endpoint": format!("{}/token", discovery_base),
+                        "userinfo_endpoint": format!("{}/userinfo", discovery_base),
+                    }))
+                }),
+            )
+            .route("/token", post(token))
+            .route(
+                "/userinfo",
+                get(|headers: HeaderMap| async move {
+                    if !authorized(&headers) {
+                        return (StatusCode::UNAUTHORIZED, Json(json!({})));
+                    }
+                    (
+                        StatusCode::OK,
+                        Json(json!({ "sub": "oidc-user-1", "email": "trader@example.com", "email_verified": "true" })),
+                    )
+                }),
+            )
+            .route(
+                "/api/user",
+                get(|headers: HeaderMap| async move {
+                    if !authorized(&headers) {
+                        return (StatusCode::UNAUTHORIZED, Json(json!({})));
+                    }
+                    (StatusCode::OK, Json(json!({ "id": 42, "login": "octo", "email": null })))
+                }),
+            )
+            .route(
+                "/api/user/emails",
+                get(|| async {
+                    Json(json!([
+                        { "email": "old@example.com", "primary": false, "verified": true },
+                        { "email": "octo@example.com", "primary": true, "verified": true },
+                        { "email": "spam@example.com", "primary": false, "verified": false },
+                    ]))
+                }),
+            )
+            .with_state(challenge.clone());
+
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (base, challenge)
+    }
+
+    fn query_param(url: &str, name: &str) -> Option<String> {
+        let url = oauth2::url::Url::parse(url).unwrap();
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    fn oidc_service(base: &str) -> OAuthService {
+        OAuthService::with_providers(
+            vec![OAuthProvider::oidc(OAUTH_PROVIDER_OIDC, "Company SSO", base, "client-1", Some("secret"))],
+            REDIRECT_URL,
+        )
+    }
+
+    #[tokio::test]
+    async fn oidc_flow_uses_discovery_state_and_pkce() {
+        let (base, challenge) = mock_idp(None).await;
+        let service = oidc_service(&base);
+
+        let request = service.authorization_url(OAUTH_PROVIDER_OIDC).await.unwrap();
+        assert!(request.url.starts_with(&format!("{}/authorize?", base)));
+        assert_eq!(query_param(&request.url, "response_type").as_deref(), Some("code"));
+        assert_eq!(query_param(&request.url, "client_id").as_deref(), Some("client-1"));
+        assert_eq!(query_param(&request.url, "redirect_uri").as_deref(), Some(REDIRECT_URL));
+        assert_eq!(query_param(&request.url, "state"), Some(request.state.clone()));
+        assert_eq!(query_param(&request.url, "scope").as_deref(), Some("openid email profile"));
+        assert_eq!(query_param(&request.url, "code_challenge_method").as_deref(), Some("S256"));
+        *challenge.lock().unwrap() = query_param(&request.url, "code_challenge");
+
+        let identity = service
+            .exchange_code(OAUTH_PROVIDER_OIDC, "good-code", &request.pkce_verifier)
+            .await
+            .unwrap();
+        assert_eq!(
+            identity,
+            ExternalIdentity {
+                provider: OAUTH_PROVIDER_OIDC.to_string(),
+                subject: "oidc-user-1".to_string(),
+                email: Some("trader@example.com".to_string()),
+                email_verified: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn exchange_fails_without_matching_pkce_verifier() {
+        let (base, challenge) = mock_idp(None).await;
+        let service = oidc_service(&base);
+
+        let request = service.authorization_url(OAUTH_PROVIDER_OIDC).await.unwrap();
+        *challenge.lock().unwrap() = query_param(&request.url, "code_challenge");
+
+        let other = service.authorization_url(OAUTH_PROVIDER_OIDC).await.unwrap();
+        let result = service
+            .exchange_code(OAUTH_PROVIDER_OIDC, "good-code", &other.pkce_verifier)
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        let result = service
+            .exchange_code(OAUTH_PROVIDER_OIDC, "bad-code", &request.pkce_verifier)
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn github_flow_prefers_primary_verified_email() {
+        let (base, challenge) = mock_idp(None).await;
+        let service = OAuthService::with_providers(
+            vec![OAuthProvider::github(
+                "client-1",
+                Some("secret"),
+                &format!("{}/authorize", base),
+                &format!("{}/token", base),
+                &format!("{}/api", base),
+            )],
+            REDIRECT_URL,
+        );
+
+        let request = service.authorization_url(OAUTH_PROVIDER_GITHUB).await.unwrap();
+        assert_eq!(query_param(&request.url, "scope").as_deref(), Some("read:user user:email"));
+        *challenge.lock().unwrap() = query_param(&request.url, "code_challenge");
+
+        let identity = service
+            .exchange_code(OAUTH_PROVIDER_GITHUB, "good-code", &request.pkce_verifier)
+            .await
+            .unwrap();
+        assert_eq!(identity.subject, "42");
+        assert_eq!(identity.email.as_deref(), Some("octo@example.com"));
+        assert!(identity.email_verified);
+    }
+
+    #[tokio::test]
+    async fn login_callback_needs_the_starting_browsers_nonce() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let (base, challenge) = mock_idp(None).await;
+        let service = oidc_service(&base);
+
+        // An attacker's flow, posted back from the victim's browser
+        let attacker = service.begin(&db.pool, OAUTH_PROVIDER_OIDC, None).await.unwrap();
+        *challenge.lock().unwrap() = query_param(&attacker.authorization_url, "code_challenge");
+        let state = query_param(&attacker.authorization_url, "state").unwrap();
+        let victim_nonce = CsrfToken::new_random().secret().clone();
+        let result = service
+            .complete(&db.pool, &state, "good-code", &victim_nonce, None)
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        // The state was consumed by the failed attempt
+        let result = service
+            .complete(&db.pool, &state, "good-code", &attacker.browser_nonce, None)
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        let started = service.begin(&db.pool, OAUTH_PROVIDER_OIDC, None).await.unwrap();
+        *challenge.lock().unwrap() = query_param(&started.authorization_url, "code_challenge");
+        let state = query_param(&started.authorization_url, "state").unwrap();
+        let identity = service
+            .complete(&db.pool, &state, "good-code", &started.browser_nonce, None)
+            .await;
+        db.drop().await;
+
+        assert_eq!(identity.unwrap().subject, "oidc-user-1");
+    }
+
+    #[tokio::test]
+    async fn discovery_rejects_issuer_mismatch() {
+        let (base, _) = mock_idp(Some("https://evil.example")).await;
+        let service = oidc_service(&base);
+
+        assert!(matches!(
+            service.authorization_url(OAUTH_PROVIDER_OIDC).await,
+            Err(AppError::Internal(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_provider_is_not_found() {
+        let service = OAuthService::with_providers(Vec::new(), REDIRECT_URL);
+        assert!(service.providers().is_empty());
+        assert!(matches!(
+            service.authorization_url(OAUTH_PROVIDER_GOOGLE).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
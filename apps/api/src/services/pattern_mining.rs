@@ -0,0 +1,698 @@
+use crate::error::AppResult;
+use crate::models::{TradePatternFinding, TradePatternRun};
+use crate::services::StreakService;
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgPool};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Conditions matching fewer trades than this are noise.
+const MIN_SAMPLE_SIZE: usize = 5;
+/// Findings above this false discovery rate adjusted p-value are discarded.
+const MAX_Q_VALUE: f64 = 0.10;
+pub const MAX_PATTERN_FINDINGS: usize = 15;
+/// Most recent closed trades analyzed per run.
+const MAX_TRADES: i64 = 10_000;
+const STRESS_THRESHOLD: i32 = 7;
+const LOW_SCORE_THRESHOLD: i32 = 4;
+const LOSS_STREAK_THRESHOLD: u32 = 2;
+const MINING_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// Users mined per pass of the background job.
+const USERS_PER_PASS: i64 = 200;
+
+#[derive(Debug, FromRow)]
+struct ObservationRow {
+    id: Uuid,
+    entry_date: DateTime<Utc>,
+    net_pnl: Decimal,
+    symbol: String,
+    setup_name: Option<String>,
+    direction: String,
+    conviction: Option<String>,
+    risk_amount: Option<Decimal>,
+    tags: Vec<String>,
+    emotions: Option<Vec<String>>,
+    pre_market_mood: Option<i32>,
+    stress_level: Option<i32>,
+    sleep_quality: Option<i32>,
+}
+
+/// One closed trade with the attributes patterns are mined over. Times are
+/// local to the trader; mood fields come from that day's mood log.
+#[derive(Debug, Clone)]
+struct TradeObservation {
+    id: Uuid,
+    entry_local: NaiveDateTime,
+    net_pnl: Decimal,
+    symbol: String,
+    setup_name: Option<String>,
+    direction: String,
+    conviction: Option<String>,
+    risk_amount: Option<Decimal>,
+    tags: Vec<String>,
+    emotions: Vec<String>,
+    pre_market_mood: Option<i32>,
+    stress_level: Option<i32>,
+    sleep_quality: Option<i32>,
+}
+
+impl TradeObservation {
+    fn is_loss(&self) -> bool {
+        self.net_pnl <= Decimal::ZERO
+    }
+}
+
+/// A condition over trade attributes, e.g. `setup_hour:orb:9`.
+#[derive(Debug, Clone, PartialEq)]
+struct Condition {
+    key: String,
+    pattern_type: &'static str,
+    title: String,
+    /// Simpler condition this one refines; it must beat the parent's p-value.
+    parent: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct MinedPattern {
+    condition: Condition,
+    sample_size: usize,
+    loss_count: usize,
+    win_rate: Decimal,
+    baseline_win_rate: Decimal,
+    expectancy: Decimal,
+    baseline_expectancy: Decimal,
+    total_pnl: Decimal,
+    p_value: f64,
+    q_value: f64,
+    score: f64,
+    trade_ids: Vec<Uuid>,
+}
+
+impl MinedPattern {
+    fn description(&self) -> String {
+        format!(
+            "{} trades with a {}% win rate vs {}% otherwise; expectancy ${} vs ${} per trade (q = {:.3}).",
+            self.sample_size,
+            self.win_rate,
+            self.baseline_win_rate,
+            self.expectancy,
+            self.baseline_expectancy,
+            self.q_value,
+        )
+    }
+}
+
+/// Finds recurring losing patterns in a trader's closed trades: conditions
+/// (symbol, setup by hour, mood, loss streaks, sizing...) under which trades
+/// lose significantly more often than the rest and have negative expectancy.
+pub struct PatternMiningService;
+
+impl PatternMiningService {
+    /// Mines `user_id`'s trades and replaces their stored findings.
+    pub async fn run_for_user(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<TradePatternFinding>> {
+        let tz = StreakService::user_timezone(pool, user_id).await?;
+        let observations = Self::load_observations(pool, user_id, tz).await?;
+        let patterns = mine_patterns(&observations);
+
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM trade_pattern_findings WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut findings = Vec::with_capacity(patterns.len());
+        for (rank, pattern) in patterns.iter().enumerate() {
+            let finding = sqlx::query_as::<_, TradePatternFinding>(
+                r#"
+                INSERT INTO trade_pattern_findings (
+                    user_id, rank, pattern_type, condition_key, title, description,
+                    sample_size, loss_count, win_rate, baseline_win_rate,
+                    expectancy, baseline_expectancy, total_pnl, p_value, q_value, score,
+                    supporting_trade_ids
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                RETURNING *
+                "#,
+            )
+            .bind(user_id)
+            .bind(rank as i32 + 1)
+            .bind(pattern.condition.pattern_type)
+            .bind(&pattern.condition.key)
+            .bind(&pattern.condition.title)
+            .bind(pattern.description())
+            .bind(pattern.sample_size as i32)
+            .bind(pattern.loss_count as i32)
+            .bind(pattern.win_rate)
+            .bind(pattern.baseline_win_rate)
+            .bind(pattern.expectancy)
+            .bind(pattern.baseline_expectancy)
+            .bind(pattern.total_pnl)
+            .bind(pattern.p_value)
+            .bind(pattern.q_value)
+            .bind(pattern.score)
+            .bind(&pattern.trade_ids)
+            .fetch_one(&mut *tx)
+            .await?;
+            findings.push(finding);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO trade_pattern_runs (user_id, trades_analyzed, findings_count, completed_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                trades_analyzed = EXCLUDED.trades_analyzed,
+                findings_count = EXCLUDED.findings_count,
+                completed_at = EXCLUDED.completed_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(observations.len() as i32)
+        .bind(findings.len() as i32)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        tracing::info!(
+            user_id = %user_id,
+            trades = observations.len(),
+            findings = findings.len(),
+            "Trade patterns mined"
+        );
+
+        Ok(findings)
+    }
+
+    pub async fn findings(pool: &PgPool, user_id: Uuid, limit: i64) -> AppResult<Vec<TradePatternFinding>> {
+        Ok(sqlx::query_as::<_, TradePatternFinding>(
+            "SELECT * FROM trade_pattern_findings WHERE user_id = $1 ORDER BY rank LIMIT $2",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    pub async fn last_run(pool: &PgPool, user_id: Uuid) -> AppResult<Option<TradePatternRun>> {
+        Ok(sqlx::query_as::<_, TradePatternRun>(
+            "SELECT * FROM trade_pattern_runs WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?)
+    }
+
+    /// Re-mines users whose closed trades changed since their last run.
+    pub fn spawn_miner(pool: Arc<PgPool>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MINING_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::mine_stale_users(&pool).await {
+                    tracing::warn!(error = ?e, "Trade pattern mining pass failed");
+                }
+            }
+        });
+    }
+
+    async fn mine_stale_users(pool: &PgPool) -> AppResult<()> {
+        let user_ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT t.user_id
+            FROM trades t
+            LEFT JOIN trade_pattern_runs r ON r.user_id = t.user_id
+            WHERE t.status = 'closed'
+            GROUP BY t.user_id, r.completed_at
+            HAVING r.completed_at IS NULL OR MAX(t.updated_at) > r.completed_at
+            ORDER BY r.completed_at NULLS FIRST
+            LIMIT $1
+            "#,
+        )
+        .bind(USERS_PER_PASS)
+        .fetch_all(pool)
+        .await?;
+
+        for user_id in user_ids {
+            if let Err(e) = Self::run_for_user(pool, user_id).await {
+                tracing::warn!(user_id = %user_id, error = ?e, "Trade pattern mining failed for user");
+            }
+        }
+        Ok(())
+    }
+
+    async fn load_observations(pool: &PgPool, user_id: Uuid, tz: Tz) -> AppResult<Vec<TradeObservation>> {
+        let rows = sqlx::query_as::<_, ObservationRow>(
+            r#"
+            SELECT * FROM (
+                SELECT
+                    t.id, t.entry_date, t.net_pnl, t.symbol, t.setup_name,
+                    t.direction::TEXT AS direction, t.conviction::TEXT AS conviction, t.risk_amount,
+                    ARRAY(
+                        SELECT g.name FROM trade_tags tt JOIN tags g ON g.id = tt.tag_id
+                        WHERE tt.trade_id = t.id ORDER BY g.name
+                    )::TEXT[] AS tags,
+                    m.emotions, m.pre_market_mood, m.stress_level, m.sleep_quality
+                FROM trades t
+                LEFT JOIN mood_logs m
+                    ON m.user_id = t.user_id AND m.log_date = (t.entry_date AT TIME ZONE $2)::DATE
+                WHERE t.user_id = $1 AND t.status = 'closed' AND t.net_pnl IS NOT NULL
+                ORDER BY t.entry_date DESC
+                LIMIT $3
+            ) recent
+            ORDER BY entry_date
+            "#,
+        )
+        .bind(user_id)
+        .bind(tz.name())
+        .bind(MAX_TRADES)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| TradeObservation {
+                id: row.id,
+                entry_local: row.entry_date.with_timezone(&tz).naive_local(),
+                net_pnl: row.net_pnl,
+                symbol: row.symbol,
+                setup_name: row.setup_name.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+                direction: row.direction,
+                conviction: row.conviction,
+                risk_amount: row.risk_amount,
+                tags: row.tags,
+                emotions: row.emotions.unwrap_or_default(),
+                pre_market_mood: row.pre_market_mood,
+                stress_level: row.stress_level,
+                sleep_quality: row.sleep_quality,
+            })
+            .collect())
+    }
+}
+
+fn condition(key: String, pattern_type: &'static str, title: String) -> Condition {
+    Condition { key, pattern_type, title, parent: None }
+}
+
+/// Every condition each trade satisfies. `observations` must be in entry
+/// order, since loss streaks count the trades entered before each one.
+fn trade_conditions(observations: &[TradeObservation]) -> Vec<Vec<Condition>> {
+    let mut risks: Vec<Decimal> = observations.iter().filter_map(|o| o.risk_amount).collect();
+    risks.sort();
+    let median_risk = (!risks.is_empty()).then(|| risks[risks.len() / 2]);
+
+    let mut losing_streak = 0u32;
+    observations
+        .iter()
+        .map(|o| {
+            let hour = o.entry_local.hour();
+            let hours = format!("{:02}:00-{:02}:00", hour, (hour + 1) % 24);
+            let mut conditions = vec![
+                condition(format!("symbol:{}", o.symbol.to_uppercase()), "symbol", format!("Trading {}", o.symbol.to_uppercase())),
+                condition(format!("hour:{}", hour), "hour", format!("Entries between {}", hours)),
+                condition(
+                    format!("weekday:{}", o.entry_local.weekday()),
+                    "weekday",
+                    format!("Trading on {}", weekday_name(o.entry_local.weekday())),
+                ),
+                condition(format!("direction:{}", o.direction), "direction", format!("{} trades", capitalize(&o.direction))),
+            ];
+
+            if let Some(setup) = &o.setup_name {
+                let setup_key = format!("setup:{}", setup.to_lowercase());
+                conditions.push(condition(setup_key.clone(), "setup", format!("The \"{}\" setup", setup)));
+                conditions.push(Condition {
+                    key: format!("setup_hour:{}:{}", setup.to_lowercase(), hour),
+                    pattern_type: "setup_hour",
+                    title: format!("The \"{}\" setup entered between {}", setup, hours),
+                    parent: Some(setup_key),
+                });
+            }
+            if let Some(conviction) = &o.conviction {
+                conditions.push(condition(
+                    format!("conviction:{}", conviction),
+                    "conviction",
+                    format!("{}-conviction trades", capitalize(conviction)),
+                ));
+                if let (Some(risk), Some(median)) = (o.risk_amount, median_risk) {
+                    if conviction == "high" && risk > median {
+                        conditions.push(condition(
+                            "oversized_conviction".to_string(),
+                            "oversized_conviction",
+                            "High-conviction trades sized above your median risk".to_string(),
+                        ));
+                    }
+                }
+            }
+            for tag in &o.tags {
+                conditions.push(condition(format!("tag:{}", tag.to_lowercase()), "tag", format!("Trades tagged \"{}\"", tag)));
+            }
+            for emotion in &o.emotions {
+                conditions.push(condition(
+                    format!("emotion:{}", emotion.to_lowercase()),
+                    "emotion",
+                    format!("Days you logged feeling {}", emotion.to_lowercase()),
+                ));
+            }
+            if o.stress_level.is_some_and(|s| s >= STRESS_THRESHOLD) {
+                conditions.push(condition(
+                    "mood:high_stress".to_string(),
+                    "mood",
+                    format!("Days with stress at {}/10 or higher", STRESS_THRESHOLD),
+                ));
+            }
+            if o.sleep_quality.is_some_and(|s| s <= LOW_SCORE_THRESHOLD) {
+                conditions.push(condition(
+                    "mood:poor_sleep".to_string(),
+                    "mood",
+                    format!("Days after sleep rated {}/10 or lower", LOW_SCORE_THRESHOLD),
+                ));
+            }
+            if o.pre_market_mood.is_some_and(|m| m <= LOW_SCORE_THRESHOLD) {
+                conditions.push(condition(
+                    "mood:low_pre_market".to_string(),
+                    "mood",
+                    format!("Days starting with mood at {}/10 or lower", LOW_SCORE_THRESHOLD),
+                ));
+            }
+            if losing_streak >= LOSS_STREAK_THRESHOLD {
+                conditions.push(condition(
+                    format!("after_losses:{}", LOSS_STREAK_THRESHOLD),
+                    "after_losses",
+                    format!("Trades taken after {} or more consecutive losses", LOSS_STREAK_THRESHOLD),
+                ));
+            }
+
+            losing_streak = if o.is_loss() { losing_streak + 1 } else { 0 };
+            conditions
+        })
+        .collect()
+}
+
+/// Ranks conditions whose trades lose significantly more often than the
+/// rest (one-sided Fisher exact test, Benjamini-Hochberg adjusted across
+/// every condition tested) and have negative expectancy below the rest's.
+/// Score is the excess dollars lost, discounted by q-value.
+fn mine_patterns(observations: &[TradeObservation]) -> Vec<MinedPattern> {
+    let total = observations.len();
+    if total < MIN_SAMPLE_SIZE * 2 {
+        return Vec::new();
+    }
+    let total_losses = observations.iter().filter(|o| o.is_loss()).count();
+    let total_pnl: Decimal = observations.iter().map(|o| o.net_pnl).sum();
+    let log_factorials = log_factorials(total);
+
+    // BTreeMap keeps runs deterministic when scores tie
+    let mut groups: BTreeMap<String, (Condition, Vec<usize>)> = BTreeMap::new();
+    for (index, conditions) in trade_conditions(observations).into_iter().enumerate() {
+        for condition in conditions {
+            groups
+                .entry(condition.key.clone())
+                .or_insert_with(|| (condition, Vec::new()))
+                .1
+                .push(index);
+        }
+    }
+
+    // Every condition with enough trades on both sides is a hypothesis, even
+    // the ones the expectancy filter drops below
+    let tested: Vec<(Condition, Vec<usize>, usize, f64)> = groups
+        .into_values()
+        .filter(|(_, indexes)| {
+            indexes.len() >= MIN_SAMPLE_SIZE && total - indexes.len() >= MIN_SAMPLE_SIZE
+        })
+        .map(|(condition, indexes)| {
+            let n = indexes.len();
+            let losses = indexes.iter().filter(|&&i| observations[i].is_loss()).count();
+            let p_value = fisher_one_sided(total, total_losses, n, losses, &log_factorials);
+            (condition, indexes, losses, p_value)
+        })
+        .collect();
+    let q_values = benjamini_hochberg(&tested.iter().map(|t| t.3).collect::<Vec<_>>());
+
+    let mut candidates: Vec<MinedPattern> = tested
+        .into_iter()
+        .zip(q_values)
+        .filter_map(|((condition, indexes, losses, p_value), q_value)| {
+            if q_value > MAX_Q_VALUE {
+                return None;
+            }
+            let n = indexes.len();
+            let group_pnl: Decimal = indexes.iter().map(|&i| observations[i].net_pnl).sum();
+            let rest = total - n;
+            let expectancy = group_pnl / Decimal::from(n);
+            let baseline_expectancy = (total_pnl - group_pnl) / Decimal::from(rest);
+            if expectancy >= Decimal::ZERO || expectancy >= baseline_expectancy {
+                return None;
+            }
+
+            let excess_loss = ((baseline_expectancy - expectancy) * Decimal::from(n))
+                .to_f64()
+                .unwrap_or(0.0);
+            let win_rate = |wins: usize, count: usize| {
+                (Decimal::from(wins * 100) / Decimal::from(count)).round_dp(2)
+            };
+            Some(MinedPattern {
+                sample_size: n,
+                loss_count: losses,
+                win_rate: win_rate(n - losses, n),
+                baseline_win_rate: win_rate(rest - (total_losses - losses), rest),
+                expectancy: expectancy.round_dp(2),
+                baseline_expectancy: baseline_expectancy.round_dp(2),
+                total_pnl: group_pnl.round_dp(2),
+                p_value,
+                q_value,
+                score: excess_loss * (1.0 - q_value),
+                trade_ids: indexes.iter().map(|&i| observations[i].id).collect(),
+                condition,
+            })
+        })
+        .collect();
+
+    // A refinement only adds information if it is more significant
+    let parent_p: BTreeMap<String, f64> = candidates
+        .iter()
+        .map(|c| (c.condition.key.clone(), c.p_value))
+        .collect();
+    candidates.retain(|c| match &c.condition.parent {
+        Some(parent) => parent_p.get(parent).is_none_or(|&p| c.p_value < p),
+        None => true,
+    });
+
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.p_value.total_cmp(&b.p_value))
+            .then(a.condition.parent.is_some().cmp(&b.condition.parent.is_some()))
+    });
+
+    // Different conditions can select the same trades; keep the best-ranked
+    let mut seen: HashSet<Vec<Uuid>> = HashSet::new();
+    candidates.retain(|c| {
+        let mut ids = c.trade_ids.clone();
+        ids.sort();
+        seen.insert(ids)
+    });
+    candidates.truncate(MAX_PATTERN_FINDINGS);
+    candidates
+}
+
+/// `ln(k!)` for `k` in `0..=n`.
+fn log_factorials(n: usize) -> Vec<f64> {
+    let mut table = Vec::with_capacity(n + 1);
+    table.push(0.0);
+    for k in 1..=n {
+        table.push(table[k - 1] + (k as f64).ln());
+    }
+    table
+}
+
+fn ln_choose(n: usize, k: usize, log_factorials: &[f64]) -> f64 {
+    log_factorials[n] - log_factorials[k] - log_factorials[n - k]
+}
+
+/// Benjamini-Hochberg step-up adjustment: the smallest false discovery rate
+/// at which each hypothesis would be rejected. Returned in input order.
+fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let m = p_values.len();
+    let mut order: Vec<usize> = (0..m).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+
+    let mut q_values = vec![1.0; m];
+    let mut running_min: f64 = 1.0;
+    for (rank, &i) in order.iter().enumerate().rev() {
+        running_min = running_min.min(p_values[i] * m as f64 / (rank + 1) as f64);
+        q_values[i] = running_min;
+    }
+    q_values
+}
+
+/// P(X >= observed) for X hypergeometric: drawing `drawn` trades out of
+/// `total`, of which `successes` are losses.
+fn fisher_one_sided(total: usize, successes: usize, drawn: usize, observed: usize, log_factorials: &[f64]) -> f64 {
+    let denominator = ln_choose(total, drawn, log_factorials);
+    let upper = successes.min(drawn);
+    let lower = observed.max(drawn.saturating_sub(total - successes));
+    let p: f64 = (lower..=upper)
+        .map(|x| {
+            (ln_choose(successes, x, log_factorials)
+                + ln_choose(total - successes, drawn - x, log_factorials)
+                - denominator)
+                .exp()
+        })
+        .sum();
+    p.min(1.0)
+}
+
+fn weekday_name(weekday: chrono::Weekday) -> &'static str {
+    match weekday {
+        chrono::Weekday::Mon => "Mondays",
+        chrono::Weekday::Tue => "Tuesdays",
+        chrono::Weekday::Wed => "Wednesdays",
+        chrono::Weekday::Thu => "Thursdays",
+        chrono::Weekday::Fri => "Fridays",
+        chrono::Weekday::Sat => "Saturdays",
+        chrono::Weekday::Sun => "Sundays",
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn observation(day: u32, hour: u32, symbol: &str, setup: &str, pnl: i64) -> TradeObservation {
+        TradeObservation {
+            id: Uuid::new_v4(),
+            entry_local: NaiveDate::from_ymd_opt(2026, 9, day)
+                .unwrap()
+                .and_hms_opt(hour, 30, 0)
+                .unwrap(),
+            net_pnl: Decimal::from(pnl),
+            symbol: symbol.to_string(),
+            setup_name: Some(setup.to_string()),
+            direction: "long".to_string(),
+            conviction: None,
+            risk_amount: None,
+            tags: Vec::new(),
+            emotions: Vec::new(),
+            pre_market_mood: None,
+            stress_level: None,
+            sleep_quality: None,
+        }
+    }
+
+    #[test]
+    fn test_fisher_one_sided_matches_reference_values() {
+        let table = log_factorials(20);
+        // 10 of 20 trades lose; all 5 trades in the group lost
+        let p = fisher_one_sided(20, 10, 5, 5, &table);
+        assert!((p - 0.016254).abs() < 1e-5, "{}", p);
+        // Observing nothing is certain
+        assert!((fisher_one_sided(20, 10, 5, 0, &table) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_benjamini_hochberg_matches_reference_values() {
+        let q = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.5]);
+        let expected = [0.04, 0.16 / 3.0, 0.16 / 3.0, 0.5];
+        for (q, expected) in q.iter().zip(expected) {
+            assert!((q - expected).abs() < 1e-12, "{} != {}", q, expected);
+        }
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_mine_patterns_finds_nothing_in_random_trades() {
+        // xorshift, so the data is noise but the test is repeatable
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        let symbols = ["NQ", "ES", "CL", "GC", "AAPL", "TSLA"];
+        let setups = ["ORB", "Pullback", "Breakout", "Reversal"];
+        let trades: Vec<_> = (0..400)
+            .map(|_| {
+                let day = next(28) as u32 + 1;
+                let hour = next(7) as u32 + 9;
+                let symbol = symbols[next(symbols.len() as u64) as usize];
+                let setup = setups[next(setups.len() as u64) as usize];
+                let pnl = if next(2) == 0 { -100 } else { 100 };
+                observation(day, hour, symbol, setup, pnl)
+            })
+            .collect();
+
+        let patterns = mine_patterns(&trades);
+        assert!(patterns.is_empty(), "{:?}", patterns.first().map(|p| &p.condition.key));
+    }
+
+    #[test]
+    fn test_mine_patterns_finds_losing_setup_hour() {
+        let mut trades = Vec::new();
+        for day in 1..=24 {
+            // "ORB" wins at 10:00 but loses at 9:00; "Pullback" wins at any hour
+            trades.push(observation(day, 9, "NQ", "ORB", -100));
+            trades.push(observation(day, 10, "ES", "ORB", 120));
+            trades.push(observation(day, 11, "ES", "Pullback", 80));
+        }
+
+        let patterns = mine_patterns(&trades);
+        assert!(!patterns.is_empty());
+        let top = &patterns[0];
+        assert!(top.p_value < 0.001);
+        assert!(top.q_value >= top.p_value && top.q_value < 0.01);
+        assert_eq!(top.sample_size, 24);
+        assert_eq!(top.win_rate, Decimal::ZERO);
+        assert_eq!(top.expectancy, Decimal::from(-100));
+        assert_eq!(top.baseline_expectancy, Decimal::from(100));
+
+        // symbol:NQ, hour:9 and setup_hour:orb:9 select the same trades; one survives
+        let same_trades = patterns.iter().filter(|p| p.sample_size == 24 && p.loss_count == 24).count();
+        assert_eq!(same_trades, 1);
+        assert!(patterns.iter().all(|p| p.condition.key != "setup:pullback"));
+    }
+
+    #[test]
+    fn test_trade_conditions_tracks_loss_streaks_and_sizing() {
+        let mut trades: Vec<TradeObservation> = [-50, -50, -50, 100]
+            .iter()
+            .enumerate()
+            .map(|(i, pnl)| observation(i as u32 + 1, 9, "NQ", "ORB", *pnl))
+            .collect();
+        for (trade, risk) in trades.iter_mut().zip([100, 100, 300, 100]) {
+            trade.conviction = Some("high".to_string());
+            trade.risk_amount = Some(Decimal::from(risk));
+        }
+
+        let conditions = trade_conditions(&trades);
+        let has = |i: usize, key: &str| conditions[i].iter().any(|c| c.key == key);
+        assert!(!has(1, "after_losses:2"));
+        assert!(has(2, "after_losses:2"));
+        assert!(has(3, "after_losses:2"));
+        assert!(has(2, "oversized_conviction"));
+        assert!(!has(0, "oversized_conviction"));
+    }
+
+    #[test]
+    fn test_mine_patterns_needs_enough_trades() {
+        let trades: Vec<_> = (1..=6).map(|d| observation(d, 9, "NQ", "ORB", -10)).collect();
+        assert!(mine_patterns(&trades).is_empty());
+    }
+}
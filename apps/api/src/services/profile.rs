@@ -0,0 +1,390 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    DailyLimits, DailyPlan, TradingDefaults, UpdateProfileRequest, UserProfile,
+    DAILY_LIMITS_FROM_PLAN, DAILY_LIMITS_FROM_PROFILE,
+};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const TRADING_STYLES: &[&str] = &["day_trading", "swing_trading", "scalping", "position_trading"];
+pub const EXPERIENCE_LEVELS: &[&str] = &["beginner", "intermediate", "advanced"];
+pub const PRIMARY_ASSETS: &[&str] = &["stocks", "options", "forex", "futures", "crypto"];
+pub const AI_PERSONALITIES: &[&str] = &["strict_coach", "encouraging_mentor", "balanced"];
+
+const MAX_LIST_ITEMS: usize = 20;
+const MAX_SHORT_TEXT_CHARS: usize = 50;
+const MAX_GOAL_CHARS: usize = 200;
+const MAX_TRADES_PER_DAY: i32 = 1000;
+/// Column limits: DECIMAL(15,2) for the daily loss, DECIMAL(10,2) for commissions.
+const MAX_DAILY_LOSS: i64 = 1_000_000_000_000;
+const MAX_COMMISSIONS: i64 = 100_000_000;
+
+fn check_choice(field: &str, value: &Option<String>, allowed: &[&str]) -> Result<(), String> {
+    match value {
+        Some(v) if !allowed.contains(&v.as_str()) => Err(format!(
+            "{} must be one of: {}",
+            field,
+            allowed.join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_list(field: &str, items: &Option<Vec<String>>, max_chars: usize) -> Result<(), String> {
+    let Some(items) = items else { return Ok(()) };
+    if items.len() > MAX_LIST_ITEMS {
+        return Err(format!("{} may have at most {} entries", field, MAX_LIST_ITEMS));
+    }
+    if items.iter().any(|i| i.trim().is_empty() || i.chars().count() > max_chars) {
+        return Err(format!("Each {} entry must be 1-{} characters", field, max_chars));
+    }
+    Ok(())
+}
+
+pub fn validate_profile_update(req: &UpdateProfileRequest) -> Result<(), String> {
+    check_choice("trading_style", &req.trading_style, TRADING_STYLES)?;
+    check_choice("experience_level", &req.experience_level, EXPERIENCE_LEVELS)?;
+    check_choice("ai_personality", &req.ai_personality, AI_PERSONALITIES)?;
+
+    if let Some(assets) = &req.primary_assets {
+        if let Some(asset) = assets.iter().find(|a| !PRIMARY_ASSETS.contains(&a.as_str())) {
+            return Err(format!(
+                "Unknown asset '{}'; primary_assets must be among: {}",
+                asset,
+                PRIMARY_ASSETS.join(", ")
+            ));
+        }
+    }
+
+    if let Some(range) = &req.account_size_range {
+        if range.trim().is_empty() || range.chars().count() > MAX_SHORT_TEXT_CHARS {
+            return Err(format!(
+                "account_size_range must be 1-{} characters",
+                MAX_SHORT_TEXT_CHARS
+            ));
+        }
+    }
+
+    check_list("active_sessions", &req.active_sessions, MAX_SHORT_TEXT_CHARS)?;
+    check_list("goals", &req.goals, MAX_GOAL_CHARS)?;
+
+    if let Some(tz) = &req.timezone {
+        if tz.parse::<Tz>().is_err() {
+            return Err(format!("Unknown timezone '{}'; use an IANA name like America/New_York", tz));
+        }
+    }
+
+    if let Some(risk) = req.default_risk_pct {
+        if risk <= Decimal::ZERO || risk > Decimal::from(100) {
+            return Err("default_risk_pct must be greater than 0 and at most 100".to_string());
+        }
+    }
+
+    if let Some(max_trades) = req.max_trades_per_day {
+        if !(1..=MAX_TRADES_PER_DAY).contains(&max_trades) {
+            return Err(format!("max_trades_per_day must be between 1 and {}", MAX_TRADES_PER_DAY));
+        }
+    }
+
+    if let Some(loss) = req.max_daily_loss {
+        if loss <= Decimal::ZERO || loss >= Decimal::from(MAX_DAILY_LOSS) {
+            return Err("max_daily_loss must be a positive amount".to_string());
+        }
+    }
+
+    if let Some(commissions) = req.default_commissions {
+        if commissions < Decimal::ZERO || commissions >= Decimal::from(MAX_COMMISSIONS) {
+            return Err("default_commissions must be zero or a positive amount".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+/// Onboarding needs the answers the AI coach and analytics lean on.
+pub fn validate_onboarding(req: &UpdateProfileRequest) -> Result<(), String> {
+    if req.trading_style.is_none() {
+        return Err("trading_style is required to finish onboarding".to_string());
+    }
+    if req.experience_level.is_none() {
+        return Err("experience_level is required to finish onboarding".to_string());
+    }
+    if req.primary_assets.as_ref().is_none_or(|a| a.is_empty()) {
+        return Err("Pick at least one primary asset to finish onboarding".to_string());
+    }
+    validate_profile_update(req)
+}
+
+/// The plan's limits on days with a plan; the profile's standing limits otherwise.
+pub fn daily_limits(date: NaiveDate, plan: Option<&DailyPlan>, defaults: &TradingDefaults) -> DailyLimits {
+    match plan {
+        Some(plan) => DailyLimits {
+            date,
+            max_trades: plan.max_trades,
+            max_daily_loss: plan.max_daily_loss,
+            source: DAILY_LIMITS_FROM_PLAN,
+        },
+        None => DailyLimits {
+            date,
+            max_trades: defaults.max_trades_per_day,
+            max_daily_loss: defaults.max_daily_loss,
+            source: DAILY_LIMITS_FROM_PROFILE,
+        },
+    }
+}
+
+/// The trader's profile: onboarding answers and the defaults applied to
+/// new trades, the position-size calculator and days without a plan.
+pub struct ProfileService;
+
+impl ProfileService {
+    /// Accounts created before profiles were always inserted get an empty
+    /// row on first read.
+    pub async fn get(pool: &PgPool, user_id: Uuid) -> AppResult<UserProfile> {
+        sqlx::query("INSERT INTO user_profiles (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        Ok(sqlx::query_as::<_, UserProfile>("SELECT * FROM user_profiles WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?)
+    }
+
+    /// Fields left out of the request keep their current value.
+    pub async fn update(
+        pool: &PgPool,
+        user_id: Uuid,
+        req: &UpdateProfileRequest,
+        complete_onboarding: bool,
+    ) -> AppResult<UserProfile> {
+        if complete_onboarding {
+            validate_onboarding(req)
+        } else {
+            validate_profile_update(req)
+        }
+        .map_err(AppError::Validation)?;
+
+        sqlx::query("INSERT INTO user_profiles (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+
+        let trimmed_list = |items: &Option<Vec<String>>| {
+            items
+                .as_ref()
+                .map(|items| items.iter().map(|i| i.trim().to_string()).collect::<Vec<_>>())
+        };
+
+        Ok(sqlx::query_as::<_, UserProfile>(
+            r#"
+            UPDATE user_profiles SET
+                trading_style = COALESCE($2, trading_style),
+                primary_assets = COALESCE($3, primary_assets),
+                experience_level = COALESCE($4, experience_level),
+                account_size_range = COALESCE($5, account_size_range),
+                default_risk_pct = COALESCE($6, default_risk_pct),
+                timezone = COALESCE($7, timezone),
+                active_sessions = COALESCE($8, active_sessions),
+                goals = COALESCE($9, goals),
+                ai_personality = COALESCE($10, ai_personality),
+                max_trades_per_day = COALESCE($11, max_trades_per_day),
+                max_daily_loss = COALESCE($12, max_daily_loss),
+                default_commissions = COALESCE($13, default_commissions),
+                onboarding_completed = COALESCE(onboarding_completed, FALSE) OR $14,
+                onboarding_completed_at = CASE
+                    WHEN $14 THEN COALESCE(onboarding_completed_at, NOW())
+                    ELSE onboarding_completed_at
+                END
+            WHERE user_id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(&req.trading_style)
+        .bind(&req.primary_assets)
+        .bind(&req.experience_level)
+        .bind(req.account_size_range.as_deref().map(str::trim))
+        .bind(req.default_risk_pct)
+        .bind(&req.timezone)
+        .bind(trimmed_list(&req.active_sessions))
+        .bind(trimmed_list(&req.goals))
+        .bind(&req.ai_personality)
+        .bind(req.max_trades_per_day)
+        .bind(req.max_daily_loss)
+        .bind(req.default_commissions)
+        .bind(complete_onboarding)
+        .fetch_one(pool)
+        .await?)
+    }
+
+    pub async fn trading_defaults(pool: &PgPool, user_id: Uuid) -> AppResult<TradingDefaults> {
+        Ok(sqlx::query_as::<_, TradingDefaults>(
+            r#"
+            SELECT default_risk_pct, default_commissions, max_trades_per_day, max_daily_loss
+            FROM user_profiles
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .unwrap_or_default())
+    }
+
+    /// Per-trade commissions to apply when a new trade doesn't give any;
+    /// `None` when the profile leaves them unset or at zero.
+    pub async fn default_commissions(pool: &PgPool, user_id: Uuid) -> AppResult<Option<Decimal>> {
+        Ok(Self::trading_defaults(pool, user_id)
+            .await?
+            .default_commissions
+            .filter(|c| !c.is_zero()))
+    }
+
+    pub async fn daily_limits_for(
+        pool: &PgPool,
+        user_id: Uuid,
+        date: NaiveDate,
+    ) -> AppResult<DailyLimits> {
+        let plan = sqlx::query_as::<_, DailyPlan>(
+            "SELECT * FROM daily_plans WHERE user_id = $1 AND plan_date = $2",
+        )
+        .bind(user_id)
+        .bind(date)
+        .fetch_optional(pool)
+        .await?;
+
+        let defaults = Self::trading_defaults(pool, user_id).await?;
+        Ok(daily_limits(date, plan.as_ref(), &defaults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> UpdateProfileRequest {
+        UpdateProfileRequest {
+            trading_style: None,
+            primary_assets: None,
+            experience_level: None,
+            account_size_range: None,
+            default_risk_pct: None,
+            timezone: None,
+            active_sessions: None,
+            goals: None,
+            ai_personality: None,
+            max_trades_per_day: None,
+            max_daily_loss: None,
+            default_commissions: None,
+        }
+    }
+
+    #[test]
+    fn empty_update_is_valid() {
+        assert!(validate_profile_update(&request()).is_ok());
+    }
+
+    #[test]
+    fn choices_must_be_known_values() {
+        let mut req = request();
+        req.trading_style = Some("yolo".to_string());
+        assert!(validate_profile_update(&req).unwrap_err().contains("trading_style"));
+
+        let mut req = request();
+        req.primary_assets = Some(vec!["stocks".to_string(), "bonds".to_string()]);
+        assert!(validate_profile_update(&req).unwrap_err().contains("'bonds'"));
+
+        let mut req = request();
+        req.ai_personality = Some("strict_coach".to_string());
+        req.trading_style = Some("swing_trading".to_string());
+        assert!(validate_profile_update(&req).is_ok());
+    }
+
+    #[test]
+    fn timezone_must_be_iana() {
+        let mut req = request();
+        req.timezone = Some("Europe/London".to_string());
+        assert!(validate_profile_update(&req).is_ok());
+
+        req.timezone = Some("EST5".to_string());
+        assert!(validate_profile_update(&req).unwrap_err().contains("timezone"));
+    }
+
+    #[test]
+    fn numeric_defaults_are_range_checked() {
+        let mut req = request();
+        req.default_risk_pct = Some(Decimal::ZERO);
+        assert!(validate_profile_update(&req).is_err());
+        req.default_risk_pct = Some(Decimal::new(15, 1));
+        assert!(validate_profile_update(&req).is_ok());
+
+        req.max_trades_per_day = Some(0);
+        assert!(validate_profile_update(&req).is_err());
+        req.max_trades_per_day = Some(3);
+
+        req.default_commissions = Some(Decimal::new(-1, 0));
+        assert!(validate_profile_update(&req).is_err());
+        req.default_commissions = Some(Decimal::ZERO);
+        assert!(validate_profile_update(&req).is_ok());
+    }
+
+    #[test]
+    fn onboarding_requires_core_answers() {
+        let mut req = request();
+        assert!(validate_onboarding(&req).is_err());
+
+        req.trading_style = Some("day_trading".to_string());
+        req.experience_level = Some("beginner".to_string());
+        req.primary_assets = Some(Vec::new());
+        assert!(validate_onboarding(&req).unwrap_err().contains("primary asset"));
+
+        req.primary_assets = Some(vec!["futures".to_string()]);
+        assert!(validate_onboarding(&req).is_ok());
+    }
+
+    #[test]
+    fn profile_limits_apply_only_without_a_plan() {
+        let date = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let defaults = TradingDefaults {
+            max_trades_per_day: Some(4),
+            max_daily_loss: Some(Decimal::from(300)),
+            ..Default::default()
+        };
+
+        let limits = daily_limits(date, None, &defaults);
+        assert_eq!(limits.source, DAILY_LIMITS_FROM_PROFILE);
+        assert_eq!(limits.max_trades, Some(4));
+        assert_eq!(limits.max_daily_loss, Some(Decimal::from(300)));
+    }
+
+    #[tokio::test]
+    async fn test_default_commissions_skip_unset_and_zero() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let user_id = db.insert_user("commissions@example.com").await;
+        let no_profile = ProfileService::default_commissions(&db.pool, user_id).await.unwrap();
+
+        let set_commissions = |value: &str| {
+            sqlx::query(
+                "INSERT INTO user_profiles (user_id, default_commissions) VALUES ($1, $2::DECIMAL)
+                 ON CONFLICT (user_id) DO UPDATE SET default_commissions = EXCLUDED.default_commissions",
+            )
+            .bind(user_id)
+            .bind(value.to_string())
+            .execute(&db.pool)
+        };
+        set_commissions("0").await.unwrap();
+        let zero = ProfileService::default_commissions(&db.pool, user_id).await.unwrap();
+        set_commissions("2.50").await.unwrap();
+        let set = ProfileService::default_commissions(&db.pool, user_id).await.unwrap();
+        db.drop().await;
+
+        assert_eq!(no_profile, None);
+        assert_eq!(zero, None);
+        assert_eq!(set, Some(Decimal::new(250, 2)));
+    }
+}
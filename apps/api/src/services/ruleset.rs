@@ -0,0 +1,195 @@
+use crate::error::{AppError, AppResult};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+
+const SHARE_TOKEN_BYTES: usize = 24;
+const MAX_NAME_LENGTH: usize = 255;
+
+/// How an import resolves a name that already exists in the importer's account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Import under "Name (2)", "Name (3)", ...
+    Rename,
+    /// Overwrite the existing entity with the ruleset contents
+    Replace,
+    /// Reject the import with 409
+    Fail,
+}
+
+impl ConflictStrategy {
+    pub fn parse(input: Option<&str>) -> AppResult<Self> {
+        match input.unwrap_or("rename") {
+            "rename" => Ok(Self::Rename),
+            "replace" => Ok(Self::Replace),
+            "fail" => Ok(Self::Fail),
+            other => Err(AppError::Validation(format!(
+                "Invalid on_conflict '{}'. Allowed: rename, replace, fail",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NameResolution {
+    pub name: String,
+    pub renamed: bool,
+    pub replaced: bool,
+}
+
+pub struct RulesetService;
+
+impl RulesetService {
+    /// Generate an unguessable, URL-safe share token
+    pub fn generate_share_token() -> String {
+        let mut bytes = [0u8; SHARE_TOKEN_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Pick the name an imported entity is stored under, given the names the
+    /// importer already uses for that entity type.
+    pub fn resolve_import_name(
+        desired: &str,
+        existing: &[String],
+        strategy: ConflictStrategy,
+        kind: &str,
+    ) -> AppResult<NameResolution> {
+        let desired = desired.trim();
+
+        if !existing.iter().any(|n| n == desired) {
+            return Ok(NameResolution {
+                name: desired.to_string(),
+                renamed: false,
+                replaced: false,
+            });
+        }
+
+        match strategy {
+            ConflictStrategy::Fail => Err(AppError::Conflict(format!(
+                "A {} named '{}' already exists",
+                kind, desired
+            ))),
+            ConflictStrategy::Replace => Ok(NameResolution {
+                name: desired.to_string(),
+                renamed: false,
+                replaced: true,
+            }),
+            ConflictStrategy::Rename => {
+                let mut n = 2;
+                loop {
+                    let suffix = format!(" ({})", n);
+                    let base: String = desired
+                        .chars()
+                        .take(MAX_NAME_LENGTH.saturating_sub(suffix.len()))
+                        .collect();
+                    let candidate = format!("{}{}", base, suffix);
+                    if !existing.contains(&candidate) {
+                        return Ok(NameResolution {
+                            name: candidate,
+                            renamed: true,
+                            replaced: false,
+                        });
+                    }
+                    n += 1;
+                }
+            }
+        }
+    }
+
+    /// Append checklist items that are not already present (matched on `text`,
+    /// case-insensitively).
+    pub fn merge_checklist_items(
+        existing: Option<&serde_json::Value>,
+        incoming: &serde_json::Value,
+    ) -> serde_json::Value {
+        let mut merged: Vec<serde_json::Value> = existing
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        let item_key = |item: &serde_json::Value| {
+            item.get("text")
+                .and_then(|t| t.as_str())
+                .map(|t| t.trim().to_lowercase())
+        };
+
+        for item in incoming.as_array().into_iter().flatten() {
+            let key = item_key(item);
+            let duplicate = key.is_some() && merged.iter().any(|m| item_key(m) == key);
+            if !duplicate {
+                merged.push(item.clone());
+            }
+        }
+
+        serde_json::Value::Array(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_share_token_is_url_safe() {
+        let token = RulesetService::generate_share_token();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_ne!(token, RulesetService::generate_share_token());
+    }
+
+    #[test]
+    fn test_resolve_import_name_without_conflict() {
+        let existing = vec!["ORB".to_string()];
+        let res = RulesetService::resolve_import_name(
+            "Bull Flag",
+            &existing,
+            ConflictStrategy::Fail,
+            "playbook setup",
+        )
+        .unwrap();
+        assert_eq!(res.name, "Bull Flag");
+        assert!(!res.renamed && !res.replaced);
+    }
+
+    #[test]
+    fn test_resolve_import_name_rename_skips_taken_suffixes() {
+        let existing = vec!["ORB".to_string(), "ORB (2)".to_string()];
+        let res = RulesetService::resolve_import_name(
+            "ORB",
+            &existing,
+            ConflictStrategy::Rename,
+            "playbook setup",
+        )
+        .unwrap();
+        assert_eq!(res.name, "ORB (3)");
+        assert!(res.renamed);
+    }
+
+    #[test]
+    fn test_resolve_import_name_replace_and_fail() {
+        let existing = vec!["ORB".to_string()];
+        let res =
+            RulesetService::resolve_import_name("ORB", &existing, ConflictStrategy::Replace, "rubric")
+                .unwrap();
+        assert!(res.replaced);
+
+        let err =
+            RulesetService::resolve_import_name("ORB", &existing, ConflictStrategy::Fail, "rubric");
+        assert!(matches!(err, Err(AppError::Conflict(_))));
+    }
+
+    #[test]
+    fn test_merge_checklist_items_skips_duplicates() {
+        let existing = json!([{ "text": "Check VIX", "required": true }]);
+        let incoming = json!([
+            { "text": "check vix ", "required": false },
+            { "text": "Mark levels", "required": true }
+        ]);
+        let merged = RulesetService::merge_checklist_items(Some(&existing), &incoming);
+        let items = merged.as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1]["text"], "Mark levels");
+    }
+}
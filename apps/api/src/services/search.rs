@@ -0,0 +1,603 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use crate::models::{SearchQuery, SearchResponse, SearchResult, SEARCH_ENTITY_TYPES, SEARCH_MODES};
+use crate::services::embedding::{cosine_similarity, embedder_from_config, tokenize, Embedder};
+use chrono::NaiveDate;
+use sqlx::{FromRow, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+const MAX_QUERY_CHARS: usize = 500;
+/// Embeddings compared per semantic query; newest documents win beyond this.
+const SEMANTIC_CANDIDATE_LIMIT: i64 = 5_000;
+const SEMANTIC_MIN_SIMILARITY: f32 = 0.15;
+/// Standard reciprocal rank fusion constant.
+const RRF_K: f64 = 60.0;
+/// Documents embedded per batch, and the most a search embeds inline.
+const INDEX_BATCH: i64 = 64;
+const INDEXER_INTERVAL: Duration = Duration::from_secs(30);
+const SNIPPET_CHARS: usize = 200;
+const SNIPPET_LEAD_CHARS: usize = 50;
+
+#[derive(Debug, Clone, FromRow)]
+struct SearchDocument {
+    id: Uuid,
+    entity_type: String,
+    entity_id: Uuid,
+    field: String,
+    title: String,
+    entity_date: Option<NaiveDate>,
+    content: String,
+}
+
+#[derive(Debug, FromRow)]
+struct RankedDocument {
+    #[sqlx(flatten)]
+    document: SearchDocument,
+    score: f64,
+}
+
+#[derive(Debug, FromRow)]
+struct EmbeddedDocument {
+    #[sqlx(flatten)]
+    document: SearchDocument,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, FromRow)]
+struct PendingDocument {
+    id: Uuid,
+    content: String,
+}
+
+/// Searches journal text kept in `journal_search_documents` by the
+/// migration 024 triggers. Embeddings are filled in lazily, both by the
+/// background indexer and just before a semantic search.
+pub struct SearchService {
+    embedder: Option<Arc<dyn Embedder>>,
+}
+
+impl SearchService {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            embedder: embedder_from_config(config),
+        }
+    }
+
+    /// Embeds documents that have no vector for the current model yet.
+    /// Returns how many were written.
+    pub async fn index_pending(
+        &self,
+        pool: &PgPool,
+        user_id: Option<Uuid>,
+        limit: i64,
+    ) -> AppResult<usize> {
+        let Some(embedder) = &self.embedder else {
+            return Ok(0);
+        };
+
+        let pending = sqlx::query_as::<_, PendingDocument>(
+            r#"
+            SELECT id, content
+            FROM journal_search_documents
+            WHERE (embedding IS NULL OR embedding_model IS DISTINCT FROM $2)
+              AND ($1::uuid IS NULL OR user_id = $1)
+            ORDER BY updated_at
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(embedder.model())
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let texts: Vec<String> = pending.iter().map(|d| d.content.clone()).collect();
+        let vectors = embedder.embed(&texts).await?;
+
+        let mut tx = pool.begin().await?;
+        for (document, vector) in pending.iter().zip(vectors) {
+            // The content check skips rows edited while we were embedding;
+            // the trigger already cleared their vector for the next pass.
+            sqlx::query(
+                r#"
+                UPDATE journal_search_documents
+                SET embedding = $2, embedding_model = $3
+                WHERE id = $1 AND content = $4
+                "#,
+            )
+            .bind(document.id)
+            .bind(&vector)
+            .bind(embedder.model())
+            .bind(&document.content)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(pending.len())
+    }
+
+    /// Keeps embeddings current in the background. A no-op when semantic
+    /// search is disabled.
+    pub fn spawn_indexer(self: Arc<Self>, pool: Arc<PgPool>) {
+        let Some(embedder) = &self.embedder else {
+            return;
+        };
+        tracing::info!(
+            provider = embedder.name(),
+            model = embedder.model(),
+            "Starting journal search indexer"
+        );
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(INDEXER_INTERVAL);
+            loop {
+                interval.tick().await;
+                loop {
+                    match self.index_pending(&pool, None, INDEX_BATCH).await {
+                        Ok(count) if count as i64 == INDEX_BATCH => continue,
+                        Ok(_) => break,
+                        Err(e) => {
+                            tracing::warn!(error = ?e, "Journal search indexing failed");
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    pub async fn search(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &SearchQuery,
+    ) -> AppResult<SearchResponse> {
+        let q = query.q.trim();
+        if q.is_empty() {
+            return Err(AppError::Validation("q must not be empty".to_string()));
+        }
+        if q.chars().count() > MAX_QUERY_CHARS {
+            return Err(AppError::Validation(format!(
+                "q must be at most {} characters",
+                MAX_QUERY_CHARS
+            )));
+        }
+
+        let types = parse_entity_types(query.types.as_deref()).map_err(AppError::Validation)?;
+        let mode = match query.mode.as_deref() {
+            Some(mode) if SEARCH_MODES.contains(&mode) => mode,
+            Some(_) => {
+                return Err(AppError::Validation(format!(
+                    "mode must be one of: {}",
+                    SEARCH_MODES.join(", ")
+                )))
+            }
+            None if self.embedder.is_some() => "hybrid",
+            None => "text",
+        };
+        if mode != "text" && self.embedder.is_none() {
+            return Err(AppError::Validation(
+                "Semantic search is not enabled on this server".to_string(),
+            ));
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let text_hits = if mode == "semantic" {
+            Vec::new()
+        } else {
+            self.text_search(pool, user_id, q, &types, limit).await?
+        };
+
+        let semantic_hits = match mode {
+            "semantic" => self.semantic_search(pool, user_id, q, &types, limit).await?,
+            "hybrid" => match self.semantic_search(pool, user_id, q, &types, limit).await {
+                Ok(hits) => hits,
+                Err(e) => {
+                    // Keyword results are still useful if the embedding API is down
+                    tracing::warn!(error = ?e, "Semantic search failed, using text results only");
+                    Vec::new()
+                }
+            },
+            _ => Vec::new(),
+        };
+
+        let results = merge_hits(q, text_hits, semantic_hits, limit as usize);
+
+        Ok(SearchResponse {
+            query: q.to_string(),
+            mode: mode.to_string(),
+            results,
+        })
+    }
+
+    async fn text_search(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        q: &str,
+        types: &[String],
+        limit: i64,
+    ) -> AppResult<Vec<(SearchDocument, f64)>> {
+        // Any query word may match; documents matching more of them rank higher
+        let rows = sqlx::query_as::<_, RankedDocument>(
+            r#"
+            SELECT d.id, d.entity_type, d.entity_id, d.field, d.title, d.entity_date, d.content,
+                   ts_rank_cd(d.search_vector, q.query)::FLOAT8 AS score
+            FROM journal_search_documents d,
+                 (SELECT NULLIF(replace(plainto_tsquery('english', $2)::TEXT, ' & ', ' | '), '')::TSQUERY AS query) q
+            WHERE d.user_id = $1
+              AND d.entity_type = ANY($3)
+              AND d.search_vector @@ q.query
+            ORDER BY score DESC, d.entity_date DESC NULLS LAST
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(q)
+        .bind(types)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| (r.document, r.score)).collect())
+    }
+
+    async fn semantic_search(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        q: &str,
+        types: &[String],
+        limit: i64,
+    ) -> AppResult<Vec<(SearchDocument, f64)>> {
+        let Some(embedder) = &self.embedder else {
+            return Ok(Vec::new());
+        };
+
+        // Make notes written since the last indexer pass searchable right away
+        self.index_pending(pool, Some(user_id), INDEX_BATCH).await?;
+
+        let query_vector = embedder
+            .embed(&[q.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| AppError::AiError("Embedder returned no vector".to_string()))?;
+
+        let candidates = sqlx::query_as::<_, EmbeddedDocument>(
+            r#"
+            SELECT id, entity_type, entity_id, field, title, entity_date, content, embedding
+            FROM journal_search_documents
+            WHERE user_id = $1
+              AND entity_type = ANY($2)
+              AND embedding_model = $3
+              AND embedding IS NOT NULL
+            ORDER BY updated_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(types)
+        .bind(embedder.model())
+        .bind(SEMANTIC_CANDIDATE_LIMIT)
+        .fetch_all(pool)
+        .await?;
+
+        let mut hits: Vec<(SearchDocument, f64)> = candidates
+            .into_iter()
+            .filter_map(|c| {
+                let similarity = cosine_similarity(&query_vector, &c.embedding);
+                (similarity >= SEMANTIC_MIN_SIMILARITY).then_some((c.document, similarity as f64))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        hits.truncate(limit as usize);
+
+        Ok(hits)
+    }
+}
+
+/// Defaults to every entity type.
+fn parse_entity_types(raw: Option<&str>) -> Result<Vec<String>, String> {
+    let Some(raw) = raw.filter(|r| !r.trim().is_empty()) else {
+        return Ok(SEARCH_ENTITY_TYPES.iter().map(|t| t.to_string()).collect());
+    };
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            SEARCH_ENTITY_TYPES
+                .contains(&t)
+                .then(|| t.to_string())
+                .ok_or_else(|| {
+                    format!("types must be a subset of: {}", SEARCH_ENTITY_TYPES.join(", "))
+                })
+        })
+        .collect()
+}
+
+/// Reciprocal rank fusion: each list contributes `1 / (k + rank)`, so a
+/// document near the top of both lists beats one that tops only one.
+fn reciprocal_rank_fusion(lists: &[Vec<Uuid>]) -> Vec<(Uuid, f64)> {
+    let mut scores: HashMap<Uuid, f64> = HashMap::new();
+    let mut first_seen: Vec<Uuid> = Vec::new();
+    for list in lists {
+        for (rank, id) in list.iter().enumerate() {
+            let score = scores.entry(*id).or_insert_with(|| {
+                first_seen.push(*id);
+                0.0
+            });
+            *score += 1.0 / (RRF_K + rank as f64 + 1.0);
+        }
+    }
+
+    let mut fused: Vec<(Uuid, f64)> = first_seen.into_iter().map(|id| (id, scores[&id])).collect();
+    // Stable sort keeps text order for ties
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+    fused
+}
+
+/// Single-list modes keep their native score; hybrid uses the fused one.
+fn merge_hits(
+    q: &str,
+    text_hits: Vec<(SearchDocument, f64)>,
+    semantic_hits: Vec<(SearchDocument, f64)>,
+    limit: usize,
+) -> Vec<SearchResult> {
+    let text_ids: Vec<Uuid> = text_hits.iter().map(|(d, _)| d.id).collect();
+    let semantic_ids: Vec<Uuid> = semantic_hits.iter().map(|(d, _)| d.id).collect();
+    let fuse = !text_ids.is_empty() && !semantic_ids.is_empty();
+    let text_set: HashSet<Uuid> = text_ids.iter().copied().collect();
+    let semantic_set: HashSet<Uuid> = semantic_ids.iter().copied().collect();
+
+    let mut documents: HashMap<Uuid, (SearchDocument, f64)> = HashMap::new();
+    for (document, score) in text_hits.into_iter().chain(semantic_hits) {
+        documents.entry(document.id).or_insert((document, score));
+    }
+
+    let ranked: Vec<(Uuid, Option<f64>)> = if fuse {
+        reciprocal_rank_fusion(&[text_ids, semantic_ids])
+            .into_iter()
+            .map(|(id, score)| (id, Some(score)))
+            .collect()
+    } else {
+        text_ids.into_iter().chain(semantic_ids).map(|id| (id, None)).collect()
+    };
+
+    ranked
+        .into_iter()
+        .take(limit)
+        .filter_map(|(id, fused_score)| {
+            let (document, native_score) = documents.remove(&id)?;
+            let mut matched_by = Vec::new();
+            if text_set.contains(&id) {
+                matched_by.push("text".to_string());
+            }
+            if semantic_set.contains(&id) {
+                matched_by.push("semantic".to_string());
+            }
+            Some(SearchResult {
+                snippet: highlight_snippet(&document.content, q),
+                entity_type: document.entity_type,
+                entity_id: document.entity_id,
+                field: document.field,
+                title: document.title,
+                entity_date: document.entity_date,
+                score: fused_score.unwrap_or(native_score),
+                matched_by,
+            })
+        })
+        .collect()
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Byte ranges of alphanumeric runs.
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// An excerpt around the first matching word, HTML-escaped, with every
+/// word sharing a stem with the query wrapped in `<mark>`. Semantic-only
+/// hits may have no matching word and get the opening of the text.
+fn highlight_snippet(content: &str, q: &str) -> String {
+    let terms: HashSet<String> = tokenize(q).into_iter().collect();
+    let is_match = |word: &str| tokenize(word).first().is_some_and(|t| terms.contains(t));
+    let words = word_spans(content);
+
+    let anchor = words
+        .iter()
+        .find(|(s, e)| is_match(&content[*s..*e]))
+        .map(|(s, _)| *s)
+        .unwrap_or(0);
+
+    // Start a little before the match, on a word boundary
+    let before = &content[..anchor];
+    let start = match before.char_indices().rev().nth(SNIPPET_LEAD_CHARS) {
+        None => 0,
+        Some((i, _)) => before[i..]
+            .char_indices()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(w, c)| i + w + c.len_utf8())
+            .unwrap_or(anchor),
+    };
+
+    let end = match content[start..].char_indices().nth(SNIPPET_CHARS) {
+        None => content.len(),
+        Some((i, _)) => content[start..start + i]
+            .rfind(char::is_whitespace)
+            .filter(|&w| w > 0)
+            .map_or(start + i, |w| start + w),
+    };
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    let mut cursor = start;
+    for (s, e) in words.iter().filter(|(s, e)| *s >= start && *e <= end) {
+        snippet.push_str(&escape_html(&content[cursor..*s]));
+        let word = escape_html(&content[*s..*e]);
+        if is_match(&content[*s..*e]) {
+            snippet.push_str("<mark>");
+            snippet.push_str(&word);
+            snippet.push_str("</mark>");
+        } else {
+            snippet.push_str(&word);
+        }
+        cursor = *e;
+    }
+    snippet.push_str(&escape_html(&content[cursor..end]));
+    if end < content.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(content: &str) -> SearchDocument {
+        SearchDocument {
+            id: Uuid::new_v4(),
+            entity_type: "trade".to_string(),
+            entity_id: Uuid::new_v4(),
+            field: "notes".to_string(),
+            title: "NVDA long".to_string(),
+            entity_date: None,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_highlight_snippet_marks_stems_and_escapes() {
+        let snippet = highlight_snippet("Chased the <open> & chasing again", "chase open");
+        assert_eq!(
+            snippet,
+            "<mark>Chased</mark> the &lt;<mark>open</mark>&gt; &amp; <mark>chasing</mark> again"
+        );
+    }
+
+    #[test]
+    fn test_highlight_snippet_windows_long_text() {
+        let filler = "calm session ".repeat(40);
+        let content = format!("{}then I chased the open{}", filler, filler);
+        let snippet = highlight_snippet(&content, "chased");
+
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.ends_with('…'));
+        assert!(snippet.contains("<mark>chased</mark>"));
+        assert!(snippet.chars().count() < SNIPPET_CHARS + 20);
+
+        let no_match = highlight_snippet(&content, "gap fill");
+        assert!(no_match.starts_with("calm session"));
+        assert!(!no_match.contains("<mark>"));
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion_rewards_agreement() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let fused = reciprocal_rank_fusion(&[vec![a, b], vec![b, c]]);
+        let order: Vec<Uuid> = fused.iter().map(|(id, _)| *id).collect();
+        assert_eq!(order, vec![b, a, c]);
+        assert!((fused[0].1 - (1.0 / 62.0 + 1.0 / 61.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_merge_hits_reports_match_sources() {
+        let both = document("chased the open");
+        let text_only = document("open interest");
+        let semantic_only = document("entered late on the first candle");
+
+        let results = merge_hits(
+            "chased open",
+            vec![(both.clone(), 0.4), (text_only.clone(), 0.1)],
+            vec![(semantic_only.clone(), 0.5), (both.clone(), 0.45)],
+            10,
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].entity_id, both.entity_id);
+        assert_eq!(results[0].matched_by, vec!["text", "semantic"]);
+        assert_eq!(results[2].matched_by, vec!["text"]);
+
+        let text_results = merge_hits("open", vec![(text_only, 0.1)], Vec::new(), 10);
+        assert_eq!(text_results[0].score, 0.1);
+    }
+
+    #[test]
+    fn test_parse_entity_types() {
+        assert_eq!(parse_entity_types(None).unwrap().len(), SEARCH_ENTITY_TYPES.len());
+        assert_eq!(
+            parse_entity_types(Some("trade, mood_log")).unwrap(),
+            vec!["trade", "mood_log"]
+        );
+        assert!(parse_entity_types(Some("trade,users")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trade_notes_are_indexed_on_insert() {
+        let Some(db) = crate::test_db::TestDatabase::migrated().await else { return };
+        let user_id = db.insert_user("search@example.com").await;
+
+        sqlx::query(
+            r#"
+            INSERT INTO trades (user_id, symbol, asset_class, direction, entry_time, pre_trade_thesis, notes)
+            VALUES ($1, 'NVDA', 'stock', 'long', NOW(), 'Breakout over the open range', 'Chased the entry')
+            "#,
+        )
+        .bind(user_id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let fields: Vec<String> = sqlx::query_scalar(
+            "SELECT field FROM journal_search_documents WHERE user_id = $1 ORDER BY field",
+        )
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        db.drop().await;
+
+        assert_eq!(fields, ["notes", "thesis"]);
+    }
+}
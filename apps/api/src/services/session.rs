@@ -0,0 +1,385 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{AuthSession, ClientInfo, RefreshToken, SessionInfo};
+use crate::services::AuthService;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const SESSION_REVOKED_LOGOUT: &str = "logout";
+pub const SESSION_REVOKED_BY_USER: &str = "user";
+pub const SESSION_REVOKED_REUSE: &str = "reuse_detected";
+pub const SESSION_REVOKED_PASSWORD_RESET: &str = "password_reset";
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Revoked sessions stay listed in the database this long for investigation.
+const REVOKED_SESSION_RETENTION_DAYS: i32 = 7;
+
+/// A fresh refresh token and the session family it belongs to.
+#[derive(Debug)]
+pub struct IssuedSession {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub refresh_token: String,
+}
+
+/// Describes a user agent as "Browser on OS" for the session list.
+pub fn describe_user_agent(user_agent: Option<&str>) -> String {
+    let Some(ua) = user_agent else {
+        return "Unknown device".to_string();
+    };
+
+    let browser = if ua.contains("Edg/") {
+        Some("Edge")
+    } else if ua.contains("OPR/") {
+        Some("Opera")
+    } else if ua.contains("Firefox/") {
+        Some("Firefox")
+    } else if ua.contains("Chrome/") || ua.contains("CriOS/") {
+        Some("Chrome")
+    } else if ua.contains("Safari/") {
+        Some("Safari")
+    } else {
+        None
+    };
+
+    let os = if ua.contains("iPhone") || ua.contains("iPad") {
+        Some("iOS")
+    } else if ua.contains("Android") {
+        Some("Android")
+    } else if ua.contains("Windows") {
+        Some("Windows")
+    } else if ua.contains("Mac OS X") || ua.contains("Macintosh") {
+        Some("macOS")
+    } else if ua.contains("CrOS") {
+        Some("ChromeOS")
+    } else if ua.contains("Linux") {
+        Some("Linux")
+    } else {
+        None
+    };
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        // API clients such as curl: show the product token
+        (None, None) => ua
+            .split(['/', ' '])
+            .next()
+            .filter(|s| !s.is_empty())
+            .unwrap_or("Unknown device")
+            .to_string(),
+    }
+}
+
+/// Refresh token families. Each sign-in starts a session; every refresh
+/// rotates its token, and presenting a token that was already rotated means
+/// it leaked, so the whole session is revoked.
+pub struct SessionService;
+
+impl SessionService {
+    pub async fn create(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        client: &ClientInfo,
+    ) -> AppResult<IssuedSession> {
+        let (refresh_token, token_hash) = auth_service.generate_refresh_token();
+        let expires_at = auth_service.get_refresh_token_expiry();
+
+        let mut tx = pool.begin().await?;
+
+        let session_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO auth_sessions (user_id, user_agent, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .bind(expires_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (user_id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .bind(&token_hash)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(IssuedSession {
+            session_id,
+            user_id,
+            refresh_token,
+        })
+    }
+
+    /// Exchange a refresh token for a new one in the same session.
+    pub async fn rotate(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        refresh_token: &str,
+        client: &ClientInfo,
+    ) -> AppResult<IssuedSession> {
+        let invalid = || AppError::Unauthorized("Invalid or expired refresh token".to_string());
+        let token_hash = auth_service.hash_token(refresh_token);
+
+        let mut tx = pool.begin().await?;
+
+        // Row lock: of two concurrent exchanges of one token, the second sees
+        // it rotated and is treated as reuse
+        let token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT id, session_id, expires_at, revoked, rotated_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(&token_hash)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid)?;
+
+        let session = sqlx::query_as::<_, AuthSession>(
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at, revoked_at
+            FROM auth_sessions
+            WHERE id = $1
+            FOR UPDATE
+            "#,
+        )
+        .bind(token.session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let now = Utc::now();
+        if session.revoked_at.is_some() || session.expires_at <= now || token.expires_at <= now {
+            return Err(invalid());
+        }
+
+        if token.revoked || token.rotated_at.is_some() {
+            sqlx::query(
+                r#"
+                UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $2 WHERE id = $1
+                "#,
+            )
+            .bind(session.id)
+            .bind(SESSION_REVOKED_REUSE)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+
+            tracing::warn!(
+                user_id = %session.user_id,
+                session_id = %session.id,
+                "Rotated refresh token reused; session revoked"
+            );
+            return Err(invalid());
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens SET revoked = true, rotated_at = NOW() WHERE id = $1
+            "#,
+        )
+        .bind(token.id)
+        .execute(&mut *tx)
+        .await?;
+
+        let (new_token, new_hash) = auth_service.generate_refresh_token();
+        let expires_at = auth_service.get_refresh_token_expiry();
+
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (user_id, session_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(session.user_id)
+        .bind(session.id)
+        .bind(&new_hash)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE auth_sessions
+            SET last_used_at = NOW(), expires_at = $2,
+                user_agent = COALESCE($3, user_agent), ip_address = COALESCE($4, ip_address)
+            WHERE id = $1
+            "#,
+        )
+        .bind(session.id)
+        .bind(expires_at)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(IssuedSession {
+            session_id: session.id,
+            user_id: session.user_id,
+            refresh_token: new_token,
+        })
+    }
+
+    /// Active sessions, most recently used first.
+    pub async fn list(
+        pool: &PgPool,
+        user_id: Uuid,
+        current: Option<Uuid>,
+    ) -> AppResult<Vec<SessionInfo>> {
+        let sessions = sqlx::query_as::<_, AuthSession>(
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at, revoked_at
+            FROM auth_sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY last_used_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|s| SessionInfo {
+                id: s.id,
+                device: describe_user_agent(s.user_agent.as_deref()),
+                current: current == Some(s.id),
+                user_agent: s.user_agent,
+                ip_address: s.ip_address,
+                created_at: s.created_at,
+                last_used_at: s.last_used_at,
+                expires_at: s.expires_at,
+            })
+            .collect())
+    }
+
+    /// Returns whether an active session was revoked.
+    pub async fn revoke(
+        pool: &PgPool,
+        user_id: Uuid,
+        session_id: Uuid,
+        reason: &str,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $3
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(reason)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every active session, optionally sparing one. Returns the count.
+    pub async fn revoke_all<'e, E>(
+        executor: E,
+        user_id: Uuid,
+        except: Option<Uuid>,
+        reason: &str,
+    ) -> AppResult<u64>
+    where
+        E: sqlx::PgExecutor<'e>,
+    {
+        let result = sqlx::query(
+            r#"
+            UPDATE auth_sessions SET revoked_at = NOW(), revoked_reason = $3
+            WHERE user_id = $1 AND revoked_at IS NULL AND ($2::UUID IS NULL OR id <> $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(except)
+        .bind(reason)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes expired tokens and sessions, and revoked sessions past retention.
+    pub async fn purge_expired(pool: &PgPool) -> AppResult<u64> {
+        let sessions = sqlx::query(
+            r#"
+            DELETE FROM auth_sessions
+            WHERE expires_at < NOW()
+                OR revoked_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(REVOKED_SESSION_RETENTION_DAYS)
+        .execute(pool)
+        .await?;
+
+        let tokens = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < NOW()")
+            .execute(pool)
+            .await?;
+
+        Ok(sessions.rows_affected() + tokens.rows_affected())
+    }
+
+    pub fn spawn_purger(pool: Arc<PgPool>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match Self::purge_expired(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!(count, "Purged expired sessions and refresh tokens"),
+                    Err(e) => tracing::warn!(error = ?e, "Session purge failed"),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_common_browsers() {
+        let chrome_mac = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36";
+        assert_eq!(describe_user_agent(Some(chrome_mac)), "Chrome on macOS");
+
+        let edge_windows = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36 Edg/129.0.0.0";
+        assert_eq!(describe_user_agent(Some(edge_windows)), "Edge on Windows");
+
+        let safari_iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.6 Mobile/15E148 Safari/604.1";
+        assert_eq!(describe_user_agent(Some(safari_iphone)), "Safari on iOS");
+
+        let firefox_linux = "Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0";
+        assert_eq!(describe_user_agent(Some(firefox_linux)), "Firefox on Linux");
+
+        let chrome_android = "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Mobile Safari/537.36";
+        assert_eq!(describe_user_agent(Some(chrome_android)), "Chrome on Android");
+    }
+
+    #[test]
+    fn describes_api_clients_and_missing_agents() {
+        assert_eq!(describe_user_agent(Some("curl/8.5.0")), "curl");
+        assert_eq!(describe_user_agent(Some("okhttp/4.12.0")), "okhttp");
+        assert_eq!(describe_user_agent(None), "Unknown device");
+    }
+}
@@ -0,0 +1,457 @@
+use crate::config::Config;
+use crate::error::AppResult;
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+pub const DEFAULT_SESSION_TIMEZONE: &str = "America/New_York";
+/// US equity sessions, in exchange time.
+const DEFAULT_SESSIONS: &str =
+    "premarket=04:00-09:30,first_30_minutes=09:30-10:00,midday=11:30-14:00,power_hour=15:00-16:00";
+pub const OUTSIDE_SESSIONS: &str = "outside_sessions";
+
+pub const HISTOGRAM_BUCKET_MINUTES: &[u32] = &[1, 5, 10, 15, 30, 60];
+pub const DEFAULT_HISTOGRAM_BUCKET_MINUTES: u32 = 5;
+
+/// Label and exclusive upper bound in minutes; the last bucket is open-ended.
+const HOLD_TIME_BUCKETS: &[(&str, Option<i64>)] = &[
+    ("under_1m", Some(1)),
+    ("1m_5m", Some(5)),
+    ("5m_15m", Some(15)),
+    ("15m_1h", Some(60)),
+    ("1h_4h", Some(240)),
+    ("4h_1d", Some(1440)),
+    ("1d_1w", Some(10080)),
+    ("over_1w", None),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradingSession {
+    pub id: String,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// Named time-of-day windows in an exchange's timezone. Trades are labelled
+/// by their entry time in that timezone, whatever the trader's own is.
+#[derive(Debug, Clone)]
+pub struct SessionTable {
+    timezone: Tz,
+    sessions: Vec<TradingSession>,
+}
+
+impl SessionTable {
+    /// `definitions` is `id=HH:MM-HH:MM` entries separated by commas, e.g.
+    /// `open=09:30-10:00,close=15:30-16:00`. Windows may not cross midnight
+    /// or overlap. Unset uses the US equity sessions.
+    pub fn parse(definitions: Option<&str>, timezone: &str) -> Result<Self, String> {
+        let timezone = timezone
+            .parse::<Tz>()
+            .map_err(|_| format!("Unknown session timezone '{}'", timezone))?;
+
+        let definitions = definitions
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .unwrap_or(DEFAULT_SESSIONS);
+
+        let mut sessions: Vec<TradingSession> = Vec::new();
+        for entry in definitions.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let invalid = || format!("Invalid session '{}': expected id=HH:MM-HH:MM", entry);
+            let (id, window) = entry.split_once('=').ok_or_else(invalid)?;
+            let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+            let time = |v: &str| NaiveTime::parse_from_str(v.trim(), "%H:%M").map_err(|_| invalid());
+
+            let id = id.trim();
+            if id.is_empty()
+                || id == OUTSIDE_SESSIONS
+                || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(format!(
+                    "Invalid session id '{}': use lowercase letters, digits and underscores",
+                    id
+                ));
+            }
+
+            let session = TradingSession {
+                id: id.to_string(),
+                start: time(start)?,
+                end: time(end)?,
+            };
+            if session.start >= session.end {
+                return Err(format!("Session '{}' must end after it starts", id));
+            }
+            if let Some(other) = sessions
+                .iter()
+                .find(|s| s.id == session.id || (session.start < s.end && s.start < session.end))
+            {
+                return Err(format!("Session '{}' overlaps or duplicates '{}'", id, other.id));
+            }
+            sessions.push(session);
+        }
+
+        if sessions.is_empty() {
+            return Err("At least one session is required".to_string());
+        }
+
+        sessions.sort_by_key(|s| s.start);
+        Ok(Self { timezone, sessions })
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    pub fn sessions(&self) -> &[TradingSession] {
+        &self.sessions
+    }
+
+    pub fn session_for(&self, at: DateTime<Utc>) -> Option<&TradingSession> {
+        self.position_for(at).map(|index| &self.sessions[index])
+    }
+
+    fn position_for(&self, at: DateTime<Utc>) -> Option<usize> {
+        let local = at.with_timezone(&self.timezone).time();
+        self.sessions
+            .iter()
+            .position(|s| s.start <= local && local < s.end)
+    }
+}
+
+/// What the time-of-day breakdowns need from a closed trade.
+#[derive(Debug, Clone, FromRow)]
+pub struct ClosedTradeTiming {
+    pub entry_date: DateTime<Utc>,
+    pub exit_date: Option<DateTime<Utc>>,
+    pub hold_time_minutes: Option<i32>,
+    pub net_pnl: Decimal,
+}
+
+impl ClosedTradeTiming {
+    fn hold_minutes(&self) -> Option<i64> {
+        self.hold_time_minutes
+            .map(i64::from)
+            .or_else(|| self.exit_date.map(|exit| (exit - self.entry_date).num_minutes()))
+            .filter(|m| *m >= 0)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BucketPerformance {
+    pub trade_count: i64,
+    pub win_rate: Decimal,
+    pub avg_pnl: Decimal,
+    pub total_pnl: Decimal,
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    count: i64,
+    wins: i64,
+    total_pnl: Decimal,
+}
+
+impl Tally {
+    fn add(&mut self, pnl: Decimal) {
+        self.count += 1;
+        if pnl > Decimal::ZERO {
+            self.wins += 1;
+        }
+        self.total_pnl += pnl;
+    }
+
+    fn performance(&self) -> BucketPerformance {
+        if self.count == 0 {
+            return BucketPerformance::default();
+        }
+        let count = Decimal::from(self.count);
+        BucketPerformance {
+            trade_count: self.count,
+            win_rate: (Decimal::from(self.wins) / count * Decimal::from(100)).round_dp(2),
+            avg_pnl: (self.total_pnl / count).round_dp(2),
+            total_pnl: self.total_pnl,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionPerformance {
+    pub session: String,
+    /// `HH:MM` in the session timezone; absent for `outside_sessions`
+    pub start: Option<String>,
+    pub end: Option<String>,
+    #[serde(flatten)]
+    pub performance: BucketPerformance,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MinuteBucketPerformance {
+    /// Start of the bucket, minutes after local midnight
+    pub minute_of_day: u32,
+    pub time: String,
+    #[serde(flatten)]
+    pub performance: BucketPerformance,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HoldTimePerformance {
+    pub bucket: &'static str,
+    pub min_minutes: i64,
+    pub max_minutes: Option<i64>,
+    #[serde(flatten)]
+    pub performance: BucketPerformance,
+}
+
+/// Every configured session in order, then trades that fell outside them.
+pub fn session_performance(
+    trades: &[ClosedTradeTiming],
+    table: &SessionTable,
+) -> Vec<SessionPerformance> {
+    let mut tallies: Vec<Tally> = table.sessions.iter().map(|_| Tally::default()).collect();
+    let mut outside = Tally::default();
+
+    for trade in trades {
+        match table.position_for(trade.entry_date) {
+            Some(index) => tallies[index].add(trade.net_pnl),
+            None => outside.add(trade.net_pnl),
+        }
+    }
+
+    let mut sessions: Vec<SessionPerformance> = table
+        .sessions
+        .iter()
+        .zip(&tallies)
+        .map(|(session, tally)| SessionPerformance {
+            session: session.id.clone(),
+            start: Some(session.start.format("%H:%M").to_string()),
+            end: Some(session.end.format("%H:%M").to_string()),
+            performance: tally.performance(),
+        })
+        .collect();
+
+    if outside.count > 0 {
+        sessions.push(SessionPerformance {
+            session: OUTSIDE_SESSIONS.to_string(),
+            start: None,
+            end: None,
+            performance: outside.performance(),
+        });
+    }
+
+    sessions
+}
+
+/// Entry time-of-day in `tz`, in buckets of `bucket_minutes`. Empty buckets
+/// are left out.
+pub fn minute_histogram(
+    trades: &[ClosedTradeTiming],
+    tz: Tz,
+    bucket_minutes: u32,
+) -> Vec<MinuteBucketPerformance> {
+    let bucket_minutes = bucket_minutes.max(1);
+    let mut tallies = std::collections::BTreeMap::<u32, Tally>::new();
+
+    for trade in trades {
+        let local = trade.entry_date.with_timezone(&tz);
+        let minute = local.hour() * 60 + local.minute();
+        tallies
+            .entry(minute - minute % bucket_minutes)
+            .or_default()
+            .add(trade.net_pnl);
+    }
+
+    tallies
+        .into_iter()
+        .map(|(minute_of_day, tally)| MinuteBucketPerformance {
+            minute_of_day,
+            time: format!("{:02}:{:02}", minute_of_day / 60, minute_of_day % 60),
+            performance: tally.performance(),
+        })
+        .collect()
+}
+
+/// All hold-time buckets, shortest first. Trades without an exit or hold
+/// time are skipped.
+pub fn hold_time_buckets(trades: &[ClosedTradeTiming]) -> Vec<HoldTimePerformance> {
+    let mut tallies: Vec<Tally> = HOLD_TIME_BUCKETS.iter().map(|_| Tally::default()).collect();
+
+    for trade in trades {
+        let Some(minutes) = trade.hold_minutes() else { continue };
+        let index = HOLD_TIME_BUCKETS
+            .iter()
+            .position(|(_, upper)| upper.is_none_or(|upper| minutes < upper))
+            .unwrap_or(HOLD_TIME_BUCKETS.len() - 1);
+        tallies[index].add(trade.net_pnl);
+    }
+
+    let mut lower = 0;
+    HOLD_TIME_BUCKETS
+        .iter()
+        .zip(&tallies)
+        .map(|((bucket, upper), tally)| {
+            let min_minutes = lower;
+            lower = upper.unwrap_or(lower);
+            HoldTimePerformance {
+                bucket,
+                min_minutes,
+                max_minutes: *upper,
+                performance: tally.performance(),
+            }
+        })
+        .collect()
+}
+
+/// Session, time-of-day and hold-time breakdowns of closed trades.
+pub struct SessionAnalyticsService {
+    sessions: SessionTable,
+}
+
+impl SessionAnalyticsService {
+    pub fn new(config: &Config) -> Self {
+        let sessions = SessionTable::parse(
+            config.trading_sessions.as_deref(),
+            &config.trading_session_timezone,
+        )
+        .unwrap_or_else(|e| {
+            tracing::error!(error = %e, "Invalid TRADING_SESSIONS; using US equity sessions");
+            SessionTable::parse(None, DEFAULT_SESSION_TIMEZONE)
+                .expect("built-in sessions are valid")
+        });
+        Self { sessions }
+    }
+
+    pub fn sessions(&self) -> &SessionTable {
+        &self.sessions
+    }
+
+    pub async fn closed_trades(pool: &PgPool, user_id: Uuid) -> AppResult<Vec<ClosedTradeTiming>> {
+        Ok(sqlx::query_as::<_, ClosedTradeTiming>(
+            r#"
+            SELECT entry_date, exit_date, hold_time_minutes, COALESCE(net_pnl, 0) as net_pnl
+            FROM trades
+            WHERE user_id = $1 AND status = 'closed'
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn trade(entry: DateTime<Utc>, hold_minutes: Option<i32>, pnl: i64) -> ClosedTradeTiming {
+        ClosedTradeTiming {
+            entry_date: entry,
+            exit_date: None,
+            hold_time_minutes: hold_minutes,
+            net_pnl: Decimal::from(pnl),
+        }
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    #[test]
+    fn default_sessions_parse_in_order() {
+        let table = SessionTable::parse(None, DEFAULT_SESSION_TIMEZONE).unwrap();
+        let ids: Vec<_> = table.sessions().iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["premarket", "first_30_minutes", "midday", "power_hour"]);
+    }
+
+    #[test]
+    fn session_definitions_are_validated() {
+        let parse = |d: &str| SessionTable::parse(Some(d), DEFAULT_SESSION_TIMEZONE);
+        assert!(parse("open=09:30-10:00,close=15:30-16:00").is_ok());
+        assert!(parse("open=09:30").unwrap_err().contains("expected id=HH:MM-HH:MM"));
+        assert!(parse("open=10:00-09:30").unwrap_err().contains("end after"));
+        assert!(parse("a=09:30-10:30,b=10:00-11:00").unwrap_err().contains("overlaps"));
+        assert!(parse("Open=09:30-10:00").is_err());
+        assert!(SessionTable::parse(None, "Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn sessions_follow_exchange_time_across_dst() {
+        let table = SessionTable::parse(None, DEFAULT_SESSION_TIMEZONE).unwrap();
+        // The 9:30 New York open is 14:30 UTC in winter and 13:30 UTC in summer
+        let winter_open = utc(2026, 1, 15, 14, 35);
+        let summer_open = utc(2026, 7, 15, 13, 35);
+        assert_eq!(table.session_for(winter_open).unwrap().id, "first_30_minutes");
+        assert_eq!(table.session_for(summer_open).unwrap().id, "first_30_minutes");
+        assert!(table.session_for(utc(2026, 7, 15, 14, 35)).is_none());
+    }
+
+    #[test]
+    fn session_performance_counts_outside_trades() {
+        let table = SessionTable::parse(None, DEFAULT_SESSION_TIMEZONE).unwrap();
+        let trades = vec![
+            trade(utc(2026, 1, 15, 14, 35), None, 100),
+            trade(utc(2026, 1, 15, 14, 50), None, -50),
+            trade(utc(2026, 1, 15, 20, 30), None, -20), // 15:30 ET
+            trade(utc(2026, 1, 15, 23, 0), None, 10),   // 18:00 ET
+        ];
+
+        let sessions = session_performance(&trades, &table);
+        let first_30 = sessions.iter().find(|s| s.session == "first_30_minutes").unwrap();
+        assert_eq!(first_30.performance.trade_count, 2);
+        assert_eq!(first_30.performance.win_rate, Decimal::from(50));
+        assert_eq!(first_30.performance.avg_pnl, Decimal::from(25));
+
+        let midday = sessions.iter().find(|s| s.session == "midday").unwrap();
+        assert_eq!(midday.performance, BucketPerformance::default());
+
+        let outside = sessions.last().unwrap();
+        assert_eq!(outside.session, OUTSIDE_SESSIONS);
+        assert_eq!(outside.performance.trade_count, 1);
+    }
+
+    #[test]
+    fn minute_histogram_uses_local_time() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let trades = vec![
+            trade(utc(2026, 1, 15, 14, 31), None, 10), // 09:31 EST
+            trade(utc(2026, 7, 15, 13, 34), None, 20), // 09:34 EDT
+            trade(utc(2026, 7, 15, 13, 36), None, -5), // 09:36 EDT
+        ];
+
+        let histogram = minute_histogram(&trades, tz, 5);
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[0].minute_of_day, 570);
+        assert_eq!(histogram[0].time, "09:30");
+        assert_eq!(histogram[0].performance.trade_count, 2);
+        assert_eq!(histogram[1].time, "09:35");
+    }
+
+    #[test]
+    fn hold_time_buckets_cover_all_durations() {
+        let entry = utc(2026, 1, 15, 14, 30);
+        let mut from_exit = trade(entry, None, 5);
+        from_exit.exit_date = Some(entry + chrono::Duration::hours(2));
+        let trades = vec![
+            trade(entry, Some(0), 10),
+            trade(entry, Some(3), -10),
+            trade(entry, Some(20_000), 30),
+            from_exit,
+            trade(entry, None, 99), // no exit or hold time
+        ];
+
+        let buckets = hold_time_buckets(&trades);
+        assert_eq!(buckets.len(), HOLD_TIME_BUCKETS.len());
+        let count = |name: &str| {
+            buckets.iter().find(|b| b.bucket == name).unwrap().performance.trade_count
+        };
+        assert_eq!(count("under_1m"), 1);
+        assert_eq!(count("1m_5m"), 1);
+        assert_eq!(count("1h_4h"), 1);
+        assert_eq!(count("over_1w"), 1);
+
+        let one_to_four = buckets.iter().find(|b| b.bucket == "1h_4h").unwrap();
+        assert_eq!((one_to_four.min_minutes, one_to_four.max_minutes), (60, Some(240)));
+    }
+}
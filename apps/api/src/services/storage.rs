@@ -0,0 +1,379 @@
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+use aws_sdk_s3::config::{
+    BehaviorVersion, Credentials, Region, RequestChecksumCalculation, ResponseChecksumValidation,
+};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
+use aws_sdk_s3::Client;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ColorType, ImageFormat};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const PRESIGN_EXPIRY: Duration = Duration::from_secs(15 * 60);
+/// Request body cap for multipart uploads. Larger recordings go through a
+/// presigned PUT straight to the bucket.
+pub const MAX_MULTIPART_BYTES: usize = 25 * 1024 * 1024;
+const MAX_IMAGE_BYTES: i64 = 10 * 1024 * 1024;
+const MAX_VIDEO_BYTES: i64 = 200 * 1024 * 1024;
+const MAX_DOCUMENT_BYTES: i64 = 10 * 1024 * 1024;
+const THUMBNAIL_MAX_DIMENSION: u32 = 320;
+const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+pub const MEDIA_TYPES: &[&str] = &["screenshot", "recording", "order_confirmation"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Document,
+}
+
+impl MediaKind {
+    /// Allowed upload mime types and the object extension used for each.
+    pub fn from_mime(mime: &str) -> Option<(Self, &'static str)> {
+        match mime {
+            "image/png" => Some((Self::Image, "png")),
+            "image/jpeg" => Some((Self::Image, "jpg")),
+            "image/webp" => Some((Self::Image, "webp")),
+            "image/gif" => Some((Self::Image, "gif")),
+            "video/mp4" => Some((Self::Video, "mp4")),
+            "video/webm" => Some((Self::Video, "webm")),
+            "application/pdf" => Some((Self::Document, "pdf")),
+            _ => None,
+        }
+    }
+
+    pub fn max_bytes(&self) -> i64 {
+        match self {
+            Self::Image => MAX_IMAGE_BYTES,
+            Self::Video => MAX_VIDEO_BYTES,
+            Self::Document => MAX_DOCUMENT_BYTES,
+        }
+    }
+}
+
+/// Upload metadata after validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatedUpload {
+    pub kind: MediaKind,
+    pub mime_type: String,
+    pub extension: &'static str,
+}
+
+pub struct StorageService {
+    client: Client,
+    bucket: String,
+    public_base_url: String,
+}
+
+impl StorageService {
+    pub fn new(config: &Config) -> Self {
+        // Path-style addressing and opt-in checksums keep MinIO and R2 happy;
+        // browsers can't compute the CRC headers the SDK would otherwise sign.
+        let s3_config = aws_sdk_s3::config::Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(config.s3_region.clone()))
+            .endpoint_url(&config.s3_endpoint)
+            .credentials_provider(Credentials::new(
+                &config.s3_access_key,
+                &config.s3_secret_key,
+                None,
+                None,
+                "trademaster-config",
+            ))
+            .force_path_style(true)
+            .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+            .response_checksum_validation(ResponseChecksumValidation::WhenRequired)
+            .build();
+
+        Self {
+            client: Client::from_conf(s3_config),
+            bucket: config.s3_bucket.clone(),
+            public_base_url: format!(
+                "{}/{}",
+                config.s3_endpoint.trim_end_matches('/'),
+                config.s3_bucket
+            ),
+        }
+    }
+
+    pub fn object_key(user_id: Uuid, trade_id: Uuid, media_id: Uuid, extension: &str) -> String {
+        format!("users/{}/trades/{}/{}.{}", user_id, trade_id, media_id, extension)
+    }
+
+    pub fn thumbnail_key(user_id: Uuid, trade_id: Uuid, media_id: Uuid) -> String {
+        format!("users/{}/trades/{}/{}_thumb.jpg", user_id, trade_id, media_id)
+    }
+
+    pub fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base_url, key)
+    }
+
+    /// Checks media type, declared mime type and size against the allowlist.
+    pub fn validate_upload(media_type: &str, mime_type: &str, size: i64) -> Result<ValidatedUpload, String> {
+        if !MEDIA_TYPES.contains(&media_type) {
+            return Err(format!(
+                "Invalid media_type '{}'. Must be one of: {}",
+                media_type,
+                MEDIA_TYPES.join(", ")
+            ));
+        }
+
+        let mime_type = mime_type.trim().to_lowercase();
+        let (kind, extension) = MediaKind::from_mime(&mime_type)
+            .ok_or_else(|| format!("Unsupported file type '{}'", mime_type))?;
+
+        if size <= 0 {
+            return Err("File is empty".to_string());
+        }
+        if size > kind.max_bytes() {
+            return Err(format!(
+                "File exceeds the {} MB limit for {}",
+                kind.max_bytes() / (1024 * 1024),
+                mime_type
+            ));
+        }
+
+        Ok(ValidatedUpload {
+            kind,
+            mime_type,
+            extension,
+        })
+    }
+
+    /// Detects the actual file type from its leading bytes so a renamed
+    /// file can't slip past the mime allowlist.
+    pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+        if bytes.starts_with(b"%PDF-") {
+            return Some("application/pdf");
+        }
+        if bytes.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+            return Some("video/webm");
+        }
+        if bytes.len() >= 8 && &bytes[4..8] == b"ftyp" {
+            return Some("video/mp4");
+        }
+        match image::guess_format(bytes).ok()? {
+            ImageFormat::Png => Some("image/png"),
+            ImageFormat::Jpeg => Some("image/jpeg"),
+            ImageFormat::WebP => Some("image/webp"),
+            ImageFormat::Gif => Some("image/gif"),
+            _ => None,
+        }
+    }
+
+    pub fn verify_content(upload: &ValidatedUpload, bytes: &[u8]) -> Result<(), String> {
+        match Self::sniff_mime(bytes) {
+            Some(actual) if actual == upload.mime_type => Ok(()),
+            Some(actual) => Err(format!(
+                "File content is {} but was declared as {}",
+                actual, upload.mime_type
+            )),
+            None => Err("File content does not match any supported type".to_string()),
+        }
+    }
+
+    /// Downscales an image to fit a 320px box and re-encodes it as JPEG.
+    /// CPU-bound; call from `spawn_blocking`.
+    pub fn generate_thumbnail(bytes: &[u8]) -> Result<Vec<u8>, String> {
+        let img = image::load_from_memory(bytes).map_err(|e| format!("Could not decode image: {}", e))?;
+        let rgb = img
+            .thumbnail(THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_DIMENSION)
+            .to_rgb8();
+
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, THUMBNAIL_JPEG_QUALITY)
+            .encode(&rgb, rgb.width(), rgb.height(), ColorType::Rgb8)
+            .map_err(|e| format!("Could not encode thumbnail: {}", e))?;
+        Ok(out)
+    }
+
+    pub async fn put_object(&self, key: &str, body: Vec<u8>, content_type: &str) -> AppResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!(key, "S3 put_object failed: {:?}", e);
+                AppError::Internal("Failed to store file".to_string())
+            })?;
+        Ok(())
+    }
+
+    /// Reads an object, or only its first `limit` bytes when sniffing the
+    /// type of a large upload.
+    pub async fn get_object(&self, key: &str, limit: Option<u64>) -> AppResult<Vec<u8>> {
+        let output = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .set_range(limit.map(|n| format!("bytes=0-{}", n.saturating_sub(1))))
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!(key, "S3 get_object failed: {:?}", e);
+                AppError::Internal("Failed to read stored file".to_string())
+            })?;
+
+        let data = output.body.collect().await.map_err(|e| {
+            tracing::error!(key, "S3 body read failed: {:?}", e);
+            AppError::Internal("Failed to read stored file".to_string())
+        })?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    /// Size and content type of a stored object, or `None` if it doesn't exist.
+    pub async fn head_object(&self, key: &str) -> AppResult<Option<(i64, Option<String>)>> {
+        match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+            Ok(output) => Ok(Some((
+                output.content_length().unwrap_or(0),
+                output.content_type().map(str::to_string),
+            ))),
+            Err(e) if e.as_service_error().map(|s| s.is_not_found()).unwrap_or(false) => Ok(None),
+            Err(e) => {
+                tracing::error!(key, "S3 head_object failed: {:?}", e);
+                Err(AppError::Internal("Failed to check stored file".to_string()))
+            }
+        }
+    }
+
+    /// Presigned PUT bound to the object's content type.
+    pub async fn presign_put(&self, key: &str, content_type: &str) -> AppResult<String> {
+        let presign_config = PresigningConfig::expires_in(PRESIGN_EXPIRY)
+            .map_err(|e| AppError::Internal(format!("Invalid presign config: {}", e)))?;
+
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .presigned(presign_config)
+            .await
+            .map_err(|e| {
+                tracing::error!(key, "S3 presign failed: {:?}", e);
+                AppError::Internal("Failed to create upload URL".to_string())
+            })?;
+
+        Ok(request.uri().to_string())
+    }
+
+    /// Best-effort removal. Orphaned objects are only wasted space, so a
+    /// storage outage never fails the request that deleted the rows.
+    pub async fn delete_objects(&self, keys: Vec<String>) {
+        let objects = keys
+            .into_iter()
+            .filter(|k| !k.is_empty())
+            .filter_map(|k| ObjectIdentifier::builder().key(k).build().ok())
+            .collect::<Vec<_>>();
+
+        // DeleteObjects accepts at most 1000 keys per call
+        for chunk in objects.chunks(1000) {
+            let delete = match Delete::builder().set_objects(Some(chunk.to_vec())).quiet(true).build() {
+                Ok(d) => d,
+                Err(e) => {
+                    tracing::warn!("Failed to build S3 delete request: {}", e);
+                    continue;
+                }
+            };
+
+            match self.client.delete_objects().bucket(&self.bucket).delete(delete).send().await {
+                Ok(output) if !output.errors().is_empty() => {
+                    tracing::warn!(failed = output.errors().len(), "Some stored objects could not be deleted");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("S3 delete_objects failed: {:?}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = ImageBuffer::from_pixel(width, height, Rgb([20u8, 120, 200]));
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn test_config() -> Config {
+        Config {
+            s3_endpoint: "http://localhost:9000/".to_string(),
+            s3_region: "us-east-1".to_string(),
+            s3_bucket: "trademaster-media".to_string(),
+            s3_access_key: "minioadmin".to_string(),
+            s3_secret_key: "minioadmin".to_string(),
+            ..Config::for_tests()
+        }
+    }
+
+    #[test]
+    fn test_keys_and_public_url() {
+        let service = StorageService::new(&test_config());
+        let (user, trade, media) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let key = StorageService::object_key(user, trade, media, "png");
+        assert_eq!(key, format!("users/{}/trades/{}/{}.png", user, trade, media));
+        assert!(StorageService::thumbnail_key(user, trade, media).ends_with("_thumb.jpg"));
+        assert_eq!(
+            service.public_url(&key),
+            format!("http://localhost:9000/trademaster-media/{}", key)
+        );
+    }
+
+    #[test]
+    fn test_validate_upload() {
+        let ok = StorageService::validate_upload("screenshot", "Image/PNG", 1024).unwrap();
+        assert_eq!(ok.kind, MediaKind::Image);
+        assert_eq!(ok.extension, "png");
+        assert_eq!(ok.mime_type, "image/png");
+
+        assert!(StorageService::validate_upload("selfie", "image/png", 1024).is_err());
+        assert!(StorageService::validate_upload("screenshot", "image/svg+xml", 1024).is_err());
+        assert!(StorageService::validate_upload("screenshot", "image/png", 0).is_err());
+        assert!(StorageService::validate_upload("screenshot", "image/png", MAX_IMAGE_BYTES + 1).is_err());
+        // Recordings get a larger allowance than images
+        assert!(StorageService::validate_upload("recording", "video/mp4", MAX_IMAGE_BYTES + 1).is_ok());
+        assert!(StorageService::validate_upload("recording", "video/mp4", MAX_VIDEO_BYTES + 1).is_err());
+    }
+
+    #[test]
+    fn test_sniff_and_verify_content() {
+        let png = png_bytes(4, 4);
+        assert_eq!(StorageService::sniff_mime(&png), Some("image/png"));
+        assert_eq!(StorageService::sniff_mime(b"%PDF-1.7\n..."), Some("application/pdf"));
+        assert_eq!(
+            StorageService::sniff_mime(b"\x00\x00\x00\x18ftypmp42"),
+            Some("video/mp4")
+        );
+        assert_eq!(StorageService::sniff_mime(b"<svg></svg>"), None);
+
+        let declared_jpeg = StorageService::validate_upload("screenshot", "image/jpeg", 10).unwrap();
+        assert!(StorageService::verify_content(&declared_jpeg, &png).is_err());
+        let declared_png = StorageService::validate_upload("screenshot", "image/png", 10).unwrap();
+        assert!(StorageService::verify_content(&declared_png, &png).is_ok());
+    }
+
+    #[test]
+    fn test_generate_thumbnail_preserves_aspect_ratio() {
+        let thumb = StorageService::generate_thumbnail(&png_bytes(1280, 640)).unwrap();
+        assert_eq!(StorageService::sniff_mime(&thumb), Some("image/jpeg"));
+
+        let decoded = image::load_from_memory(&thumb).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (320, 160));
+
+        assert!(StorageService::generate_thumbnail(b"not an image").is_err());
+    }
+}
@@ -0,0 +1,636 @@
+use crate::error::AppResult;
+use crate::models::{DailyPlan, MoodLog, PeriodicReview, StreakSummary, Trade, UserStreak};
+use chrono::{Datelike, Duration, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const DEFAULT_TIMEZONE: Tz = chrono_tz::America::New_York;
+
+/// Entries scoring below this are "low quality": they keep a streak alive but
+/// decay its quality-weighted value (or break it, for plan adherence).
+const LOW_QUALITY_THRESHOLD: Decimal = Decimal::from_parts(5, 0, 0, false, 1);
+const LOW_QUALITY_DECAY: Decimal = Decimal::from_parts(8, 0, 0, false, 1);
+
+/// Free-text notes shorter than this don't count as a substantive entry.
+const MIN_NOTE_CHARS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreakType {
+    Journal,
+    Planning,
+    PlanAdherence,
+    Review,
+}
+
+impl StreakType {
+    pub const ALL: [StreakType; 4] = [
+        StreakType::Journal,
+        StreakType::Planning,
+        StreakType::PlanAdherence,
+        StreakType::Review,
+    ];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "journal" => Ok(StreakType::Journal),
+            "planning" => Ok(StreakType::Planning),
+            "plan_adherence" => Ok(StreakType::PlanAdherence),
+            "review" => Ok(StreakType::Review),
+            other => Err(format!(
+                "Invalid streak type '{}'. Must be one of: journal, planning, plan_adherence, review",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreakType::Journal => "journal",
+            StreakType::Planning => "planning",
+            StreakType::PlanAdherence => "plan_adherence",
+            StreakType::Review => "review",
+        }
+    }
+
+    /// The period an activity on `date` counts toward: the trading day itself
+    /// (weekends and holidays roll back to the previous session), or the
+    /// Monday of the week for weekly reviews.
+    pub fn credit_date(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            StreakType::Review => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            _ if MarketCalendar::is_trading_day(date) => date,
+            _ => MarketCalendar::previous_trading_day(date),
+        }
+    }
+
+    fn previous_period(&self, credit_date: NaiveDate) -> NaiveDate {
+        match self {
+            StreakType::Review => credit_date - Duration::days(7),
+            _ => MarketCalendar::previous_trading_day(credit_date),
+        }
+    }
+
+    /// A day that misses the plan is a failed day, not a low-quality one.
+    fn breaks_on_low_quality(&self) -> bool {
+        matches!(self, StreakType::PlanAdherence)
+    }
+}
+
+/// NYSE full-day closures, computed from the exchange's observance rules.
+pub struct MarketCalendar;
+
+impl MarketCalendar {
+    pub fn is_trading_day(date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !Self::is_market_holiday(date)
+    }
+
+    pub fn previous_trading_day(date: NaiveDate) -> NaiveDate {
+        let mut d = date - Duration::days(1);
+        while !Self::is_trading_day(d) {
+            d -= Duration::days(1);
+        }
+        d
+    }
+
+    pub fn is_market_holiday(date: NaiveDate) -> bool {
+        let year = date.year();
+        let fixed = |month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day);
+
+        // New Year's Day falling on a Saturday is not observed on the prior Friday.
+        let new_year = fixed(1, 1).map(|d| match d.weekday() {
+            Weekday::Sun => d + Duration::days(1),
+            _ => d,
+        });
+
+        let mut holidays = vec![
+            new_year,
+            Self::nth_weekday(year, 1, Weekday::Mon, 3),  // Martin Luther King Jr. Day
+            Self::nth_weekday(year, 2, Weekday::Mon, 3),  // Washington's Birthday
+            Self::easter_sunday(year).map(|d| d - Duration::days(2)), // Good Friday
+            Self::last_weekday(year, 5, Weekday::Mon),    // Memorial Day
+            fixed(7, 4).map(Self::observed),              // Independence Day
+            Self::nth_weekday(year, 9, Weekday::Mon, 1),  // Labor Day
+            Self::nth_weekday(year, 11, Weekday::Thu, 4), // Thanksgiving
+            fixed(12, 25).map(Self::observed),            // Christmas
+        ];
+        if year >= 2022 {
+            holidays.push(fixed(6, 19).map(Self::observed)); // Juneteenth
+        }
+
+        holidays.into_iter().flatten().any(|h| h == date)
+    }
+
+    fn observed(date: NaiveDate) -> NaiveDate {
+        match date.weekday() {
+            Weekday::Sat => date - Duration::days(1),
+            Weekday::Sun => date + Duration::days(1),
+            _ => date,
+        }
+    }
+
+    fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> Option<NaiveDate> {
+        NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8)
+    }
+
+    fn last_weekday(year: i32, month: u32, weekday: Weekday) -> Option<NaiveDate> {
+        let next_month = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)?
+        };
+        let mut d = next_month - Duration::days(1);
+        while d.weekday() != weekday {
+            d -= Duration::days(1);
+        }
+        Some(d)
+    }
+
+    /// Anonymous Gregorian algorithm.
+    fn easter_sunday(year: i32) -> Option<NaiveDate> {
+        let a = year % 19;
+        let b = year / 100;
+        let c = year % 100;
+        let d = b / 4;
+        let e = b % 4;
+        let f = (b + 8) / 25;
+        let g = (b - f + 1) / 3;
+        let h = (19 * a + b - d - g + 15) % 30;
+        let i = c / 4;
+        let k = c % 4;
+        let l = (32 + 2 * e + 2 * i - h - k) % 7;
+        let m = (a + 11 * h + 22 * l) / 451;
+        let month = (h + l - 7 * m + 114) / 31;
+        let day = (h + l - 7 * m + 114) % 31 + 1;
+        NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+    }
+}
+
+/// The mutable part of a `user_streaks` row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreakState {
+    pub current_count: i32,
+    pub best_count: i32,
+    pub decay_value: Decimal,
+    pub last_activity_date: Option<NaiveDate>,
+}
+
+impl From<&UserStreak> for StreakState {
+    fn from(row: &UserStreak) -> Self {
+        Self {
+            current_count: row.current_count.unwrap_or(0),
+            best_count: row.best_count.unwrap_or(0),
+            decay_value: row.decay_value.unwrap_or(Decimal::ZERO),
+            last_activity_date: row.last_activity_date,
+        }
+    }
+}
+
+pub struct StreakService;
+
+impl StreakService {
+    /// Applies one activity to a streak. Activity for a period that has
+    /// already been credited (or an earlier one) leaves the streak unchanged.
+    pub fn advance(
+        state: &StreakState,
+        streak_type: StreakType,
+        activity_date: NaiveDate,
+        quality: Decimal,
+    ) -> StreakState {
+        let credit = streak_type.credit_date(activity_date);
+        if state.last_activity_date.is_some_and(|last| credit <= last) {
+            return state.clone();
+        }
+
+        let consecutive = state.last_activity_date == Some(streak_type.previous_period(credit));
+        let low_quality = quality < LOW_QUALITY_THRESHOLD;
+
+        let (current_count, decay_value) = if streak_type.breaks_on_low_quality() && low_quality {
+            (0, Decimal::ZERO)
+        } else if consecutive {
+            let carried = if low_quality {
+                state.decay_value * LOW_QUALITY_DECAY
+            } else {
+                state.decay_value
+            };
+            (state.current_count + 1, carried + quality)
+        } else {
+            (1, quality)
+        };
+
+        StreakState {
+            current_count,
+            best_count: state.best_count.max(current_count),
+            decay_value: decay_value.round_dp(2),
+            last_activity_date: Some(credit),
+        }
+    }
+
+    /// The streak as it stands on `today`, treating a missed period as a break.
+    pub fn summarize(state: &StreakState, streak_type: StreakType, today: NaiveDate) -> StreakSummary {
+        let credit_today = streak_type.credit_date(today);
+        let completed_today = state.last_activity_date == Some(credit_today);
+        let is_active = completed_today
+            || state.last_activity_date == Some(streak_type.previous_period(credit_today));
+
+        StreakSummary {
+            streak_type: streak_type.as_str().to_string(),
+            current_count: if is_active { state.current_count } else { 0 },
+            best_count: state.best_count,
+            decay_value: if is_active { state.decay_value } else { Decimal::ZERO },
+            last_activity_date: state.last_activity_date,
+            is_active,
+            completed_today,
+        }
+    }
+
+    pub async fn user_timezone(pool: &PgPool, user_id: Uuid) -> AppResult<Tz> {
+        let name = sqlx::query_scalar::<_, Option<String>>(
+            "SELECT timezone FROM user_profiles WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+
+        Ok(name
+            .and_then(|n| n.parse::<Tz>().ok())
+            .unwrap_or(DEFAULT_TIMEZONE))
+    }
+
+    pub fn today_in(tz: Tz) -> NaiveDate {
+        Utc::now().with_timezone(&tz).date_naive()
+    }
+
+    /// Records an activity against a streak. Failures are logged rather than
+    /// surfaced so streak bookkeeping never fails the write that triggered it.
+    pub async fn track(
+        pool: &PgPool,
+        user_id: Uuid,
+        streak_type: StreakType,
+        source: &str,
+        source_id: Option<Uuid>,
+        activity_date: NaiveDate,
+        quality: Decimal,
+    ) {
+        if let Err(e) =
+            Self::record(pool, user_id, streak_type, source, source_id, activity_date, quality).await
+        {
+            tracing::warn!(
+                user_id = %user_id,
+                streak_type = streak_type.as_str(),
+                error = %e,
+                "Failed to update streak"
+            );
+        }
+    }
+
+    /// Journal streak credit for trade notes, dated to the user's local today.
+    pub async fn track_trade_notes(pool: &PgPool, trade: &Trade) {
+        let tz = match Self::user_timezone(pool, trade.user_id).await {
+            Ok(tz) => tz,
+            Err(e) => {
+                tracing::warn!(user_id = %trade.user_id, error = %e, "Failed to load timezone");
+                DEFAULT_TIMEZONE
+            }
+        };
+        Self::track(
+            pool,
+            trade.user_id,
+            StreakType::Journal,
+            "trade_notes",
+            Some(trade.id),
+            Self::today_in(tz),
+            Self::trade_notes_quality(trade),
+        )
+        .await;
+    }
+
+    /// Plan adherence credit when a daily plan is completed. Uses the stored
+    /// adherence score when present, otherwise the share of that day's trades
+    /// that followed the plan without breaking rules.
+    pub async fn track_plan_adherence(pool: &PgPool, plan: &DailyPlan) {
+        let quality = match plan.adherence_score {
+            Some(score) => Ok(Self::adherence_quality(Some(score), 0, 0)),
+            None => Self::trade_adherence_counts(pool, plan)
+                .await
+                .map(|(followed, total)| Self::adherence_quality(None, followed, total)),
+        };
+
+        match quality {
+            Ok(quality) => {
+                Self::track(
+                    pool,
+                    plan.user_id,
+                    StreakType::PlanAdherence,
+                    "daily_plan",
+                    Some(plan.id),
+                    plan.plan_date,
+                    quality,
+                )
+                .await
+            }
+            Err(e) => {
+                tracing::warn!(plan_id = %plan.id, error = %e, "Failed to compute plan adherence")
+            }
+        }
+    }
+
+    async fn trade_adherence_counts(pool: &PgPool, plan: &DailyPlan) -> AppResult<(i64, i64)> {
+        let tz = Self::user_timezone(pool, plan.user_id).await?;
+        let counts = sqlx::query_as::<_, (i64, i64)>(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE followed_plan AND NOT broke_rules),
+                COUNT(*)
+            FROM trades
+            WHERE user_id = $1 AND (entry_date AT TIME ZONE $2)::date = $3
+            "#,
+        )
+        .bind(plan.user_id)
+        .bind(tz.name())
+        .bind(plan.plan_date)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    async fn record(
+        pool: &PgPool,
+        user_id: Uuid,
+        streak_type: StreakType,
+        source: &str,
+        source_id: Option<Uuid>,
+        activity_date: NaiveDate,
+        quality: Decimal,
+    ) -> AppResult<UserStreak> {
+        let quality = quality.clamp(Decimal::ZERO, Decimal::ONE).round_dp(2);
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO user_streaks (user_id, streak_type)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id, streak_type) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(streak_type.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query_as::<_, UserStreak>(
+            "SELECT * FROM user_streaks WHERE user_id = $1 AND streak_type = $2 FOR UPDATE",
+        )
+        .bind(user_id)
+        .bind(streak_type.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let next = Self::advance(&StreakState::from(&row), streak_type, activity_date, quality);
+
+        let updated = sqlx::query_as::<_, UserStreak>(
+            r#"
+            UPDATE user_streaks SET
+                current_count = $1,
+                best_count = $2,
+                decay_value = $3,
+                last_activity_date = $4,
+                updated_at = NOW()
+            WHERE id = $5
+            RETURNING *
+            "#,
+        )
+        .bind(next.current_count)
+        .bind(next.best_count)
+        .bind(next.decay_value)
+        .bind(next.last_activity_date)
+        .bind(row.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO streak_events (
+                user_id, streak_type, source, source_id,
+                activity_date, quality, count_after, decay_value_after
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(user_id)
+        .bind(streak_type.as_str())
+        .bind(source)
+        .bind(source_id)
+        .bind(streak_type.credit_date(activity_date))
+        .bind(quality)
+        .bind(next.current_count)
+        .bind(next.decay_value)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    fn fraction(filled: usize, total: usize) -> Decimal {
+        if total == 0 {
+            return Decimal::ZERO;
+        }
+        (Decimal::from(filled as u64) / Decimal::from(total as u64)).round_dp(2)
+    }
+
+    fn has_text(value: &Option<String>) -> bool {
+        value.as_deref().is_some_and(|s| !s.trim().is_empty())
+    }
+
+    fn has_substantive_text(value: &Option<String>) -> bool {
+        value
+            .as_deref()
+            .is_some_and(|s| s.trim().chars().count() >= MIN_NOTE_CHARS)
+    }
+
+    fn has_items<T>(value: &Option<Vec<T>>) -> bool {
+        value.as_ref().is_some_and(|v| !v.is_empty())
+    }
+
+    pub fn trade_notes_quality(trade: &Trade) -> Decimal {
+        let checks = [
+            Self::has_substantive_text(&trade.thesis),
+            Self::has_substantive_text(&trade.lessons),
+            Self::has_text(&trade.mistakes),
+            Self::has_text(&trade.emotional_state),
+            trade.overall_grade.is_some(),
+        ];
+        Self::fraction(checks.iter().filter(|c| **c).count(), checks.len())
+    }
+
+    pub fn mood_log_quality(log: &MoodLog) -> Decimal {
+        let checks = [
+            log.pre_market_mood.is_some(),
+            log.post_market_mood.is_some(),
+            log.stress_level.is_some(),
+            log.confidence_level.is_some(),
+            log.sleep_quality.is_some(),
+            Self::has_items(&log.emotions),
+            Self::has_text(&log.notes),
+        ];
+        Self::fraction(checks.iter().filter(|c| **c).count(), checks.len())
+    }
+
+    pub fn plan_quality(plan: &DailyPlan) -> Decimal {
+        let checks = [
+            Self::has_text(&plan.market_bias),
+            Self::has_substantive_text(&plan.bias_reasoning),
+            Self::has_items(&plan.session_goals),
+            plan.max_trades.is_some(),
+            plan.max_daily_loss.is_some(),
+            plan.checklist_items.is_some(),
+            Self::has_text(&plan.notes),
+        ];
+        Self::fraction(checks.iter().filter(|c| **c).count(), checks.len())
+    }
+
+    pub fn review_quality(review: &PeriodicReview) -> Decimal {
+        let checks = [
+            Self::has_substantive_text(&review.what_went_well),
+            Self::has_substantive_text(&review.what_to_improve),
+            Self::has_items(&review.key_lessons),
+            Self::has_items(&review.goals_next_period),
+            review.overall_rating.is_some(),
+        ];
+        Self::fraction(checks.iter().filter(|c| **c).count(), checks.len())
+    }
+
+    /// `adherence_score` is stored on a 0-100 scale. A day with no trades
+    /// counts as fully adherent.
+    pub fn adherence_quality(score: Option<Decimal>, followed: i64, total: i64) -> Decimal {
+        match score {
+            Some(score) => (score / Decimal::from(100)).clamp(Decimal::ZERO, Decimal::ONE),
+            None if total == 0 => Decimal::ONE,
+            None => Self::fraction(followed as usize, total as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn empty() -> StreakState {
+        StreakState {
+            current_count: 0,
+            best_count: 0,
+            decay_value: Decimal::ZERO,
+            last_activity_date: None,
+        }
+    }
+
+    #[test]
+    fn test_market_holidays() {
+        assert!(MarketCalendar::is_market_holiday(date(2026, 1, 1)));
+        assert!(MarketCalendar::is_market_holiday(date(2026, 1, 19))); // MLK
+        assert!(MarketCalendar::is_market_holiday(date(2026, 4, 3))); // Good Friday
+        assert!(MarketCalendar::is_market_holiday(date(2026, 7, 3))); // July 4th observed
+        assert!(MarketCalendar::is_market_holiday(date(2026, 11, 26))); // Thanksgiving
+        assert!(MarketCalendar::is_market_holiday(date(2027, 6, 18))); // Juneteenth observed
+        assert!(!MarketCalendar::is_market_holiday(date(2021, 12, 31))); // Sat New Year not observed
+        assert!(!MarketCalendar::is_market_holiday(date(2026, 10, 19)));
+    }
+
+    #[test]
+    fn test_previous_trading_day_skips_weekends_and_holidays() {
+        // Tuesday after Memorial Day 2026 -> previous Friday
+        assert_eq!(MarketCalendar::previous_trading_day(date(2026, 5, 26)), date(2026, 5, 22));
+        assert_eq!(MarketCalendar::previous_trading_day(date(2026, 10, 19)), date(2026, 10, 16));
+    }
+
+    #[test]
+    fn test_streak_continues_across_weekend() {
+        let s = StreakService::advance(&empty(), StreakType::Journal, date(2026, 10, 16), dec("1"));
+        let s = StreakService::advance(&s, StreakType::Journal, date(2026, 10, 19), dec("1"));
+        assert_eq!(s.current_count, 2);
+        assert_eq!(s.best_count, 2);
+        assert_eq!(s.decay_value, dec("2"));
+    }
+
+    #[test]
+    fn test_weekend_activity_credits_previous_session() {
+        let s = StreakService::advance(&empty(), StreakType::Journal, date(2026, 10, 17), dec("1"));
+        assert_eq!(s.last_activity_date, Some(date(2026, 10, 16)));
+    }
+
+    #[test]
+    fn test_missed_day_resets_but_keeps_best() {
+        let s = StreakService::advance(&empty(), StreakType::Planning, date(2026, 10, 13), dec("1"));
+        let s = StreakService::advance(&s, StreakType::Planning, date(2026, 10, 14), dec("1"));
+        let s = StreakService::advance(&s, StreakType::Planning, date(2026, 10, 16), dec("1"));
+        assert_eq!(s.current_count, 1);
+        assert_eq!(s.best_count, 2);
+    }
+
+    #[test]
+    fn test_same_day_activity_is_idempotent() {
+        let s = StreakService::advance(&empty(), StreakType::Journal, date(2026, 10, 16), dec("1"));
+        let again = StreakService::advance(&s, StreakType::Journal, date(2026, 10, 16), dec("0.2"));
+        assert_eq!(s, again);
+    }
+
+    #[test]
+    fn test_low_quality_decays_value() {
+        let s = StreakService::advance(&empty(), StreakType::Journal, date(2026, 10, 15), dec("1"));
+        let s = StreakService::advance(&s, StreakType::Journal, date(2026, 10, 16), dec("0.2"));
+        assert_eq!(s.current_count, 2);
+        assert_eq!(s.decay_value, dec("1.00"));
+    }
+
+    #[test]
+    fn test_low_adherence_breaks_streak() {
+        let s = StreakService::advance(&empty(), StreakType::PlanAdherence, date(2026, 10, 15), dec("1"));
+        let s = StreakService::advance(&s, StreakType::PlanAdherence, date(2026, 10, 16), dec("0.3"));
+        assert_eq!(s.current_count, 0);
+        assert_eq!(s.best_count, 1);
+        let s = StreakService::advance(&s, StreakType::PlanAdherence, date(2026, 10, 19), dec("1"));
+        assert_eq!(s.current_count, 1);
+    }
+
+    #[test]
+    fn test_review_streak_is_weekly() {
+        let s = StreakService::advance(&empty(), StreakType::Review, date(2026, 10, 9), dec("1"));
+        let s = StreakService::advance(&s, StreakType::Review, date(2026, 10, 12), dec("1"));
+        assert_eq!(s.current_count, 2);
+        assert_eq!(s.last_activity_date, Some(date(2026, 10, 12)));
+    }
+
+    #[test]
+    fn test_summary_lapses_after_missed_session() {
+        let s = StreakService::advance(&empty(), StreakType::Journal, date(2026, 10, 14), dec("1"));
+        let active = StreakService::summarize(&s, StreakType::Journal, date(2026, 10, 15));
+        assert!(active.is_active && !active.completed_today);
+        assert_eq!(active.current_count, 1);
+
+        let lapsed = StreakService::summarize(&s, StreakType::Journal, date(2026, 10, 16));
+        assert!(!lapsed.is_active);
+        assert_eq!(lapsed.current_count, 0);
+        assert_eq!(lapsed.best_count, 1);
+    }
+
+    #[test]
+    fn test_adherence_quality() {
+        assert_eq!(StreakService::adherence_quality(Some(dec("80")), 0, 0), dec("0.8"));
+        assert_eq!(StreakService::adherence_quality(None, 0, 0), Decimal::ONE);
+        assert_eq!(StreakService::adherence_quality(None, 1, 4), dec("0.25"));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    InterpretedTradeQuery, Trade, TradeFilters, TradeGroupBy, TradeQueryData, TradeQueryKind,
+    TradeStats, TradeStatsGroup,
+};
+use chrono_tz::Tz;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+const DEFAULT_QUERY_TRADES: i64 = 20;
+const MAX_STAT_GROUPS: i64 = 100;
+
+/// Aggregates for `TradeStats`; callers restrict to closed trades.
+const TRADE_STATS_COLUMNS: &str = r#"
+            COUNT(*) as total_trades,
+            COUNT(*) FILTER (WHERE pnl > 0) as winning_trades,
+            COUNT(*) FILTER (WHERE pnl <= 0) as losing_trades,
+            COALESCE(
+                CAST(COUNT(*) FILTER (WHERE pnl > 0) AS DECIMAL) / NULLIF(COUNT(*), 0) * 100,
+                0
+            ) as win_rate,
+            COALESCE(SUM(net_pnl), 0) as total_pnl,
+            COALESCE(AVG(net_pnl) FILTER (WHERE pnl > 0), 0) as avg_win,
+            COALESCE(AVG(net_pnl) FILTER (WHERE pnl <= 0), 0) as avg_loss,
+            CASE
+                WHEN ABS(SUM(net_pnl) FILTER (WHERE pnl <= 0)) > 0
+                THEN ABS(SUM(net_pnl) FILTER (WHERE pnl > 0) / SUM(net_pnl) FILTER (WHERE pnl <= 0))
+                ELSE NULL
+            END as profit_factor,
+            AVG(r_multiple) as avg_r_multiple,
+            COALESCE(MAX(net_pnl), 0) as largest_win,
+            COALESCE(MIN(net_pnl), 0) as largest_loss,
+            AVG(hold_time_minutes)::INTEGER as avg_hold_time_minutes
+"#;
+
+/// Builds every trade list and stats query from `TradeFilters`. Values are
+/// always bound parameters and identifiers come from fixed whitelists, so
+/// filters from query strings and from the AI query endpoint are equally safe.
+pub struct TradeQueryService;
+
+impl TradeQueryService {
+    /// Validates that a sort column name is one of the allowed trade table columns.
+    /// Returns the validated column name or an error. This prevents SQL injection
+    /// by whitelisting rather than sanitizing.
+    pub fn validate_sort_column(input: &str) -> AppResult<&'static str> {
+        match input {
+            "entry_date" => Ok("entry_date"),
+            "exit_date" => Ok("exit_date"),
+            "symbol" => Ok("symbol"),
+            "direction" => Ok("direction"),
+            "asset_class" => Ok("asset_class"),
+            "status" => Ok("status"),
+            "entry_price" => Ok("entry_price"),
+            "exit_price" => Ok("exit_price"),
+            "quantity" => Ok("quantity"),
+            "pnl" => Ok("pnl"),
+            "net_pnl" => Ok("net_pnl"),
+            "pnl_percent" => Ok("pnl_percent"),
+            "r_multiple" => Ok("r_multiple"),
+            "hold_time_minutes" => Ok("hold_time_minutes"),
+            "created_at" => Ok("created_at"),
+            "updated_at" => Ok("updated_at"),
+            _ => Err(AppError::Validation(format!(
+                "Invalid sort column '{}'. Allowed: entry_date, exit_date, symbol, direction, \
+                 asset_class, status, entry_price, exit_price, quantity, pnl, net_pnl, \
+                 pnl_percent, r_multiple, hold_time_minutes, created_at, updated_at",
+                input
+            ))),
+        }
+    }
+
+    /// Validates sort direction. Only "asc" and "desc" are allowed.
+    pub fn validate_sort_order(input: &str) -> AppResult<&'static str> {
+        match input.to_lowercase().as_str() {
+            "asc" => Ok("ASC"),
+            "desc" => Ok("DESC"),
+            _ => Err(AppError::Validation(format!(
+                "Invalid sort order '{}'. Allowed: asc, desc",
+                input
+            ))),
+        }
+    }
+
+    /// Appends the WHERE conditions (without the keyword) for `filters`.
+    /// Times of day are compared in `tz`.
+    pub fn push_filters(
+        builder: &mut QueryBuilder<'_, Postgres>,
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+    ) {
+        builder.push("user_id = ").push_bind(user_id);
+
+        if let Some(v) = &filters.status {
+            builder.push(" AND status = ").push_bind(v.clone());
+        }
+        if let Some(v) = &filters.direction {
+            builder.push(" AND direction = ").push_bind(v.clone());
+        }
+        if let Some(v) = &filters.asset_class {
+            builder.push(" AND asset_class = ").push_bind(v.clone());
+        }
+        if let Some(v) = &filters.symbol {
+            builder.push(" AND symbol ILIKE ").push_bind(format!("%{}%", v));
+        }
+        if let Some(v) = &filters.setup_name {
+            builder.push(" AND setup_name ILIKE ").push_bind(format!("%{}%", v));
+        }
+        if let Some(v) = &filters.conviction {
+            builder.push(" AND conviction = ").push_bind(v.clone());
+        }
+        for (column, value) in [
+            ("is_paper_trade", filters.is_paper_trade),
+            ("is_revenge_trade", filters.is_revenge_trade),
+            ("broke_rules", filters.broke_rules),
+            ("followed_plan", filters.followed_plan),
+        ] {
+            if let Some(v) = value {
+                builder.push(format!(" AND {} = ", column)).push_bind(v);
+            }
+        }
+        if let Some(ids) = filters.tag_ids.as_ref().filter(|ids| !ids.is_empty()) {
+            builder
+                .push(" AND id IN (SELECT trade_id FROM trade_tags WHERE tag_id = ANY(")
+                .push_bind(ids.clone())
+                .push("))");
+        }
+        if let Some(v) = filters.from_date {
+            builder.push(" AND entry_date >= ").push_bind(v);
+        }
+        if let Some(v) = filters.to_date {
+            builder.push(" AND entry_date <= ").push_bind(v);
+        }
+        if let Some(v) = filters.min_pnl {
+            builder.push(" AND net_pnl >= ").push_bind(v);
+        }
+        if let Some(v) = filters.max_pnl {
+            builder.push(" AND net_pnl <= ").push_bind(v);
+        }
+        if let Some(v) = filters.min_r_multiple {
+            builder.push(" AND r_multiple >= ").push_bind(v);
+        }
+        if let Some(v) = filters.max_r_multiple {
+            builder.push(" AND r_multiple <= ").push_bind(v);
+        }
+        match (filters.entry_time_from, filters.entry_time_to) {
+            // A window that wraps past midnight, e.g. 22:00 to 02:00
+            (Some(from), Some(to)) if from > to => {
+                builder.push(" AND (");
+                Self::push_local_entry_date(builder, tz);
+                builder.push("::TIME >= ").push_bind(from).push(" OR ");
+                Self::push_local_entry_date(builder, tz);
+                builder.push("::TIME < ").push_bind(to).push(")");
+            }
+            (from, to) => {
+                if let Some(v) = from {
+                    builder.push(" AND ");
+                    Self::push_local_entry_date(builder, tz);
+                    builder.push("::TIME >= ").push_bind(v);
+                }
+                if let Some(v) = to {
+                    builder.push(" AND ");
+                    Self::push_local_entry_date(builder, tz);
+                    builder.push("::TIME < ").push_bind(v);
+                }
+            }
+        }
+    }
+
+    fn push_local_entry_date(builder: &mut QueryBuilder<'_, Postgres>, tz: Tz) {
+        builder
+            .push("(entry_date AT TIME ZONE ")
+            .push_bind(tz.name().to_string())
+            .push(")");
+    }
+
+    pub async fn count(
+        pool: &PgPool,
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+    ) -> AppResult<i64> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM trades WHERE ");
+        Self::push_filters(&mut builder, user_id, filters, tz);
+        Ok(builder.build_query_scalar::<i64>().fetch_one(pool).await?)
+    }
+
+    /// `sort_column` and `sort_direction` must come from the validators above.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn list(
+        pool: &PgPool,
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+        sort_column: &'static str,
+        sort_direction: &'static str,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<Vec<Trade>> {
+        let mut builder = QueryBuilder::new("SELECT * FROM trades WHERE ");
+        Self::push_filters(&mut builder, user_id, filters, tz);
+        builder
+            .push(format!(" ORDER BY {} {} LIMIT ", sort_column, sort_direction))
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        Ok(builder.build_query_as::<Trade>().fetch_all(pool).await?)
+    }
+
+    /// Statistics over the closed trades matching `filters`.
+    pub async fn stats(
+        pool: &PgPool,
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+    ) -> AppResult<TradeStats> {
+        let mut builder = QueryBuilder::new(format!("SELECT {} FROM trades WHERE ", TRADE_STATS_COLUMNS));
+        Self::push_filters(&mut builder, user_id, filters, tz);
+        builder.push(" AND status = 'closed'");
+
+        Ok(builder.build_query_as::<TradeStats>().fetch_one(pool).await?)
+    }
+
+    /// `stats` per symbol, setup, weekday, etc. Calendar groups are in `tz`.
+    pub async fn grouped_stats(
+        pool: &PgPool,
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+        group_by: TradeGroupBy,
+    ) -> AppResult<Vec<TradeStatsGroup>> {
+        let mut builder = Self::grouped_stats_query(user_id, filters, tz, group_by);
+        Ok(builder.build_query_as::<TradeStatsGroup>().fetch_all(pool).await?)
+    }
+
+    fn grouped_stats_query(
+        user_id: Uuid,
+        filters: &TradeFilters,
+        tz: Tz,
+        group_by: TradeGroupBy,
+    ) -> QueryBuilder<'static, Postgres> {
+        let mut builder = QueryBuilder::new("SELECT ");
+        match group_by {
+            TradeGroupBy::Symbol => {
+                builder.push("symbol");
+            }
+            TradeGroupBy::Setup => {
+                builder.push("COALESCE(setup_name, 'No setup')");
+            }
+            TradeGroupBy::Direction => {
+                builder.push("direction::TEXT");
+            }
+            TradeGroupBy::AssetClass => {
+                builder.push("asset_class::TEXT");
+            }
+            TradeGroupBy::Weekday => {
+                builder.push("trim(to_char(");
+                Self::push_local_entry_date(&mut builder, tz);
+                builder.push(", 'Day'))");
+            }
+            TradeGroupBy::Hour => {
+                builder.push("to_char(");
+                Self::push_local_entry_date(&mut builder, tz);
+                builder.push(", 'HH24:00')");
+            }
+            TradeGroupBy::Month => {
+                builder.push("to_char(");
+                Self::push_local_entry_date(&mut builder, tz);
+                builder.push(", 'YYYY-MM')");
+            }
+        }
+        builder.push(format!(" AS key, {} FROM trades WHERE ", TRADE_STATS_COLUMNS));
+        Self::push_filters(&mut builder, user_id, filters, tz);
+        builder.push(" AND status = 'closed' GROUP BY 1 ORDER BY ");
+        match group_by {
+            TradeGroupBy::Weekday => {
+                builder.push("MIN(EXTRACT(ISODOW FROM ");
+                Self::push_local_entry_date(&mut builder, tz);
+                builder.push("))");
+            }
+            TradeGroupBy::Hour | TradeGroupBy::Month => {
+                builder.push("key");
+            }
+            _ => {
+                builder.push("total_trades DESC, key");
+            }
+        }
+        builder.push(" LIMIT ").push_bind(MAX_STAT_GROUPS);
+        builder
+    }
+
+    /// Runs an interpreted natural-language query. The query must already
+    /// have passed `validate_trade_filters` and `validate_trade_aggregation`.
+    pub async fn execute(
+        pool: &PgPool,
+        user_id: Uuid,
+        query: &InterpretedTradeQuery,
+        tz: Tz,
+    ) -> AppResult<TradeQueryData> {
+        let aggregation = &query.aggregation;
+        match (aggregation.kind, aggregation.group_by) {
+            (TradeQueryKind::Stats, None) => Ok(TradeQueryData::Stats {
+                stats: Self::stats(pool, user_id, &query.filters, tz).await?,
+            }),
+            (TradeQueryKind::Stats, Some(group_by)) => Ok(TradeQueryData::Groups {
+                groups: Self::grouped_stats(pool, user_id, &query.filters, tz, group_by).await?,
+            }),
+            (TradeQueryKind::List, _) => {
+                let sort_column =
+                    Self::validate_sort_column(aggregation.sort_by.as_deref().unwrap_or("entry_date"))?;
+                let sort_direction =
+                    Self::validate_sort_order(aggregation.sort_order.as_deref().unwrap_or("desc"))?;
+                let trades = Self::list(
+                    pool,
+                    user_id,
+                    &query.filters,
+                    tz,
+                    sort_column,
+                    sort_direction,
+                    aggregation.limit.unwrap_or(DEFAULT_QUERY_TRADES),
+                    0,
+                )
+                .await?;
+                Ok(TradeQueryData::Trades { trades })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeDirection;
+    use chrono::NaiveTime;
+
+    #[test]
+    fn test_push_filters_binds_every_value() {
+        let filters = TradeFilters {
+            direction: Some(TradeDirection::Short),
+            symbol: Some("NQ".to_string()),
+            broke_rules: Some(false),
+            tag_ids: Some(vec![Uuid::new_v4()]),
+            entry_time_to: NaiveTime::from_hms_opt(10, 0, 0),
+            ..Default::default()
+        };
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM trades WHERE ");
+        TradeQueryService::push_filters(&mut builder, Uuid::new_v4(), &filters, chrono_tz::America::New_York);
+
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM trades WHERE user_id = $1 AND direction = $2 AND symbol ILIKE $3 \
+             AND broke_rules = $4 AND id IN (SELECT trade_id FROM trade_tags WHERE tag_id = ANY($5)) \
+             AND (entry_date AT TIME ZONE $6)::TIME < $7"
+        );
+    }
+
+    #[test]
+    fn test_entry_time_window_wraps_past_midnight() {
+        let filters = TradeFilters {
+            entry_time_from: NaiveTime::from_hms_opt(22, 0, 0),
+            entry_time_to: NaiveTime::from_hms_opt(2, 0, 0),
+            ..Default::default()
+        };
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM trades WHERE ");
+        TradeQueryService::push_filters(&mut builder, Uuid::new_v4(), &filters, chrono_tz::UTC);
+
+        assert_eq!(
+            builder.sql(),
+            "SELECT * FROM trades WHERE user_id = $1 AND ((entry_date AT TIME ZONE $2)::TIME >= $3 \
+             OR (entry_date AT TIME ZONE $4)::TIME < $5)"
+        );
+    }
+
+    #[test]
+    fn test_grouped_stats_query_orders_weekdays() {
+        let builder = TradeQueryService::grouped_stats_query(
+            Uuid::new_v4(),
+            &TradeFilters::default(),
+            chrono_tz::UTC,
+            TradeGroupBy::Weekday,
+        );
+        let sql = builder.sql();
+        assert!(sql.starts_with("SELECT trim(to_char((entry_date AT TIME ZONE $1), 'Day')) AS key,"));
+        assert!(sql.ends_with(
+            "WHERE user_id = $2 AND status = 'closed' GROUP BY 1 ORDER BY MIN(EXTRACT(ISODOW FROM (entry_date AT TIME ZONE $3))) LIMIT $4"
+        ));
+    }
+
+    #[test]
+    fn test_sort_validation_is_a_whitelist() {
+        assert_eq!(TradeQueryService::validate_sort_column("net_pnl").unwrap(), "net_pnl");
+        assert!(TradeQueryService::validate_sort_column("net_pnl; DROP TABLE trades").is_err());
+        assert_eq!(TradeQueryService::validate_sort_order("ASC").unwrap(), "ASC");
+        assert!(TradeQueryService::validate_sort_order("sideways").is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use crate::error::{AppError, AppResult};
+use crate::models::{TwoFactorSetupResponse, TwoFactorStatus, UserTwoFactor};
+use crate::services::AuthService;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Wrong codes allowed before verification is locked for `LOCKOUT_MINUTES`.
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// Failure count and lock to store after one more wrong code.
+pub fn register_failed_attempt(
+    failed_attempts: i32,
+    now: DateTime<Utc>,
+) -> (i32, Option<DateTime<Utc>>) {
+    let failed_attempts = failed_attempts + 1;
+    if failed_attempts >= MAX_FAILED_ATTEMPTS {
+        (0, Some(now + Duration::minutes(LOCKOUT_MINUTES)))
+    } else {
+        (failed_attempts, None)
+    }
+}
+
+/// TOTP enrolment and verification state. The code and secret handling
+/// lives on `AuthService`; this owns the rows.
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    pub async fn status(pool: &PgPool, user_id: Uuid) -> AppResult<TwoFactorStatus> {
+        let enabled_at = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+            "SELECT enabled_at FROM user_two_factor WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+
+        let recovery_codes_remaining = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM two_factor_recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(TwoFactorStatus {
+            enabled: enabled_at.is_some(),
+            enabled_at,
+            recovery_codes_remaining,
+        })
+    }
+
+    pub async fn is_enabled(pool: &PgPool, user_id: Uuid) -> AppResult<bool> {
+        Ok(sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_two_factor WHERE user_id = $1 AND enabled_at IS NOT NULL
+            )
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?)
+    }
+
+    /// Starts (or restarts) enrolment with a new secret. 2FA stays off until
+    /// `confirm_enrolment` sees a valid code from it.
+    pub async fn begin_enrolment(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        email: &str,
+    ) -> AppResult<TwoFactorSetupResponse> {
+        let secret = auth_service.generate_totp_secret();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO user_two_factor (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, failed_attempts = 0,
+                locked_until = NULL, updated_at = NOW()
+            WHERE user_two_factor.enabled_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .bind(&secret)
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        Ok(TwoFactorSetupResponse {
+            otpauth_uri: auth_service.totp_provisioning_uri(&secret, email)?,
+            secret,
+        })
+    }
+
+    /// Turns 2FA on once the user proves their authenticator works, and
+    /// returns the first set of recovery codes.
+    pub async fn confirm_enrolment(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        code: &str,
+    ) -> AppResult<Vec<String>> {
+        let mut tx = pool.begin().await?;
+
+        let pending = sqlx::query_as::<_, UserTwoFactor>(
+            r#"
+            SELECT secret, last_used_step, failed_attempts, locked_until
+            FROM user_two_factor
+            WHERE user_id = $1 AND enabled_at IS NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("Start two-factor setup before confirming it".to_string())
+        })?;
+
+        let now = Utc::now();
+        let step = auth_service
+            .verify_totp(&pending.secret, code, now.timestamp() as u64, pending.last_used_step)?
+            .ok_or_else(|| AppError::Validation("Invalid verification code".to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE user_two_factor
+            SET enabled_at = NOW(), last_used_step = $2, updated_at = NOW()
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await?;
+
+        let codes = Self::replace_recovery_codes(&mut tx, auth_service, user_id).await?;
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Checks an authenticator code or a recovery code for a user with 2FA
+    /// enabled. Codes are single-use and repeated failures lock verification.
+    pub async fn verify(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        code: Option<&str>,
+        recovery_code: Option<&str>,
+    ) -> AppResult<()> {
+        let code = code.map(str::trim).filter(|c| !c.is_empty());
+        let recovery_code = recovery_code.map(str::trim).filter(|c| !c.is_empty());
+        if code.is_some() == recovery_code.is_some() {
+            return Err(AppError::Validation(
+                "Provide either an authenticator code or a recovery code".to_string(),
+            ));
+        }
+
+        let mut tx = pool.begin().await?;
+
+        let state = sqlx::query_as::<_, UserTwoFactor>(
+            r#"
+            SELECT secret, last_used_step, failed_attempts, locked_until
+            FROM user_two_factor
+            WHERE user_id = $1 AND enabled_at IS NOT NULL
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("Two-factor authentication is not enabled".to_string())
+        })?;
+
+        let now = Utc::now();
+        if let Some(until) = state.locked_until.filter(|until| *until > now) {
+            let minutes = (until - now).num_minutes() + 1;
+            return Err(AppError::RateLimited(format!(
+                "Too many invalid codes; try again in {} minutes",
+                minutes
+            )));
+        }
+
+        let verified = match (code, recovery_code) {
+            (Some(code), _) => {
+                let step = auth_service.verify_totp(
+                    &state.secret,
+                    code,
+                    now.timestamp() as u64,
+                    state.last_used_step,
+                )?;
+                if let Some(step) = step {
+                    sqlx::query("UPDATE user_two_factor SET last_used_step = $2 WHERE user_id = $1")
+                        .bind(user_id)
+                        .bind(step)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                step.is_some()
+            }
+            (None, Some(recovery_code)) => {
+                Self::use_recovery_code(&mut tx, auth_service, user_id, recovery_code).await?
+            }
+            (None, None) => unreachable!("checked above"),
+        };
+
+        let (failed_attempts, locked_until) = if verified {
+            (0, None)
+        } else {
+            register_failed_attempt(state.failed_attempts, now)
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE user_two_factor
+            SET failed_attempts = $2, locked_until = $3, updated_at = NOW()
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(failed_attempts)
+        .bind(locked_until)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if !verified {
+            if locked_until.is_some() {
+                tracing::warn!(user_id = %user_id, "Two-factor verification locked after repeated failures");
+            }
+            return Err(AppError::Unauthorized("Invalid verification code".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn regenerate_recovery_codes(
+        pool: &PgPool,
+        auth_service: &AuthService,
+        user_id: Uuid,
+    ) -> AppResult<Vec<String>> {
+        let mut tx = pool.begin().await?;
+        let codes = Self::replace_recovery_codes(&mut tx, auth_service, user_id).await?;
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    pub async fn disable(pool: &PgPool, user_id: Uuid) -> AppResult<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM user_two_factor WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        tx: &mut Transaction<'_, Postgres>,
+        auth_service: &AuthService,
+        user_id: Uuid,
+    ) -> AppResult<Vec<String>> {
+        sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut **tx)
+            .await?;
+
+        let codes = auth_service.generate_recovery_codes();
+        for code in &codes {
+            sqlx::query(
+                r#"
+                INSERT INTO two_factor_recovery_codes (user_id, code_hash)
+                VALUES ($1, $2)
+                "#,
+            )
+            .bind(user_id)
+            .bind(auth_service.hash_recovery_code(code)?)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(codes)
+    }
+
+    async fn use_recovery_code(
+        tx: &mut Transaction<'_, Postgres>,
+        auth_service: &AuthService,
+        user_id: Uuid,
+        recovery_code: &str,
+    ) -> AppResult<bool> {
+        let unused = sqlx::query_as::<_, (Uuid, String)>(
+            r#"
+            SELECT id, code_hash FROM two_factor_recovery_codes
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        for (id, code_hash) in unused {
+            if auth_service.verify_recovery_code(recovery_code, &code_hash)? {
+                sqlx::query("UPDATE two_factor_recovery_codes SET used_at = NOW() WHERE id = $1")
+                    .bind(id)
+                    .execute(&mut **tx)
+                    .await?;
+                tracing::info!(user_id = %user_id, "Recovery code used");
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_attempts_accumulate_then_lock() {
+        let now = Utc::now();
+        let mut failed = 0;
+        for _ in 1..MAX_FAILED_ATTEMPTS {
+            let (next, locked_until) = register_failed_attempt(failed, now);
+            assert_eq!(next, failed + 1);
+            assert!(locked_until.is_none());
+            failed = next;
+        }
+
+        let (next, locked_until) = register_failed_attempt(failed, now);
+        assert_eq!(next, 0);
+        assert_eq!(locked_until, Some(now + Duration::minutes(LOCKOUT_MINUTES)));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::services::{AiService, AuthService};
+use crate::services::{
+    AiService, AiUsageService, AuthService, EmailService, OAuthService, SearchService,
+    SessionAnalyticsService, StorageService,
+};
 use axum::extract::FromRef;
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -9,6 +12,12 @@ pub struct AppState {
     pub pool: Arc<PgPool>,
     pub auth_service: Arc<AuthService>,
     pub ai_service: Arc<AiService>,
+    pub ai_usage_service: Arc<AiUsageService>,
+    pub storage_service: Arc<StorageService>,
+    pub search_service: Arc<SearchService>,
+    pub email_service: Arc<EmailService>,
+    pub oauth_service: Arc<OAuthService>,
+    pub session_analytics_service: Arc<SessionAnalyticsService>,
 }
 
 // Allow extracting Arc<PgPool> from AppState
@@ -31,3 +40,45 @@ impl FromRef<AppState> for Arc<AiService> {
         state.ai_service.clone()
     }
 }
+
+// Allow extracting Arc<AiUsageService> from AppState
+impl FromRef<AppState> for Arc<AiUsageService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.ai_usage_service.clone()
+    }
+}
+
+// Allow extracting Arc<StorageService> from AppState
+impl FromRef<AppState> for Arc<StorageService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.storage_service.clone()
+    }
+}
+
+// Allow extracting Arc<SearchService> from AppState
+impl FromRef<AppState> for Arc<SearchService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.search_service.clone()
+    }
+}
+
+// Allow extracting Arc<EmailService> from AppState
+impl FromRef<AppState> for Arc<EmailService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.email_service.clone()
+    }
+}
+
+// Allow extracting Arc<OAuthService> from AppState
+impl FromRef<AppState> for Arc<OAuthService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.oauth_service.clone()
+    }
+}
+
+// Allow extracting Arc<SessionAnalyticsService> from AppState
+impl FromRef<AppState> for Arc<SessionAnalyticsService> {
+    fn from_ref(state: &AppState) -> Self {
+        state.session_analytics_service.clone()
+    }
+}
@@ -0,0 +1,62 @@
+//! Scratch Postgres databases for tests that need real SQL. They only run
+//! when `TEST_DATABASE_URL` points at a server the test user can create
+//! databases on; otherwise `TestDatabase::create` returns `None` and the
+//! test skips itself.
+
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{Connection, Executor, PgConnection, PgPool};
+use uuid::Uuid;
+
+pub struct TestDatabase {
+    admin_url: String,
+    name: String,
+    pub pool: PgPool,
+}
+
+impl TestDatabase {
+    /// An empty database with a unique name.
+    pub async fn create() -> Option<Self> {
+        let Ok(admin_url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set; skipping database test");
+            return None;
+        };
+
+        let name = format!("trademaster_test_{}", Uuid::new_v4().simple());
+        let mut admin = PgConnection::connect(&admin_url).await.unwrap();
+        admin
+            .execute(format!("CREATE DATABASE {}", name).as_str())
+            .await
+            .unwrap();
+
+        let options = admin_url
+            .parse::<PgConnectOptions>()
+            .unwrap()
+            .database(&name);
+        let pool = PgPool::connect_with(options).await.unwrap();
+        Some(Self { admin_url, name, pool })
+    }
+
+    /// A database with every migration applied.
+    pub async fn migrated() -> Option<Self> {
+        let db = Self::create().await?;
+        sqlx::migrate!("./migrations").run(&db.pool).await.unwrap();
+        Some(db)
+    }
+
+    pub async fn insert_user(&self, email: &str) -> Uuid {
+        sqlx::query_scalar("INSERT INTO users (email, password_hash) VALUES ($1, 'x') RETURNING id")
+            .bind(email)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+    }
+
+    pub async fn drop(self) {
+        self.pool.close().await;
+        let mut admin = PgConnection::connect(&self.admin_url).await.unwrap();
+        admin
+            .execute(format!("DROP DATABASE {} WITH (FORCE)", self.name).as_str())
+            .await
+            .unwrap();
+    }
+}